@@ -0,0 +1,443 @@
+use crate::blackjack::Blackjack;
+use crate::cli::TableFlags;
+use crate::simulation::{self, play_round, worker_rounds, worker_seed, Seat, SimulationConfig, SimulationResult};
+use rand::prelude::*;
+use std::f64::consts::PI;
+use std::thread;
+
+/**
+ * Where the win rate and standard deviation per round come from
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WinRateSource {
+    // Measured by simulating the configured number of rounds
+    Simulated,
+    // Given by the user
+    Supplied { win_rate: f64, standard_deviation: f64 },
+}
+
+/**
+ * What to calculate: the player and game to simulate, the bankroll and how much play it must last
+ */
+#[derive(Clone, Debug)]
+pub struct RiskConfig {
+    // The game, the player and the bankroll; its rounds measure the win rate
+    pub simulation: SimulationConfig,
+    pub source: WinRateSource,
+    pub sessions: u64,
+    pub hours_per_session: f64,
+    pub rounds_per_hour: f64,
+    // The risk of ruin the required bankroll is calculated for
+    pub target_risk: f64,
+    // The number of bankrolls played through to simulate ruin, 0 to skip the simulation
+    pub trials: u64,
+}
+impl RiskConfig {
+    /**
+     * Parses risk of ruin options:
+     *   bankroll <units>          the bankroll in betting units (default 1000)
+     *   sessions <number>         the number of sessions the bankroll must last (default 10)
+     *   hours <number>            hours per session (default 4)
+     *   rate <rounds>             rounds per hour (default 100)
+     *   target <percent>          the risk of ruin to size a bankroll for (default 5)
+     *   trials <number>           bankrolls played to simulate ruin (default 1000, 0 to skip)
+     *   win-rate <units> sd <units>  use these per round values instead of simulating
+     * and any simulate option, which set the game and player whose win rate is measured.
+     * Ex: ["player", "deviations", "bankroll", "400", "sessions", "20", "target", "1"]
+     */
+    pub fn parse(args: &[String]) -> Result<RiskConfig, String> {
+        Self::parse_with(args, &TableFlags::default())
+    }
+
+    /**
+     * Parses risk of ruin options, with the table flags overriding the game that is simulated
+     */
+    pub fn parse_with(args: &[String], flags: &TableFlags) -> Result<RiskConfig, String> {
+        let mut sessions = 10;
+        let mut hours_per_session = 4.0;
+        let mut rounds_per_hour = 100.0;
+        let mut target_risk = 0.05;
+        let mut trials = 1000;
+        let mut win_rate = None;
+        let mut standard_deviation = None;
+        let mut simulation_args = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let text = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let number = || text.replace(['_', ','], "").parse::<f64>().ok().filter(|number| number.is_finite());
+            match arg.as_str() {
+                "sessions" => {
+                    sessions = text.parse().ok().filter(|&sessions| sessions > 0)
+                        .ok_or_else(|| format!("invalid number of sessions {}", text))?;
+                }
+                "hours" => {
+                    hours_per_session = number().filter(|&hours| hours > 0.0)
+                        .ok_or_else(|| format!("invalid number of hours {}", text))?;
+                }
+                "rate" => {
+                    rounds_per_hour = number().filter(|&rate| rate > 0.0)
+                        .ok_or_else(|| format!("invalid rate {}", text))?;
+                }
+                "target" => {
+                    target_risk = number().filter(|&percent| percent > 0.0 && percent < 100.0)
+                        .ok_or_else(|| format!("invalid target risk {}", text))? / 100.0;
+                }
+                "trials" => {
+                    trials = text.replace(['_', ','], "").parse()
+                        .map_err(|_| format!("invalid number of trials {}", text))?;
+                }
+                "win-rate" => win_rate = Some(number().ok_or_else(|| format!("invalid win rate {}", text))?),
+                "sd" => {
+                    standard_deviation = Some(number().filter(|&sd| sd > 0.0)
+                        .ok_or_else(|| format!("invalid standard deviation {}", text))?);
+                }
+                _ => {
+                    simulation_args.push(arg.clone());
+                    simulation_args.push(text.clone());
+                }
+            }
+        }
+
+        let source = match (win_rate, standard_deviation) {
+            (Some(win_rate), Some(standard_deviation)) => WinRateSource::Supplied { win_rate, standard_deviation },
+            (None, None) => WinRateSource::Simulated,
+            _ => return Err("win-rate and sd must be given together".to_string()),
+        };
+        let simulation = SimulationConfig::parse_with(&simulation_args, flags)?;
+        // The win rate and standard deviation are those of the main bet
+        if !simulation.side_bets.is_empty() {
+            return Err("side bets are not part of the risk of ruin".to_string());
+        }
+        Ok(RiskConfig {
+            simulation,
+            source,
+            sessions,
+            hours_per_session,
+            rounds_per_hour,
+            target_risk,
+            trials,
+        })
+    }
+
+    /**
+     * Returns the number of rounds the bankroll must last
+     */
+    pub fn rounds(&self) -> u64 {
+        (self.sessions as f64 * self.hours_per_session * self.rounds_per_hour).round() as u64
+    }
+}
+
+/**
+ * Returns the standard normal cumulative distribution function at x
+ * (Abramowitz and Stegun 7.1.26, accurate to about 1e-7)
+ */
+pub fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / 2f64.sqrt();
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/**
+ * Returns the risk of ever losing the bankroll playing forever: e^(-2 x win rate x bankroll / variance),
+ * or 1 if the player does not have the edge
+ * @param win_rate the expected win per round
+ * @param standard_deviation the standard deviation per round
+ * @param bankroll the bankroll, in the same units
+ */
+pub fn risk_of_ruin(win_rate: f64, standard_deviation: f64, bankroll: f64) -> f64 {
+    if win_rate <= 0.0 {
+        return 1.0;
+    }
+    (-2.0 * win_rate * bankroll / (standard_deviation * standard_deviation)).exp().min(1.0)
+}
+
+/**
+ * Returns the risk of losing the bankroll at some point within the specified number of rounds,
+ * treating the results as a random walk with drift
+ * @param win_rate the expected win per round
+ * @param standard_deviation the standard deviation per round
+ * @param bankroll the bankroll, in the same units
+ * @param rounds the number of rounds played
+ */
+pub fn trip_risk_of_ruin(win_rate: f64, standard_deviation: f64, bankroll: f64, rounds: u64) -> f64 {
+    if rounds == 0 {
+        return 0.0;
+    }
+    let n = rounds as f64;
+    let spread = standard_deviation * n.sqrt();
+    let variance = standard_deviation * standard_deviation;
+    let reflected = (-2.0 * win_rate * bankroll / variance).exp();
+
+    // Without an edge the reflection can overflow, but then the tail it multiplies underflows faster
+    let reflected_tail = if reflected.is_finite() {
+        reflected * normal_cdf((-bankroll + win_rate * n) / spread)
+    } else {
+        0.0
+    };
+    (normal_cdf((-bankroll - win_rate * n) / spread) + reflected_tail).clamp(0.0, 1.0)
+}
+
+/**
+ * Returns N0, the number of rounds after which the expected win equals one standard deviation,
+ * or None if the player does not have the edge
+ */
+pub fn n_zero(win_rate: f64, standard_deviation: f64) -> Option<f64> {
+    if win_rate <= 0.0 {
+        None
+    } else {
+        Some(standard_deviation * standard_deviation / (win_rate * win_rate))
+    }
+}
+
+/**
+ * Returns the bankroll giving the specified lifetime risk of ruin,
+ * or None if no bankroll is enough because the player does not have the edge
+ */
+pub fn required_bankroll(win_rate: f64, standard_deviation: f64, risk: f64) -> Option<f64> {
+    if win_rate <= 0.0 {
+        None
+    } else {
+        Some(-standard_deviation * standard_deviation * risk.ln() / (2.0 * win_rate))
+    }
+}
+
+/**
+ * The bankrolls played through in a ruin simulation and how many of them were lost
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RuinResult {
+    pub trials: u64,
+    pub ruined: u64,
+}
+impl RuinResult {
+    pub fn rate(&self) -> f64 {
+        if self.trials == 0 {
+            0.0
+        } else {
+            self.ruined as f64 / self.trials as f64
+        }
+    }
+
+    /**
+     * Returns the half width of the 95% confidence interval of the ruin rate
+     */
+    pub fn confidence_interval(&self) -> f64 {
+        if self.trials == 0 {
+            return 0.0;
+        }
+        let rate = self.rate();
+        1.96 * (rate * (1.0 - rate) / self.trials as f64).sqrt()
+    }
+}
+
+// Returns a normally distributed number (Box-Muller)
+fn normal_sample(rng: &mut StdRng, mean: f64, standard_deviation: f64) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    mean + standard_deviation * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+// Plays bankrolls through the engine with the configured player, returning the number lost
+fn engine_ruin_worker(config: &RiskConfig, seed: u64, trials: u64) -> u64 {
+    let simulation = &config.simulation;
+    let mut game = Blackjack::with_seed(simulation.rules, seed);
+    let player = simulation.player.build(&simulation.strategy, worker_seed(seed, 0));
+    let mut seat = Seat::new(player, simulation.system, simulation.bankroll);
+    // Rounds are played through the simulator, which needs somewhere to record them
+    let mut totals = SimulationResult::default();
+    let mut ruined = 0;
+
+    for _ in 0..trials {
+        seat.bankroll = simulation.bankroll;
+        for _ in 0..config.rounds() {
+            play_round(&mut game, &mut seat, &mut totals);
+            if seat.bankroll <= 0.0 {
+                ruined += 1;
+                break;
+            }
+        }
+    }
+    ruined
+}
+
+// Plays bankrolls as random walks with normally distributed rounds, returning the number lost
+fn random_walk_ruin_worker(config: &RiskConfig, seed: u64, trials: u64, win_rate: f64, standard_deviation: f64) -> u64 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut ruined = 0;
+
+    for _ in 0..trials {
+        let mut bankroll = config.simulation.bankroll;
+        for _ in 0..config.rounds() {
+            bankroll += normal_sample(&mut rng, win_rate, standard_deviation);
+            if bankroll <= 0.0 {
+                ruined += 1;
+                break;
+            }
+        }
+    }
+    ruined
+}
+
+/**
+ * Simulates ruin by playing the configured number of bankrolls for the configured number of rounds each,
+ * split across the simulation's threads. Simulated win rates play the engine with the configured player;
+ * supplied win rates play random walks with normally distributed rounds.
+ */
+pub fn simulate_ruin(config: &RiskConfig) -> RuinResult {
+    let threads = config.simulation.threads;
+    let shares = worker_rounds(config.trials, threads);
+    let seed = config.simulation.seed;
+
+    let ruined: u64 = thread::scope(|scope| {
+        let workers: Vec<_> = shares
+            .iter()
+            .enumerate()
+            .map(|(worker, &trials)| {
+                // Offset from the win rate simulation's workers so the ruin trials see other shoes
+                let seed = worker_seed(seed, threads + worker);
+                scope.spawn(move || match config.source {
+                    WinRateSource::Simulated => engine_ruin_worker(config, seed, trials),
+                    WinRateSource::Supplied { win_rate, standard_deviation } => {
+                        random_walk_ruin_worker(config, seed, trials, win_rate, standard_deviation)
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().expect("ruin worker panicked")).sum()
+    });
+
+    RuinResult { trials: config.trials, ruined }
+}
+
+/**
+ * The figures of a bankroll calculation
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RiskReport {
+    pub win_rate: f64,
+    pub standard_deviation: f64,
+    pub n_zero: Option<f64>,
+    pub hourly_win: f64,
+    pub hourly_standard_deviation: f64,
+    pub lifetime_risk: f64,
+    pub trip_risk: f64,
+    pub required_bankroll: Option<f64>,
+    pub simulated: Option<RuinResult>,
+}
+impl RiskReport {
+    /**
+     * Calculates the figures from a win rate and standard deviation per round
+     */
+    pub fn calculate(config: &RiskConfig, win_rate: f64, standard_deviation: f64) -> Self {
+        let bankroll = config.simulation.bankroll;
+
+        RiskReport {
+            win_rate,
+            standard_deviation,
+            n_zero: n_zero(win_rate, standard_deviation),
+            hourly_win: win_rate * config.rounds_per_hour,
+            hourly_standard_deviation: standard_deviation * config.rounds_per_hour.sqrt(),
+            lifetime_risk: risk_of_ruin(win_rate, standard_deviation, bankroll),
+            trip_risk: trip_risk_of_ruin(win_rate, standard_deviation, bankroll, config.rounds()),
+            required_bankroll: required_bankroll(win_rate, standard_deviation, config.target_risk),
+            simulated: None,
+        }
+    }
+}
+
+/**
+ * Measures the win rate if it was not supplied, calculates the figures and simulates ruin
+ */
+pub fn calculate(config: &RiskConfig) -> (Option<SimulationResult>, RiskReport) {
+    let (measured, win_rate, standard_deviation) = match config.source {
+        WinRateSource::Supplied { win_rate, standard_deviation } => (None, win_rate, standard_deviation),
+        WinRateSource::Simulated => {
+            let result = simulation::simulate(&config.simulation);
+            (Some(result), result.mean(), result.standard_deviation())
+        }
+    };
+
+    let mut report = RiskReport::calculate(config, win_rate, standard_deviation);
+    if config.trials > 0 {
+        report.simulated = Some(simulate_ruin(config));
+    }
+    (measured, report)
+}
+
+// Formats a probability as a percentage, showing tiny risks without rounding them to zero
+fn percent(probability: f64) -> String {
+    if probability > 0.0 && probability < 0.0001 {
+        "<0.01%".to_string()
+    } else {
+        format!("{:.2}%", 100.0 * probability)
+    }
+}
+
+/**
+ * Renders the figures of a bankroll calculation, with the formula and simulated risks side by side
+ */
+pub fn render_report(config: &RiskConfig, measured: Option<&SimulationResult>, report: &RiskReport) -> String {
+    let mut out = match measured {
+        Some(result) => format!(
+            "Strategy: {}\nRules:    {}\nWin rate and standard deviation simulated over {} rounds\n\n",
+            config.simulation.player.build(&config.simulation.strategy, config.simulation.seed).name(),
+            config.simulation.rules,
+            result.rounds
+        ),
+        None => "Win rate and standard deviation supplied\n\n".to_string(),
+    };
+
+    out.push_str(&format!("Win rate:            {:+.4} units per round\n", report.win_rate));
+    out.push_str(&format!("Std dev:             {:.4} units per round\n", report.standard_deviation));
+    match report.n_zero {
+        Some(n_zero) => out.push_str(&format!(
+            "N0:                  {:.0} rounds ({:.0} hours)\n",
+            n_zero,
+            n_zero / config.rounds_per_hour
+        )),
+        None => out.push_str("N0:                  never, the player has no edge\n"),
+    }
+    out.push_str(&format!(
+        "Expected win:        {:+.2} units per hour at {} rounds per hour\n",
+        report.hourly_win, config.rounds_per_hour
+    ));
+    out.push_str(&format!("Std dev per hour:    {:.2} units\n\n", report.hourly_standard_deviation));
+
+    out.push_str(&format!(
+        "Bankroll of {} units over {} sessions of {} hours ({} rounds)\n",
+        config.simulation.bankroll,
+        config.sessions,
+        config.hours_per_session,
+        config.rounds()
+    ));
+    out.push_str(&format!("{:<22}{:>10}   {}\n", "Risk of ruin", "formula", "simulated"));
+    let simulated = match report.simulated {
+        Some(ruin) => format!(
+            "{} +/- {} ({} trials)",
+            percent(ruin.rate()),
+            percent(ruin.confidence_interval()),
+            ruin.trials
+        ),
+        None => "-".to_string(),
+    };
+    out.push_str(&format!("{:<22}{:>10}   {}\n", "  over these sessions", percent(report.trip_risk), simulated));
+    out.push_str(&format!("{:<22}{:>10}\n\n", "  playing forever", percent(report.lifetime_risk)));
+
+    match report.required_bankroll {
+        Some(bankroll) => out.push_str(&format!(
+            "Bankroll for a {} lifetime risk: {:.0} units\n",
+            percent(config.target_risk),
+            bankroll.ceil()
+        )),
+        None => out.push_str("No bankroll is enough: the player has no edge, so ruin is certain in the long run\n"),
+    }
+    out
+}
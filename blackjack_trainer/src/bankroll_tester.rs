@@ -0,0 +1,105 @@
+use crate::bankroll::{
+    calculate, n_zero, normal_cdf, render_report, required_bankroll, risk_of_ruin, simulate_ruin, trip_risk_of_ruin,
+    RiskConfig, RiskReport, RuinResult, WinRateSource,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+        assert!((normal_cdf(-1.0) - 0.158_655).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_formulas() {
+        // A 1% edge with a standard deviation of 1.1 units
+        assert!((risk_of_ruin(0.01, 1.1, 100.0) - (-2.0f64 / 1.21).exp()).abs() < 1e-12);
+        assert_eq!(risk_of_ruin(-0.005, 1.1, 1000.0), 1.0);
+        assert!((n_zero(0.01, 1.1).unwrap() - 12100.0).abs() < 1e-6);
+        assert_eq!(n_zero(0.0, 1.1), None);
+
+        let bankroll = required_bankroll(0.01, 1.1, 0.05).unwrap();
+        assert!((risk_of_ruin(0.01, 1.1, bankroll) - 0.05).abs() < 1e-12);
+        assert_eq!(required_bankroll(-0.01, 1.1, 0.05), None);
+    }
+
+    #[test]
+    fn test_trip_risk_grows_towards_lifetime_risk() {
+        let short = trip_risk_of_ruin(0.01, 1.1, 100.0, 1000);
+        let long = trip_risk_of_ruin(0.01, 1.1, 100.0, 100_000);
+        let lifetime = risk_of_ruin(0.01, 1.1, 100.0);
+
+        assert_eq!(trip_risk_of_ruin(0.01, 1.1, 100.0, 0), 0.0);
+        assert!(short > 0.0 && short < long);
+        assert!((long - lifetime).abs() < 1e-3);
+        // Without an edge the risk still takes time to build up
+        assert!(trip_risk_of_ruin(-0.005, 1.1, 1000.0, 1000) < 0.01);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = RiskConfig::parse(&args("bankroll 400 sessions 20 hours 2 rate 80 target 1 player deviations")).unwrap();
+
+        assert_eq!(config.simulation.bankroll, 400.0);
+        assert_eq!((config.sessions, config.hours_per_session, config.rounds_per_hour), (20, 2.0, 80.0));
+        assert_eq!(config.target_risk, 0.01);
+        assert_eq!(config.rounds(), 3200);
+        assert_eq!(config.source, WinRateSource::Simulated);
+
+        let config = RiskConfig::parse(&args("win-rate 0.01 sd 1.15")).unwrap();
+        assert_eq!(config.source, WinRateSource::Supplied { win_rate: 0.01, standard_deviation: 1.15 });
+        assert_eq!(RiskConfig::parse(&args("win-rate 0.01")).err().unwrap(), "win-rate and sd must be given together");
+        assert_eq!(RiskConfig::parse(&args("target 100")).err().unwrap(), "invalid target risk 100");
+        assert_eq!(RiskConfig::parse(&args("sessions")).err().unwrap(), "sessions needs a value");
+        assert_eq!(RiskConfig::parse(&args("fast 1")).err().unwrap(), "unknown option fast");
+    }
+
+    #[test]
+    fn test_simulated_ruin_matches_formula() {
+        let config = RiskConfig::parse(&args(
+            "win-rate 0.02 sd 1.1 bankroll 25 sessions 1 hours 10 rate 200 trials 2000 threads 2 seed 4",
+        ))
+        .unwrap();
+        let report = RiskReport::calculate(&config, 0.02, 1.1);
+        let ruin = simulate_ruin(&config);
+
+        assert_eq!(ruin.trials, 2000);
+        // Checking the bankroll after each round rather than continuously misses a few ruins
+        assert!((ruin.rate() - report.trip_risk).abs() < 0.05, "simulated {} formula {}", ruin.rate(), report.trip_risk);
+        assert_eq!(simulate_ruin(&config), ruin);
+    }
+
+    #[test]
+    fn test_engine_ruin_report() {
+        let config = RiskConfig::parse(&args(
+            "hands 20000 bankroll 20 sessions 1 hours 2 trials 100 threads 1 seed 9",
+        ))
+        .unwrap();
+        let (measured, report) = calculate(&config);
+        let ruin = report.simulated.unwrap();
+
+        assert_eq!(measured.unwrap().rounds, 20000);
+        assert!(ruin.ruined > 0 && ruin.ruined < ruin.trials);
+        let text = render_report(&config, measured.as_ref(), &report);
+        assert!(text.contains("Risk of ruin"));
+        assert!(text.contains("(100 trials)"));
+        assert!(text.contains("N0:"));
+    }
+
+    #[test]
+    fn test_ruin_confidence_interval() {
+        let ruin = RuinResult { trials: 400, ruined: 100 };
+
+        assert_eq!(ruin.rate(), 0.25);
+        assert!((ruin.confidence_interval() - 1.96 * (0.25f64 * 0.75 / 400.0).sqrt()).abs() < 1e-12);
+        assert_eq!(RuinResult::default().confidence_interval(), 0.0);
+    }
+}
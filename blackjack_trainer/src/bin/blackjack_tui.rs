@@ -0,0 +1,67 @@
+use blackjack_trainer::blackjack::{Blackjack, Rules};
+use blackjack_trainer::blackjackbasicstrategy::BlackjackBasicStrategy;
+use blackjack_trainer::cardstyle::CardStyle;
+use blackjack_trainer::cli::TableFlags;
+use blackjack_trainer::history::{HistoryWriter, SessionRecord, HISTORY_FORMAT};
+use blackjack_trainer::input::Keymap;
+use blackjack_trainer::tui::{self, TableApp};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+ * Parses the table flags of the full-screen table, e.g. --decks 2 --h17 --bankroll 500 --seed 7 --cards suits
+ * Returns the rules, the chart to follow, the starting bankroll, the seed of the shoe and the card style
+ */
+fn parse_args(args: &[String]) -> Result<(Rules, BlackjackBasicStrategy, f64, u64, CardStyle), String> {
+    let (flags, rest) = TableFlags::parse(args)?;
+    if let Some(arg) = rest.first() {
+        return Err(format!("unknown option {}", arg));
+    }
+    let rules = flags.rules();
+    let strategy = flags.strategy(&rules)?;
+    let cards = flags.cards.unwrap_or_else(CardStyle::detect);
+    Ok((rules, strategy, flags.bankroll.unwrap_or(100.0), flags.seed.unwrap_or_else(rand::random), cards))
+}
+
+/**
+ * A full-screen blackjack table for the terminal, graded against basic strategy, with every round
+ * kept in the hand history like the text interface
+ */
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (rules, strategy, bankroll, seed, cards) = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let keymap = match Keymap::load() {
+        Ok(keymap) => keymap,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut app = TableApp::new(Blackjack::with_seed(rules, seed), strategy, bankroll)
+        .with_keymap(keymap)
+        .with_card_style(cards);
+    let session = SessionRecord {
+        format: HISTORY_FORMAT,
+        started: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+        seed,
+        mode: "tui".to_string(),
+        rules,
+    };
+    match HistoryWriter::create(&session) {
+        Ok(writer) => app = app.with_history(writer),
+        Err(err) => eprintln!("Could not start the hand history: {}", err),
+    }
+
+    if let Err(err) = tui::run(&mut app) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    println!("Thanks for playing. Bankroll: {}", app.get_bankroll());
+}
@@ -1,11 +1,51 @@
+use crate::sidebet::SideBet;
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
+/**
+ * The suit of a card. Suits do not affect play; they are kept so cards display as they would be dealt.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Suit {
+    Hearts,
+    Diamonds,
+    Clubs,
+    Spades,
+}
+impl Suit {
+    pub const ALL: [Suit; 4] = [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades];
+
+    /**
+     * Returns the 1 character abbreviation of this suit: H, D, C or S
+     */
+    pub fn abbreviation(&self) -> char {
+        match self {
+            Suit::Hearts => 'H',
+            Suit::Diamonds => 'D',
+            Suit::Clubs => 'C',
+            Suit::Spades => 'S',
+        }
+    }
+
+    /**
+     * Returns true for the red suits, hearts and diamonds
+     */
+    pub fn is_red(&self) -> bool {
+        matches!(self, Suit::Hearts | Suit::Diamonds)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Card{
-    suit: String,
+    suit: Suit,
     rank: i64,
 }
 impl Card {
-    pub fn new(suit: String, rank: i64) -> Self {
+    pub fn new(suit: Suit, rank: i64) -> Self {
         Card{
             suit,
             rank
@@ -13,11 +53,30 @@ impl Card {
     }
 
     /**
-     * Returns this card with the 1 or 2 character value (A, 2-10, J, Q, K)
+     * Returns the rank of this card: 1 for an ace, 2-10, 11 for a jack, 12 for a queen, 13 for a king
+     */
+    pub fn get_rank(&self) -> i64 {
+        self.rank
+    }
+
+    pub fn get_suit(&self) -> Suit {
+        self.suit
+    }
+
+    /**
+     * Returns the blackjack value of this card, counting an ace as 1 and face cards as 10
+     */
+    pub fn get_value(&self) -> i32 {
+        self.rank.min(10) as i32
+    }
+}
+impl fmt::Display for Card {
+    /**
+     * Formats this card with the 1 or 2 character value (A, 2-10, J, Q, K)
      * followed by the 1 character suit (D, H, S, C)
      * Examples: JD, 10H, AS, 9C
      */
-    pub fn to_string(&self) -> String {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut card = String::new();
 
         if self.rank == 1 {
@@ -31,19 +90,64 @@ impl Card {
         } else {
             card.push_str(&self.rank.to_string());
         }
-        return format!("{}{}", card, &self.suit)
+        write!(f, "{}{}", card, self.suit.abbreviation())
     }
 }
+impl FromStr for Card {
+    type Err = String;
 
+    /**
+     * Parses a card in the format it is displayed in
+     * Examples: JD, 10H, AS, 9C
+     */
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid card {}", text);
+        let suit_char = text.chars().last().ok_or_else(invalid)?;
+        let suit = Suit::ALL.into_iter().find(|suit| suit.abbreviation() == suit_char).ok_or_else(invalid)?;
+        let rank = match &text[..text.len() - suit_char.len_utf8()] {
+            "A" => 1,
+            "J" => 11,
+            "Q" => 12,
+            "K" => 13,
+            number => number.parse().ok().filter(|rank| (2..=10).contains(rank)).ok_or_else(invalid)?,
+        };
+        Ok(Card::new(suit, rank))
+    }
+}
+// Cards are stored as the text they are displayed as, e.g. "10H"
+impl Serialize for Card {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for Card {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Hand{
-    cards: Vec<Card>
+    cards: Vec<Card>,
+    split: bool,
+    doubled: bool,
+    surrendered: bool,
+    // The seat the hand is played from, 0 unless several seats are dealt
+    seat: usize,
 }
 impl Hand {
     pub fn new(card1: Card, card2: Card) -> Self {
-        let mut cards = Vec::new();
-        cards.push(card1);
-        cards.push(card2);
-        Hand{ cards }
+        let cards = vec![card1, card2];
+        Hand{ cards, split: false, doubled: false, surrendered: false, seat: 0 }
+    }
+
+    /**
+     * Constructs one of the two hands made by splitting a pair, holding one of the paired cards
+     * @param card the card from the pair
+     */
+    fn from_split(card: Card, seat: usize) -> Self {
+        Hand{ cards: vec![card], split: true, doubled: false, surrendered: false, seat }
     }
 
     /**
@@ -71,7 +175,18 @@ impl Hand {
             sum += 10
         }
 
-        return sum
+        sum
+    }
+
+    /**
+     * Returns true if this hand is soft (contains an ace counted as 11), false otherwise
+     * @return true if this hand is soft, false otherwise
+     */
+    pub fn is_soft(&self) -> bool {
+        let hard_sum: i64 = self.cards.iter().map(|card| card.rank.min(10)).sum();
+        let has_ace = self.cards.iter().any(|card| card.rank == 1);
+
+        has_ace && hard_sum < 12
     }
 
     /**
@@ -79,26 +194,50 @@ impl Hand {
      * @return true if this hand is a blackjack, false otherwise
      */
     pub fn is_blackjack(&self) -> bool {
-        if self.cards.len() == 2 && self.get_value() == 21 {
-            return true;
-        }
-        return false;
+        // 21 on two cards after a split is not a blackjack
+        self.cards.len() == 2 && self.get_value() == 21 && !self.split
     }
-    
+
     /**
-     * Returns the cards in this hand followed by their numerical value
-     * Ex: JS AH (21)
+     * Returns the seat this hand is played from
      */
-    pub fn to_string(&self) -> String {
-        let mut hand = String::new();
+    pub fn get_seat(&self) -> usize {
+        self.seat
+    }
 
-        for card in &self.cards  {
-            hand.push_str(&card.to_string());
-            hand.push(' ');
-        }
+    /**
+     * Returns true if this hand was made by splitting a pair, false otherwise
+     */
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
 
-        let result = format!("{}({})", hand, self.get_value());
-        return result;
+    /**
+     * Returns true if the bet on this hand was doubled, false otherwise
+     */
+    pub fn is_doubled(&self) -> bool {
+        self.doubled
+    }
+
+    /**
+     * Returns true if this hand was surrendered for half its bet, false otherwise
+     */
+    pub fn is_surrendered(&self) -> bool {
+        self.surrendered
+    }
+
+    /**
+     * Returns the cards in this hand in the order they were dealt
+     */
+    pub fn get_cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    /**
+     * Returns the blackjack values of the cards in this hand, as used by the strategy
+     */
+    pub fn get_card_values(&self) -> Vec<i32> {
+        self.cards.iter().map(Card::get_value).collect()
     }
 
     /**
@@ -109,10 +248,26 @@ impl Hand {
         self.cards.push(card);
     }
 }
+impl fmt::Display for Hand {
+    /**
+     * Formats the cards in this hand followed by their numerical value
+     * Ex: JS AH (21)
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for card in &self.cards  {
+            write!(f, "{} ", card)?;
+        }
+
+        write!(f, "({})", self.get_value())
+    }
+}
 
 pub struct Shoe{
     decks: usize,
-    shoe: Vec<Card>
+    shoe: Vec<Card>,
+    rng: StdRng,
+    // The number of times this shoe has been shuffled
+    shuffles: u64,
 } 
 impl Shoe {
     /**
@@ -121,10 +276,25 @@ impl Shoe {
      * @param decks the number of decks
      */
     pub fn new(init_decks: usize) -> Self {
-        let init_shoe = Vec::<Card>::new();
+        Self::with_rng(init_decks, StdRng::from_entropy())
+    }
+
+    /**
+     * Constructs a shoe whose shuffles are determined by the seed, so the same seed
+     * always deals the same cards.
+     * @param decks the number of decks
+     * @param seed the seed of the shuffles
+     */
+    pub fn with_seed(init_decks: usize, seed: u64) -> Self {
+        Self::with_rng(init_decks, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(init_decks: usize, rng: StdRng) -> Self {
         let mut new_shoe = Shoe{
         decks: init_decks,
-        shoe: init_shoe
+        shoe: Vec::with_capacity(init_decks * CARDS_PER_DECK),
+        rng,
+        shuffles: 0,
         };
         new_shoe.reset();
         new_shoe
@@ -143,7 +313,39 @@ impl Shoe {
      * @return the number of cards left in this shoe
      */
     pub fn cards_left(&self) -> usize {
-        return self.shoe.len();
+        self.shoe.len()
+    }
+
+    /**
+     * Returns the number of cards of each value left in this shoe: index 0 is the ace, index 9 the ten-valued cards
+     */
+    pub fn composition(&self) -> [u32; 10] {
+        composition_of(&self.shoe)
+    }
+
+    /**
+     * Places the specified cards on top of this shoe so they are dealt next, in order.
+     * Each card is moved from the shoe if it is still in it, so the shoe keeps its composition.
+     * @param cards the cards to deal next, first card first
+     */
+    pub fn stack(&mut self, cards: Vec<Card>) {
+        for card in &cards {
+            if let Some(index) = self.shoe.iter().position(|in_shoe| in_shoe == card) {
+                self.shoe.remove(index);
+            }
+        }
+        // Cards are dealt from the end of the shoe
+        self.shoe.extend(cards.into_iter().rev());
+    }
+
+    /**
+     * Adds the specified cards to the cards left in this shoe and shuffles it
+     * @param cards the cards to shuffle in
+     */
+    pub fn shuffle_in(&mut self, cards: Vec<Card>) {
+        self.shoe.extend(cards);
+        self.shoe.shuffle(&mut self.rng);
+        self.shuffles += 1;
     }
 
     /**
@@ -151,166 +353,827 @@ impl Shoe {
      * This shoe will be shuffled.
      */
     pub fn reset(&mut self) {
-        let suits = ["H", "D", "C", "S"];
         self.shoe.clear();
 
-        for suit in suits {
+        for suit in Suit::ALL {
             for value in 1..=13 {
                 for _ in 0..self.decks {
                     self.shoe.push( Card {
-                        suit: suit.to_string(),
+                        suit,
                         rank: value,
                     });
                 }
             }
         }
 
-        let mut rng = thread_rng();
-        self.shoe.shuffle(&mut rng);
+        self.shoe.shuffle(&mut self.rng);
+        self.shuffles += 1;
+    }
+
+    /**
+     * Returns the number of times this shoe has been shuffled, 1 for a new shoe
+     */
+    pub fn get_shuffles(&self) -> u64 {
+        self.shuffles
+    }
+}
+
+pub const CARDS_PER_DECK: usize = 52;
+
+/**
+ * The table rules a game is played under.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+    pub decks: usize,
+    pub dealer_hits_soft_17: bool,
+    pub double_after_split: bool,
+    pub blackjack_payout: f64,
+    // Fraction of the shoe dealt before it is reshuffled
+    pub penetration: f64,
+    // Late surrender: the first two cards can be given up for half the bet once the dealer has checked for blackjack
+    #[serde(default)]
+    pub surrender: bool,
+}
+impl Default for Rules {
+    /**
+     * Six decks, dealer stands on soft 17, double after split, no surrender, blackjack pays 3:2
+     * and the shoe is reshuffled once three quarters of it have been dealt.
+     */
+    fn default() -> Self {
+        Rules {
+            decks: 6,
+            dealer_hits_soft_17: false,
+            double_after_split: true,
+            blackjack_payout: 1.5,
+            penetration: 0.75,
+            surrender: false,
+        }
+    }
+}
+impl fmt::Display for Rules {
+    /**
+     * Formats these rules the way they are written on a table layout
+     * Ex: 6 decks, S17, DAS, BJ pays 3:2, 75% penetration, or 6 decks, H17, DAS, LS, BJ pays 3:2, 75% penetration
+     * with late surrender
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let payout = if self.blackjack_payout == 1.5 {
+            "3:2".to_string()
+        } else if self.blackjack_payout == 1.2 {
+            "6:5".to_string()
+        } else {
+            format!("{}:1", self.blackjack_payout)
+        };
+
+        write!(
+            f,
+            "{} deck{}, {}, {}{}, BJ pays {}, {:.0}% penetration",
+            self.decks,
+            if self.decks == 1 { "" } else { "s" },
+            if self.dealer_hits_soft_17 { "H17" } else { "S17" },
+            if self.double_after_split { "DAS" } else { "no DAS" },
+            if self.surrender { ", LS" } else { "" },
+            payout,
+            self.penetration * 100.0
+        )
+    }
+}
+
+/**
+ * The decisions a player can make on a hand
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+}
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Action::Hit => "hit",
+            Action::Stand => "stand",
+            Action::Double => "double",
+            Action::Split => "split",
+            Action::Surrender => "surrender",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ * The result of a player's hand once the dealer's hand has been played
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandOutcome {
+    Blackjack,
+    Win,
+    Push,
+    Loss,
+    Bust,
+    Surrender,
+}
+
+/**
+ * The probability of each way the dealer's hand can finish
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DealerFinals {
+    // The probability of finishing on 17, 18, 19, 20 and 21 without a blackjack
+    pub totals: [f64; 5],
+    pub bust: f64,
+    pub blackjack: f64,
+}
+
+// Counts the cards of each value, index 0 being the ace
+fn composition_of(cards: &[Card]) -> [u32; 10] {
+    let mut composition = [0; 10];
+    for card in cards {
+        composition[(card.get_value() - 1) as usize] += 1;
+    }
+    composition
+}
+
+/**
+ * Returns the exact probabilities of the dealer's final hand, drawing without replacement from a shoe.
+ * Without peek a blackjack is one of the outcomes; with peek the dealer is known not to have one.
+ * @param composition the number of cards of each value in the shoe, the hole card included and the upcard
+ *                    removed: index 0 is the ace, index 9 the ten-valued cards
+ * @param upcard the value of the dealer's upcard (1 for an ace)
+ * @param hits_soft_17 true if the dealer hits soft 17
+ * @param peek true if the dealer has checked for blackjack and does not have it
+ */
+pub fn dealer_final_probabilities(composition: &[u32; 10], upcard: i32, hits_soft_17: bool, peek: bool) -> DealerFinals {
+    let mut counts = *composition;
+    let total: u32 = counts.iter().sum();
+    let mut finals = DealerFinals::default();
+    let mut memo = HashMap::new();
+
+    for index in 0..10 {
+        if counts[index] == 0 {
+            continue;
+        }
+        let probability = counts[index] as f64 / total as f64;
+        let hole = index as i32 + 1;
+        if (upcard == 1 && hole == 10) || (upcard == 10 && hole == 1) {
+            finals.blackjack += probability;
+            continue;
+        }
+
+        counts[index] -= 1;
+        let next = dealer_finish(&mut counts, upcard + hole, upcard == 1 || hole == 1, hits_soft_17, 1 << (5 * index), &mut memo);
+        counts[index] += 1;
+        for (total, next_probability) in finals.totals.iter_mut().zip(next) {
+            *total += probability * next_probability;
+        }
+        finals.bust += probability * next[5];
+    }
+
+    if peek && finals.blackjack < 1.0 {
+        let no_blackjack = 1.0 - finals.blackjack;
+        finals.totals = finals.totals.map(|probability| probability / no_blackjack);
+        finals.bust /= no_blackjack;
+        finals.blackjack = 0.0;
     }
+    finals
 }
 
-const DECKS: usize = 6;
-const CARDS_PER_DECK: usize = 54;
+// Returns the probabilities of finishing on 17-21 and busting from a hand, drawing from the counts.
+// The drawn key packs the number of cards of each value drawn, which fixes both the hand and the counts left.
+fn dealer_finish(
+    counts: &mut [u32; 10],
+    hard_sum: i32,
+    has_ace: bool,
+    hits_soft_17: bool,
+    drawn: u64,
+    memo: &mut HashMap<u64, [f64; 6]>,
+) -> [f64; 6] {
+    let soft = has_ace && hard_sum + 10 <= 21;
+    let total = if soft { hard_sum + 10 } else { hard_sum };
+
+    if total > 21 {
+        return [0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    }
+    if total > 17 || (total == 17 && !(soft && hits_soft_17)) {
+        let mut outcomes = [0.0; 6];
+        outcomes[(total - 17) as usize] = 1.0;
+        return outcomes;
+    }
+    if let Some(&outcomes) = memo.get(&drawn) {
+        return outcomes;
+    }
+
+    // A shoe that runs out leaves the hand unfinished, so its outcomes add up to less than one
+    let mut outcomes = [0.0; 6];
+    let left: u32 = counts.iter().sum();
+    for index in 0..10 {
+        if counts[index] == 0 {
+            continue;
+        }
+        let probability = counts[index] as f64 / left as f64;
+        let card = index as i32 + 1;
+
+        counts[index] -= 1;
+        let next = dealer_finish(counts, hard_sum + card, has_ace || card == 1, hits_soft_17, drawn + (1 << (5 * index)), memo);
+        counts[index] += 1;
+        for (outcome, next_probability) in outcomes.iter_mut().zip(next) {
+            *outcome += probability * next_probability;
+        }
+    }
+
+    memo.insert(drawn, outcomes);
+    outcomes
+}
+
+// Splitting is allowed until the player holds this many hands
+const MAX_HANDS: usize = 4;
 
 pub struct Blackjack {
+    rules: Rules,
     shoe: Shoe,
-    player_hand: Option<Hand>,
-    dealer_hand: Option<Hand>,
+    pub(crate) player_hands: Vec<Hand>,
+    // Index of the hand the player is playing; equal to the number of hands once the player is done
+    current_hand: usize,
+    pub(crate) dealer_hand: Option<Hand>,
+    // Cards from the rounds played since the shoe was last shuffled
+    discards: Vec<Card>,
+    // Cards dealt this round, in the order they were dealt
+    dealt: Vec<Card>,
+    // The first two cards of the first seat's hand, which the side bets are settled on whatever splits follow
+    starting_cards: Option<[Card; 2]>,
+    // The side bets the player makes every round
+    side_bets: Vec<SideBet>,
+    // True once the player has taken insurance this round
+    insured: bool,
 }
 impl Blackjack {
     pub fn new() -> Self {
-        let init_shoe = Shoe::new(DECKS);
+        Self::with_rules(Rules::default())
+    }
+
+    /**
+     * Constructs a game played under the specified rules
+     * @param rules the table rules
+     */
+    pub fn with_rules(rules: Rules) -> Self {
+        Self::with_shoe(rules, Shoe::new(rules.decks))
+    }
 
-        let new_blackjack = Blackjack{
+    /**
+     * Constructs a game whose shoe is shuffled from the seed, so the same seed always deals the same rounds
+     * @param rules the table rules
+     * @param seed the seed of the shoe's shuffles
+     */
+    pub fn with_seed(rules: Rules, seed: u64) -> Self {
+        Self::with_shoe(rules, Shoe::with_seed(rules.decks, seed))
+    }
+
+    fn with_shoe(rules: Rules, init_shoe: Shoe) -> Self {
+        Blackjack{
+            rules,
             shoe: init_shoe,
-            player_hand: None,
+            player_hands: Vec::new(),
+            current_hand: 0,
             dealer_hand: None,
-        };
-        new_blackjack
+            discards: Vec::new(),
+            dealt: Vec::new(),
+            starting_cards: None,
+            side_bets: Vec::new(),
+            insured: false,
+        }
     }
 
     /**
     * Resets for another round, including reseting shoe if necessary
     */
     pub fn reset(&mut self) {
-        if self.shoe.cards_left() as f64 / (CARDS_PER_DECK as f64 * DECKS as f64) <= 0.25 {
-            self.shoe.reset()
+        if self.shoe.cards_left() as f64 / (CARDS_PER_DECK as f64 * self.rules.decks as f64) <= 1.0 - self.rules.penetration {
+            self.shoe.reset();
+            self.discards.clear();
+        }
+    }
+
+    /**
+     * Moves the cards of the last round to the discards and reshuffles the shoe if necessary,
+     * so the table is ready for the next bet
+     */
+    pub fn clear_table(&mut self) {
+        for hand in self.player_hands.drain(..) {
+            self.discards.extend(hand.cards);
         }
+        if let Some(hand) = self.dealer_hand.take() {
+            self.discards.extend(hand.cards);
+        }
+        self.current_hand = 0;
+        self.dealt.clear();
+        self.starting_cards = None;
+        self.insured = false;
+        self.reset();
+    }
+
+    /**
+     * Places the specified cards on top of the shoe so the next round deals them in order:
+     * player, player, dealer upcard, dealer hole card, then any cards drawn.
+     * The shoe is reshuffled first if it has reached the cut card.
+     * @param cards the cards to deal next, first card first
+     */
+    pub fn stack_cards(&mut self, cards: Vec<Card>) {
+        self.clear_table();
+        self.shoe.stack(cards);
     }
 
     /**
      * Deals cards to the player and dealer.
      */
     pub fn deal_cards(&mut self) {
-        self.reset();
+        self.clear_table();
+
+        let card1 = self.draw_card();
+        let card2 = self.draw_card();
+        let card3 = self.draw_card();
+        let card4 = self.draw_card();
+        self.starting_cards = Some([card1, card2]);
 
-        let card1 = self.shoe.deal_card().expect("No more cards in the deck");
-        let card2 = self.shoe.deal_card().expect("No more cards in the deck");
-        let card3 = self.shoe.deal_card().expect("No more cards in the deck");
-        let card4 = self.shoe.deal_card().expect("No more cards in the deck");
- 
-        self.player_hand = Some(Hand::new(card1, card2));
+        self.player_hands = vec![Hand::new(card1, card2)];
+        self.current_hand = 0;
         self.dealer_hand = Some(Hand::new(card3, card4));
+        self.skip_finished_hands();
+    }
+
+    /**
+     * Deals a round to several seats as a table does: a card to each seat in turn, the dealer's upcard,
+     * a second card to each seat, then the dealer's hole card. The seats' hands are played in seat order,
+     * each tagged with the seat from 0, so the current hand also says whose turn it is.
+     * @param seats the number of seats dealt in
+     */
+    pub fn deal_seats(&mut self, seats: usize) {
+        self.clear_table();
+
+        let first: Vec<Card> = (0..seats).map(|_| self.draw_card()).collect();
+        let upcard = self.draw_card();
+        let hands: Vec<Hand> = first
+            .into_iter()
+            .enumerate()
+            .map(|(seat, card)| Hand { seat, ..Hand::new(card, self.draw_card()) })
+            .collect();
+        let hole_card = self.draw_card();
+
+        self.starting_cards = hands.first().map(|hand| [hand.cards[0], hand.cards[1]]);
+        self.player_hands = hands;
+        self.current_hand = 0;
+        self.dealer_hand = Some(Hand::new(upcard, hole_card));
+        self.skip_finished_hands();
+    }
+
+    // Deals the next card of the shoe. A round that uses up the shoe has the discards of the earlier rounds
+    // shuffled back in, as a dealer would, while the cards on the table stay in play.
+    fn draw_card(&mut self) -> Card {
+        if self.shoe.cards_left() == 0 {
+            self.shoe.shuffle_in(std::mem::take(&mut self.discards));
+        }
+        let card = self.shoe.deal_card().expect("No more cards in the deck");
+        self.dealt.push(card);
+        card
+    }
+
+    /**
+     * Returns true if the player still has a hand to play, false otherwise.
+     * The player has nothing to play when the dealer has blackjack.
+     */
+    pub fn is_players_turn(&self) -> bool {
+        let dealer_hand = self.dealer_hand.as_ref().expect("NONE");
+
+        self.current_hand < self.player_hands.len() && !dealer_hand.is_blackjack()
     }
 
     /**
      * Returns true if the player can hit, false otherwise
      */
     pub fn can_hit(&self) -> bool {
-        let hand1 = &self.player_hand.as_ref().expect("NONE");
-        let hand2 = &self.dealer_hand.as_ref().expect("NONE");
-        
-        return hand1.get_value() < 21 && !hand2.is_blackjack();
+        self.is_players_turn()
+    }
+
+    /**
+     * Returns true if the player can double the current hand, false otherwise.
+     * Only the first two cards can be doubled, and split hands only with DAS.
+     */
+    pub fn can_double(&self) -> bool {
+        if !self.is_players_turn() {
+            return false;
+        }
+        let hand = &self.player_hands[self.current_hand];
+
+        hand.cards.len() == 2 && (!hand.split || self.rules.double_after_split)
+    }
+
+    /**
+     * Returns true if the player can split the current hand, false otherwise.
+     * Any two cards of the same value can be split, up to four hands in each seat.
+     */
+    pub fn can_split(&self) -> bool {
+        if !self.is_players_turn() {
+            return false;
+        }
+        let hand = &self.player_hands[self.current_hand];
+        let seat_hands = self.player_hands.iter().filter(|other| other.seat == hand.seat).count();
+
+        seat_hands < MAX_HANDS && hand.cards.len() == 2 && hand.cards[0].get_value() == hand.cards[1].get_value()
+    }
+
+    /**
+     * Returns true if the player can surrender the current hand, false otherwise.
+     * Only the first two cards of a seat's only hand can be surrendered, and only under late surrender.
+     */
+    pub fn can_surrender(&self) -> bool {
+        if !self.rules.surrender || !self.is_players_turn() {
+            return false;
+        }
+        let hand = &self.player_hands[self.current_hand];
+        let seat_hands = self.player_hands.iter().filter(|other| other.seat == hand.seat).count();
+
+        seat_hands == 1 && hand.cards.len() == 2
+    }
+
+    /**
+     * Returns true if the player can take insurance, false otherwise: the dealer shows an ace and
+     * the player has not yet acted on the hand, whether or not the dealer has blackjack.
+     * Insurance is only offered when a single seat is dealt.
+     */
+    pub fn can_insure(&self) -> bool {
+        let (Some(dealer_hand), [hand]) = (self.dealer_hand.as_ref(), self.player_hands.as_slice()) else {
+            return false;
+        };
+        let undecided = hand.cards.len() == 2 && !hand.doubled && !hand.surrendered && (self.current_hand == 0 || hand.is_blackjack());
+
+        !self.insured && dealer_hand.cards.len() == 2 && dealer_hand.cards[0].rank == 1 && undecided
+    }
+
+    /**
+     * Takes insurance: a side bet of half the initial bet that pays 2:1 if the dealer has blackjack.
+     * 
+     * Precondition: canInsure()
+     */
+    pub fn insure(&mut self) {
+        self.insured = true;
+    }
+
+    /**
+     * Returns true if the player took insurance this round, false otherwise
+     */
+    pub fn is_insured(&self) -> bool {
+        self.insured
+    }
+
+    /**
+     * Returns the amount won or lost on insurance this round in units of the initial bet:
+     * 1 if the dealer has blackjack, -0.5 if not and 0 without insurance
+     */
+    pub fn insurance_result(&self) -> f64 {
+        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
+
+        match (self.insured, dealer_hand.is_blackjack()) {
+            (false, _) => 0.0,
+            (true, true) => 1.0,
+            (true, false) => -0.5,
+        }
     }
 
     /**
-     * Deals another card to the player's hand.
+     * Returns the actions the player can take on the current hand
+     */
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.can_hit() {
+            actions.push(Action::Hit);
+            actions.push(Action::Stand);
+        }
+        if self.can_double() {
+            actions.push(Action::Double);
+        }
+        if self.can_split() {
+            actions.push(Action::Split);
+        }
+        if self.can_surrender() {
+            actions.push(Action::Surrender);
+        }
+        actions
+    }
+
+    /**
+     * Takes the specified action on the current hand
+     * 
+     * Precondition: legal_actions() contains the action
+     */
+    pub fn act(&mut self, action: Action) {
+        match action {
+            Action::Hit => self.hit(),
+            Action::Stand => self.stand(),
+            Action::Double => self.double(),
+            Action::Split => self.split(),
+            Action::Surrender => self.surrender(),
+        }
+    }
+
+    /**
+     * Deals another card to the player's hand, moving on once it reaches 21 or busts.
      * 
      * Precondition: canHit()
      */
     pub fn hit(&mut self) {
-        self.player_hand.as_mut().expect("NONE").add_card(self.shoe.deal_card().expect("EMPTY"))
+        let card = self.draw_card();
+        self.player_hands[self.current_hand].add_card(card);
+
+        if self.player_hands[self.current_hand].get_value() >= 21 {
+            self.next_hand();
+        }
     }
 
     /**
-     * Plays the dealer's hand.
+     * Stands on the current hand.
+     * 
+     * Precondition: canHit()
      */
-    pub fn play_dealers_hand(&mut self) {
-        // Make sure dealer's hand exists.
-        let dealer_hand = self.dealer_hand.as_mut().expect("Dealer hand is not initialized");
+    pub fn stand(&mut self) {
+        self.next_hand();
+    }
 
-        // While the value of the dealer's hand is less than 17, continue to deal cards
-        while dealer_hand.get_value() < 17 {
-            let card = self.shoe.deal_card(); // Deal a card from the shoe
-            println!("Dealer gets: {}", card.as_ref().expect("nothing").to_string());
+    /**
+     * Doubles the bet on the current hand, which receives exactly one more card.
+     * 
+     * Precondition: canDouble()
+     */
+    pub fn double(&mut self) {
+        let card = self.draw_card();
+        let hand = &mut self.player_hands[self.current_hand];
+        hand.add_card(card);
+        hand.doubled = true;
 
-            dealer_hand.add_card(card.expect("None")); // Add it to the dealer's hand
-        }
+        self.next_hand();
+    }
 
-        // Print the dealer's final hand
-        println!("Dealer has: {}", dealer_hand.to_string());
+    /**
+     * Gives up the current hand for half its bet.
+     * 
+     * Precondition: canSurrender()
+     */
+    pub fn surrender(&mut self) {
+        self.player_hands[self.current_hand].surrendered = true;
+        self.next_hand();
     }
 
     /**
-     * Returns true if the player's hand is a push, false otherwise
+     * Splits the current hand into two hands, each of which receives a second card when it is played.
+     * Split aces receive one card each and cannot be played further.
+     * 
+     * Precondition: canSplit()
      */
-    pub fn is_push(&self) -> bool {
-        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
-        let player_hand = self.player_hand.as_ref().expect("Player hand is not initialized");
+    pub fn split(&mut self) {
+        let hand = &mut self.player_hands[self.current_hand];
+        let second = hand.cards.pop().expect("pair");
+        hand.split = true;
+        let seat = hand.seat;
+        self.player_hands.insert(self.current_hand + 1, Hand::from_split(second, seat));
 
-        // Condition 1: Dealer has blackjack, player does not, and player has 21
-        if dealer_hand.is_blackjack() && !player_hand.is_blackjack() && player_hand.get_value() == 21 {
-            return false;
+        self.deal_second_card();
+        self.skip_finished_hands();
+    }
+
+    // Deals the second card to a hand made by a split
+    fn deal_second_card(&mut self) {
+        if self.current_hand < self.player_hands.len() && self.player_hands[self.current_hand].cards.len() == 1 {
+            let card = self.draw_card();
+            self.player_hands[self.current_hand].add_card(card);
         }
+    }
 
-        // Condition 2: Player has blackjack, dealer does not, and dealer has 21
-        if player_hand.is_blackjack() && !dealer_hand.is_blackjack() && dealer_hand.get_value() == 21 {
-            return false;
+    fn next_hand(&mut self) {
+        self.current_hand += 1;
+        self.deal_second_card();
+        self.skip_finished_hands();
+    }
+
+    // Moves past hands that need no decisions: 21, blackjack and split aces
+    fn skip_finished_hands(&mut self) {
+        while self.current_hand < self.player_hands.len() {
+            let hand = &self.player_hands[self.current_hand];
+            let split_aces = hand.split && hand.cards[0].rank == 1;
+
+            if hand.get_value() < 21 && !split_aces {
+                break;
+            }
+            self.current_hand += 1;
+            self.deal_second_card();
         }
+    }
 
-        // Condition 3: It's a push if both hands have the same value
-        return dealer_hand.get_value() == player_hand.get_value();
+    /**
+     * Returns true if the dealer needs to draw: some player hand is neither bust, surrendered nor a blackjack,
+     * which is paid without the dealer drawing, or a Buster Blackjack bet is settled on the dealer's final hand
+     */
+    pub fn dealer_needs_to_play(&self) -> bool {
+        self.player_hands.iter().any(|hand| hand.get_value() <= 21 && !hand.surrendered && !hand.is_blackjack())
+            || self.side_bets.contains(&SideBet::BusterBlackjack)
     }
 
     /**
-     * Returns true if the player's hand is a player win, false otherwise
+     * Plays the dealer's hand: the dealer draws to 17 (and on soft 17 under H17) unless
+     * every player hand is bust or a blackjack.
      */
-    pub fn is_player_win(&self) -> bool {
-        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
-        let player_hand = self.player_hand.as_ref().expect("Player hand is not initialized");
+    pub fn play_dealers_hand(&mut self) {
+        if !self.dealer_needs_to_play() {
+            return;
+        }
 
-        // Dealer has blackjack, player loses
-        if dealer_hand.is_blackjack() {
-            return false;
+        // While the value of the dealer's hand is less than 17 (or soft 17 under H17), continue to deal cards
+        while self.dealer_draws() {
+            let card = self.draw_card();
+            self.dealer_hand.as_mut().expect("Dealer hand is not initialized").add_card(card);
         }
+    }
 
-        // Player has blackjack, player wins
-        if player_hand.is_blackjack() {
-            return true;
+    // Returns true if the dealer's hand is below 17, or soft 17 under H17
+    fn dealer_draws(&self) -> bool {
+        // Make sure dealer's hand exists.
+        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
+
+        dealer_hand.get_value() < 17 || (self.rules.dealer_hits_soft_17 && dealer_hand.get_value() == 17 && dealer_hand.is_soft())
+    }
+
+    /**
+     * Returns the exact probabilities of the dealer's final hand as the player sees it: from the shoe
+     * with the dealer's hole card and any cards the dealer has drawn still unseen,
+     * or None if no cards have been dealt
+     * @param peek true if the dealer has checked for blackjack and does not have it
+     */
+    pub fn dealer_final_probabilities(&self, peek: bool) -> Option<DealerFinals> {
+        let dealer_hand = self.dealer_hand.as_ref()?;
+        let mut composition = self.shoe.composition();
+        for (index, count) in composition_of(&dealer_hand.cards[1..]).iter().enumerate() {
+            composition[index] += count;
         }
+        Some(dealer_final_probabilities(&composition, dealer_hand.cards[0].get_value(), self.rules.dealer_hits_soft_17, peek))
+    }
 
-        // Dealer busts (over 21) and player does not bust
-        if dealer_hand.get_value() > 21 && player_hand.get_value() <= 21 {
-            return true;
+    /**
+     * Sets the side bets the player makes every round, 1 unit each
+     */
+    pub fn set_side_bets(&mut self, side_bets: Vec<SideBet>) {
+        self.side_bets = side_bets;
+    }
+
+    pub fn get_side_bets(&self) -> &[SideBet] {
+        &self.side_bets
+    }
+
+    /**
+     * Returns the amount won or lost on each side bet this round, in units of the side bet,
+     * once the dealer's hand has been played. At a table of several seats the side bets are the first seat's.
+     */
+    pub fn side_bet_results(&self) -> Vec<(SideBet, f64)> {
+        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
+        let player = self.starting_cards.expect("Player hand is not initialized");
+        self.side_bets.iter().map(|&bet| (bet, bet.settle(player, &dealer_hand.cards))).collect()
+    }
+
+    /**
+     * Returns the outcome of the specified player hand against the dealer's hand
+     * @param index the index of the player's hand
+     */
+    pub fn outcome(&self, index: usize) -> HandOutcome {
+        let dealer_hand = self.dealer_hand.as_ref().expect("Dealer hand is not initialized");
+        let player_hand = &self.player_hands[index];
+
+        if player_hand.surrendered {
+            HandOutcome::Surrender
+        } else if player_hand.get_value() > 21 {
+            HandOutcome::Bust
+        } else if player_hand.is_blackjack() && dealer_hand.is_blackjack() {
+            HandOutcome::Push
+        } else if player_hand.is_blackjack() {
+            HandOutcome::Blackjack
+        } else if dealer_hand.is_blackjack() {
+            HandOutcome::Loss
+        } else if dealer_hand.get_value() > 21 || player_hand.get_value() > dealer_hand.get_value() {
+            HandOutcome::Win
+        } else if player_hand.get_value() == dealer_hand.get_value() {
+            HandOutcome::Push
+        } else {
+            HandOutcome::Loss
         }
+    }
+
+    /**
+     * Returns the amount won or lost on the specified hand in units of the initial bet,
+     * e.g. 1.5 for a blackjack paying 3:2, -2 for a lost double and -0.5 for a surrender.
+     * Insurance is settled separately by insurance_result.
+     * @param index the index of the player's hand
+     */
+    pub fn net_result(&self, index: usize) -> f64 {
+        let bet = if self.player_hands[index].doubled { 2.0 } else { 1.0 };
 
-        // Player has a higher hand value than the dealer and doesn't bust
-        if player_hand.get_value() > dealer_hand.get_value() && player_hand.get_value() <= 21 {
-            return true;
+        match self.outcome(index) {
+            HandOutcome::Blackjack => self.rules.blackjack_payout,
+            HandOutcome::Win => bet,
+            HandOutcome::Push => 0.0,
+            HandOutcome::Loss | HandOutcome::Bust => -bet,
+            HandOutcome::Surrender => -0.5,
         }
+    }
+
+    /**
+     * Returns true if the player's hand is a push, false otherwise
+     */
+    pub fn is_push(&self) -> bool {
+        self.outcome(0) == HandOutcome::Push
+    }
+
+    /**
+     * Returns true if the player's hand is a player win, false otherwise
+     */
+    pub fn is_player_win(&self) -> bool {
+        matches!(self.outcome(0), HandOutcome::Win | HandOutcome::Blackjack)
+    }
 
-        // In all other cases, player loses
-        return false;
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
     }
 
+    /**
+     * Returns the hand the player is playing, or the last hand once the player is done
+     */
     pub fn get_players_hand(&self) -> Option<&Hand> {
-        return self.player_hand.as_ref();
+        let index = self.current_hand.min(self.player_hands.len().max(1) - 1);
+        self.player_hands.get(index)
+    }
+
+    /**
+     * Returns all of the player's hands, more than one after a split
+     */
+    pub fn get_players_hands(&self) -> &[Hand] {
+        &self.player_hands
+    }
+
+    /**
+     * Returns the index of the hand the player is playing
+     */
+    pub fn get_current_hand_index(&self) -> usize {
+        self.current_hand
+    }
+
+    /**
+     * Returns the seat whose hand is being played, or None once every hand has been played
+     */
+    pub fn get_current_seat(&self) -> Option<usize> {
+        self.player_hands.get(self.current_hand).filter(|_| self.is_players_turn()).map(Hand::get_seat)
     }
 
     pub fn get_dealers_hand(&self) -> Option<&Hand> {
-        return self.dealer_hand.as_ref();
+        self.dealer_hand.as_ref()
     }
 
-}
+    /**
+     * Returns the dealer's face up card, the first card dealt to the dealer
+     */
+    pub fn get_dealers_upcard(&self) -> Option<&Card> {
+        self.dealer_hand.as_ref().map(|hand| &hand.cards[0])
+    }
+
+    /**
+     * Returns the cards of the earlier rounds dealt from this shoe, in the order they were discarded
+     */
+    pub fn get_discards(&self) -> &[Card] {
+        &self.discards
+    }
+
+    /**
+     * Returns the number of cards left in the shoe
+     */
+    pub fn cards_left(&self) -> usize {
+        self.shoe.cards_left()
+    }
+
+    /**
+     * Returns the cards dealt this round, in the order they were dealt
+     */
+    pub fn get_dealt_cards(&self) -> &[Card] {
+        &self.dealt
+    }
 
+    /**
+     * Returns where the game is in its shoe: the shoe number (1 for the first shuffle) and
+     * the number of cards dealt from it
+     */
+    pub fn shoe_position(&self) -> (u64, usize) {
+        (self.shoe.get_shuffles(), self.rules.decks * CARDS_PER_DECK - self.shoe.cards_left())
+    }
+}
+impl Default for Blackjack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
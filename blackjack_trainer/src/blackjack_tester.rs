@@ -1,4 +1,5 @@
-use crate::blackjack::{Card, Hand, Shoe, Blackjack};
+use crate::blackjack::{dealer_final_probabilities, Action, Blackjack, Card, Hand, HandOutcome, Rules, Shoe, Suit};
+use crate::ev::{dealer_outcomes, draw_probabilities};
 
 #[cfg(test)]
 mod tests {
@@ -6,26 +7,26 @@ mod tests {
 
     #[test]
     fn test_card_creation() {
-        let card = Card::new("H".to_string(), 1);
+        let card = Card::new(Suit::Hearts, 1);
         assert_eq!(card.to_string(), "AH");
 
-        let card = Card::new("D".to_string(), 13);
+        let card = Card::new(Suit::Diamonds, 13);
         assert_eq!(card.to_string(), "KD");
 
-        let card = Card::new("C".to_string(), 10);
+        let card = Card::new(Suit::Clubs, 10);
         assert_eq!(card.to_string(), "10C");
     }
 
     #[test]
     fn test_hand_value() {
-        let card1 = Card::new("H".to_string(), 1); // Ace
-        let card2 = Card::new("S".to_string(), 10);
+        let card1 = Card::new(Suit::Hearts, 1); // Ace
+        let card2 = Card::new(Suit::Spades, 10);
         let hand = Hand::new(card1, card2);
         assert_eq!(hand.get_value(), 21);
         assert!(hand.is_blackjack());
 
-        let card3 = Card::new("D".to_string(), 7);
-        let card4 = Card::new("C".to_string(), 6);
+        let card3 = Card::new(Suit::Diamonds, 7);
+        let card4 = Card::new(Suit::Clubs, 6);
         let hand2 = Hand::new(card3, card4);
         assert_eq!(hand2.get_value(), 13);
         assert!(!hand2.is_blackjack());
@@ -33,8 +34,8 @@ mod tests {
 
     #[test]
     fn test_hand_add_card() {
-        let mut hand = Hand::new(Card::new("H".to_string(), 5), Card::new("S".to_string(), 6));
-        hand.add_card(Card::new("D".to_string(), 10));
+        let mut hand = Hand::new(Card::new(Suit::Hearts, 5), Card::new(Suit::Spades, 6));
+        hand.add_card(Card::new(Suit::Diamonds, 10));
         assert_eq!(hand.get_value(), 21);
         assert!(!hand.is_blackjack()); // More than two cards, so not a blackjack
     }
@@ -61,7 +62,7 @@ mod tests {
     #[test]
     fn test_blackjack_deal_and_play() {
         let mut game = Blackjack::new();
-        game.deal_card();
+        game.deal_cards();
 
         let player_hand = game.get_players_hand();
         let dealer_hand = game.get_dealers_hand();
@@ -81,8 +82,8 @@ mod tests {
         let mut game = Blackjack::new();
         
         // Manually set a winning condition for the player
-        game.player_hand = Some(Hand::new(Card::new("H".to_string(), 10), Card::new("S".to_string(), 10)));
-        game.dealer_hand = Some(Hand::new(Card::new("D".to_string(), 9), Card::new("C".to_string(), 7)));
+        game.player_hands = vec![Hand::new(Card::new(Suit::Hearts, 10), Card::new(Suit::Spades, 10))];
+        game.dealer_hand = Some(Hand::new(Card::new(Suit::Diamonds, 9), Card::new(Suit::Clubs, 7)));
         
         assert!(game.is_player_win());
     }
@@ -92,8 +93,8 @@ mod tests {
         let mut game = Blackjack::new();
         
         // Set up a push condition where both have the same value
-        game.player_hand = Some(Hand::new(Card::new("H".to_string(), 10), Card::new("S".to_string(), 10)));
-        game.dealer_hand = Some(Hand::new(Card::new("D".to_string(), 10), Card::new("C".to_string(), 10)));
+        game.player_hands = vec![Hand::new(Card::new(Suit::Hearts, 10), Card::new(Suit::Spades, 10))];
+        game.dealer_hand = Some(Hand::new(Card::new(Suit::Diamonds, 10), Card::new(Suit::Clubs, 10)));
         
         assert!(game.is_push());
     }
@@ -101,11 +102,355 @@ mod tests {
     #[test]
     fn test_blackjack_dealer_plays_hand() {
         let mut game = Blackjack::new();
-        game.deal_card();
-        
+        game.deal_cards();
+        // The dealer does not draw against a blackjack
+        while game.get_players_hand().unwrap().is_blackjack() {
+            game.deal_cards();
+        }
+
         // Check if dealer plays to 17 or higher
         game.play_dealers_hand();
         let dealer_hand = game.get_dealers_hand().unwrap();
         assert!(dealer_hand.get_value() >= 17);
     }
+
+    fn card(rank: i64) -> Card {
+        Card::new(Suit::Spades, rank)
+    }
+
+    fn stacked_game(rules: Rules, ranks: &[i64]) -> Blackjack {
+        let mut game = Blackjack::with_rules(rules);
+        game.stack_cards(ranks.iter().map(|&rank| card(rank)).collect());
+        game.deal_cards();
+        game
+    }
+
+    #[test]
+    fn test_stacked_deal() {
+        let game = stacked_game(Rules::default(), &[10, 6, 9, 7]);
+
+        assert_eq!(game.get_players_hand().unwrap().to_string(), "10S 6S (16)");
+        assert_eq!(game.get_dealers_upcard().unwrap().to_string(), "9S");
+        assert_eq!(game.get_dealers_hand().unwrap().get_value(), 16);
+    }
+
+    #[test]
+    fn test_stack_keeps_shoe_composition() {
+        let mut shoe = Shoe::new(1);
+        shoe.stack(vec![card(1), card(1)]);
+
+        // The first ace of spades is moved from the shoe, the second is an extra card
+        assert_eq!(shoe.cards_left(), 53);
+        assert_eq!(shoe.deal_card(), Some(card(1)));
+    }
+
+    #[test]
+    fn test_double() {
+        let mut game = stacked_game(Rules::default(), &[6, 5, 10, 7, 9]);
+        assert_eq!(game.legal_actions(), vec![Action::Hit, Action::Stand, Action::Double]);
+
+        game.act(Action::Double);
+        assert!(!game.is_players_turn());
+        assert!(game.get_players_hand().unwrap().is_doubled());
+        assert_eq!(game.get_players_hand().unwrap().get_value(), 20);
+
+        assert_eq!(game.outcome(0), HandOutcome::Win);
+        assert_eq!(game.net_result(0), 2.0);
+    }
+
+    #[test]
+    fn test_split_plays_each_hand() {
+        let mut game = stacked_game(Rules::default(), &[8, 8, 10, 9, 3, 10, 10]);
+        assert!(game.can_split());
+
+        game.act(Action::Split);
+        assert_eq!(game.get_players_hands().len(), 2);
+        assert_eq!(game.get_current_hand_index(), 0);
+        assert_eq!(game.get_players_hand().unwrap().get_value(), 11);
+
+        // Doubling after split is allowed by default
+        assert!(game.can_double());
+        game.act(Action::Double);
+        assert_eq!(game.get_current_hand_index(), 1);
+        assert_eq!(game.get_players_hand().unwrap().get_value(), 18);
+        assert!(!game.can_split());
+
+        game.act(Action::Stand);
+        assert!(!game.is_players_turn());
+        assert_eq!(game.net_result(0), 2.0);
+        assert_eq!(game.outcome(1), HandOutcome::Loss);
+    }
+
+    #[test]
+    fn test_split_aces_get_one_card() {
+        let mut game = stacked_game(Rules::default(), &[1, 1, 10, 7, 10, 5]);
+        game.act(Action::Split);
+
+        assert!(!game.is_players_turn());
+        let hands = game.get_players_hands();
+        assert_eq!(hands[0].get_value(), 21);
+        assert!(!hands[0].is_blackjack());
+        assert_eq!(hands[1].get_value(), 16);
+        assert_eq!(game.outcome(0), HandOutcome::Win);
+        assert_eq!(game.net_result(0), 1.0);
+    }
+
+    #[test]
+    fn test_no_double_after_split() {
+        let rules = Rules { double_after_split: false, ..Rules::default() };
+        let mut game = stacked_game(rules, &[8, 8, 10, 9, 3, 10]);
+        game.act(Action::Split);
+
+        assert!(!game.can_double());
+        assert_eq!(game.legal_actions(), vec![Action::Hit, Action::Stand]);
+    }
+
+    #[test]
+    fn test_dealer_blackjack_ends_the_round() {
+        let game = stacked_game(Rules::default(), &[10, 9, 1, 10]);
+
+        assert!(!game.is_players_turn());
+        assert!(game.legal_actions().is_empty());
+        assert_eq!(game.outcome(0), HandOutcome::Loss);
+    }
+
+    #[test]
+    fn test_late_surrender() {
+        let rules = Rules { surrender: true, ..Rules::default() };
+        let mut game = stacked_game(rules, &[10, 6, 10, 9, 5]);
+        assert_eq!(game.legal_actions(), vec![Action::Hit, Action::Stand, Action::Double, Action::Surrender]);
+
+        game.act(Action::Surrender);
+        assert!(!game.is_players_turn());
+        assert!(!game.dealer_needs_to_play());
+        game.play_dealers_hand();
+        assert_eq!(game.outcome(0), HandOutcome::Surrender);
+        assert_eq!(game.net_result(0), -0.5);
+
+        // Only the first two cards of an unsplit hand, and only where the table offers it
+        let mut game = stacked_game(rules, &[8, 8, 10, 9, 3, 10]);
+        assert!(game.can_surrender());
+        game.act(Action::Split);
+        assert!(!game.can_surrender());
+        let mut game = stacked_game(rules, &[10, 2, 10, 9, 3]);
+        game.act(Action::Hit);
+        assert!(!game.can_surrender());
+        assert!(!stacked_game(Rules::default(), &[10, 6, 10, 9]).can_surrender());
+    }
+
+    #[test]
+    fn test_insurance() {
+        // Offered against an ace before the player acts, and paid 2:1 on half the bet when the dealer has blackjack
+        let mut game = stacked_game(Rules::default(), &[10, 9, 1, 13]);
+        assert!(game.can_insure());
+        game.insure();
+        assert!(!game.can_insure());
+        assert_eq!(game.net_result(0) + game.insurance_result(), 0.0);
+
+        let mut game = stacked_game(Rules::default(), &[10, 9, 1, 7]);
+        assert!(game.is_players_turn());
+        assert_eq!(game.insurance_result(), 0.0);
+        game.insure();
+        game.act(Action::Stand);
+        game.play_dealers_hand();
+        assert_eq!(game.outcome(0), HandOutcome::Win);
+        assert_eq!(game.insurance_result(), -0.5);
+
+        // Even money is insurance on a blackjack
+        assert!(stacked_game(Rules::default(), &[1, 13, 1, 7]).can_insure());
+        assert!(!stacked_game(Rules::default(), &[10, 9, 10, 1]).can_insure());
+        let mut game = stacked_game(Rules::default(), &[10, 2, 1, 7, 3]);
+        game.act(Action::Hit);
+        assert!(!game.can_insure());
+    }
+
+    #[test]
+    fn test_blackjack_payout() {
+        let rules = Rules { blackjack_payout: 1.2, ..Rules::default() };
+        let game = stacked_game(rules, &[1, 13, 10, 9]);
+
+        assert!(!game.is_players_turn());
+        assert_eq!(game.outcome(0), HandOutcome::Blackjack);
+        assert_eq!(game.net_result(0), 1.2);
+    }
+
+    #[test]
+    fn test_dealer_does_not_draw_against_a_blackjack() {
+        let mut game = stacked_game(Rules::default(), &[1, 13, 10, 6, 5]);
+
+        assert!(!game.dealer_needs_to_play());
+        game.play_dealers_hand();
+        assert_eq!(game.get_dealers_hand().unwrap().get_cards().len(), 2);
+        assert_eq!(game.outcome(0), HandOutcome::Blackjack);
+    }
+
+    #[test]
+    fn test_both_bust_is_not_a_push() {
+        let mut game = Blackjack::new();
+        game.player_hands = vec![Hand::new(card(10), card(10))];
+        game.player_hands[0].add_card(card(4));
+        let mut dealer_hand = Hand::new(card(10), card(6));
+        dealer_hand.add_card(card(8));
+        game.dealer_hand = Some(dealer_hand);
+
+        assert_eq!(game.outcome(0), HandOutcome::Bust);
+        assert!(!game.is_push());
+        assert!(!game.dealer_needs_to_play());
+    }
+
+    #[test]
+    fn test_dealer_hits_soft_17() {
+        let rules = Rules { dealer_hits_soft_17: true, ..Rules::default() };
+        let mut game = stacked_game(rules, &[10, 8, 1, 6, 2]);
+        game.act(Action::Stand);
+        game.play_dealers_hand();
+        assert_eq!(game.get_dealers_hand().unwrap().get_value(), 19);
+
+        let mut game = stacked_game(Rules::default(), &[10, 8, 1, 6, 2]);
+        game.act(Action::Stand);
+        game.play_dealers_hand();
+        assert_eq!(game.get_dealers_hand().unwrap().get_value(), 17);
+    }
+
+    #[test]
+    fn test_seeded_shoes_deal_the_same_cards() {
+        let mut first = Shoe::with_seed(6, 99);
+        let mut second = Shoe::with_seed(6, 99);
+        let mut other = Shoe::with_seed(6, 100);
+
+        let deal = |shoe: &mut Shoe| (0..20).map(|_| shoe.deal_card().unwrap()).collect::<Vec<Card>>();
+        let cards = deal(&mut first);
+        assert_eq!(cards, deal(&mut second));
+        assert_ne!(cards, deal(&mut other));
+
+        first.reset();
+        second.reset();
+        assert_eq!(deal(&mut first), deal(&mut second));
+    }
+
+    #[test]
+    fn test_card_suits() {
+        assert_eq!(Card::new(Suit::Spades, 12).to_string(), "QS");
+        assert_eq!(Suit::ALL.map(|suit| suit.abbreviation()), ['H', 'D', 'C', 'S']);
+    }
+
+    // A full shoe of the specified number of decks with one upcard removed
+    fn shoe_without(decks: u32, upcard: i32) -> [u32; 10] {
+        let mut composition = [4 * decks; 10];
+        composition[9] = 16 * decks;
+        composition[(upcard - 1) as usize] -= 1;
+        composition
+    }
+
+    #[test]
+    fn test_dealer_finals_draw_without_replacement() {
+        // A 10 and a 7 left: the hole card is one of them and the dealer stands
+        let mut composition = [0; 10];
+        composition[9] = 1;
+        composition[6] = 1;
+        let finals = dealer_final_probabilities(&composition, 10, false, false);
+        assert_eq!(finals.totals, [0.5, 0.0, 0.0, 0.5, 0.0]);
+
+        // Two tens left behind a 6: the dealer makes 16 and must bust on the other ten
+        let mut composition = [0; 10];
+        composition[9] = 2;
+        let finals = dealer_final_probabilities(&composition, 6, false, false);
+        assert_eq!((finals.bust, finals.blackjack), (1.0, 0.0));
+
+        // An 8 and a ten behind an ace: blackjack half the time, soft 19 otherwise
+        let mut composition = [0; 10];
+        composition[7] = 1;
+        composition[9] = 1;
+        let finals = dealer_final_probabilities(&composition, 1, true, false);
+        assert_eq!((finals.blackjack, finals.totals[2]), (0.5, 0.5));
+        let peeked = dealer_final_probabilities(&composition, 1, true, true);
+        assert_eq!((peeked.blackjack, peeked.totals[2]), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_dealer_finals_from_a_full_shoe() {
+        for upcard in 1..=10 {
+            for peek in [false, true] {
+                let finals = dealer_final_probabilities(&shoe_without(6, upcard), upcard, false, peek);
+                let total = finals.totals.iter().sum::<f64>() + finals.bust + finals.blackjack;
+                assert!((total - 1.0).abs() < 1e-12, "upcard {} peek {}", upcard, peek);
+            }
+        }
+
+        // The exact figures are close to the infinite deck approximation for six decks
+        let approximate = dealer_outcomes(&draw_probabilities(6, &[6]), 6, false);
+        let six = dealer_final_probabilities(&shoe_without(6, 6), 6, false, true);
+        assert!((six.bust - approximate[5]).abs() < 0.005);
+        assert!((six.bust - 0.4228).abs() < 0.0001, "{}", six.bust);
+
+        // 96 of the 311 cards behind an ace are tens
+        let ace = dealer_final_probabilities(&shoe_without(6, 1), 1, false, false);
+        assert!((ace.blackjack - 96.0 / 311.0).abs() < 1e-12);
+        let h17 = dealer_final_probabilities(&shoe_without(6, 6), 6, true, true);
+        assert!(h17.totals[0] < six.totals[0] && h17.bust > six.bust);
+    }
+
+    #[test]
+    fn test_dealer_finals_of_a_game() {
+        let game = stacked_game(Rules::default(), &[10, 6, 9, 7]);
+        let finals = game.dealer_final_probabilities(true).unwrap();
+        let mut composition = Shoe::new(6).composition();
+        assert_eq!(composition.iter().sum::<u32>(), 312);
+
+        // The player's cards and the upcard are out of the shoe, the hole card is not
+        for value in [10, 6, 9] {
+            composition[value - 1] -= 1;
+        }
+        assert_eq!(finals, dealer_final_probabilities(&composition, 9, false, true));
+        assert!(Blackjack::new().dealer_final_probabilities(true).is_none());
+    }
+
+    #[test]
+    fn test_parse_cards() {
+        assert_eq!("10H".parse::<Card>(), Ok(Card::new(Suit::Hearts, 10)));
+        assert_eq!("AS".parse::<Card>(), Ok(Card::new(Suit::Spades, 1)));
+        assert_eq!("QD".parse::<Card>().unwrap().to_string(), "QD");
+        assert_eq!("1C".parse::<Card>(), Err("invalid card 1C".to_string()));
+        assert_eq!("KX".parse::<Card>(), Err("invalid card KX".to_string()));
+        assert_eq!("".parse::<Card>(), Err("invalid card ".to_string()));
+    }
+
+    #[test]
+    fn test_deal_seats_in_table_order() {
+        let mut game = Blackjack::new();
+        let cards = ["10H", "8C", "9D", "6S", "8D", "7C", "5H", "10S", "10C"];
+        game.stack_cards(cards.iter().map(|card| card.parse().unwrap()).collect());
+        game.deal_seats(2);
+
+        // A card to each seat, the upcard, a second card to each seat, then the hole card
+        let hands = game.get_players_hands();
+        assert_eq!(hands[0].to_string(), "10H 6S (16)");
+        assert_eq!(hands[1].to_string(), "8C 8D (16)");
+        assert_eq!(game.get_dealers_hand().unwrap().to_string(), "9D 7C (16)");
+        assert_eq!(game.get_current_seat(), Some(0));
+
+        game.act(Action::Stand);
+        assert_eq!(game.get_current_seat(), Some(1));
+        game.act(Action::Split);
+        let hands = game.get_players_hands();
+        assert_eq!(hands.iter().map(Hand::get_seat).collect::<Vec<_>>(), vec![0, 1, 1]);
+        assert_eq!(hands[1].to_string(), "8C 5H (13)");
+
+        game.act(Action::Stand);
+        game.act(Action::Stand);
+        assert_eq!(game.get_current_seat(), None);
+        assert_eq!(game.get_players_hands()[2].to_string(), "8D 10S (18)");
+    }
+
+    #[test]
+    fn test_dealt_cards_and_shoe_position() {
+        let mut game = stacked_game(Rules::default(), &[10, 6, 9, 7, 5]);
+        game.act(Action::Hit);
+
+        assert_eq!(game.get_dealt_cards().iter().map(Card::get_rank).collect::<Vec<_>>(), vec![10, 6, 9, 7, 5]);
+        assert_eq!(game.shoe_position(), (1, 5));
+        game.clear_table();
+        assert!(game.get_dealt_cards().is_empty());
+        assert_eq!(game.get_discards().len(), 5);
+    }
 }
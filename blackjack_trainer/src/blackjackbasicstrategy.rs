@@ -1,11 +1,184 @@
+use crate::blackjack::{Action, Rules};
+use crate::ev::{ActionEvs, EvCalculator};
+use std::fmt;
+use std::fs;
+
+/**
+ * Plays stored in the strategy tables.
+ * The "or" plays fall back to the second action when the first is not allowed,
+ * e.g. doubling after the third card or surrendering where the table does not offer it.
+ */
+pub const HIT: i32 = 1;
+pub const STAND: i32 = 2;
+pub const DOUBLE_OR_HIT: i32 = 3;
+pub const DOUBLE_OR_STAND: i32 = 4;
+pub const SPLIT: i32 = 5;
+pub const SURRENDER_OR_HIT: i32 = 6;
+pub const SURRENDER_OR_STAND: i32 = 7;
+
+/**
+ * Returns the chart abbreviation of a play: H, S, D, Ds, P, Rh or Rs
+ */
+pub fn play_abbreviation(play: i32) -> &'static str {
+  match play {
+      HIT => "H",
+      STAND => "S",
+      DOUBLE_OR_HIT => "D",
+      DOUBLE_OR_STAND => "Ds",
+      SPLIT => "P",
+      SURRENDER_OR_HIT => "Rh",
+      SURRENDER_OR_STAND => "Rs",
+      _ => "?",
+  }
+}
+
+/**
+ * Returns the play for a chart abbreviation, ignoring case, or None if it is not one
+ */
+pub fn parse_play(abbreviation: &str) -> Option<i32> {
+  match abbreviation.to_lowercase().as_str() {
+      "h" => Some(HIT),
+      "s" => Some(STAND),
+      "d" => Some(DOUBLE_OR_HIT),
+      "ds" => Some(DOUBLE_OR_STAND),
+      "p" => Some(SPLIT),
+      "rh" => Some(SURRENDER_OR_HIT),
+      "rs" => Some(SURRENDER_OR_STAND),
+      _ => None,
+  }
+}
+
+/**
+ * Returns the name of a play as shown to the player
+ */
+pub fn play_name(play: i32) -> &'static str {
+  match play {
+      HIT => "hit",
+      STAND => "stand",
+      DOUBLE_OR_HIT => "double (otherwise hit)",
+      DOUBLE_OR_STAND => "double (otherwise stand)",
+      SPLIT => "split",
+      SURRENDER_OR_HIT => "surrender (otherwise hit)",
+      SURRENDER_OR_STAND => "surrender (otherwise stand)",
+      _ => "unknown",
+  }
+}
+
+/**
+ * The chart row a player's hand is looked up in.
+ * Hard and soft rows hold the hand total (hard 4-21, soft 12-21),
+ * pair rows hold the value of the paired card (1 for aces, 2-10).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HandCategory {
+  Hard(i32),
+  Soft(i32),
+  Pair(i32),
+}
+
+/**
+ * A single cell of the strategy chart: a hand category against a dealer upcard (1 for an ace).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChartCell {
+  pub category: HandCategory,
+  pub dealer_card: i32,
+}
+
+/**
+ * Dealer upcards in chart column order.
+ */
+pub const DEALER_CARDS: [i32; 10] = [2, 3, 4, 5, 6, 7, 8, 9, 10, 1];
+
+/**
+ * Returns the name of a chart row
+ * Ex: hard 16, soft 18, pair of 8s
+ */
+pub fn row_name(category: HandCategory) -> String {
+  match category {
+      HandCategory::Hard(total) => format!("hard {}", total),
+      HandCategory::Soft(total) => format!("soft {}", total),
+      HandCategory::Pair(1) => "pair of aces".to_string(),
+      HandCategory::Pair(card) => format!("pair of {}s", card),
+  }
+}
+
+/**
+ * Returns the column header for a dealer upcard
+ */
+pub fn dealer_label(dealer_card: i32) -> String {
+  if dealer_card == 1 {
+      "A".to_string()
+  } else {
+      dealer_card.to_string()
+  }
+}
+
+/**
+ * Why the strategy recommends a play: where the hand was looked up in the chart,
+ * the expected value of each action and a short rationale.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+  pub play: i32,
+  pub category: HandCategory,
+  pub dealer_card: i32,
+  // The chart row and column the play was read from, e.g. "hard 16" and "10"
+  pub row: String,
+  pub column: String,
+  pub evs: ActionEvs,
+  pub dealer_bust: f64,
+  pub rationale: String,
+}
+impl fmt::Display for Explanation {
+  /**
+   * Formats the explanation over three lines
+   * Ex: hard 16 vs 10: hit
+   *       EV: hit -0.535  stand -0.540  double -1.070
+   *       Dealer 10 busts 21% of the time; hitting is +0.005 better than standing.
+   */
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      writeln!(f, "{} vs {}: {}", self.row, self.column, play_name(self.play))?;
+      write!(f, "  EV: hit {:+.3}  stand {:+.3}", self.evs.hit, self.evs.stand)?;
+      if let Some(double) = self.evs.double {
+          write!(f, "  double {:+.3}", double)?;
+      }
+      if let Some(split) = self.evs.split {
+          write!(f, "  split {:+.3}", split)?;
+      }
+      if let Some(surrender) = self.evs.surrender {
+          write!(f, "  surrender {:+.3}", surrender)?;
+      }
+      write!(f, "\n  {}", self.rationale)
+  }
+}
+
+/**
+ * Returns the action a play resolves to when every action is available, as the present participle
+ * Ex: hitting, doubling
+ */
+fn action_participle(play: i32) -> &'static str {
+  match play {
+      HIT => "hitting",
+      STAND => "standing",
+      DOUBLE_OR_HIT | DOUBLE_OR_STAND => "doubling",
+      SPLIT => "splitting",
+      SURRENDER_OR_HIT | SURRENDER_OR_STAND => "surrendering",
+      _ => "playing",
+  }
+}
+
+#[derive(Clone, Debug)]
 pub struct BlackjackBasicStrategy {
-  hard: [[i32; 10]; 17],
-  soft: [[i32; 10]; 9],
+  rules: Rules,
+  hard: [[i32; 10]; 18],
+  soft: [[i32; 10]; 10],
   pair: [[i32; 10]; 10],
 }
 impl BlackjackBasicStrategy {
   pub fn new() -> Self {
       let hard = [
+          [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], //4
           [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], //5
           [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], //6
           [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], //7
@@ -26,6 +199,7 @@ impl BlackjackBasicStrategy {
       ];
 
       let soft = [
+          [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], //12
           [1, 1, 1, 3, 3, 1, 1, 1, 1, 1], //13
           [1, 1, 1, 3, 3, 1, 1, 1, 1, 1], //14
           [1, 1, 3, 3, 3, 1, 1, 1, 1, 1], //15
@@ -50,69 +224,494 @@ impl BlackjackBasicStrategy {
           [5, 5, 5, 5, 5, 5, 5, 5, 5, 5], // A, A
       ];
 
-      Self { hard, soft, pair }
+      Self { rules: Rules::default(), hard, soft, pair }
   }
 
-  pub fn get_correct_play(&self, hand: &[i32], dealer_card: i32) -> i32 {
-      if self.check_if_pair(hand) {
-          self.check_pairs(hand, dealer_card)
-      } else if self.check_if_soft(hand) {
-          self.check_soft_hands(hand, dealer_card)
-      } else {
-          self.check_hard_hands(hand, dealer_card)
+  /**
+   * Constructs the basic strategy chart for the specified rules.
+   * The default chart is for four or more decks, S17 and DAS; the number of decks (1, 2 or more),
+   * H17, no DAS and late surrender each adjust the cells that change under those rules.
+   * @param rules the table rules
+   */
+  pub fn for_rules(rules: &Rules) -> Self {
+      let mut strategy = Self::new();
+      strategy.rules = *rules;
+
+      let h17 = rules.dealer_hits_soft_17;
+
+      if rules.decks == 1 {
+          strategy.set(HandCategory::Hard(8), &[5, 6], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Hard(9), &[2, 3, 4, 5, 6], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Hard(11), &[1], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Soft(13), &[4, 5, 6], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Soft(14), &[4, 5, 6], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Soft(17), &[2], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Soft(19), &[6], DOUBLE_OR_STAND);
+          strategy.set(HandCategory::Pair(3), &[8], SPLIT);
+          strategy.set(HandCategory::Pair(4), &[4], SPLIT);
+          strategy.set(HandCategory::Pair(6), &[7], SPLIT);
+          strategy.set(HandCategory::Pair(7), &[8], SPLIT);
+          strategy.set(HandCategory::Pair(7), &[10], STAND);
+      } else if rules.decks == 2 {
+          strategy.set(HandCategory::Hard(9), &[2], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Hard(11), &[1], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Pair(6), &[7], SPLIT);
+          strategy.set(HandCategory::Pair(7), &[8], SPLIT);
+      }
+
+      if h17 {
+          strategy.set(HandCategory::Hard(11), &[1], DOUBLE_OR_HIT);
+          strategy.set(HandCategory::Soft(18), &[2], DOUBLE_OR_STAND);
+          strategy.set(HandCategory::Soft(19), &[6], DOUBLE_OR_STAND);
+          if rules.decks == 1 {
+              strategy.set(HandCategory::Soft(18), &[1], HIT);
+          }
+      }
+
+      if !rules.double_after_split {
+          // Without DAS the small pairs are only split when the dealer is weakest
+          strategy.set(HandCategory::Pair(2), &[2], HIT);
+          strategy.set(HandCategory::Pair(3), &[4, 5, 6, 7], SPLIT);
+          strategy.set(HandCategory::Pair(3), &[2, 3, 8], HIT);
+          strategy.set(HandCategory::Pair(4), &DEALER_CARDS, HIT);
+          if rules.decks == 1 {
+              strategy.set(HandCategory::Pair(4), &[5, 6], DOUBLE_OR_HIT);
+              strategy.set(HandCategory::Pair(6), &[7], HIT);
+              strategy.set(HandCategory::Pair(7), &[8], HIT);
+          } else if rules.decks == 2 {
+              strategy.set(HandCategory::Pair(6), &[7], HIT);
+              strategy.set(HandCategory::Pair(7), &[8], HIT);
+          } else {
+              strategy.set(HandCategory::Pair(6), &[2], HIT);
+          }
+      }
+
+      if rules.surrender {
+          // Late surrender gives up the hard 15s and 16s that lose most, and 17 against an ace that may be soft 17
+          if rules.decks <= 2 {
+              strategy.set(HandCategory::Hard(16), &[10, 1], SURRENDER_OR_HIT);
+          } else {
+              strategy.set(HandCategory::Hard(16), &[9, 10, 1], SURRENDER_OR_HIT);
+          }
+          strategy.set(HandCategory::Hard(15), &[10], SURRENDER_OR_HIT);
+          if h17 {
+              strategy.set(HandCategory::Hard(15), &[1], SURRENDER_OR_HIT);
+              strategy.set(HandCategory::Hard(17), &[1], SURRENDER_OR_STAND);
+              if rules.decks == 2 {
+                  strategy.set(HandCategory::Hard(16), &[9], SURRENDER_OR_HIT);
+              }
+          }
       }
+
+      strategy
   }
 
-  fn get_sum(&self, hand: &[i32]) -> Option<i32> {
-      let mut sum = self.sum_aces(hand);
+  /**
+   * Constructs a chart from a built-in variant name or a chart file.
+   * Variant names give the decks and soft 17 rule, optionally followed by -nodas
+   * Ex: 6d-h17, 2d-s17, 1d-s17-nodas
+   * @param spec the variant name or the path of a chart file
+   */
+  pub fn from_spec(spec: &str) -> Result<Self, String> {
+      match parse_variant(spec) {
+          Some(rules) => Ok(Self::for_rules(&rules)),
+          None => Self::from_chart_file(spec),
+      }
+  }
 
-      for &card in hand {
-          if card != 1 {
-              sum += card;
+  /**
+   * Loads a chart from a file in the format written by to_chart_text
+   * @param path the path of the chart file
+   */
+  pub fn from_chart_file(path: &str) -> Result<Self, String> {
+      let text = fs::read_to_string(path).map_err(|err| format!("Cannot read chart file {}: {}", path, err))?;
+      Self::parse_chart(&text).map_err(|err| format!("{}: {}", path, err))
+  }
+
+  /**
+   * Parses a chart file. Lines are either a rule ("decks 2", "h17 yes", "das no", "surrender yes", "payout 1.5",
+   * "penetration 0.75") or a chart row: the table, the row and one play per dealer upcard 2-10, A
+   * Ex: "hard 16 S S S S S H H H H H", "soft 18 S Ds Ds Ds Ds S S H H H", "pair A P P P P P P P P P P"
+   * Rows that are not listed are taken from the built-in chart for the file's rules.
+   * Blank lines and lines starting with # are ignored.
+   */
+  pub fn parse_chart(text: &str) -> Result<Self, String> {
+      let mut rules = Rules::default();
+      let mut rows = Vec::new();
+
+      for (index, line) in text.lines().enumerate() {
+          let line_number = index + 1;
+          let words: Vec<&str> = line.split_whitespace().collect();
+          if words.is_empty() || words[0].starts_with('#') {
+              continue;
+          }
+
+          let error = |message: &str| format!("line {}: {}", line_number, message);
+
+          match words[0].to_lowercase().as_str() {
+              "decks" | "h17" | "das" | "surrender" | "payout" | "penetration" => {
+                  if words.len() != 2 {
+                      return Err(error("expected a rule and a single value"));
+                  }
+                  parse_rule(&mut rules, &words[0].to_lowercase(), words[1]).map_err(|err| error(&err))?;
+              }
+              "hard" | "soft" | "pair" => {
+                  if words.len() != 12 {
+                      return Err(error("expected a table, a row and 10 plays"));
+                  }
+                  let row = if words[1].eq_ignore_ascii_case("a") { Some(1) } else { words[1].parse::<i32>().ok() };
+                  let category = match (words[0].to_lowercase().as_str(), row) {
+                      ("hard", Some(total)) => HandCategory::Hard(total),
+                      ("soft", Some(total)) => HandCategory::Soft(total),
+                      (_, Some(card)) => HandCategory::Pair(card),
+                      _ => return Err(error(&format!("invalid row {}", words[1]))),
+                  };
+                  let mut plays = Vec::new();
+                  for word in &words[2..] {
+                      plays.push(parse_play(word).ok_or_else(|| error(&format!("invalid play {}", word)))?);
+                  }
+                  rows.push((category, plays, line_number));
+              }
+              other => return Err(error(&format!("unknown entry {}", other))),
           }
       }
 
-      if sum > 21 {
-          None
+      let mut strategy = Self::for_rules(&rules);
+      for (category, plays, line_number) in rows {
+          if strategy.get_play(category, 2).is_none() {
+              return Err(format!("line {}: row is not in the chart", line_number));
+          }
+          if plays.contains(&SPLIT) && !matches!(category, HandCategory::Pair(_)) {
+              return Err(format!("line {}: only pairs can be split", line_number));
+          }
+          for (dealer_card, play) in DEALER_CARDS.iter().zip(plays) {
+              strategy.set(category, &[*dealer_card], play);
+          }
+      }
+
+      Ok(strategy)
+  }
+
+  /**
+   * Returns this chart in the chart file format read by parse_chart
+   */
+  pub fn to_chart_text(&self) -> String {
+      let mut text = format!("# Basic strategy: {}\n", self.rules);
+      text.push_str(&format!("decks {}\n", self.rules.decks));
+      text.push_str(&format!("h17 {}\n", if self.rules.dealer_hits_soft_17 { "yes" } else { "no" }));
+      text.push_str(&format!("das {}\n", if self.rules.double_after_split { "yes" } else { "no" }));
+      text.push_str(&format!("surrender {}\n", if self.rules.surrender { "yes" } else { "no" }));
+      text.push_str(&format!("payout {}\n", self.rules.blackjack_payout));
+      text.push_str(&format!("penetration {}\n", self.rules.penetration));
+
+      let rows = self.hard_rows().into_iter().chain(self.soft_rows()).chain(self.pair_rows());
+      for category in rows {
+          let (table, row) = match category {
+              HandCategory::Hard(total) => ("hard", total.to_string()),
+              HandCategory::Soft(total) => ("soft", total.to_string()),
+              HandCategory::Pair(1) => ("pair", "A".to_string()),
+              HandCategory::Pair(card) => ("pair", card.to_string()),
+          };
+          text.push_str(&format!("{} {:<2}", table, row));
+          for dealer_card in DEALER_CARDS {
+              let play = self.get_play(category, dealer_card).expect("row is in the chart");
+              text.push_str(&format!(" {:<2}", play_abbreviation(play)));
+          }
+          text = text.trim_end().to_string();
+          text.push('\n');
+      }
+      text
+  }
+
+  /**
+   * Sets the play for a row against each of the specified dealer upcards
+   */
+  fn set(&mut self, category: HandCategory, dealer_cards: &[i32], play: i32) {
+      for &dealer_card in dealer_cards {
+          let column = self.dealer_column(dealer_card).expect("valid dealer card");
+          match category {
+              HandCategory::Hard(total) => self.hard[(total - 4) as usize][column] = play,
+              HandCategory::Soft(total) => self.soft[(total - 12) as usize][column] = play,
+              HandCategory::Pair(1) => self.pair[9][column] = play,
+              HandCategory::Pair(card) => self.pair[(card - 2) as usize][column] = play,
+          }
+      }
+  }
+
+  /**
+   * Returns the rules this chart was built for
+   */
+  pub fn get_rules(&self) -> &Rules {
+      &self.rules
+  }
+
+  /**
+   * Returns the rows of the chart for each table in display order: hard, soft and pairs.
+   */
+  pub fn hard_rows(&self) -> Vec<HandCategory> {
+      (4..=21).map(HandCategory::Hard).collect()
+  }
+
+  pub fn soft_rows(&self) -> Vec<HandCategory> {
+      (12..=21).map(HandCategory::Soft).collect()
+  }
+
+  pub fn pair_rows(&self) -> Vec<HandCategory> {
+      (2..=10).chain(1..=1).map(HandCategory::Pair).collect()
+  }
+
+  /**
+   * Returns the correct play for the specified hand against the dealer's upcard,
+   * or None if the hand is bust or not a valid hand.
+   * Cards are given by their blackjack value: 1 or 11 for an ace, 2-10 otherwise.
+   * @param hand the values of the cards in the player's hand
+   * @param dealer_card the value of the dealer's upcard
+   */
+  pub fn get_correct_play(&self, hand: &[i32], dealer_card: i32) -> Option<i32> {
+      self.get_play(self.classify(hand, true)?, dealer_card)
+  }
+
+  /**
+   * Returns the action basic strategy takes on the specified hand given which actions are allowed,
+   * or None if the hand is bust or not a valid hand.
+   * @param hand the values of the cards in the player's hand
+   * @param dealer_card the value of the dealer's upcard
+   * @param can_double true if the hand may be doubled
+   * @param can_split true if the hand may be split
+   * @param can_surrender true if the hand may be surrendered
+   */
+  pub fn recommend(&self, hand: &[i32], dealer_card: i32, can_double: bool, can_split: bool, can_surrender: bool) -> Option<Action> {
+      let play = if can_split {
+          self.get_correct_play(hand, dealer_card)?
       } else {
-          Some(sum)
+          self.get_correct_play_without_split(hand, dealer_card)?
+      };
+
+      match play {
+          HIT => Some(Action::Hit),
+          STAND => Some(Action::Stand),
+          DOUBLE_OR_HIT if can_double => Some(Action::Double),
+          DOUBLE_OR_HIT => Some(Action::Hit),
+          DOUBLE_OR_STAND if can_double => Some(Action::Double),
+          DOUBLE_OR_STAND => Some(Action::Stand),
+          SPLIT => Some(Action::Split),
+          SURRENDER_OR_HIT if can_surrender => Some(Action::Surrender),
+          SURRENDER_OR_HIT => Some(Action::Hit),
+          SURRENDER_OR_STAND if can_surrender => Some(Action::Surrender),
+          SURRENDER_OR_STAND => Some(Action::Stand),
+          _ => None,
       }
   }
 
-  fn sum_aces(&self, hand: &[i32]) -> i32 {
-      let num_ones = hand.iter().filter(|&&card| card == 1).count() as i32;
-      let num_elevens = if num_ones > 0 { 1 } else { 0 };
+  /**
+   * Returns the correct play for a hand that may not be split,
+   * e.g. A, A after splitting aces when resplitting is not allowed.
+   */
+  pub fn get_correct_play_without_split(&self, hand: &[i32], dealer_card: i32) -> Option<i32> {
+      self.get_play(self.classify(hand, false)?, dealer_card)
+  }
+
+  /**
+   * Returns the correct play for the specified hand together with the chart cell it was read from,
+   * the expected value of each action under this chart's rules and a short rationale,
+   * or None if the hand is bust or not a valid hand.
+   * @param hand the values of the cards in the player's hand
+   * @param dealer_card the value of the dealer's upcard
+   */
+  pub fn explain(&self, hand: &[i32], dealer_card: i32) -> Option<Explanation> {
+      let category = self.classify(hand, true)?;
+      let play = match self.get_play(category, dealer_card)? {
+          // Doubling and surrender are only allowed on the first two cards
+          DOUBLE_OR_HIT | SURRENDER_OR_HIT if hand.len() > 2 => HIT,
+          DOUBLE_OR_STAND | SURRENDER_OR_STAND if hand.len() > 2 => STAND,
+          play => play,
+      };
+      let dealer_card = self.card_value(dealer_card)?;
+      let values: Vec<i32> = hand.iter().map(|&card| self.card_value(card)).collect::<Option<_>>()?;
 
-      num_elevens * 11 + (num_ones - num_elevens)
+      let mut calculator = EvCalculator::new(&self.rules, &values, dealer_card);
+      let evs = calculator.action_evs(&values);
+      let dealer_bust = calculator.dealer_outcomes()[5];
+
+      // Compare the chart's play against the best of the other actions
+      let play_ev = evs.play_ev(play)?;
+      let alternatives = [
+          (HIT, Some(evs.hit)),
+          (STAND, Some(evs.stand)),
+          (DOUBLE_OR_HIT, evs.double),
+          (SPLIT, evs.split),
+          (SURRENDER_OR_HIT, evs.surrender),
+      ];
+      let next_best = alternatives
+          .iter()
+          .filter(|(alternative, _)| action_participle(*alternative) != action_participle(play))
+          .filter_map(|&(alternative, ev)| ev.map(|ev| (alternative, ev)))
+          .max_by(|a, b| a.1.total_cmp(&b.1));
+
+      let mut rationale = format!(
+          "Dealer {} busts {:.0}% of the time",
+          dealer_label(dealer_card),
+          dealer_bust * 100.0
+      );
+      if let Some((alternative, alternative_ev)) = next_best {
+          rationale.push_str(&format!(
+              "; {} on {} is {:+.3} better than {}.",
+              action_participle(play),
+              row_name(category),
+              play_ev - alternative_ev,
+              action_participle(alternative)
+          ));
+      } else {
+          rationale.push('.');
+      }
+
+      Some(Explanation {
+          play,
+          category,
+          dealer_card,
+          row: row_name(category),
+          column: dealer_label(dealer_card),
+          evs,
+          dealer_bust,
+          rationale,
+      })
   }
 
-  fn check_if_soft(&self, hand: &[i32]) -> bool {
-      self.sum_aces(hand) > 1
+  /**
+   * Returns the play stored in the chart for the specified row and dealer upcard,
+   * or None if either is outside the chart.
+   */
+  pub fn get_play(&self, category: HandCategory, dealer_card: i32) -> Option<i32> {
+      let column = self.dealer_column(dealer_card)?;
+
+      match category {
+          HandCategory::Hard(total) if (4..=21).contains(&total) => Some(self.hard[(total - 4) as usize][column]),
+          HandCategory::Soft(total) if (12..=21).contains(&total) => Some(self.soft[(total - 12) as usize][column]),
+          HandCategory::Pair(1) => Some(self.pair[9][column]),
+          HandCategory::Pair(card) if (2..=10).contains(&card) => Some(self.pair[(card - 2) as usize][column]),
+          _ => None,
+      }
   }
 
-  fn check_if_pair(&self, hand: &[i32]) -> bool {
-      hand[0] == hand[1]
+  /**
+   * Returns the chart row for the specified hand, or None if the hand has
+   * fewer than two cards, contains an invalid card or is bust.
+   * Only two card hands of equal value are pairs, and only when they may be split.
+   * A hand is soft when one of its aces can still count as 11 without busting.
+   * @param hand the values of the cards in the player's hand
+   * @param can_split true if a pair may be split
+   */
+  pub fn classify(&self, hand: &[i32], can_split: bool) -> Option<HandCategory> {
+      if hand.len() < 2 {
+          return None;
+      }
+
+      let mut values = Vec::with_capacity(hand.len());
+      for &card in hand {
+          values.push(self.card_value(card)?);
+      }
+
+      let total = self.get_sum(&values)?;
+
+      if can_split && values.len() == 2 && values[0] == values[1] {
+          Some(HandCategory::Pair(values[0]))
+      } else if self.check_if_soft(&values) {
+          Some(HandCategory::Soft(total))
+      } else {
+          Some(HandCategory::Hard(total))
+      }
   }
 
-  fn check_hard_hands(&self, hand: &[i32], dealer_card: i32) -> i32 {
-      match self.get_sum(hand) {
-          Some(sum) if sum >= 5 && sum <= 21 => self.hard[(sum - 5) as usize][(dealer_card - 2) as usize],
-          _ => -1,
+  /**
+   * Returns the value of a card with aces counted as 1, or None if the value is invalid.
+   */
+  fn card_value(&self, card: i32) -> Option<i32> {
+      match card {
+          1 | 11 => Some(1),
+          2..=10 => Some(card),
+          _ => None,
       }
   }
 
-  fn check_soft_hands(&self, hand: &[i32], dealer_card: i32) -> i32 {
-      match self.get_sum(hand) {
-          Some(sum) if sum >= 13 && sum <= 21 => self.soft[(sum - 13) as usize][(dealer_card - 2) as usize],
-          _ => -1,
+  /**
+   * Returns the table column for the dealer's upcard, 2-10 followed by the ace.
+   */
+  fn dealer_column(&self, dealer_card: i32) -> Option<usize> {
+      match self.card_value(dealer_card)? {
+          1 => Some(9),
+          card => Some((card - 2) as usize),
       }
   }
 
-  fn check_pairs(&self, hand: &[i32], dealer_card: i32) -> i32 {
-      if hand[0] == 1 {
-          5
+  /**
+   * Returns the best total of the hand, or None if the hand is bust.
+   */
+  fn get_sum(&self, hand: &[i32]) -> Option<i32> {
+      let hard_sum: i32 = hand.iter().sum();
+
+      if hard_sum > 21 {
+          None
+      } else if self.check_if_soft(hand) {
+          Some(hard_sum + 10)
       } else {
-          self.pair[(hand[0] - 2) as usize][(dealer_card - 2) as usize]
+          Some(hard_sum)
       }
   }
+
+  fn check_if_soft(&self, hand: &[i32]) -> bool {
+      let hard_sum: i32 = hand.iter().sum();
+      hand.contains(&1) && hard_sum + 10 <= 21
+  }
+}
+/**
+ * Returns the rules for a built-in variant name, or None if it is not one
+ * Ex: 6d-h17 is six decks with the dealer hitting soft 17, 2d-s17-nodas is double deck without DAS
+ */
+pub fn parse_variant(name: &str) -> Option<Rules> {
+  let lower = name.to_lowercase();
+  let mut parts = lower.split('-');
+  let decks = parts.next()?.strip_suffix('d')?.parse::<usize>().ok().filter(|&decks| decks > 0)?;
+  let dealer_hits_soft_17 = match parts.next()? {
+      "h17" => true,
+      "s17" => false,
+      _ => return None,
+  };
+  let double_after_split = match parts.next() {
+      None => true,
+      Some("nodas") => false,
+      Some(_) => return None,
+  };
+  if parts.next().is_some() {
+      return None;
+  }
+
+  Some(Rules { decks, dealer_hits_soft_17, double_after_split, ..Rules::default() })
+}
+
+fn parse_rule(rules: &mut Rules, name: &str, value: &str) -> Result<(), String> {
+  let invalid = || format!("invalid value {} for {}", value, name);
+  let yes_no = |value: &str| match value.to_lowercase().as_str() {
+      "yes" | "true" => Some(true),
+      "no" | "false" => Some(false),
+      _ => None,
+  };
+
+  match name {
+      "decks" => rules.decks = value.parse().ok().filter(|&decks| decks > 0).ok_or_else(invalid)?,
+      "h17" => rules.dealer_hits_soft_17 = yes_no(value).ok_or_else(invalid)?,
+      "das" => rules.double_after_split = yes_no(value).ok_or_else(invalid)?,
+      "surrender" => rules.surrender = yes_no(value).ok_or_else(invalid)?,
+      "payout" => rules.blackjack_payout = value.parse().ok().filter(|&payout: &f64| payout > 0.0).ok_or_else(invalid)?,
+      "penetration" => {
+          rules.penetration = value.parse().ok().filter(|&p: &f64| p > 0.0 && p < 1.0).ok_or_else(invalid)?
+      }
+      _ => return Err(format!("unknown rule {}", name)),
+  }
+  Ok(())
+}
+
+impl Default for BlackjackBasicStrategy {
+  fn default() -> Self {
+      Self::new()
+  }
 }
@@ -0,0 +1,305 @@
+use crate::blackjack::{Action, Card, Hand, Rules, Suit};
+use crate::blackjackbasicstrategy::{
+    parse_variant, BlackjackBasicStrategy, HandCategory, DOUBLE_OR_HIT, DOUBLE_OR_STAND, HIT, SPLIT, STAND,
+    SURRENDER_OR_HIT, SURRENDER_OR_STAND,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_hands(cards: usize) -> Vec<Vec<i32>> {
+        let mut hands = vec![Vec::new()];
+        for _ in 0..cards {
+            let mut longer = Vec::new();
+            for hand in &hands {
+                for card in 1..=10 {
+                    let mut next = hand.clone();
+                    next.push(card);
+                    longer.push(next);
+                }
+            }
+            hands = longer;
+        }
+        hands
+    }
+
+    #[test]
+    fn test_classify_known_hands() {
+        let strategy = BlackjackBasicStrategy::new();
+        let hands: [(&[i32], bool, Option<HandCategory>); 18] = [
+            (&[10, 6], true, Some(HandCategory::Hard(16))),
+            (&[9, 2], true, Some(HandCategory::Hard(11))),
+            (&[1, 7], true, Some(HandCategory::Soft(18))),
+            (&[1, 10], true, Some(HandCategory::Soft(21))),
+            (&[11, 6], true, Some(HandCategory::Soft(17))),
+            (&[1, 1], true, Some(HandCategory::Pair(1))),
+            (&[1, 1], false, Some(HandCategory::Soft(12))),
+            (&[8, 8], true, Some(HandCategory::Pair(8))),
+            (&[8, 8], false, Some(HandCategory::Hard(16))),
+            (&[10, 10], true, Some(HandCategory::Pair(10))),
+            (&[2, 2, 1], true, Some(HandCategory::Soft(15))),
+            (&[1, 5, 10], true, Some(HandCategory::Hard(16))),
+            (&[1, 1, 9], true, Some(HandCategory::Soft(21))),
+            (&[4, 4, 4], true, Some(HandCategory::Hard(12))),
+            (&[10, 10, 2], true, None),
+            (&[10, 0], true, None),
+            (&[5], true, None),
+            (&[], true, None),
+        ];
+
+        for (hand, can_split, category) in hands {
+            assert_eq!(strategy.classify(hand, can_split), category, "hand {:?}, can split {}", hand, can_split);
+        }
+    }
+
+    #[test]
+    fn test_classify_all_two_and_three_card_hands() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        for cards in 2..=3 {
+            for hand in all_hands(cards) {
+                // The engine's hand totals the cards on its own, counting an ace as 11 when it can
+                let card = |value: i32| Card::new(Suit::Spades, value as i64);
+                let mut dealt = Hand::new(card(hand[0]), card(hand[1]));
+                for &value in &hand[2..] {
+                    dealt.add_card(card(value));
+                }
+                let total = dealt.get_value() as i32;
+                let pair = cards == 2 && hand[0] == hand[1];
+
+                for can_split in [true, false] {
+                    let expected = if total > 21 {
+                        None
+                    } else if pair && can_split {
+                        Some(HandCategory::Pair(hand[0]))
+                    } else if dealt.is_soft() {
+                        Some(HandCategory::Soft(total))
+                    } else {
+                        Some(HandCategory::Hard(total))
+                    };
+                    assert_eq!(strategy.classify(&hand, can_split), expected, "hand {:?}, can split {}", hand, can_split);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_live_hand_has_a_play() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        for cards in 2..=3 {
+            for hand in all_hands(cards) {
+                let bust = hand.iter().sum::<i32>() > 21;
+                for dealer_card in 1..=10 {
+                    let play = strategy.get_correct_play(&hand, dealer_card);
+                    let play_without_split = strategy.get_correct_play_without_split(&hand, dealer_card);
+                    if bust {
+                        assert_eq!(play, None, "hand {:?} vs {}", hand, dealer_card);
+                        assert_eq!(play_without_split, None, "hand {:?} vs {}", hand, dealer_card);
+                    } else {
+                        assert!(play.is_some(), "hand {:?} vs {}", hand, dealer_card);
+                        assert!(play_without_split.is_some(), "hand {:?} vs {}", hand, dealer_card);
+                        assert_ne!(play_without_split, Some(SPLIT), "hand {:?} vs {}", hand, dealer_card);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_three_card_hands_are_never_pairs() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.classify(&[8, 8, 3], true), Some(HandCategory::Hard(19)));
+        assert_eq!(strategy.get_correct_play(&[8, 8, 3], 10), Some(STAND));
+    }
+
+    #[test]
+    fn test_ace_counted_as_one_is_hard() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.classify(&[1, 6, 9], true), Some(HandCategory::Hard(16)));
+        assert_eq!(strategy.get_correct_play(&[1, 6, 9], 10), Some(HIT));
+        assert_eq!(strategy.classify(&[1, 1, 9], true), Some(HandCategory::Soft(21)));
+        assert_eq!(strategy.classify(&[1, 1, 10], true), Some(HandCategory::Hard(12)));
+    }
+
+    #[test]
+    fn test_unsplittable_pairs() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.classify(&[1, 1], false), Some(HandCategory::Soft(12)));
+        assert_eq!(strategy.get_correct_play_without_split(&[1, 1], 6), Some(HIT));
+        assert_eq!(strategy.classify(&[2, 2], false), Some(HandCategory::Hard(4)));
+        assert_eq!(strategy.get_correct_play_without_split(&[2, 2], 6), Some(HIT));
+        assert_eq!(strategy.get_correct_play(&[1, 1], 6), Some(SPLIT));
+    }
+
+    #[test]
+    fn test_bust_and_invalid_hands() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.get_correct_play(&[10, 6, 8], 6), None);
+        assert_eq!(strategy.get_correct_play(&[10], 6), None);
+        assert_eq!(strategy.get_correct_play(&[10, 0], 6), None);
+        assert_eq!(strategy.get_correct_play(&[10, 6], 12), None);
+    }
+
+    #[test]
+    fn test_dealer_ace_as_one_or_eleven() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.get_correct_play(&[10, 6], 1), strategy.get_correct_play(&[10, 6], 11));
+        assert_eq!(strategy.get_correct_play(&[8, 8], 11), Some(SPLIT));
+    }
+
+    #[test]
+    fn test_default_rules_match_default_chart() {
+        let default_chart = BlackjackBasicStrategy::new();
+        let built = BlackjackBasicStrategy::for_rules(&Rules::default());
+
+        assert_eq!(default_chart.to_chart_text(), built.to_chart_text());
+    }
+
+    #[test]
+    fn test_rule_variants() {
+        let h17 = BlackjackBasicStrategy::from_spec("6d-h17").unwrap();
+        assert!(h17.get_rules().dealer_hits_soft_17);
+        assert_eq!(h17.get_play(HandCategory::Hard(11), 1), Some(DOUBLE_OR_HIT));
+        assert_eq!(h17.get_play(HandCategory::Soft(19), 6), Some(DOUBLE_OR_STAND));
+
+        let double_deck = BlackjackBasicStrategy::from_spec("2d-s17").unwrap();
+        assert_eq!(double_deck.get_rules().decks, 2);
+        assert_eq!(double_deck.get_play(HandCategory::Hard(9), 2), Some(DOUBLE_OR_HIT));
+        assert_eq!(double_deck.get_play(HandCategory::Soft(19), 6), Some(STAND));
+
+        let no_das = BlackjackBasicStrategy::from_spec("6d-s17-nodas").unwrap();
+        assert_eq!(no_das.get_play(HandCategory::Pair(4), 5), Some(HIT));
+        assert_eq!(no_das.get_play(HandCategory::Pair(2), 2), Some(HIT));
+        assert_eq!(no_das.get_play(HandCategory::Pair(2), 4), Some(SPLIT));
+    }
+
+    #[test]
+    fn test_late_surrender_chart() {
+        let rules = Rules { surrender: true, ..Rules::default() };
+        let strategy = BlackjackBasicStrategy::for_rules(&rules);
+        assert_eq!(strategy.get_play(HandCategory::Hard(16), 9), Some(SURRENDER_OR_HIT));
+        assert_eq!(strategy.get_play(HandCategory::Hard(15), 10), Some(SURRENDER_OR_HIT));
+        assert_eq!(strategy.get_play(HandCategory::Hard(15), 1), Some(HIT));
+        assert_eq!(strategy.get_play(HandCategory::Pair(8), 10), Some(SPLIT));
+        assert_eq!(BlackjackBasicStrategy::new().get_play(HandCategory::Hard(16), 10), Some(HIT));
+
+        let h17 = BlackjackBasicStrategy::for_rules(&Rules { dealer_hits_soft_17: true, ..rules });
+        assert_eq!(h17.get_play(HandCategory::Hard(15), 1), Some(SURRENDER_OR_HIT));
+        assert_eq!(h17.get_play(HandCategory::Hard(17), 1), Some(SURRENDER_OR_STAND));
+        let single_deck = BlackjackBasicStrategy::for_rules(&Rules { decks: 1, ..rules });
+        assert_eq!(single_deck.get_play(HandCategory::Hard(16), 9), Some(HIT));
+
+        // Surrender falls back to hitting or standing where it is not allowed, e.g. after a third card
+        assert_eq!(strategy.recommend(&[10, 6], 10, true, false, true), Some(Action::Surrender));
+        assert_eq!(strategy.recommend(&[10, 6], 10, true, false, false), Some(Action::Hit));
+        assert_eq!(h17.recommend(&[10, 7], 1, true, false, false), Some(Action::Stand));
+        let explanation = strategy.explain(&[10, 6], 10).unwrap();
+        assert_eq!(explanation.evs.surrender, Some(-0.5));
+        assert!(explanation.to_string().contains("surrender (otherwise hit)"));
+        assert_eq!(strategy.explain(&[10, 3, 3], 10).unwrap().play, HIT);
+
+        let parsed = BlackjackBasicStrategy::parse_chart(&h17.to_chart_text()).unwrap();
+        assert_eq!(parsed.get_rules(), h17.get_rules());
+        assert_eq!(parsed.to_chart_text(), h17.to_chart_text());
+    }
+
+    #[test]
+    fn test_parse_variant() {
+        assert_eq!(parse_variant("2D-H17").map(|rules| (rules.decks, rules.dealer_hits_soft_17)), Some((2, true)));
+        assert_eq!(parse_variant("8d-s17-nodas").map(|rules| rules.double_after_split), Some(false));
+        assert_eq!(parse_variant("0d-s17"), None);
+        assert_eq!(parse_variant("6d"), None);
+        assert_eq!(parse_variant("6d-s17-das"), None);
+        assert_eq!(parse_variant("charts/custom.txt"), None);
+    }
+
+    #[test]
+    fn test_chart_text_round_trip() {
+        let strategy = BlackjackBasicStrategy::from_spec("1d-h17-nodas").unwrap();
+        let parsed = BlackjackBasicStrategy::parse_chart(&strategy.to_chart_text()).unwrap();
+
+        assert_eq!(parsed.get_rules(), strategy.get_rules());
+        assert_eq!(parsed.to_chart_text(), strategy.to_chart_text());
+    }
+
+    #[test]
+    fn test_parse_custom_rows() {
+        let text = "# my chart\ndecks 2\nh17 yes\nhard 16 S S S S S H H H S S\npair a p p p p p p p p p h\n";
+        let strategy = BlackjackBasicStrategy::parse_chart(text).unwrap();
+
+        assert_eq!(strategy.get_rules().decks, 2);
+        assert_eq!(strategy.get_play(HandCategory::Hard(16), 10), Some(STAND));
+        assert_eq!(strategy.get_play(HandCategory::Pair(1), 1), Some(HIT));
+        // Rows that are not listed come from the built-in chart for the rules
+        assert_eq!(strategy.get_play(HandCategory::Hard(11), 1), Some(DOUBLE_OR_HIT));
+    }
+
+    #[test]
+    fn test_parse_chart_errors() {
+        let error = |text: &str| BlackjackBasicStrategy::parse_chart(text).err().unwrap();
+
+        assert_eq!(error("decks two"), "line 1: invalid value two for decks");
+        assert_eq!(error("\nhard 16 S S S"), "line 2: expected a table, a row and 10 plays");
+        assert_eq!(error("hard 16 S S S S S H H H X H"), "line 1: invalid play X");
+        assert_eq!(error("hard 3 H H H H H H H H H H"), "line 1: row is not in the chart");
+        assert_eq!(error("hard 16 P H H H H H H H H H"), "line 1: only pairs can be split");
+        assert_eq!(error("surrender maybe"), "line 1: invalid value maybe for surrender");
+        assert_eq!(error("insurance yes"), "line 1: unknown entry insurance");
+    }
+
+    #[test]
+    fn test_explain_stand_on_12_vs_6() {
+        let strategy = BlackjackBasicStrategy::new();
+        let explanation = strategy.explain(&[10, 2], 6).unwrap();
+
+        assert_eq!(explanation.play, STAND);
+        assert_eq!(explanation.category, HandCategory::Hard(12));
+        assert_eq!((explanation.row.as_str(), explanation.column.as_str()), ("hard 12", "6"));
+        assert!(explanation.evs.stand > explanation.evs.hit);
+        assert!(explanation.rationale.starts_with("Dealer 6 busts 42% of the time; standing on hard 12 is +0.0"));
+        assert!(explanation.rationale.ends_with("better than hitting."));
+    }
+
+    #[test]
+    fn test_explain_split_and_double() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        let explanation = strategy.explain(&[8, 8], 10).unwrap();
+        assert_eq!(explanation.play, SPLIT);
+        assert!(explanation.evs.split.is_some());
+        assert!(explanation.rationale.contains("splitting on pair of 8s"));
+        assert!(explanation.to_string().starts_with("pair of 8s vs 10: split\n  EV: hit"));
+
+        // A double on three cards becomes a hit
+        let explanation = strategy.explain(&[4, 3, 4], 6).unwrap();
+        assert_eq!(explanation.play, HIT);
+        assert_eq!(explanation.evs.double, None);
+    }
+
+    #[test]
+    fn test_explain_bust_hand() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.explain(&[10, 6, 9], 6), None);
+    }
+
+    #[test]
+    fn test_recommend_resolves_plays_to_legal_actions() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(strategy.recommend(&[6, 5], 6, true, false, false), Some(Action::Double));
+        assert_eq!(strategy.recommend(&[6, 5], 6, false, false, false), Some(Action::Hit));
+        assert_eq!(strategy.recommend(&[1, 7], 4, true, false, false), Some(Action::Double));
+        assert_eq!(strategy.recommend(&[1, 7], 4, false, false, false), Some(Action::Stand));
+        assert_eq!(strategy.recommend(&[8, 8], 10, true, true, false), Some(Action::Split));
+        assert_eq!(strategy.recommend(&[8, 8], 10, true, false, false), Some(Action::Hit));
+        assert_eq!(strategy.recommend(&[10, 6, 9], 10, false, false, false), None);
+    }
+}
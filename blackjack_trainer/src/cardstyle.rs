@@ -0,0 +1,245 @@
+use crate::blackjack::{Card, Hand, Suit};
+use std::env;
+use std::io::{self, IsTerminal};
+
+// ANSI escapes for red cards and for going back to the terminal's own colour
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// The lines of an ASCII-art card back
+const ART_BACK: [&str; 5] = [".-----.", "|#####|", "|#####|", "|#####|", "'-----'"];
+
+/**
+ * How cards are drawn in the terminal
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardStyle {
+    // The rank and suit letter, e.g. 10H, as cards are written in files
+    Text,
+    // The rank and suit symbol, e.g. 10♥
+    Suits,
+    // The Unicode playing card characters, e.g. 🂺
+    Glyphs,
+    // Cards drawn over five lines, laid side by side
+    Art,
+}
+impl CardStyle {
+    pub const ALL: [CardStyle; 4] = [CardStyle::Text, CardStyle::Suits, CardStyle::Glyphs, CardStyle::Art];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardStyle::Text => "text",
+            CardStyle::Suits => "suits",
+            CardStyle::Glyphs => "glyphs",
+            CardStyle::Art => "art",
+        }
+    }
+
+    /**
+     * Parses a style by its name, ignoring case
+     */
+    pub fn parse(name: &str) -> Option<CardStyle> {
+        CardStyle::ALL.into_iter().find(|style| style.name().eq_ignore_ascii_case(name))
+    }
+
+    /**
+     * Returns the style for the terminal: suit symbols if stdout is a terminal whose locale is UTF-8,
+     * otherwise text. Glyphs and art depend on the font and the width of the terminal, so they are only used when chosen.
+     */
+    pub fn detect() -> CardStyle {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()));
+        let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+        if io::stdout().is_terminal() && !dumb && locale.is_some_and(|locale| is_utf8_locale(&locale)) {
+            CardStyle::Suits
+        } else {
+            CardStyle::Text
+        }
+    }
+}
+
+/**
+ * Returns true if a locale such as en_GB.UTF-8 uses UTF-8
+ */
+pub fn is_utf8_locale(locale: &str) -> bool {
+    let locale = locale.to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+/**
+ * Returns the Unicode symbol of a suit: ♥, ♦, ♣ or ♠
+ */
+pub fn suit_symbol(suit: Suit) -> char {
+    match suit {
+        Suit::Hearts => '♥',
+        Suit::Diamonds => '♦',
+        Suit::Clubs => '♣',
+        Suit::Spades => '♠',
+    }
+}
+
+/**
+ * Returns the Unicode playing card character of a card, e.g. 🂡 for the ace of spades
+ */
+pub fn card_glyph(card: &Card) -> char {
+    let suit = match card.get_suit() {
+        Suit::Spades => 0x1F0A0,
+        Suit::Hearts => 0x1F0B0,
+        Suit::Diamonds => 0x1F0C0,
+        Suit::Clubs => 0x1F0D0,
+    };
+    // The block has a knight between the jack and the queen, which blackjack does not use
+    let rank = match card.get_rank() {
+        rank @ 1..=11 => rank as u32,
+        rank => rank as u32 + 1,
+    };
+    char::from_u32(suit + rank).expect("every card has a playing card character")
+}
+
+// The rank as it is written on a card: A, 2-10, J, Q or K
+fn rank_label(card: &Card) -> String {
+    match card.get_rank() {
+        1 => "A".to_string(),
+        11 => "J".to_string(),
+        12 => "Q".to_string(),
+        13 => "K".to_string(),
+        rank => rank.to_string(),
+    }
+}
+
+/**
+ * Draws cards and hands in a style, with red suits in red when colour is on
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CardRenderer {
+    style: CardStyle,
+    colour: bool,
+}
+impl Default for CardRenderer {
+    /**
+     * Cards as text without colour, as they are displayed by Card
+     */
+    fn default() -> Self {
+        CardRenderer::new(CardStyle::Text, false)
+    }
+}
+impl CardRenderer {
+    pub fn new(style: CardStyle, colour: bool) -> Self {
+        CardRenderer { style, colour }
+    }
+
+    /**
+     * Draws cards in the chosen style, or the style detected for the terminal, with colour
+     * unless stdout is not a terminal or NO_COLOR is set
+     */
+    pub fn for_terminal(style: Option<CardStyle>) -> Self {
+        let style = style.unwrap_or_else(CardStyle::detect);
+        // Text is what is written to files, so it is only coloured in the other styles
+        let colour = style != CardStyle::Text && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        CardRenderer::new(style, colour)
+    }
+
+    pub fn get_style(&self) -> CardStyle {
+        self.style
+    }
+
+    /**
+     * Returns true if cards take more than one line
+     */
+    pub fn is_multiline(&self) -> bool {
+        self.style == CardStyle::Art
+    }
+
+    // Colours the text of a red card
+    fn paint(&self, card: &Card, text: String) -> String {
+        if self.colour && card.get_suit().is_red() {
+            format!("{}{}{}", RED, text, RESET)
+        } else {
+            text
+        }
+    }
+
+    /**
+     * Draws a card on one line. Art cards are drawn with suit symbols where only one line fits.
+     * Ex: 10H, 10♥ or 🂺
+     */
+    pub fn card(&self, card: &Card) -> String {
+        let text = match self.style {
+            CardStyle::Text => card.to_string(),
+            CardStyle::Suits | CardStyle::Art => format!("{}{}", rank_label(card), suit_symbol(card.get_suit())),
+            CardStyle::Glyphs => card_glyph(card).to_string(),
+        };
+        self.paint(card, text)
+    }
+
+    /**
+     * Draws a face down card on one line
+     * Ex: ??, ▒▒ or 🂠
+     */
+    pub fn back(&self) -> String {
+        match self.style {
+            CardStyle::Text => "??".to_string(),
+            CardStyle::Suits | CardStyle::Art => "▒▒".to_string(),
+            CardStyle::Glyphs => '\u{1F0A0}'.to_string(),
+        }
+    }
+
+    /**
+     * Draws cards, followed by face down cards, on one line separated by spaces
+     * Ex: 10♥ ▒▒
+     */
+    pub fn inline(&self, cards: &[Card], face_down: usize) -> String {
+        let mut drawn: Vec<String> = cards.iter().map(|card| self.card(card)).collect();
+        drawn.extend((0..face_down).map(|_| self.back()));
+        drawn.join(" ")
+    }
+
+    // The five lines of an art card, e.g. a frame with 10 in the corners and ♥ in the middle
+    fn art_lines(&self, card: &Card) -> [String; 5] {
+        let rank = rank_label(card);
+        let suit = suit_symbol(card.get_suit()).to_string();
+        [
+            ".-----.".to_string(),
+            format!("|{}|", self.paint(card, format!("{:<5}", rank))),
+            format!("|{}|", self.paint(card, format!("  {}  ", suit))),
+            format!("|{}|", self.paint(card, format!("{:>5}", rank))),
+            "'-----'".to_string(),
+        ]
+    }
+
+    /**
+     * Draws cards, followed by face down cards, laid side by side. Only art takes more than one line;
+     * the other styles are drawn as by inline.
+     */
+    pub fn cards(&self, cards: &[Card], face_down: usize) -> String {
+        if !self.is_multiline() {
+            return self.inline(cards, face_down);
+        }
+        let mut drawn: Vec<[String; 5]> = cards.iter().map(|card| self.art_lines(card)).collect();
+        drawn.extend((0..face_down).map(|_| ART_BACK.map(str::to_string)));
+
+        let lines: Vec<String> = (0..5)
+            .map(|line| drawn.iter().map(|card| card[line].as_str()).collect::<Vec<_>>().join(" "))
+            .collect();
+        lines.join("\n")
+    }
+
+    /**
+     * Draws a hand with its value. Art cards are drawn below the value.
+     * Ex: 10♥ 6♠ (16)
+     */
+    pub fn hand(&self, hand: &Hand) -> String {
+        if self.is_multiline() {
+            format!("({})\n{}", hand.get_value(), self.cards(hand.get_cards(), 0))
+        } else {
+            format!("{} ({})", self.inline(hand.get_cards(), 0), hand.get_value())
+        }
+    }
+
+    /**
+     * Draws a hand with its value on one line
+     * Ex: 10♥ 6♠ (16)
+     */
+    pub fn hand_inline(&self, hand: &Hand) -> String {
+        format!("{} ({})", self.inline(hand.get_cards(), 0), hand.get_value())
+    }
+}
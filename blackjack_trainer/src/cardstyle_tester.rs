@@ -0,0 +1,85 @@
+use crate::blackjack::{Card, Hand, Suit};
+use crate::cardstyle::{card_glyph, is_utf8_locale, suit_symbol, CardRenderer, CardStyle};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(text: &str) -> Vec<Card> {
+        text.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn hand(text: &str) -> Hand {
+        let cards = cards(text);
+        Hand::new(cards[0], cards[1])
+    }
+
+    #[test]
+    fn test_style_names() {
+        for style in CardStyle::ALL {
+            assert_eq!(CardStyle::parse(style.name()), Some(style));
+        }
+        assert_eq!(CardStyle::parse("ART"), Some(CardStyle::Art));
+        assert_eq!(CardStyle::parse("emoji"), None);
+        assert!(is_utf8_locale("en_GB.UTF-8"));
+        assert!(is_utf8_locale("C.utf8"));
+        assert!(!is_utf8_locale("C"));
+        assert!(!is_utf8_locale("POSIX"));
+    }
+
+    #[test]
+    fn test_text_matches_display() {
+        let renderer = CardRenderer::default();
+        let hand = hand("10H AS");
+        assert_eq!(renderer.hand(&hand), hand.to_string());
+        assert_eq!(renderer.inline(&cards("10C"), 1), "10C ??");
+        assert!(!renderer.is_multiline());
+    }
+
+    #[test]
+    fn test_suits_and_colour() {
+        let plain = CardRenderer::new(CardStyle::Suits, false);
+        assert_eq!(plain.hand(&hand("10H KS")), "10♥ K♠ (20)");
+        assert_eq!(plain.inline(&cards("AD"), 1), "A♦ ▒▒");
+        assert_eq!(suit_symbol(Suit::Clubs), '♣');
+
+        // Only red cards are coloured
+        let coloured = CardRenderer::new(CardStyle::Suits, true);
+        assert_eq!(coloured.card(&"QD".parse().unwrap()), "\x1b[31mQ♦\x1b[0m");
+        assert_eq!(coloured.card(&"QC".parse().unwrap()), "Q♣");
+    }
+
+    #[test]
+    fn test_glyphs() {
+        let glyph = |text: &str| card_glyph(&text.parse().unwrap());
+        assert_eq!(glyph("AS"), '🂡');
+        assert_eq!(glyph("10H"), '🂺');
+        // The knight between the jack and the queen is skipped
+        assert_eq!(glyph("JD"), '🃋');
+        assert_eq!(glyph("QD"), '🃍');
+        assert_eq!(glyph("KC"), '🃞');
+
+        let renderer = CardRenderer::new(CardStyle::Glyphs, false);
+        assert_eq!(renderer.inline(&cards("2C"), 1), "🃒 🂠");
+    }
+
+    #[test]
+    fn test_art() {
+        let renderer = CardRenderer::new(CardStyle::Art, false);
+        assert!(renderer.is_multiline());
+        let drawn = renderer.cards(&cards("10H"), 1);
+        assert_eq!(
+            drawn,
+            ".-----. .-----.\n\
+             |10   | |#####|\n\
+             |  ♥  | |#####|\n\
+             |   10| |#####|\n\
+             '-----' '-----'"
+        );
+        // The value comes first so a label can share its line
+        let soft = hand("AS 7D");
+        assert!(renderer.hand(&soft).starts_with("(18)\n.-----. .-----.\n|A    | |7    |"));
+        // Where only one line fits, art is drawn with suit symbols
+        assert_eq!(renderer.hand_inline(&soft), "A♠ 7♦ (18)");
+    }
+}
@@ -0,0 +1,282 @@
+use crate::blackjack::{dealer_final_probabilities, Rules};
+use crate::blackjackbasicstrategy::{
+    play_abbreviation, BlackjackBasicStrategy, ChartCell, HandCategory, DEALER_CARDS, DOUBLE_OR_HIT,
+    DOUBLE_OR_STAND, HIT, SPLIT, STAND, SURRENDER_OR_HIT, SURRENDER_OR_STAND,
+};
+// The row and column names are the strategy's own, and are kept here for the modules that draw charts
+pub use crate::blackjackbasicstrategy::{dealer_label, row_name};
+use crate::ev;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal};
+
+const RESET: &str = "\x1b[0m";
+const BOLD_UNDERLINE: &str = "\x1b[1;4m";
+
+/**
+ * Cells with a recorded accuracy below this fraction are highlighted.
+ */
+pub const LOW_ACCURACY: f64 = 0.8;
+
+/**
+ * Returns true if stdout is a terminal and the user has not opted out of colour with NO_COLOR
+ */
+pub fn colour_enabled() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/**
+ * Returns the ANSI background colour of a play, following the familiar printed card:
+ * green to hit, red to stand, yellow to double, blue to split and white to surrender.
+ */
+fn play_colour(play: i32) -> &'static str {
+    match play {
+        HIT => "\x1b[30;42m",
+        STAND => "\x1b[30;41m",
+        DOUBLE_OR_HIT | DOUBLE_OR_STAND => "\x1b[30;43m",
+        SPLIT => "\x1b[30;46m",
+        SURRENDER_OR_HIT | SURRENDER_OR_STAND => "\x1b[30;47m",
+        _ => "",
+    }
+}
+
+/**
+ * Returns the label of a chart row
+ * Ex: 16, A,7 (for soft 18), 8,8
+ */
+pub fn row_label(category: HandCategory) -> String {
+    match category {
+        HandCategory::Hard(total) => total.to_string(),
+        HandCategory::Soft(12) => "A,A".to_string(),
+        HandCategory::Soft(total) => format!("A,{}", total - 11),
+        HandCategory::Pair(1) => "A,A".to_string(),
+        HandCategory::Pair(card) => format!("{},{}", card, card),
+    }
+}
+
+fn render_table(
+    out: &mut String,
+    title: &str,
+    rows: &[HandCategory],
+    cell_text: &dyn Fn(ChartCell) -> Option<(String, i32)>,
+    accuracy: Option<&HashMap<ChartCell, f64>>,
+    colour: bool,
+) {
+    out.push_str(&format!("{:<6}", title));
+    for dealer_card in DEALER_CARDS {
+        out.push_str(&format!("{:^4}", dealer_label(dealer_card)));
+    }
+    out.push('\n');
+
+    for &category in rows {
+        out.push_str(&format!("{:<6}", row_label(category)));
+
+        for dealer_card in DEALER_CARDS {
+            let cell = ChartCell { category, dealer_card };
+            let (text, play) = match cell_text(cell) {
+                Some(entry) => entry,
+                None => {
+                    out.push_str("    ");
+                    continue;
+                }
+            };
+            let weak = accuracy
+                .and_then(|accuracy| accuracy.get(&cell))
+                .is_some_and(|&accuracy| accuracy < LOW_ACCURACY);
+            let text = if weak { format!("{}*", text) } else { text };
+
+            if colour {
+                let emphasis = if weak { BOLD_UNDERLINE } else { "" };
+                out.push_str(&format!("{}{}{:^4}{}", play_colour(play), emphasis, text, RESET));
+            } else {
+                out.push_str(&format!("{:^4}", text));
+            }
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+}
+
+/**
+ * Renders the hard, soft and pair tables of a strategy as a grid with one cell per dealer upcard.
+ * The header shows the rules the chart was built for.
+ * @param strategy the strategy to render
+ * @param accuracy the user's recorded accuracy per cell; cells below LOW_ACCURACY are marked with *
+ * @param colour true to colour each cell by its play
+ */
+pub fn render_chart(
+    strategy: &BlackjackBasicStrategy,
+    accuracy: Option<&HashMap<ChartCell, f64>>,
+    colour: bool,
+) -> String {
+    let title = format!("Basic strategy: {}", strategy.get_rules());
+    render_with(&title, strategy, &|cell| {
+        strategy
+            .get_play(cell.category, cell.dealer_card)
+            .map(|play| (play_abbreviation(play).to_string(), play))
+    }, accuracy, colour)
+}
+
+/**
+ * Renders the tables of a strategy using the specified text and play for each cell.
+ * Cells without an entry are left blank, cells with a play of 0 are not coloured.
+ */
+pub fn render_with(
+    title: &str,
+    strategy: &BlackjackBasicStrategy,
+    cell_text: &dyn Fn(ChartCell) -> Option<(String, i32)>,
+    accuracy: Option<&HashMap<ChartCell, f64>>,
+    colour: bool,
+) -> String {
+    let mut out = format!("{}\n\n", title);
+
+    render_table(&mut out, "Hard", &strategy.hard_rows(), cell_text, accuracy, colour);
+    render_table(&mut out, "Soft", &strategy.soft_rows(), cell_text, accuracy, colour);
+    render_table(&mut out, "Pairs", &strategy.pair_rows(), cell_text, accuracy, colour);
+
+    out.push_str(&legend(strategy.get_rules().surrender, colour));
+    if accuracy.is_some() {
+        out.push_str(&format!("* accuracy below {:.0}%\n", LOW_ACCURACY * 100.0));
+    }
+    out
+}
+
+/**
+ * A cell where two charts disagree
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChartDifference {
+    pub cell: ChartCell,
+    pub first_play: i32,
+    pub second_play: i32,
+    // Expected value lost per hand in this cell by playing the second chart's game with the first chart
+    pub ev_cost: Option<f64>,
+}
+
+/**
+ * Returns every cell where the two charts disagree, in chart order.
+ * The cost of each difference is computed under the second chart's rules.
+ * @param first the chart the player knows
+ * @param second the chart for the game the player is moving to
+ */
+pub fn diff_charts(first: &BlackjackBasicStrategy, second: &BlackjackBasicStrategy) -> Vec<ChartDifference> {
+    let rows = first.hard_rows().into_iter().chain(first.soft_rows()).chain(first.pair_rows());
+    let mut differences = Vec::new();
+
+    for category in rows {
+        for dealer_card in DEALER_CARDS {
+            let first_play = first.get_play(category, dealer_card).expect("row is in the chart");
+            let second_play = second.get_play(category, dealer_card).expect("row is in the chart");
+            if first_play == second_play {
+                continue;
+            }
+
+            let evs = ev::cell_evs(second.get_rules(), category, dealer_card);
+            let ev_cost = match (evs.play_ev(second_play), evs.play_ev(first_play)) {
+                (Some(right), Some(wrong)) => Some(right - wrong),
+                _ => None,
+            };
+
+            differences.push(ChartDifference {
+                cell: ChartCell { category, dealer_card },
+                first_play,
+                second_play,
+                ev_cost,
+            });
+        }
+    }
+    differences
+}
+
+/**
+ * Renders the differences between two charts: a grid showing the second chart's play in each
+ * differing cell (identical cells are shown as .), followed by a list of the differences.
+ */
+pub fn render_diff(
+    first: &BlackjackBasicStrategy,
+    second: &BlackjackBasicStrategy,
+    differences: &[ChartDifference],
+    colour: bool,
+) -> String {
+    let title = format!("Chart differences\n  from: {}\n  to:   {}", first.get_rules(), second.get_rules());
+    let mut out = render_with(&title, second, &|cell| {
+        match differences.iter().find(|difference| difference.cell == cell) {
+            Some(difference) => Some((play_abbreviation(difference.second_play).to_string(), difference.second_play)),
+            None => Some((".".to_string(), 0)),
+        }
+    }, None, colour);
+
+    out.push_str(&format!("\n{} differing cells (cost: EV lost per hand in the cell using the old play)\n", differences.len()));
+    for difference in differences {
+        out.push_str(&format!(
+            "{:<14} vs {:<2}  {:<2} -> {:<2}",
+            row_name(difference.cell.category),
+            dealer_label(difference.cell.dealer_card),
+            play_abbreviation(difference.first_play),
+            play_abbreviation(difference.second_play)
+        ));
+        if let Some(cost) = difference.ev_cost {
+            out.push_str(&format!("  cost {:.4}", cost));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/**
+ * Renders the exact probability of each dealer final total for every upcard, dealt from a full shoe
+ * @param rules the number of decks and the soft 17 rule
+ * @param peek true if the dealer checks for blackjack, so hands only go on when the dealer does not have it
+ */
+pub fn render_dealer_table(rules: &Rules, peek: bool) -> String {
+    let condition = if peek { "dealer peeks, so without blackjack" } else { "no peek" };
+    let mut out = format!("Dealer final totals, {} decks, {} ({})\n", rules.decks, if rules.dealer_hits_soft_17 { "H17" } else { "S17" }, condition);
+    out.push_str(&format!("{:<4}", "Up"));
+    for label in ["17", "18", "19", "20", "21", "Bust"] {
+        out.push_str(&format!("{:>8}", label));
+    }
+    if !peek {
+        out.push_str(&format!("{:>8}", "BJ"));
+    }
+    out.push('\n');
+
+    for dealer_card in DEALER_CARDS {
+        let mut composition = [4 * rules.decks as u32; 10];
+        composition[9] = 16 * rules.decks as u32;
+        composition[(dealer_card - 1) as usize] -= 1;
+        let finals = dealer_final_probabilities(&composition, dealer_card, rules.dealer_hits_soft_17, peek);
+
+        out.push_str(&format!("{:<4}", dealer_label(dealer_card)));
+        for probability in finals.totals.iter().chain([finals.bust].iter()) {
+            out.push_str(&format!("{:>7.2}%", 100.0 * probability));
+        }
+        if !peek {
+            out.push_str(&format!("{:>7.2}%", 100.0 * finals.blackjack));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// The surrender plays are only listed for charts with late surrender
+fn legend(surrender: bool, colour: bool) -> String {
+    let entries = [
+        (HIT, "hit"),
+        (STAND, "stand"),
+        (DOUBLE_OR_HIT, "double, otherwise hit"),
+        (DOUBLE_OR_STAND, "double, otherwise stand"),
+        (SPLIT, "split"),
+        (SURRENDER_OR_HIT, "surrender, otherwise hit"),
+        (SURRENDER_OR_STAND, "surrender, otherwise stand"),
+    ];
+    let mut out = String::new();
+
+    for (play, name) in entries.into_iter().filter(|&(play, _)| surrender || !matches!(play, SURRENDER_OR_HIT | SURRENDER_OR_STAND)) {
+        if colour {
+            out.push_str(&format!("{}{:^4}{} {}  ", play_colour(play), play_abbreviation(play), RESET, name));
+        } else {
+            out.push_str(&format!("{} = {}  ", play_abbreviation(play), name));
+        }
+    }
+    out.trim_end().to_string() + "\n"
+}
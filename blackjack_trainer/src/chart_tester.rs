@@ -0,0 +1,98 @@
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory, DOUBLE_OR_HIT, HIT};
+use crate::blackjack::Rules;
+use crate::chart::{diff_charts, render_chart, render_dealer_table, render_diff, row_label};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart_header_and_rows() {
+        let strategy = BlackjackBasicStrategy::new();
+        let chart = render_chart(&strategy, None, false);
+
+        assert!(chart.starts_with("Basic strategy: 6 decks, S17, DAS, BJ pays 3:2, 75% penetration"));
+        assert!(chart.contains("Hard   2   3   4   5   6   7   8   9   10  A"));
+        assert!(chart.contains("\n16     S   S   S   S   S   H   H   H   H   H  \n"));
+        assert!(chart.contains("\nA,7    S   Ds  Ds  Ds  Ds  S   S   H   H   H  \n"));
+        assert!(chart.contains("\n9,9    P   P   P   P   P   S   P   P   S   S  \n"));
+        assert!(!chart.contains('\x1b'));
+        assert!(!chart.contains("Rh"));
+    }
+
+    #[test]
+    fn test_chart_with_surrender() {
+        let strategy = BlackjackBasicStrategy::for_rules(&Rules { surrender: true, ..Rules::default() });
+        let chart = render_chart(&strategy, None, false);
+
+        assert!(chart.starts_with("Basic strategy: 6 decks, S17, DAS, LS, BJ pays 3:2, 75% penetration"));
+        assert!(chart.contains("\n16     S   S   S   S   S   H   H   Rh  Rh  Rh \n"));
+        assert!(chart.contains("Rh = surrender, otherwise hit  Rs = surrender, otherwise stand\n"));
+    }
+
+    #[test]
+    fn test_chart_colour() {
+        let strategy = BlackjackBasicStrategy::new();
+        let chart = render_chart(&strategy, None, true);
+
+        assert!(chart.contains("\x1b[30;41m S  \x1b[0m"));
+    }
+
+    #[test]
+    fn test_low_accuracy_highlight() {
+        let strategy = BlackjackBasicStrategy::new();
+        let mut accuracy = HashMap::new();
+        accuracy.insert(ChartCell { category: HandCategory::Hard(12), dealer_card: 3 }, 0.5);
+        accuracy.insert(ChartCell { category: HandCategory::Hard(12), dealer_card: 4 }, 0.95);
+        let chart = render_chart(&strategy, Some(&accuracy), false);
+
+        assert!(chart.contains("\n12     H   H*  S   S   S   H   H   H   H   H  \n"));
+        assert!(chart.contains("* accuracy below 80%"));
+    }
+
+    #[test]
+    fn test_row_labels() {
+        assert_eq!(row_label(HandCategory::Hard(4)), "4");
+        assert_eq!(row_label(HandCategory::Soft(18)), "A,7");
+        assert_eq!(row_label(HandCategory::Pair(1)), "A,A");
+        assert_eq!(row_label(HandCategory::Pair(10)), "10,10");
+    }
+
+    #[test]
+    fn test_diff_s17_to_h17() {
+        let s17 = BlackjackBasicStrategy::from_spec("6d-s17").unwrap();
+        let h17 = BlackjackBasicStrategy::from_spec("6d-h17").unwrap();
+        let differences = diff_charts(&s17, &h17);
+
+        assert_eq!(differences.len(), 3);
+        let eleven = differences[0];
+        assert_eq!(eleven.cell, ChartCell { category: HandCategory::Hard(11), dealer_card: 1 });
+        assert_eq!((eleven.first_play, eleven.second_play), (HIT, DOUBLE_OR_HIT));
+        assert!(eleven.ev_cost.unwrap() > 0.0);
+
+        let rendered = render_diff(&s17, &h17, &differences, false);
+        assert!(rendered.contains("3 differing cells"));
+        assert!(rendered.contains("hard 11        vs A   H  -> D"));
+        assert!(rendered.contains("\n11     .   .   .   .   .   .   .   .   .   D  \n"));
+    }
+
+    #[test]
+    fn test_diff_identical_charts() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert!(diff_charts(&strategy, &strategy).is_empty());
+    }
+
+    #[test]
+    fn test_dealer_table() {
+        let peek = render_dealer_table(&Rules::default(), true);
+        let no_peek = render_dealer_table(&Rules::default(), false);
+
+        assert!(peek.starts_with("Dealer final totals, 6 decks, S17 (dealer peeks"));
+        assert!(peek.contains("\n6     16.57%  10.62%  10.64%  10.16%   9.73%  42.28%\n"));
+        assert!(no_peek.lines().nth(1).unwrap().ends_with("Bust      BJ"));
+        assert!(no_peek.contains("  30.87%\n"));
+        assert_eq!(peek.lines().count(), 12);
+    }
+}
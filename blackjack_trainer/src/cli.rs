@@ -0,0 +1,397 @@
+use crate::blackjack::{Rules, Suit};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::cardstyle::{suit_symbol, CardStyle};
+
+/**
+ * The usage shown by "help" and when a command is not recognised
+ */
+pub const USAGE: &str = "\
+usage: blackjack_trainer [command] [options] [flags]
+
+Commands:
+  play                      play hands at the table (the default)
+  train [block]             play with every decision graded against basic strategy
+  drill                     practise the situations you most often get wrong
+  flash [hard|soft|pair]    quiz strategy decisions without playing hands out
+  count [deck|true|systems] practise card counting
+  query <cards> vs <card>   show the basic strategy play for a hand, e.g. query 10 6 vs 10
+  chart [diff|export|weak]  print strategy charts
+  dealer [peek|no-peek]     print the dealer's chances of each final total
+  simulate [options]        measure the house edge with a computer player
+  bankroll [options]        report the risk of ruin of a bankroll
+  replay [list|<number>]    step through the hand history of a past session
+  export sessions|stats     write training results as CSV or JSON
+  engine                    speak the JSON line protocol on stdin and stdout, for bots and front-ends
+  serve [options]           host a table for several players on a local TCP port
+  join [address] [options]  sit at, or watch, a table hosted with serve
+  stats [reset]             show the accuracy of every training session
+  keys [reset]              show the keys used to play, set in keymap.txt in the data directory
+  help                      show this message
+
+Flags shared by every command that deals cards:
+  --decks <number>          the number of decks in the shoe (default 6)
+  --h17, --s17              whether the dealer hits soft 17 (default --s17)
+  --das, --no-das           whether doubling after a split is allowed (default --das)
+  --surrender, --no-surrender
+                            whether late surrender is offered (default --no-surrender)
+  --payout <ratio>          what a blackjack pays: 3:2, 6:5 or a number such as 1.2
+  --penetration <fraction>  how much of the shoe is dealt before it is reshuffled, e.g. 0.75 or 75%
+  --seed <number>           the seed of the shoe, so the same seed deals the same cards
+  --chart <variant|file>    the strategy chart to follow (default the chart for the rules)
+  --bankroll <units>        the starting bankroll
+  --cards <style>           how cards are drawn: text (10H), suits (10♥), glyphs (🂺) or art
+                            (default suits on a UTF-8 terminal, otherwise text)
+Flags can be written anywhere on the command line, also as --name=value.
+";
+
+/**
+ * The table settings given as flags, which override the defaults and the matching command options.
+ * A flag that is not given leaves its setting as it was.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableFlags {
+    pub decks: Option<usize>,
+    pub dealer_hits_soft_17: Option<bool>,
+    pub double_after_split: Option<bool>,
+    pub surrender: Option<bool>,
+    pub payout: Option<f64>,
+    pub penetration: Option<f64>,
+    pub seed: Option<u64>,
+    // A built-in variant name or the path of a chart file
+    pub chart: Option<String>,
+    pub bankroll: Option<f64>,
+    pub cards: Option<CardStyle>,
+}
+impl TableFlags {
+    /**
+     * Separates the flags from the other arguments, which are returned in their order
+     * Ex: ["simulate", "--decks", "2", "hands", "1000", "--h17"] gives 2 decks, H17 and
+     * ["simulate", "hands", "1000"]
+     */
+    pub fn parse(args: &[String]) -> Result<(TableFlags, Vec<String>), String> {
+        let mut flags = TableFlags::default();
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                rest.push(arg.clone());
+                continue;
+            };
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let mut value = || inline.clone().or_else(|| args.next().cloned()).ok_or_else(|| format!("--{} needs a value", name));
+
+            match name {
+                "decks" => {
+                    let text = value()?;
+                    flags.decks = Some(text.parse().ok().filter(|&decks| decks > 0 && decks <= 8)
+                        .ok_or_else(|| format!("invalid number of decks {}", text))?);
+                }
+                "h17" => flags.dealer_hits_soft_17 = Some(true),
+                "s17" => flags.dealer_hits_soft_17 = Some(false),
+                "das" => flags.double_after_split = Some(true),
+                "no-das" | "nodas" => flags.double_after_split = Some(false),
+                "surrender" => flags.surrender = Some(true),
+                "no-surrender" => flags.surrender = Some(false),
+                "payout" => {
+                    let text = value()?;
+                    flags.payout = Some(parse_payout(&text).ok_or_else(|| format!("invalid payout {}", text))?);
+                }
+                "penetration" => {
+                    let text = value()?;
+                    flags.penetration = Some(parse_penetration(&text).ok_or_else(|| format!("invalid penetration {}", text))?);
+                }
+                "seed" => {
+                    let text = value()?;
+                    flags.seed = Some(text.parse().map_err(|_| format!("invalid seed {}", text))?);
+                }
+                "chart" => flags.chart = Some(value()?),
+                "bankroll" => {
+                    let text = value()?;
+                    flags.bankroll = Some(text.replace(['_', ','], "").parse().ok().filter(|&units: &f64| units > 0.0)
+                        .ok_or_else(|| format!("invalid bankroll {}", text))?);
+                }
+                "cards" => {
+                    let text = value()?;
+                    flags.cards = Some(CardStyle::parse(&text)
+                        .ok_or_else(|| format!("unknown card style {}, use text, suits, glyphs or art", text))?);
+                }
+                "help" => rest.insert(0, "help".to_string()),
+                _ => return Err(format!("unknown flag --{}", name)),
+            }
+        }
+        Ok((flags, rest))
+    }
+
+    /**
+     * Overrides the rules with the rule flags that were given
+     */
+    pub fn apply(&self, rules: &mut Rules) {
+        rules.decks = self.decks.unwrap_or(rules.decks);
+        rules.dealer_hits_soft_17 = self.dealer_hits_soft_17.unwrap_or(rules.dealer_hits_soft_17);
+        rules.double_after_split = self.double_after_split.unwrap_or(rules.double_after_split);
+        rules.surrender = self.surrender.unwrap_or(rules.surrender);
+        rules.blackjack_payout = self.payout.unwrap_or(rules.blackjack_payout);
+        rules.penetration = self.penetration.unwrap_or(rules.penetration);
+    }
+
+    /**
+     * Returns the default rules with the rule flags applied
+     */
+    pub fn rules(&self) -> Rules {
+        let mut rules = Rules::default();
+        self.apply(&mut rules);
+        rules
+    }
+
+    /**
+     * Returns the chart given with --chart, or the chart for the rules
+     */
+    pub fn strategy(&self, rules: &Rules) -> Result<BlackjackBasicStrategy, String> {
+        match &self.chart {
+            Some(spec) => BlackjackBasicStrategy::from_spec(spec),
+            None => Ok(BlackjackBasicStrategy::for_rules(rules)),
+        }
+    }
+
+    /**
+     * Returns the names of the flags that were given, e.g. ["--decks", "--seed"]
+     */
+    pub fn given(&self) -> Vec<&'static str> {
+        let flags = [
+            ("--decks", self.decks.is_some()),
+            ("--h17/--s17", self.dealer_hits_soft_17.is_some()),
+            ("--das/--no-das", self.double_after_split.is_some()),
+            ("--surrender/--no-surrender", self.surrender.is_some()),
+            ("--payout", self.payout.is_some()),
+            ("--penetration", self.penetration.is_some()),
+            ("--seed", self.seed.is_some()),
+            ("--chart", self.chart.is_some()),
+            ("--bankroll", self.bankroll.is_some()),
+            ("--cards", self.cards.is_some()),
+        ];
+        flags.into_iter().filter(|&(_, given)| given).map(|(name, _)| name).collect()
+    }
+}
+
+/**
+ * Parses what a blackjack pays, as a ratio or a number
+ * Ex: "3:2" is 1.5, "6:5" and "1.2" are 1.2
+ */
+pub fn parse_payout(text: &str) -> Option<f64> {
+    let payout = match text.split_once(':') {
+        Some((win, stake)) => win.trim().parse::<f64>().ok()? / stake.trim().parse::<f64>().ok()?,
+        None => text.parse().ok()?,
+    };
+    Some(payout).filter(|payout| payout.is_finite() && *payout > 0.0)
+}
+
+/**
+ * Parses the fraction of the shoe dealt before reshuffling, as a fraction or a percentage
+ * Ex: "0.75" and "75%" are 0.75
+ */
+pub fn parse_penetration(text: &str) -> Option<f64> {
+    let penetration = match text.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok()? / 100.0,
+        None => text.parse().ok()?,
+    };
+    Some(penetration).filter(|&penetration| penetration > 0.0 && penetration < 1.0)
+}
+
+/**
+ * The commands of the command line
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Play,
+    Train,
+    Drill,
+    Flash,
+    Count,
+    Query,
+    Chart,
+    Dealer,
+    Simulate,
+    Bankroll,
+    Replay,
+    Export,
+    Engine,
+    Serve,
+    Join,
+    Stats,
+    Keys,
+    Help,
+}
+impl Command {
+    pub fn parse(name: &str) -> Option<Command> {
+        match name.to_lowercase().as_str() {
+            "play" => Some(Command::Play),
+            "train" => Some(Command::Train),
+            "drill" => Some(Command::Drill),
+            "flash" => Some(Command::Flash),
+            "count" => Some(Command::Count),
+            "query" => Some(Command::Query),
+            "chart" => Some(Command::Chart),
+            "dealer" => Some(Command::Dealer),
+            "simulate" => Some(Command::Simulate),
+            "bankroll" => Some(Command::Bankroll),
+            "replay" => Some(Command::Replay),
+            "export" => Some(Command::Export),
+            "engine" => Some(Command::Engine),
+            "serve" => Some(Command::Serve),
+            "join" => Some(Command::Join),
+            "stats" => Some(Command::Stats),
+            "keys" => Some(Command::Keys),
+            "help" | "-h" => Some(Command::Help),
+            _ => None,
+        }
+    }
+
+    /**
+     * Returns the flags this command uses
+     */
+    pub fn accepted_flags(&self) -> &'static [&'static str] {
+        const RULES: [&str; 5] = ["--decks", "--h17/--s17", "--das/--no-das", "--payout", "--penetration"];
+        const TABLE: [&str; 9] = [
+            "--decks",
+            "--h17/--s17",
+            "--das/--no-das",
+            "--surrender/--no-surrender",
+            "--payout",
+            "--penetration",
+            "--seed",
+            "--chart",
+            "--bankroll",
+        ];
+        const PLAYED: [&str; 10] = [
+            "--decks",
+            "--h17/--s17",
+            "--das/--no-das",
+            "--surrender/--no-surrender",
+            "--payout",
+            "--penetration",
+            "--seed",
+            "--chart",
+            "--bankroll",
+            "--cards",
+        ];
+        const SERVED: [&str; 8] = [
+            "--decks",
+            "--h17/--s17",
+            "--das/--no-das",
+            "--surrender/--no-surrender",
+            "--payout",
+            "--penetration",
+            "--seed",
+            "--bankroll",
+        ];
+        const CHART: [&str; 7] =
+            ["--decks", "--h17/--s17", "--das/--no-das", "--surrender/--no-surrender", "--payout", "--penetration", "--chart"];
+        match self {
+            Command::Play | Command::Train | Command::Drill => &PLAYED,
+            Command::Simulate | Command::Bankroll | Command::Engine => &TABLE,
+            Command::Query | Command::Chart | Command::Flash | Command::Stats => &CHART,
+            Command::Dealer => &RULES,
+            Command::Serve => &SERVED,
+            Command::Join => &["--cards"],
+            Command::Count => &["--decks", "--cards"],
+            Command::Replay | Command::Export | Command::Keys | Command::Help => &[],
+        }
+    }
+}
+
+/**
+ * A parsed command line: the command, the shared flags and the command's own arguments
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub flags: TableFlags,
+    pub args: Vec<String>,
+}
+impl Cli {
+    /**
+     * Parses a command line, without the program name. With no command the table is played.
+     * Flags the command does not use are rejected rather than ignored.
+     * Ex: ["simulate", "hands", "1000", "--decks", "2"]
+     */
+    pub fn parse(args: &[String]) -> Result<Cli, String> {
+        let (flags, mut rest) = TableFlags::parse(args)?;
+        let command = match rest.first() {
+            None => Command::Play,
+            Some(name) => Command::parse(name).ok_or_else(|| format!("unknown command {}\n\n{}", name, USAGE))?,
+        };
+        if !rest.is_empty() {
+            rest.remove(0);
+        }
+
+        if let Some(flag) = flags.given().into_iter().find(|flag| !command.accepted_flags().contains(flag)) {
+            return Err(format!("{} does not apply to {}", flag, format!("{:?}", command).to_lowercase()));
+        }
+        Ok(Cli { command, flags, args: rest })
+    }
+}
+
+/**
+ * Parses the hand of the query command: the player's cards, "vs" and the dealer's upcard.
+ * Cards are ranks (A, 2-10, J, Q, K) or cards as they are displayed (AS, 10H), and two cards
+ * may be written together (A7, 88)
+ * Ex: ["10", "6", "vs", "10"] or ["A7", "vs", "9"]
+ * Returns the values of the player's cards and the dealer's upcard, counting an ace as 1
+ */
+pub fn parse_query(args: &[String]) -> Result<(Vec<i32>, i32), String> {
+    let usage = "usage: query <cards> vs <upcard>, e.g. query 10 6 vs 10";
+    let split = args.iter().position(|arg| arg.eq_ignore_ascii_case("vs")).ok_or(usage)?;
+    let (player, dealer) = (&args[..split], &args[split + 1..]);
+
+    let mut hand = Vec::new();
+    for arg in player {
+        hand.extend(parse_card_values(arg)?);
+    }
+    let upcard = match dealer {
+        [card] => match parse_card_values(card)?.as_slice() {
+            [value] => *value,
+            _ => return Err(format!("the dealer shows one card, not {}", card)),
+        },
+        _ => return Err(usage.to_string()),
+    };
+    if hand.len() < 2 {
+        return Err("the player's hand needs at least two cards".to_string());
+    }
+    Ok((hand, upcard))
+}
+
+// The values of one card or of several ranks written together, e.g. "10", "QS", "A7", "88"
+fn parse_card_values(text: &str) -> Result<Vec<i32>, String> {
+    let invalid = || format!("invalid card {}", text);
+    let rank = |rank: &str| match rank.to_uppercase().as_str() {
+        "A" => Some(1),
+        "J" | "Q" | "K" | "T" => Some(10),
+        number => number.parse().ok().filter(|value| (2..=10).contains(value)),
+    };
+
+    if let Some(value) = rank(text) {
+        return Ok(vec![value]);
+    }
+    // Cards copied from the table may be drawn with suit symbols, e.g. 10♥
+    let lettered: String = text
+        .chars()
+        .map(|c| Suit::ALL.into_iter().find(|&suit| suit_symbol(suit) == c).map_or(c, |suit| suit.abbreviation()))
+        .collect();
+    if let Ok(card) = lettered.to_uppercase().parse::<crate::blackjack::Card>() {
+        return Ok(vec![card.get_value()]);
+    }
+    // Ranks written together, where 10 is the only rank with two characters
+    let upper = text.to_uppercase();
+    let mut values = Vec::new();
+    let mut rest = upper.as_str();
+    while !rest.is_empty() {
+        let length = if rest.starts_with("10") { 2 } else { 1 };
+        values.push(rest.get(..length).and_then(rank).ok_or_else(invalid)?);
+        rest = &rest[length..];
+    }
+    Ok(values)
+}
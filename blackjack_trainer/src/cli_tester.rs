@@ -0,0 +1,109 @@
+use crate::bankroll::RiskConfig;
+use crate::cli::{parse_payout, parse_penetration, parse_query, Cli, Command, TableFlags};
+use crate::simulation::SimulationConfig;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_flags_anywhere() {
+        let cli = Cli::parse(&args("--decks 2 simulate hands 1000 --h17 --payout=6:5 --no-das --surrender seed 4")).unwrap();
+        assert_eq!(cli.command, Command::Simulate);
+        assert_eq!(cli.args, args("hands 1000 seed 4"));
+
+        let rules = cli.flags.rules();
+        assert_eq!(rules.decks, 2);
+        assert!(rules.dealer_hits_soft_17);
+        assert!(!rules.double_after_split);
+        assert!(rules.surrender);
+        assert_eq!(rules.blackjack_payout, 1.2);
+        assert_eq!(rules.penetration, 0.75);
+    }
+
+    #[test]
+    fn test_default_and_unknown_commands() {
+        let cli = Cli::parse(&[]).unwrap();
+        assert_eq!(cli.command, Command::Play);
+        assert_eq!(cli.flags, TableFlags::default());
+
+        assert_eq!(Cli::parse(&args("--seed 9")).unwrap().flags.seed, Some(9));
+        assert_eq!(Cli::parse(&args("--help")).unwrap().command, Command::Help);
+        assert!(Cli::parse(&args("deal")).unwrap_err().starts_with("unknown command deal"));
+    }
+
+    #[test]
+    fn test_invalid_flags() {
+        assert_eq!(Cli::parse(&args("--decks 9")).unwrap_err(), "invalid number of decks 9");
+        assert_eq!(Cli::parse(&args("--payout two")).unwrap_err(), "invalid payout two");
+        assert_eq!(Cli::parse(&args("play --seed")).unwrap_err(), "--seed needs a value");
+        assert_eq!(Cli::parse(&args("--insurance")).unwrap_err(), "unknown flag --insurance");
+        // Flags a command does not use are refused rather than silently ignored
+        assert_eq!(Cli::parse(&args("replay 2 --seed 3")).unwrap_err(), "--seed does not apply to replay");
+        assert_eq!(Cli::parse(&args("query 10 6 vs 10 --bankroll 5")).unwrap_err(), "--bankroll does not apply to query");
+        assert_eq!(Cli::parse(&args("serve --chart s17")).unwrap_err(), "--chart does not apply to serve");
+        assert_eq!(Cli::parse(&args("flash soft --h17 --chart h17")).unwrap().flags.chart.as_deref(), Some("h17"));
+        assert_eq!(Cli::parse(&args("stats --decks 1")).unwrap().flags.rules().decks, 1);
+    }
+
+    #[test]
+    fn test_payout_and_penetration() {
+        assert_eq!(parse_payout("3:2"), Some(1.5));
+        assert_eq!(parse_payout("6:5"), Some(1.2));
+        assert_eq!(parse_payout("1"), Some(1.0));
+        assert_eq!(parse_payout("3:0"), None);
+        assert_eq!(parse_penetration("80%"), Some(0.8));
+        assert_eq!(parse_penetration("0.5"), Some(0.5));
+        assert_eq!(parse_penetration("100%"), None);
+    }
+
+    #[test]
+    fn test_chart_flag() {
+        let flags = Cli::parse(&args("query --chart 1d-h17")).unwrap().flags;
+        let strategy = flags.strategy(&flags.rules()).unwrap();
+        assert_eq!(strategy.get_rules().decks, 1);
+        assert!(strategy.get_rules().dealer_hits_soft_17);
+
+        let flags = Cli::parse(&args("query --chart missing.chart")).unwrap().flags;
+        assert!(flags.strategy(&flags.rules()).is_err());
+    }
+
+    #[test]
+    fn test_flags_override_options() {
+        let flags = Cli::parse(&args("simulate --h17 --seed 5 --bankroll 200 --penetration 50%")).unwrap().flags;
+        let config = SimulationConfig::parse_with(&args("rules 2d-s17 seed 1 penetration 0.9"), &flags).unwrap();
+        assert_eq!(config.rules.decks, 2);
+        assert!(config.rules.dealer_hits_soft_17);
+        assert_eq!(config.rules.penetration, 0.5);
+        assert_eq!(config.seed, 5);
+        assert_eq!(config.bankroll, 200.0);
+        assert_eq!(config.strategy.get_rules(), &config.rules);
+
+        let risk = RiskConfig::parse_with(&args("sessions 5"), &flags).unwrap();
+        assert_eq!(risk.simulation.seed, 5);
+        assert!(risk.simulation.rules.dealer_hits_soft_17);
+    }
+
+    #[test]
+    fn test_query_hands() {
+        assert_eq!(parse_query(&args("10 6 vs 10")), Ok((vec![10, 6], 10)));
+        assert_eq!(parse_query(&args("A 7 VS 9")), Ok((vec![1, 7], 9)));
+        assert_eq!(parse_query(&args("a7 vs k")), Ok((vec![1, 7], 10)));
+        assert_eq!(parse_query(&args("88 vs A")), Ok((vec![8, 8], 1)));
+        assert_eq!(parse_query(&args("QS 5H 2D vs 10C")), Ok((vec![10, 5, 2], 10)));
+        assert_eq!(parse_query(&args("1010 vs 6")), Ok((vec![10, 10], 6)));
+
+        assert!(parse_query(&args("10 6 10")).is_err());
+        assert!(parse_query(&args("10 vs 6")).is_err());
+        assert!(parse_query(&args("10 6 vs 66")).is_err());
+        assert_eq!(parse_query(&args("10 X vs 6")), Err("invalid card X".to_string()));
+        // Cards drawn with suit symbols are read like their letters, and other symbols are refused rather than panicking
+        assert_eq!(parse_query(&args("A♥ 7♠ vs 10♦")), Ok((vec![1, 7], 10)));
+        assert_eq!(parse_query(&args("10 6 vs 10★")), Err("invalid card 10★".to_string()));
+        assert_eq!(parse_query(&args("A✓ vs 9")), Err("invalid card A✓".to_string()));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::cardstyle::CardRenderer;
+use crate::input::{Input, Keymap};
+use crate::server::{ServerConfig, TableEvent, TableReply, TableRequest, TABLE_PROTOCOL_VERSION};
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+/**
+ * The settings of a player joining a served table
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct JoinConfig {
+    pub address: String,
+    pub name: String,
+    // The request that takes a seat, or None to watch
+    pub sit: Option<TableRequest>,
+}
+impl JoinConfig {
+    /**
+     * Parses the join command's options. The name defaults to the user's login name.
+     * Ex: ["127.0.0.1:7021", "name", "Ann", "seat", "2"], ["rejoin", "<token>"], ["watch"]
+     */
+    pub fn parse(args: &[String]) -> Result<JoinConfig, String> {
+        let mut config = JoinConfig {
+            address: format!("127.0.0.1:{}", ServerConfig::default().port),
+            name: env::var("USER").unwrap_or_else(|_| "player".to_string()),
+            sit: Some(TableRequest::Join { seat: None }),
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "name" => config.name = value()?.clone(),
+                "seat" => config.sit = Some(parse_table_command(&format!("join {}", value()?), &Keymap::default())?),
+                "rejoin" => config.sit = Some(TableRequest::Rejoin { token: value()?.clone() }),
+                "watch" => config.sit = None,
+                text if text.contains(':') => config.address = text.to_string(),
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/**
+ * What a player at a served table sees, kept up to date from the replies of the server
+ */
+pub struct TableView {
+    cards: CardRenderer,
+    keymap: Keymap,
+    address: String,
+    // The player's seat, if they have one
+    you: Option<usize>,
+    // The name of the player in each seat
+    names: Vec<Option<String>>,
+    // True once it is the player's turn, until the state that follows shows them their hand
+    prompt: bool,
+}
+impl TableView {
+    pub fn new(cards: CardRenderer, keymap: Keymap, address: &str) -> Self {
+        TableView { cards, keymap, address: address.to_string(), you: None, names: Vec::new(), prompt: false }
+    }
+
+    fn name(&self, seat: usize) -> String {
+        match self.names.get(seat).cloned().flatten() {
+            Some(name) if self.you == Some(seat) => format!("{} (you)", name),
+            Some(name) => name,
+            None => format!("seat {}", seat + 1),
+        }
+    }
+
+    /**
+     * Returns the lines to print for a reply from the table
+     */
+    pub fn show(&mut self, reply: TableReply) -> Vec<String> {
+        match reply {
+            TableReply::Welcome { rules, seats, .. } => vec![format!("Connected to a table of {} seats: {}", seats, rules)],
+            TableReply::Seated { seat, token, bankroll } => {
+                self.you = Some(seat);
+                vec![
+                    format!("You sit in seat {} with a bankroll of {}.", seat + 1, bankroll),
+                    format!("If your connection drops, take your seat back with: blackjack_trainer join {} rejoin {}", self.address, token),
+                ]
+            }
+            TableReply::Event(event) => self.show_event(event),
+            TableReply::State(state) => {
+                let mut lines = Vec::new();
+                self.you = state.you;
+                self.names = state.seats.iter().map(|seat| seat.as_ref().map(|seat| seat.name.clone())).collect();
+                let hand = state.turn.filter(|turn| Some(turn.seat) == self.you).and_then(|turn| {
+                    state.seats[turn.seat].as_ref().and_then(|seat| seat.hands.get(turn.hand))
+                });
+                if let (true, Some(hand)) = (self.prompt, hand) {
+                    let inputs: Vec<Input> = state.legal_actions.iter().map(|&action| Input::for_action(action)).collect();
+                    lines.push(format!("Your hand: {} ({})", self.cards.inline(&hand.cards, 0), hand.value));
+                    lines.push(format!("Your turn: {}", self.keymap.describe(&inputs)));
+                }
+                self.prompt = false;
+                lines
+            }
+            TableReply::Error { message } => vec![message],
+            TableReply::Goodbye => vec!["Goodbye".to_string()],
+        }
+    }
+
+    fn show_event(&mut self, event: TableEvent) -> Vec<String> {
+        match event {
+            TableEvent::PlayerJoined { seat, name } => vec![format!("{} sits in seat {}", name, seat + 1)],
+            TableEvent::PlayerLeft { seat, name } => vec![format!("{} leaves seat {}", name, seat + 1)],
+            TableEvent::PlayerDisconnected { name, .. } => vec![format!("{} lost their connection, their seat is kept for them", name)],
+            TableEvent::PlayerReconnected { name, .. } => vec![format!("{} is back", name)],
+            TableEvent::BettingOpen { round } => {
+                let mut lines = vec![String::new(), format!("Round {}: place your bets", round)];
+                if self.you.is_some() {
+                    lines.push("Type bet <amount>, or just the amount".to_string());
+                }
+                lines
+            }
+            TableEvent::BetPlaced { seat, amount } => vec![format!("{} bets {}", self.name(seat), amount)],
+            TableEvent::Dealt { seats, upcard } => {
+                let mut lines = vec![format!("Dealer shows {}", self.cards.inline(&[upcard], 1))];
+                for dealt in seats {
+                    lines.push(format!("{}: {}", self.name(dealt.seat), self.cards.inline(&dealt.cards, 0)));
+                }
+                lines
+            }
+            TableEvent::Turn { seat, seconds, .. } if Some(seat) == self.you => {
+                self.prompt = true;
+                vec![format!("You have {} seconds to act", seconds)]
+            }
+            TableEvent::Turn { seat, .. } => vec![format!("Waiting for {}", self.name(seat))],
+            TableEvent::Acted { seat, action, automatic: true, .. } => {
+                vec![format!("The table plays {} for {}", action, self.name(seat))]
+            }
+            TableEvent::Acted { seat, action, .. } => vec![format!("{} plays {}", self.name(seat), action)],
+            TableEvent::Split { .. } => Vec::new(),
+            TableEvent::Card { seat, hand, card } => {
+                vec![format!("{} draws {} on hand {}", self.name(seat), self.cards.card(&card), hand + 1)]
+            }
+            TableEvent::HoleCard { card } => vec![format!("Dealer turns over {}", self.cards.card(&card))],
+            TableEvent::DealerCard { card } => vec![format!("Dealer draws {}", self.cards.card(&card))],
+            TableEvent::RoundOver { results, .. } => results
+                .iter()
+                .map(|result| {
+                    let outcomes: Vec<String> = result.outcomes.iter().map(|outcome| format!("{:?}", outcome).to_lowercase()).collect();
+                    format!("{}: {} ({:+}, bankroll {})", self.name(result.seat), outcomes.join(", "), result.net, result.bankroll)
+                })
+                .collect(),
+        }
+    }
+}
+
+/**
+ * Reads a line typed at a served table as a request
+ * Ex: "bet 5", "5", "join 2", "rejoin <token>", "leave", "state", or an action in the keymap such as "h"
+ */
+pub fn parse_table_command(line: &str, keymap: &Keymap) -> Result<TableRequest, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let seat = |text: &str| text.parse::<usize>().ok().filter(|&seat| seat > 0).map(|seat| seat - 1).ok_or(format!("invalid seat {}", text));
+    let amount = |text: &str| text.parse::<f64>().map_err(|_| format!("invalid bet {}", text));
+    match words.as_slice() {
+        ["bet", text] => Ok(TableRequest::Bet { amount: amount(text)? }),
+        [text] if text.parse::<f64>().is_ok() => Ok(TableRequest::Bet { amount: amount(text)? }),
+        ["join"] => Ok(TableRequest::Join { seat: None }),
+        ["join", text] => Ok(TableRequest::Join { seat: Some(seat(text)?) }),
+        ["rejoin", token] => Ok(TableRequest::Rejoin { token: token.to_string() }),
+        ["leave"] => Ok(TableRequest::Leave),
+        ["state"] => Ok(TableRequest::QueryState),
+        _ => match keymap.parse_line(line) {
+            Some(Input::Quit) => Ok(TableRequest::Quit),
+            Some(input) => input.action().map(|action| TableRequest::Action { action }).ok_or(format!("{} is not offered at this table", input)),
+            None => Err(format!(
+                "Type bet <amount>, {}, join [seat], rejoin <token>, leave, state or quit",
+                keymap.describe(&[Input::Hit, Input::Stand, Input::Double, Input::Split, Input::Surrender])
+            )),
+        },
+    }
+}
+
+/**
+ * Plays at a served table until the input ends or the player quits: the replies of the table are printed
+ * as they arrive, and each line typed is sent as a request.
+ * @param config where to connect, the player's name and the seat to take
+ * @param cards how cards are drawn
+ * @param keymap the keys of the actions
+ */
+pub fn join(config: &JoinConfig, cards: CardRenderer, keymap: Keymap) -> Result<(), String> {
+    let stream = TcpStream::connect(&config.address).map_err(|err| format!("Could not connect to {}: {}", config.address, err))?;
+    let reader = stream.try_clone().map_err(|err| err.to_string())?;
+    let mut view = TableView::new(cards, keymap.clone(), &config.address);
+    let replies = thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            match serde_json::from_str::<TableReply>(&line) {
+                Ok(reply) => view.show(reply).iter().for_each(|line| println!("{}", line)),
+                Err(err) => println!("Unreadable reply from the table: {}", err),
+            }
+        }
+        println!("The connection to the table is closed");
+    });
+
+    let mut requests = vec![TableRequest::Hello { version: TABLE_PROTOCOL_VERSION, name: config.name.clone() }];
+    requests.extend(config.sit.clone());
+    let mut writer = stream;
+    let mut send = |request: &TableRequest| writeln!(writer, "{}", serde_json::to_string(request).expect("requests are always serializable"));
+    for request in &requests {
+        send(request).map_err(|err| err.to_string())?;
+    }
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_table_command(line.trim(), &keymap) {
+            Ok(request) => {
+                let quit = request == TableRequest::Quit;
+                if send(&request).is_err() || quit {
+                    break;
+                }
+            }
+            Err(message) => println!("{}", message),
+        }
+    }
+    // At the end of the input the player quits, and the server closes the connection once it has said goodbye
+    let _ = send(&TableRequest::Quit);
+    let _ = replies.join();
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use crate::blackjack::{Action, Card, HandOutcome};
+use crate::cardstyle::CardRenderer;
+use crate::client::{parse_table_command, JoinConfig, TableView};
+use crate::input::Keymap;
+use crate::protocol::{HandState, Phase};
+use crate::server::{SeatCards, SeatResult, SeatState, TableEvent, TableReply, TableRequest, TableSnapshot, TurnState};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(text: &str) -> Card {
+        text.parse().unwrap()
+    }
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    fn seat(name: &str, cards: &[Card], value: i64) -> Option<SeatState> {
+        let hand = HandState { cards: cards.to_vec(), value, soft: false, doubled: false, outcome: None };
+        Some(SeatState { name: name.to_string(), bankroll: 95.0, bet: Some(5.0), connected: true, hands: vec![hand] })
+    }
+
+    #[test]
+    fn test_parse_table_command() {
+        let keymap = Keymap::default();
+
+        assert_eq!(parse_table_command("bet 5", &keymap), Ok(TableRequest::Bet { amount: 5.0 }));
+        assert_eq!(parse_table_command("2.5", &keymap), Ok(TableRequest::Bet { amount: 2.5 }));
+        assert_eq!(parse_table_command("join", &keymap), Ok(TableRequest::Join { seat: None }));
+        assert_eq!(parse_table_command("join 3", &keymap), Ok(TableRequest::Join { seat: Some(2) }));
+        assert_eq!(parse_table_command("rejoin abc", &keymap), Ok(TableRequest::Rejoin { token: "abc".to_string() }));
+        assert_eq!(parse_table_command("leave", &keymap), Ok(TableRequest::Leave));
+        assert_eq!(parse_table_command("state", &keymap), Ok(TableRequest::QueryState));
+        assert_eq!(parse_table_command("h", &keymap), Ok(TableRequest::Action { action: Action::Hit }));
+        assert_eq!(parse_table_command("stand", &keymap), Ok(TableRequest::Action { action: Action::Stand }));
+        assert_eq!(parse_table_command("q", &keymap), Ok(TableRequest::Quit));
+    }
+
+    #[test]
+    fn test_parse_table_command_errors() {
+        let keymap = Keymap::default();
+
+        assert_eq!(parse_table_command("bet five", &keymap), Err("invalid bet five".to_string()));
+        assert_eq!(parse_table_command("join 0", &keymap), Err("invalid seat 0".to_string()));
+        assert_eq!(
+            parse_table_command("fold", &keymap),
+            Err("Type bet <amount>, hit (h), stand (s), double (d), split (p) or surrender (r), join [seat], rejoin <token>, leave, state or quit".to_string())
+        );
+    }
+
+    #[test]
+    fn test_join_config() {
+        let config = JoinConfig::parse(&args("10.0.0.2:9000 name Ann seat 2")).unwrap();
+        assert_eq!(config.address, "10.0.0.2:9000");
+        assert_eq!(config.name, "Ann");
+        assert_eq!(config.sit, Some(TableRequest::Join { seat: Some(1) }));
+
+        let config = JoinConfig::parse(&args("rejoin abc")).unwrap();
+        assert_eq!(config.address, "127.0.0.1:7021");
+        assert_eq!(config.sit, Some(TableRequest::Rejoin { token: "abc".to_string() }));
+
+        assert_eq!(JoinConfig::parse(&args("watch")).unwrap().sit, None);
+        assert_eq!(JoinConfig::parse(&args("name")), Err("name needs a value".to_string()));
+        assert_eq!(JoinConfig::parse(&args("seat x")), Err("invalid seat x".to_string()));
+        assert_eq!(JoinConfig::parse(&args("fast")), Err("unknown option fast".to_string()));
+    }
+
+    #[test]
+    fn test_view_shows_the_round() {
+        let mut view = TableView::new(CardRenderer::default(), Keymap::default(), "127.0.0.1:7021");
+
+        assert_eq!(
+            view.show(TableReply::Seated { seat: 1, token: "abc".to_string(), bankroll: 100.0 }),
+            vec![
+                "You sit in seat 2 with a bankroll of 100.",
+                "If your connection drops, take your seat back with: blackjack_trainer join 127.0.0.1:7021 rejoin abc",
+            ]
+        );
+        assert_eq!(
+            view.show(TableReply::Event(TableEvent::BettingOpen { round: 1 })),
+            vec!["", "Round 1: place your bets", "Type bet <amount>, or just the amount"]
+        );
+
+        let snapshot = TableSnapshot {
+            phase: Phase::Playing,
+            round: 1,
+            you: Some(1),
+            seats: vec![seat("Bob", &[card("9H"), card("7C")], 16), seat("Ann", &[card("10S"), card("2D")], 12)],
+            dealer: None,
+            turn: Some(TurnState { seat: 0, hand: 0 }),
+            legal_actions: vec![Action::Hit, Action::Stand],
+            spectators: 0,
+            cards_left: 300,
+        };
+        // Before it is the player's turn a state only updates the names
+        assert!(view.show(TableReply::State(snapshot.clone())).is_empty());
+        assert_eq!(
+            view.show(TableReply::Event(TableEvent::Dealt {
+                seats: vec![SeatCards { seat: 0, cards: vec![card("9H"), card("7C")] }, SeatCards { seat: 1, cards: vec![card("10S"), card("2D")] }],
+                upcard: card("6H"),
+            })),
+            vec!["Dealer shows 6H ??", "Bob: 9H 7C", "Ann (you): 10S 2D"]
+        );
+        assert_eq!(view.show(TableReply::Event(TableEvent::Turn { seat: 0, hand: 0, seconds: 30.0 })), vec!["Waiting for Bob"]);
+
+        assert_eq!(view.show(TableReply::Event(TableEvent::Turn { seat: 1, hand: 0, seconds: 30.0 })), vec!["You have 30 seconds to act"]);
+        let your_turn = TableSnapshot { turn: Some(TurnState { seat: 1, hand: 0 }), ..snapshot };
+        assert_eq!(
+            view.show(TableReply::State(your_turn.clone())),
+            vec!["Your hand: 10S 2D (12)", "Your turn: hit (h) or stand (s)"]
+        );
+        // The hand is shown once per turn
+        assert!(view.show(TableReply::State(your_turn)).is_empty());
+
+        assert_eq!(
+            view.show(TableReply::Event(TableEvent::Acted { seat: 0, hand: 0, action: Action::Stand, automatic: true })),
+            vec!["The table plays stand for Bob"]
+        );
+        assert_eq!(
+            view.show(TableReply::Event(TableEvent::RoundOver {
+                round: 1,
+                results: vec![SeatResult { seat: 1, name: "Ann".to_string(), outcomes: vec![HandOutcome::Win], net: 5.0, bankroll: 105.0 }],
+            })),
+            vec!["Ann (you): win (+5, bankroll 105)"]
+        );
+    }
+}
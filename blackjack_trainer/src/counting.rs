@@ -0,0 +1,449 @@
+use crate::blackjack::{Card, Shoe};
+use crate::countingsystem::{self, system_by_name, CountingSystem, HiLo};
+use crate::storage;
+use rand::prelude::*;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/**
+ * The file in the data directory that keeps the deck countdown personal best
+ */
+pub const COUNTDOWN_FILE: &str = "countdown.txt";
+
+/**
+ * The target time for counting down a deck
+ */
+pub const COUNTDOWN_TARGET: Duration = Duration::from_secs(25);
+
+/**
+ * Parses the name following a system option
+ */
+fn parse_system(name: Option<&String>) -> Result<&'static dyn CountingSystem, String> {
+    let name = name.ok_or("system needs a name")?;
+    system_by_name(name).ok_or_else(|| format!("unknown counting system {}", name))
+}
+
+/**
+ * Lists the built-in counting systems with their tags
+ */
+pub fn render_systems() -> String {
+    let mut out = format!("{:<13}", "System");
+    for label in ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10"] {
+        out.push_str(&format!("{:>5}", label));
+    }
+    out.push_str("  Type\n");
+
+    for system in countingsystem::SYSTEMS {
+        out.push_str(&format!("{:<13}", system.name()));
+        for tag in system.tags() {
+            out.push_str(&format!("{:>5}", countingsystem::format_count(tag)));
+        }
+        let kind = if system.is_balanced() { "balanced" } else { "unbalanced" };
+        let side = if system.ace_side_count() { ", ace side count" } else { "" };
+        out.push_str(&format!("  {}{}\n", kind, side));
+    }
+    out
+}
+
+/**
+ * How a running count drill deals its cards
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrillConfig {
+    pub system: &'static dyn CountingSystem,
+    // The number of cards shown at a time: 1, 2 or 3
+    pub group_size: usize,
+    // How long each group is shown for
+    pub pace: Duration,
+    // The number of cards dealt in the drill
+    pub cards: usize,
+    // The chance of being asked for the count after each group, besides at the end
+    pub checkpoint_chance: f64,
+}
+impl Default for DrillConfig {
+    fn default() -> Self {
+        DrillConfig {
+            system: &HiLo,
+            group_size: 1,
+            pace: Duration::from_millis(1000),
+            cards: 52,
+            checkpoint_chance: 0.1,
+        }
+    }
+}
+impl DrillConfig {
+    /**
+     * Parses drill options: singles, pairs or triples, pace <seconds per group>, cards <number>
+     * and system <name>
+     * Ex: ["pairs", "pace", "0.5", "cards", "104", "system", "zen"]
+     */
+    pub fn parse(args: &[String]) -> Result<DrillConfig, String> {
+        let mut config = DrillConfig::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.to_lowercase().as_str() {
+                "singles" => config.group_size = 1,
+                "pairs" => config.group_size = 2,
+                "triples" => config.group_size = 3,
+                "system" => config.system = parse_system(args.next())?,
+                "pace" => {
+                    let seconds = args.next().ok_or("pace needs a number of seconds")?;
+                    config.pace = match seconds.parse::<f64>() {
+                        Ok(seconds) if seconds > 0.0 && seconds <= 60.0 => Duration::from_secs_f64(seconds),
+                        _ => return Err(format!("invalid pace {}", seconds)),
+                    };
+                }
+                "cards" => {
+                    let cards = args.next().ok_or("cards needs a number")?;
+                    config.cards = match cards.parse::<usize>() {
+                        Ok(cards) if cards > 0 && cards <= 8 * 52 => cards,
+                        _ => return Err(format!("invalid number of cards {}", cards)),
+                    };
+                }
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/**
+ * A group of cards shown together, and whether the user is asked for the count after it
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrillStep {
+    pub cards: Vec<Card>,
+    pub checkpoint: bool,
+    // The running count after this group
+    pub running_count: f64,
+    // The number of aces seen after this group, for systems with an ace side count
+    pub aces: u32,
+}
+
+/**
+ * Returns the number of decks in the shoe a drill deals from
+ */
+pub fn drill_decks(config: &DrillConfig) -> usize {
+    config.cards.div_ceil(52)
+}
+
+/**
+ * Deals the cards for a running count drill from a freshly shuffled shoe and chooses the
+ * checkpoints. The user is always asked for the count after the last group.
+ * Unbalanced systems start from their initial running count for the shoe.
+ * @param config how to deal the cards
+ */
+pub fn plan_drill(config: &DrillConfig, rng: &mut impl Rng) -> Vec<DrillStep> {
+    let decks = drill_decks(config);
+    let mut shoe = Shoe::new(decks);
+    let mut steps = Vec::new();
+    let mut count = config.system.initial_running_count(decks);
+    let mut aces = 0;
+    let mut dealt = 0;
+
+    while dealt < config.cards {
+        let size = config.group_size.min(config.cards - dealt);
+        let cards: Vec<Card> = (0..size).map(|_| shoe.deal_card().expect("shoe holds every card")).collect();
+        dealt += size;
+        count += countingsystem::count_cards(config.system, &cards);
+        aces += cards.iter().filter(|card| card.get_value() == 1).count() as u32;
+
+        steps.push(DrillStep {
+            cards,
+            checkpoint: dealt == config.cards || rng.gen_bool(config.checkpoint_chance),
+            running_count: count,
+            aces,
+        });
+    }
+    steps
+}
+
+/**
+ * Deals the cards for a deck countdown: a shuffled deck with one card held back.
+ * A correct count at the end tells the user whether the missing card was low, neutral or high.
+ * @return the 51 cards to count and the card held back
+ */
+pub fn countdown_deck() -> (Vec<Card>, Card) {
+    let mut shoe = Shoe::new(1);
+    let hidden = shoe.deal_card().expect("deck has 52 cards");
+    let cards = (0..51).map(|_| shoe.deal_card().expect("deck has 52 cards")).collect();
+    (cards, hidden)
+}
+
+/**
+ * Loads the fastest correct deck countdown, or None if there is none yet
+ */
+pub fn load_countdown_best() -> Result<Option<Duration>, String> {
+    let text = match storage::read_data_file(COUNTDOWN_FILE) {
+        Ok(Some(text)) => text,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(format!("Cannot read {}: {}", COUNTDOWN_FILE, err)),
+    };
+    match text.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        ["best_ms", millis] => millis
+            .parse()
+            .map(|millis| Some(Duration::from_millis(millis)))
+            .map_err(|_| format!("{}: invalid personal best", COUNTDOWN_FILE)),
+        _ => Err(format!("{}: invalid personal best", COUNTDOWN_FILE)),
+    }
+}
+
+/**
+ * Records a correct deck countdown, returning true if it is a new personal best
+ * @param time how long the countdown took
+ */
+pub fn record_countdown(time: Duration) -> Result<bool, String> {
+    let best = load_countdown_best()?;
+    if best.is_some_and(|best| best <= time) {
+        return Ok(false);
+    }
+    storage::write_data_file(COUNTDOWN_FILE, &format!("best_ms {}\n", time.as_millis()))
+        .map_err(|err: io::Error| format!("Cannot write {}: {}", COUNTDOWN_FILE, err))?;
+    Ok(true)
+}
+
+/**
+ * The file in the data directory that keeps the true count drill results of every session
+ */
+pub const TRUE_COUNT_FILE: &str = "true_count.txt";
+
+/**
+ * How a true count is rounded to a whole number
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    // Down to the next lower whole number: +2.5 is +2, -2.5 is -3
+    Floor,
+    // Towards zero: +2.5 is +2, -2.5 is -2
+    Truncate,
+    // To the nearest whole number, halves away from zero: +2.5 is +3, -2.5 is -3
+    Round,
+}
+impl Rounding {
+    pub fn parse(name: &str) -> Option<Rounding> {
+        match name.to_lowercase().as_str() {
+            "floor" => Some(Rounding::Floor),
+            "truncate" => Some(Rounding::Truncate),
+            "round" => Some(Rounding::Round),
+            _ => None,
+        }
+    }
+
+    /**
+     * Returns the true count: the running count divided by the decks remaining, rounded
+     * @param running_count the running count
+     * @param decks_remaining the number of decks left to be dealt
+     */
+    pub fn true_count(&self, running_count: f64, decks_remaining: f64) -> i32 {
+        let exact = running_count / decks_remaining;
+        // Running counts and deck fractions are exact in binary, so this only absorbs division error
+        let exact = (exact * 1e9).round() / 1e9;
+        match self {
+            Rounding::Floor => exact.floor() as i32,
+            Rounding::Truncate => exact.trunc() as i32,
+            Rounding::Round => exact.round() as i32,
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Rounding::Floor => "floor",
+            Rounding::Truncate => "truncate",
+            Rounding::Round => "round",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ * How a true count drill estimates the decks in the discard tray and rounds the true count
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrueCountConfig {
+    // A balanced counting system
+    pub system: &'static dyn CountingSystem,
+    pub decks: usize,
+    // The discard tray is read to the nearest half deck (2) or quarter deck (4)
+    pub parts_per_deck: usize,
+    pub rounding: Rounding,
+}
+impl Default for TrueCountConfig {
+    fn default() -> Self {
+        TrueCountConfig {
+            system: &HiLo,
+            decks: 6,
+            parts_per_deck: 2,
+            rounding: Rounding::Floor,
+        }
+    }
+}
+impl TrueCountConfig {
+    /**
+     * Parses drill options: half or quarter, floor, truncate or round, decks <number> and
+     * system <name>. Unbalanced systems are not played with a true count and are rejected.
+     * Ex: ["quarter", "truncate", "decks", "8"]
+     */
+    pub fn parse(args: &[String]) -> Result<TrueCountConfig, String> {
+        let mut config = TrueCountConfig::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if let Some(rounding) = Rounding::parse(arg) {
+                config.rounding = rounding;
+                continue;
+            }
+            match arg.to_lowercase().as_str() {
+                "half" => config.parts_per_deck = 2,
+                "quarter" => config.parts_per_deck = 4,
+                "system" => {
+                    config.system = parse_system(args.next())?;
+                    if !config.system.is_balanced() {
+                        return Err(format!("{} is unbalanced and is played without a true count", config.system.name()));
+                    }
+                }
+                "decks" => {
+                    let decks = args.next().ok_or("decks needs a number")?;
+                    config.decks = match decks.parse::<usize>() {
+                        Ok(decks) if (2..=8).contains(&decks) => decks,
+                        _ => return Err(format!("invalid number of decks {}", decks)),
+                    };
+                }
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/**
+ * A true count question: the discard tray depth and running count shown to the user
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrueCountQuestion {
+    pub decks_discarded: f64,
+    pub decks_remaining: f64,
+    pub running_count: f64,
+    pub true_count: i32,
+}
+
+/**
+ * Deals part of a shoe into the discard tray, a whole number of half or quarter decks with
+ * at least one deck left, and asks for the true count from the cards dealt.
+ * @param config the shoe size, tray reading and rounding
+ */
+pub fn true_count_question(config: &TrueCountConfig, rng: &mut impl Rng) -> TrueCountQuestion {
+    let part = 52 / config.parts_per_deck;
+    let parts = rng.gen_range(1..=(config.decks - 1) * config.parts_per_deck);
+    let mut shoe = Shoe::new(config.decks);
+
+    let dealt: Vec<Card> = (0..parts * part).map(|_| shoe.deal_card().expect("shoe holds every card")).collect();
+    let decks_remaining = shoe.cards_left() as f64 / 52.0;
+    let running_count = countingsystem::running_count(config.system, config.decks, &dealt);
+
+    TrueCountQuestion {
+        decks_discarded: dealt.len() as f64 / 52.0,
+        decks_remaining,
+        running_count,
+        true_count: config.rounding.true_count(running_count, decks_remaining),
+    }
+}
+
+/**
+ * Draws a discard tray as a column of the specified depth, one character per part of a deck,
+ * with a mark at every whole deck
+ * Ex: |##|##|#.|..|..|..| for 2.5 of 6 decks read in half decks
+ */
+pub fn render_tray(config: &TrueCountConfig, decks_discarded: f64) -> String {
+    let filled = (decks_discarded * config.parts_per_deck as f64).round() as usize;
+    let mut tray = String::from("|");
+
+    for part in 0..config.decks * config.parts_per_deck {
+        tray.push(if part < filled { '#' } else { '.' });
+        if (part + 1) % config.parts_per_deck == 0 {
+            tray.push('|');
+        }
+    }
+    tray
+}
+
+/**
+ * The results of one true count drill session
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrueCountSession {
+    // Seconds since the Unix epoch when the session ended
+    pub time: u64,
+    pub answers: u32,
+    pub correct: u32,
+    pub mean_absolute_error: f64,
+}
+impl TrueCountSession {
+    /**
+     * Summarises the signed errors of a session's answers
+     */
+    pub fn from_errors(time: u64, errors: &[i32]) -> Self {
+        let answers = errors.len() as u32;
+        let total: i32 = errors.iter().map(|error| error.abs()).sum();
+        TrueCountSession {
+            time,
+            answers,
+            correct: errors.iter().filter(|&&error| error == 0).count() as u32,
+            mean_absolute_error: if answers == 0 { 0.0 } else { total as f64 / answers as f64 },
+        }
+    }
+
+    /**
+     * Parses the sessions saved in the true count file: one session per line with the time,
+     * number of answers, correct answers and mean absolute error
+     * Ex: 1760000000 20 14 0.35
+     */
+    pub fn parse_all(text: &str) -> Result<Vec<TrueCountSession>, String> {
+        let mut sessions = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = || format!("line {}: invalid session", index + 1);
+            match words.as_slice() {
+                [] => {}
+                [time, answers, correct, mean] => sessions.push(TrueCountSession {
+                    time: time.parse().map_err(|_| error())?,
+                    answers: answers.parse().map_err(|_| error())?,
+                    correct: correct.parse().map_err(|_| error())?,
+                    mean_absolute_error: mean.parse().map_err(|_| error())?,
+                }),
+                _ => return Err(error()),
+            }
+        }
+        Ok(sessions)
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{} {} {} {}\n", self.time, self.answers, self.correct, self.mean_absolute_error)
+    }
+}
+
+/**
+ * Loads every saved true count drill session, oldest first
+ */
+pub fn load_true_count_sessions() -> Result<Vec<TrueCountSession>, String> {
+    match storage::read_data_file(TRUE_COUNT_FILE) {
+        Ok(Some(text)) => TrueCountSession::parse_all(&text),
+        Ok(None) => Ok(Vec::new()),
+        Err(err) => Err(format!("Cannot read {}: {}", TRUE_COUNT_FILE, err)),
+    }
+}
+
+/**
+ * Adds a session to the saved true count drill sessions
+ */
+pub fn save_true_count_session(session: &TrueCountSession) -> Result<(), String> {
+    let mut text = match storage::read_data_file(TRUE_COUNT_FILE) {
+        Ok(text) => text.unwrap_or_default(),
+        Err(err) => return Err(format!("Cannot read {}: {}", TRUE_COUNT_FILE, err)),
+    };
+    text.push_str(&session.to_line());
+    storage::write_data_file(TRUE_COUNT_FILE, &text).map_err(|err| format!("Cannot write {}: {}", TRUE_COUNT_FILE, err))
+}
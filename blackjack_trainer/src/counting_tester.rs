@@ -0,0 +1,164 @@
+use crate::counting::{
+    countdown_deck, plan_drill, render_systems, render_tray, true_count_question, DrillConfig, Rounding,
+    TrueCountConfig, TrueCountSession,
+};
+use crate::countingsystem::{count_cards, CountingSystem, HiLo, HiOptI, KnockOut};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_full_deck_counts_to_zero() {
+        let (cards, hidden) = countdown_deck();
+
+        assert_eq!(cards.len(), 51);
+        assert!(!cards.contains(&hidden));
+        assert_eq!(count_cards(&HiLo, &cards) + HiLo.tag(&hidden), 0.0);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = DrillConfig::parse(&args("triples pace 0.5 cards 104")).unwrap();
+
+        assert_eq!(config.group_size, 3);
+        assert_eq!(config.system.name(), "Hi-Lo");
+        assert_eq!(config.pace, Duration::from_millis(500));
+        assert_eq!(config.cards, 104);
+        assert_eq!(DrillConfig::parse(&[]).unwrap(), DrillConfig::default());
+        assert_eq!(DrillConfig::parse(&args("pace 0")).err().unwrap(), "invalid pace 0");
+        assert_eq!(DrillConfig::parse(&args("cards")).err().unwrap(), "cards needs a number");
+        assert_eq!(DrillConfig::parse(&args("quads")).err().unwrap(), "unknown option quads");
+        assert_eq!(DrillConfig::parse(&args("system omega2")).unwrap().system.name(), "Omega II");
+        assert_eq!(DrillConfig::parse(&args("system red7")).err().unwrap(), "unknown counting system red7");
+    }
+
+    #[test]
+    fn test_plan_groups_and_checkpoints() {
+        let config = DrillConfig {
+            group_size: 3,
+            cards: 52,
+            ..DrillConfig::default()
+        };
+        let steps = plan_drill(&config, &mut StdRng::seed_from_u64(11));
+
+        // 17 triples and a single card
+        assert_eq!(steps.len(), 18);
+        assert_eq!(steps.last().unwrap().cards.len(), 1);
+        assert!(steps.last().unwrap().checkpoint);
+        assert_eq!(steps.last().unwrap().running_count, 0.0);
+        assert_eq!(steps.last().unwrap().aces, 4);
+
+        let mut count = 0.0;
+        for step in &steps {
+            count += count_cards(&HiLo, &step.cards);
+            assert_eq!(step.running_count, count);
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_chance() {
+        let always = DrillConfig {
+            checkpoint_chance: 1.0,
+            cards: 20,
+            ..DrillConfig::default()
+        };
+        let never = DrillConfig { checkpoint_chance: 0.0, ..always };
+        let mut rng = StdRng::seed_from_u64(4);
+
+        assert!(plan_drill(&always, &mut rng).iter().all(|step| step.checkpoint));
+        assert_eq!(plan_drill(&never, &mut rng).iter().filter(|step| step.checkpoint).count(), 1);
+    }
+
+    #[test]
+    fn test_rounding_conventions() {
+        assert_eq!(Rounding::Floor.true_count(5.0, 2.0), 2);
+        assert_eq!(Rounding::Floor.true_count(-5.0, 2.0), -3);
+        assert_eq!(Rounding::Truncate.true_count(-5.0, 2.0), -2);
+        assert_eq!(Rounding::Round.true_count(5.0, 2.0), 3);
+        assert_eq!(Rounding::Round.true_count(-5.0, 2.0), -3);
+        assert_eq!(Rounding::Round.true_count(7.0, 4.5), 2);
+        assert_eq!(Rounding::Floor.true_count(-3.0, 1.5), -2);
+        assert_eq!(Rounding::Truncate.true_count(0.0, 3.25), 0);
+        assert_eq!(Rounding::parse("TRUNCATE"), Some(Rounding::Truncate));
+        assert_eq!(Rounding::parse("ceil"), None);
+    }
+
+    #[test]
+    fn test_parse_true_count_config() {
+        let config = TrueCountConfig::parse(&args("quarter round decks 8")).unwrap();
+
+        assert_eq!((config.decks, config.parts_per_deck, config.rounding), (8, 4, Rounding::Round));
+        assert_eq!(TrueCountConfig::parse(&[]).unwrap(), TrueCountConfig::default());
+        assert_eq!(TrueCountConfig::parse(&args("decks 1")).err().unwrap(), "invalid number of decks 1");
+        assert_eq!(TrueCountConfig::parse(&args("eighth")).err().unwrap(), "unknown option eighth");
+        assert_eq!(TrueCountConfig::parse(&args("system zen")).unwrap().system.name(), "Zen");
+        assert_eq!(
+            TrueCountConfig::parse(&args("system ko")).err().unwrap(),
+            "KO is unbalanced and is played without a true count"
+        );
+    }
+
+    #[test]
+    fn test_true_count_questions() {
+        let config = TrueCountConfig::parse(&args("quarter truncate")).unwrap();
+        let mut rng = StdRng::seed_from_u64(9);
+
+        for _ in 0..100 {
+            let question = true_count_question(&config, &mut rng);
+            assert_eq!(question.decks_discarded + question.decks_remaining, 6.0);
+            assert_eq!((question.decks_discarded * 4.0).fract(), 0.0);
+            assert!(question.decks_remaining >= 1.0);
+            assert_eq!(question.true_count, Rounding::Truncate.true_count(question.running_count, question.decks_remaining));
+        }
+    }
+
+    #[test]
+    fn test_render_tray() {
+        let half = TrueCountConfig::default();
+        let quarter = TrueCountConfig::parse(&args("quarter decks 2")).unwrap();
+
+        assert_eq!(render_tray(&half, 2.5), "|##|##|#.|..|..|..|");
+        assert_eq!(render_tray(&quarter, 0.75), "|###.|....|");
+    }
+
+    #[test]
+    fn test_true_count_sessions() {
+        let session = TrueCountSession::from_errors(1_760_000_000, &[0, 1, -2, 0]);
+
+        assert_eq!((session.answers, session.correct, session.mean_absolute_error), (4, 2, 0.75));
+        assert_eq!(session.to_line(), "1760000000 4 2 0.75\n");
+        assert_eq!(TrueCountSession::parse_all(&session.to_line()).unwrap(), vec![session]);
+        assert_eq!(TrueCountSession::parse_all("1 2 3").err().unwrap(), "line 1: invalid session");
+    }
+
+    #[test]
+    fn test_unbalanced_drill_starts_from_initial_count() {
+        let config = DrillConfig {
+            system: &KnockOut,
+            cards: 104,
+            ..DrillConfig::default()
+        };
+        let steps = plan_drill(&config, &mut StdRng::seed_from_u64(8));
+
+        // Two decks start at 4 - 8 and every deck adds 4
+        assert_eq!(steps.last().unwrap().running_count, -4.0 + 8.0);
+    }
+
+    #[test]
+    fn test_render_systems() {
+        let table = render_systems();
+
+        assert!(table.starts_with("System           A    2"));
+        assert!(table.contains("unbalanced"));
+        assert!(table.lines().any(|line| line.starts_with(HiOptI.name()) && line.ends_with("balanced, ace side count")));
+        assert!(table.lines().any(|line| line.starts_with("Wong Halves") && line.contains("+1.5")));
+    }
+}
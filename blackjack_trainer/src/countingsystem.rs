@@ -0,0 +1,238 @@
+use crate::blackjack::Card;
+use std::fmt;
+
+/**
+ * A card counting system: the tag added to the running count for each card value.
+ * Balanced systems count a full shoe back to zero and convert the running count to a true count;
+ * unbalanced systems such as KO start from an initial running count and are played on the
+ * running count alone.
+ */
+pub trait CountingSystem: fmt::Debug + Sync {
+    /**
+     * Returns the name of this system, e.g. Hi-Lo
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Returns the tag of each card value: index 0 is the ace, index 9 the ten-valued cards
+     */
+    fn tags(&self) -> [f64; 10];
+
+    /**
+     * Returns true if players of this system keep a separate count of the aces played.
+     * Systems that tag the ace as 0 use the side count to adjust bets.
+     */
+    fn ace_side_count(&self) -> bool {
+        false
+    }
+
+    /**
+     * Returns the running count at the start of a shoe
+     * @param decks the number of decks in the shoe
+     */
+    fn initial_running_count(&self, decks: usize) -> f64 {
+        let _ = decks;
+        0.0
+    }
+
+    /**
+     * Returns the tag of a card
+     */
+    fn tag(&self, card: &Card) -> f64 {
+        self.tags()[(card.get_value().clamp(1, 10) - 1) as usize]
+    }
+
+    /**
+     * Returns true if the tags of a full deck add up to zero
+     */
+    fn is_balanced(&self) -> bool {
+        let tags = self.tags();
+        let deck: f64 = tags[..9].iter().map(|tag| 4.0 * tag).sum::<f64>() + 16.0 * tags[9];
+        deck == 0.0
+    }
+}
+
+impl PartialEq for dyn CountingSystem {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+/**
+ * The most common balanced level 1 count: +1 for 2-6, -1 for tens and aces
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct HiLo;
+impl CountingSystem for HiLo {
+    fn name(&self) -> &'static str {
+        "Hi-Lo"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0]
+    }
+}
+
+/**
+ * Knock-Out: Hi-Lo with the 7 counted as +1, which unbalances the count so no true count is needed
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct KnockOut;
+impl CountingSystem for KnockOut {
+    fn name(&self) -> &'static str {
+        "KO"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, -1.0]
+    }
+
+    /**
+     * The standard initial running count, 4 - 4 x decks, which puts the key count at +2
+     */
+    fn initial_running_count(&self, decks: usize) -> f64 {
+        4.0 - 4.0 * decks as f64
+    }
+}
+
+/**
+ * Highly Optimum I: a level 1 count that leaves the 2 and the ace out of the count
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct HiOptI;
+impl CountingSystem for HiOptI {
+    fn name(&self) -> &'static str {
+        "Hi-Opt I"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0]
+    }
+
+    fn ace_side_count(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Highly Optimum II: a level 2 count with the ace kept in a side count
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct HiOptII;
+impl CountingSystem for HiOptII {
+    fn name(&self) -> &'static str {
+        "Hi-Opt II"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [0.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 0.0, 0.0, -2.0]
+    }
+
+    fn ace_side_count(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Omega II: a level 2 count that also tags the 9, with the ace kept in a side count
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct OmegaII;
+impl CountingSystem for OmegaII {
+    fn name(&self) -> &'static str {
+        "Omega II"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [0.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 0.0, -1.0, -2.0]
+    }
+
+    fn ace_side_count(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Zen: a level 2 count that keeps the ace in the main count
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Zen;
+impl CountingSystem for Zen {
+    fn name(&self) -> &'static str {
+        "Zen"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [-1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 0.0, 0.0, -2.0]
+    }
+}
+
+/**
+ * Wong Halves: a level 3 count with half point tags for the 2, 5, 7 and 9
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct WongHalves;
+impl CountingSystem for WongHalves {
+    fn name(&self) -> &'static str {
+        "Wong Halves"
+    }
+
+    fn tags(&self) -> [f64; 10] {
+        [-1.0, 0.5, 1.0, 1.0, 1.5, 1.0, 0.5, 0.0, -0.5, -1.0]
+    }
+}
+
+/**
+ * Every built-in counting system
+ */
+pub const SYSTEMS: [&dyn CountingSystem; 7] = [&HiLo, &KnockOut, &HiOptI, &HiOptII, &OmegaII, &Zen, &WongHalves];
+
+/**
+ * Returns a built-in counting system by name, ignoring case, spaces and hyphens
+ * Ex: hilo, ko, hi-opt-ii, omega2, zen, wong-halves
+ */
+pub fn system_by_name(name: &str) -> Option<&'static dyn CountingSystem> {
+    let key: String = name.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+    let key = match key.as_str() {
+        "ko" | "knockout" => "ko",
+        "hioptii" | "hiopt2" => "hioptii",
+        "hiopti" | "hiopt1" => "hiopti",
+        "omegaii" | "omega2" => "omegaii",
+        "wong" | "halves" => "wonghalves",
+        other => other,
+    };
+
+    SYSTEMS.into_iter().find(|system| {
+        let name: String = system.name().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        name.to_lowercase() == key
+    })
+}
+
+/**
+ * Returns the running count after the specified cards, starting from the system's initial count
+ * @param system the counting system
+ * @param decks the number of decks in the shoe
+ * @param cards the cards seen
+ */
+pub fn running_count(system: &dyn CountingSystem, decks: usize, cards: &[Card]) -> f64 {
+    system.initial_running_count(decks) + count_cards(system, cards)
+}
+
+/**
+ * Returns the sum of the tags of the specified cards
+ */
+pub fn count_cards(system: &dyn CountingSystem, cards: &[Card]) -> f64 {
+    cards.iter().map(|card| system.tag(card)).sum()
+}
+
+/**
+ * Formats a count with its sign, showing halves only when there are any
+ * Ex: +3, -1.5, +0
+ */
+pub fn format_count(count: f64) -> String {
+    if count.fract() == 0.0 {
+        format!("{:+}", count as i64)
+    } else {
+        format!("{:+.1}", count)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::blackjack::{Card, Suit};
+use crate::countingsystem::{
+    format_count, running_count, system_by_name, CountingSystem, HiLo, HiOptII, KnockOut, OmegaII, WongHalves, Zen,
+    SYSTEMS,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(rank: i64) -> Card {
+        Card::new(Suit::Spades, rank)
+    }
+
+    fn full_deck() -> Vec<Card> {
+        (1..=13).flat_map(|rank| (0..4).map(move |_| card(rank))).collect()
+    }
+
+    #[test]
+    fn test_hi_lo_tags() {
+        let tags: Vec<f64> = (1..=13).map(|rank| HiLo.tag(&card(rank))).collect();
+
+        assert_eq!(tags, vec![-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(running_count(&HiLo, 6, &[card(2), card(5), card(13), card(8)]), 1.0);
+    }
+
+    #[test]
+    fn test_balanced_systems_count_a_deck_to_zero() {
+        for system in SYSTEMS {
+            let deck = running_count(system, 1, &full_deck()) - system.initial_running_count(1);
+            assert_eq!(system.is_balanced(), deck == 0.0, "{}", system.name());
+        }
+        let unbalanced: Vec<&str> = SYSTEMS.iter().filter(|system| !system.is_balanced()).map(|system| system.name()).collect();
+        assert_eq!(unbalanced, vec!["KO"]);
+    }
+
+    #[test]
+    fn test_knock_out_initial_count() {
+        assert_eq!(KnockOut.initial_running_count(1), 0.0);
+        assert_eq!(KnockOut.initial_running_count(6), -20.0);
+        // A full six deck shoe ends at +4
+        let shoe: Vec<Card> = (0..6).flat_map(|_| full_deck()).collect();
+        assert_eq!(running_count(&KnockOut, 6, &shoe), 4.0);
+    }
+
+    #[test]
+    fn test_level_two_and_three_tags() {
+        assert_eq!(HiOptII.tag(&card(5)), 2.0);
+        assert_eq!(OmegaII.tag(&card(9)), -1.0);
+        assert_eq!(Zen.tag(&card(1)), -1.0);
+        assert_eq!(WongHalves.tag(&card(5)), 1.5);
+        assert_eq!(WongHalves.tag(&card(12)), -1.0);
+        assert!(HiOptII.ace_side_count() && OmegaII.ace_side_count());
+        assert!(!HiLo.ace_side_count() && !Zen.ace_side_count());
+    }
+
+    #[test]
+    fn test_system_names() {
+        let name = |text: &str| system_by_name(text).map(|system| system.name());
+
+        assert_eq!(name("hilo"), Some("Hi-Lo"));
+        assert_eq!(name("KO"), Some("KO"));
+        assert_eq!(name("hi-opt-i"), Some("Hi-Opt I"));
+        assert_eq!(name("Hi-Opt II"), Some("Hi-Opt II"));
+        assert_eq!(name("omega2"), Some("Omega II"));
+        assert_eq!(name("wong-halves"), Some("Wong Halves"));
+        assert_eq!(name("red7"), None);
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(3.0), "+3");
+        assert_eq!(format_count(-1.5), "-1.5");
+        assert_eq!(format_count(0.0), "+0");
+        assert_eq!(format_count(-0.0), "+0");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::blackjack::{Card, Suit};
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory, DEALER_CARDS};
+use crate::chart::dealer_label;
+use crate::stats::{cell_key, parse_cell, SessionStats};
+use crate::storage;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+/**
+ * The file in the data directory that keeps the drill schedule between sessions
+ */
+pub const SCHEDULE_FILE: &str = "drill.txt";
+
+/**
+ * Correct answers slower than this are treated as hesitant and the cell comes back sooner
+ */
+pub const SLOW_RESPONSE: Duration = Duration::from_secs(5);
+
+const START_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+const MAX_EASE: f64 = 3.0;
+
+/**
+ * The review history of one chart cell
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CardState {
+    // The review number at which the cell is next due
+    pub due: u64,
+    // The number of reviews until the cell comes back after a correct answer
+    pub interval: f64,
+    // How quickly the interval grows, lowered by mistakes and slow answers
+    pub ease: f64,
+    pub reviews: u32,
+    pub lapses: u32,
+}
+
+/**
+ * Chooses the chart cells to drill with a spaced-repetition schedule.
+ * Time is measured in reviews rather than days: a cell answered correctly comes back after a
+ * growing number of reviews, a cell answered wrongly comes back almost immediately.
+ * Cells that have not been drilled yet are introduced when nothing is due, favouring
+ * cells the trainee has got wrong while playing.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scheduler {
+    reviews: u64,
+    cards: HashMap<ChartCell, CardState>,
+    // Accuracy per cell from the trainee's played hands, used to pick new cells
+    history: HashMap<ChartCell, f64>,
+}
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            reviews: 0,
+            cards: HashMap::new(),
+            history: HashMap::new(),
+        }
+    }
+
+    /**
+     * Uses the trainee's accuracy from played hands to decide which new cells to introduce first
+     */
+    pub fn set_history(&mut self, stats: &SessionStats) {
+        self.history = stats.accuracy_map();
+    }
+
+    /**
+     * Returns the number of reviews made with this schedule
+     */
+    pub fn get_reviews(&self) -> u64 {
+        self.reviews
+    }
+
+    /**
+     * Returns the review history of a cell, or None if it has not been drilled
+     */
+    pub fn get(&self, cell: &ChartCell) -> Option<&CardState> {
+        self.cards.get(cell)
+    }
+
+    /**
+     * Returns the number of drilled cells that are due now
+     */
+    pub fn due_count(&self) -> usize {
+        self.cards.values().filter(|state| state.due <= self.reviews).count()
+    }
+
+    /**
+     * Chooses the next cell to drill: the most overdue cell if any is due, otherwise a new cell,
+     * otherwise the cell that will be due soonest.
+     */
+    pub fn next_cell(&self, rng: &mut impl Rng) -> ChartCell {
+        let mut due: Vec<(&ChartCell, &CardState)> =
+            self.cards.iter().filter(|(_, state)| state.due <= self.reviews).collect();
+        if !due.is_empty() {
+            // Overdue cells first, the hardest first among equally overdue cells
+            due.sort_by(|a, b| a.1.due.cmp(&b.1.due).then(a.1.ease.total_cmp(&b.1.ease)).then(order(a.0).cmp(&order(b.0))));
+            return *due[0].0;
+        }
+
+        let new_cells: Vec<ChartCell> = drill_cells().into_iter().filter(|cell| !self.cards.contains_key(cell)).collect();
+        if let Ok(&cell) = new_cells.choose_weighted(rng, |cell| self.new_cell_weight(cell)) {
+            return cell;
+        }
+
+        let (cell, _) = self
+            .cards
+            .iter()
+            .min_by(|a, b| a.1.due.cmp(&b.1.due).then(order(a.0).cmp(&order(b.0))))
+            .expect("every drill cell has been reviewed");
+        *cell
+    }
+
+    // Cells missed while playing are far more likely to be introduced next
+    fn new_cell_weight(&self, cell: &ChartCell) -> f64 {
+        match self.history.get(cell) {
+            Some(accuracy) => 1.0 + 20.0 * (1.0 - accuracy),
+            None => 1.0,
+        }
+    }
+
+    /**
+     * Records the answer to a drilled cell and schedules its next review
+     * @param cell the cell that was drilled
+     * @param correct true if the answer matched basic strategy
+     * @param response_time how long the trainee took to answer
+     */
+    pub fn record(&mut self, cell: ChartCell, correct: bool, response_time: Duration) {
+        self.reviews += 1;
+        let state = self.cards.entry(cell).or_insert(CardState {
+            due: 0,
+            interval: 0.0,
+            ease: START_EASE,
+            reviews: 0,
+            lapses: 0,
+        });
+        state.reviews += 1;
+
+        if !correct {
+            state.lapses += 1;
+            state.ease = (state.ease - 0.2).max(MIN_EASE);
+            state.interval = 1.0;
+        } else if response_time > SLOW_RESPONSE {
+            state.ease = (state.ease - 0.15).max(MIN_EASE);
+            state.interval = (state.interval * 1.2).max(2.0);
+        } else {
+            state.interval = if state.interval < 1.0 { 4.0 } else { state.interval * state.ease };
+            state.ease = (state.ease + 0.1).min(MAX_EASE);
+        }
+        // A wrong answer comes back after the next review rather than straight away
+        state.due = self.reviews + state.interval.round() as u64;
+    }
+
+    /**
+     * Returns this schedule in the schedule file format: the number of reviews, then one line
+     * per cell with the table, row, dealer upcard, due review, interval, ease, reviews and lapses
+     * Ex: hard 16 10 42 9.5 2.3 4 1
+     */
+    pub fn to_text(&self) -> String {
+        let mut text = format!("reviews {}\n", self.reviews);
+        let mut cells: Vec<(&ChartCell, &CardState)> = self.cards.iter().collect();
+        cells.sort_by_key(|(cell, _)| order(cell));
+
+        for (cell, state) in cells {
+            let (table, row) = cell_key(cell.category);
+            text.push_str(&format!(
+                "{} {} {} {} {} {} {} {}\n",
+                table,
+                row,
+                dealer_label(cell.dealer_card),
+                state.due,
+                state.interval,
+                state.ease,
+                state.reviews,
+                state.lapses
+            ));
+        }
+        text
+    }
+
+    /**
+     * Parses a schedule written by to_text
+     */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scheduler = Scheduler::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = || format!("line {}: invalid schedule entry", index + 1);
+            match words.as_slice() {
+                [] => {}
+                ["reviews", reviews] => scheduler.reviews = reviews.parse().map_err(|_| error())?,
+                [table, row, dealer, due, interval, ease, reviews, lapses] => {
+                    let cell = parse_cell(table, row, dealer).ok_or_else(error)?;
+                    let state = CardState {
+                        due: due.parse().map_err(|_| error())?,
+                        interval: interval.parse().map_err(|_| error())?,
+                        ease: ease.parse().map_err(|_| error())?,
+                        reviews: reviews.parse().map_err(|_| error())?,
+                        lapses: lapses.parse().map_err(|_| error())?,
+                    };
+                    scheduler.cards.insert(cell, state);
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(scheduler)
+    }
+
+    /**
+     * Loads the saved schedule, or an empty schedule if there is none
+     */
+    pub fn load() -> Result<Self, String> {
+        match storage::read_data_file(SCHEDULE_FILE) {
+            Ok(Some(text)) => Self::parse(&text),
+            Ok(None) => Ok(Self::new()),
+            Err(err) => Err(format!("Cannot read {}: {}", SCHEDULE_FILE, err)),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::write_data_file(SCHEDULE_FILE, &self.to_text())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sorts cells in chart order so ties are broken the same way every time
+fn order(cell: &ChartCell) -> (u8, i32, usize) {
+    let (table, row) = match cell.category {
+        HandCategory::Hard(total) => (0, total),
+        HandCategory::Soft(total) => (1, total),
+        HandCategory::Pair(1) => (2, 11),
+        HandCategory::Pair(card) => (2, card),
+    };
+    let column = DEALER_CARDS.iter().position(|&card| card == cell.dealer_card).unwrap_or(0);
+    (table, row, column)
+}
+
+/**
+ * Returns every chart cell that can be dealt as a two card hand: hard 5 to 19 (hard 4 and 20
+ * are always pairs), soft 13 to 20 (soft 12 is a pair of aces and soft 21 is blackjack) and
+ * every pair, against every dealer upcard.
+ */
+pub fn drill_cells() -> Vec<ChartCell> {
+    let strategy = BlackjackBasicStrategy::new();
+    let rows = strategy.hard_rows().into_iter().chain(strategy.soft_rows()).chain(strategy.pair_rows());
+    let mut cells = Vec::new();
+
+    for category in rows.filter(|&category| !player_values(category).is_empty()) {
+        for dealer_card in DEALER_CARDS {
+            cells.push(ChartCell { category, dealer_card });
+        }
+    }
+    cells
+}
+
+/**
+ * Returns the two card values that make up a chart row without forming a different row,
+ * e.g. [(7, 9), (6, 10)] for hard 16. Aces are 1.
+ */
+fn player_values(category: HandCategory) -> Vec<(i32, i32)> {
+    match category {
+        HandCategory::Hard(total) => (2..=10)
+            .filter_map(|first| {
+                let second = total - first;
+                (first < second && second <= 10).then_some((first, second))
+            })
+            .collect(),
+        HandCategory::Soft(total) if (13..=20).contains(&total) => vec![(1, total - 11)],
+        HandCategory::Soft(_) => Vec::new(),
+        HandCategory::Pair(card) => vec![(card, card)],
+    }
+}
+
+/**
+ * Returns a card with the specified value, choosing the rank of ten-valued cards and the suit at random
+ */
+fn random_card(value: i32, rng: &mut impl Rng) -> Card {
+    let suit = *Suit::ALL.choose(rng).expect("suits");
+    let rank = if value == 10 { rng.gen_range(10..=13) } else { value as i64 };
+    Card::new(suit, rank)
+}
+
+/**
+ * Builds the cards to stack for a drilled cell, in deal order: the player's two cards, the dealer's
+ * upcard and a hole card that does not give the dealer blackjack, so the trainee always gets to act.
+ * @param cell the cell to drill
+ */
+pub fn scenario(cell: ChartCell, rng: &mut impl Rng) -> Vec<Card> {
+    let values = player_values(cell.category);
+    let &(first, second) = values.choose(rng).expect("cell can be dealt with two cards");
+    let (first, second) = if rng.gen() { (first, second) } else { (second, first) };
+
+    let hole = loop {
+        let hole = rng.gen_range(1..=13).min(10);
+        let blackjack = (cell.dealer_card == 1 && hole == 10) || (cell.dealer_card == 10 && hole == 1);
+        if !blackjack {
+            break hole;
+        }
+    };
+
+    vec![
+        random_card(first, rng),
+        random_card(second, rng),
+        random_card(cell.dealer_card, rng),
+        random_card(hole, rng),
+    ]
+}
@@ -0,0 +1,113 @@
+use crate::blackjack::{Blackjack, Rules};
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory};
+use crate::drill::{drill_cells, scenario, Scheduler, SLOW_RESPONSE};
+use crate::stats::SessionStats;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_secs(1);
+
+    fn cell(category: HandCategory, dealer_card: i32) -> ChartCell {
+        ChartCell { category, dealer_card }
+    }
+
+    #[test]
+    fn test_drill_cells_can_be_dealt() {
+        let cells = drill_cells();
+
+        // Hard 5-19, soft 13-20 and 10 pairs against 10 upcards
+        assert_eq!(cells.len(), (15 + 8 + 10) * 10);
+        assert!(!cells.contains(&cell(HandCategory::Hard(20), 6)));
+        assert!(!cells.contains(&cell(HandCategory::Soft(12), 6)));
+        assert!(cells.contains(&cell(HandCategory::Pair(9), 7)));
+    }
+
+    #[test]
+    fn test_scenario_deals_the_cell() {
+        let strategy = BlackjackBasicStrategy::new();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut game = Blackjack::with_rules(Rules::default());
+
+        for drilled in drill_cells() {
+            game.stack_cards(scenario(drilled, &mut rng));
+            game.deal_cards();
+
+            let hand = game.get_players_hand().unwrap().get_card_values();
+            let upcard = game.get_dealers_upcard().unwrap().get_value();
+            assert_eq!(strategy.classify(&hand, true), Some(drilled.category), "{:?}", drilled);
+            assert_eq!(upcard, drilled.dealer_card);
+            assert!(game.is_players_turn(), "dealer blackjack in {:?}", drilled);
+        }
+    }
+
+    #[test]
+    fn test_wrong_answers_come_back_first() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut scheduler = Scheduler::new();
+        let missed = cell(HandCategory::Pair(9), 7);
+        let known = cell(HandCategory::Hard(16), 10);
+
+        scheduler.record(known, true, FAST);
+        scheduler.record(missed, false, FAST);
+        assert_eq!(scheduler.get(&missed).unwrap().lapses, 1);
+        assert!(scheduler.get(&missed).unwrap().due < scheduler.get(&known).unwrap().due);
+
+        // The missed cell is due after one more review
+        let other = scheduler.next_cell(&mut rng);
+        scheduler.record(other, true, FAST);
+        assert_eq!(scheduler.next_cell(&mut rng), missed);
+    }
+
+    #[test]
+    fn test_intervals_grow_and_slow_answers_shrink_them() {
+        let mut scheduler = Scheduler::new();
+        let drilled = cell(HandCategory::Soft(18), 1);
+
+        scheduler.record(drilled, true, FAST);
+        let first = scheduler.get(&drilled).unwrap().interval;
+        scheduler.record(drilled, true, FAST);
+        let second = scheduler.get(&drilled).unwrap().interval;
+        assert!(second > first);
+
+        let ease = scheduler.get(&drilled).unwrap().ease;
+        scheduler.record(drilled, true, SLOW_RESPONSE * 2);
+        let slow = scheduler.get(&drilled).unwrap();
+        assert!(slow.ease < ease);
+        assert!(slow.interval < second * ease);
+    }
+
+    #[test]
+    fn test_new_cells_favour_past_mistakes() {
+        let weak = cell(HandCategory::Pair(9), 7);
+        let mut stats = SessionStats::new();
+        for _ in 0..5 {
+            stats.record(weak, false, 0.1);
+        }
+
+        let mut scheduler = Scheduler::new();
+        scheduler.set_history(&stats);
+        let mut rng = StdRng::seed_from_u64(3);
+        let picks = (0..1000).filter(|_| scheduler.next_cell(&mut rng) == weak).count();
+
+        // 21 times the weight of each of the other 329 cells: about 6% instead of 0.3%
+        assert!(picks > 30, "picked {} times", picks);
+    }
+
+    #[test]
+    fn test_schedule_round_trip() {
+        let mut scheduler = Scheduler::new();
+        scheduler.record(cell(HandCategory::Pair(1), 1), true, FAST);
+        scheduler.record(cell(HandCategory::Hard(12), 2), false, FAST);
+
+        let text = scheduler.to_text();
+        assert!(text.starts_with("reviews 2\nhard 12 2 3 1 2.3 1 1\npair A A "));
+        assert_eq!(Scheduler::parse(&text).unwrap(), scheduler);
+        assert_eq!(Scheduler::parse("reviews x").err().unwrap(), "line 1: invalid schedule entry");
+        assert_eq!(Scheduler::parse("hard 30 2 1 1 1 1 1").err().unwrap(), "line 1: invalid schedule entry");
+    }
+}
@@ -0,0 +1,342 @@
+use crate::blackjack::{Action, Rules};
+use crate::blackjackbasicstrategy::{
+    HandCategory, DOUBLE_OR_HIT, DOUBLE_OR_STAND, HIT, SPLIT, STAND, SURRENDER_OR_HIT, SURRENDER_OR_STAND,
+};
+use std::collections::HashMap;
+
+/**
+ * The expected value, in units of the initial bet, of each action available to a hand.
+ * Double is only available on two card hands, split only on pairs and surrender only on two card hands
+ * under late surrender.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionEvs {
+    pub hit: f64,
+    pub stand: f64,
+    pub double: Option<f64>,
+    pub split: Option<f64>,
+    pub surrender: Option<f64>,
+}
+impl ActionEvs {
+    /**
+     * Returns the expected value of a chart play, falling back to hitting or standing
+     * when doubling or surrender is not available, or None if the play is not available
+     * @param play the chart play
+     */
+    pub fn play_ev(&self, play: i32) -> Option<f64> {
+        match play {
+            HIT => Some(self.hit),
+            STAND => Some(self.stand),
+            DOUBLE_OR_HIT => Some(self.double.unwrap_or(self.hit)),
+            DOUBLE_OR_STAND => Some(self.double.unwrap_or(self.stand)),
+            SPLIT => self.split,
+            SURRENDER_OR_HIT => Some(self.surrender.unwrap_or(self.hit)),
+            SURRENDER_OR_STAND => Some(self.surrender.unwrap_or(self.stand)),
+            _ => None,
+        }
+    }
+
+    /**
+     * Returns the expected value of an action, or None if it is not available
+     */
+    pub fn action_ev(&self, action: Action) -> Option<f64> {
+        match action {
+            Action::Hit => Some(self.hit),
+            Action::Stand => Some(self.stand),
+            Action::Double => self.double,
+            Action::Split => self.split,
+            Action::Surrender => self.surrender,
+        }
+    }
+
+    /**
+     * Returns the expected value of the best available action
+     */
+    pub fn best(&self) -> f64 {
+        [Some(self.hit), Some(self.stand), self.double, self.split, self.surrender]
+            .into_iter()
+            .flatten()
+            .fold(f64::MIN, f64::max)
+    }
+}
+
+/**
+ * Returns a typical two card hand for a chart row, e.g. 10, 6 for hard 16, 5, 3 for hard 8 and A, 7 for soft 18.
+ * Hard 21 cannot be made with two cards and is given as 10, 5, 6.
+ */
+pub fn representative_hand(category: HandCategory) -> Vec<i32> {
+    match category {
+        HandCategory::Hard(21) => vec![10, 5, 6],
+        HandCategory::Hard(total) if total >= 12 => vec![10, total - 10],
+        HandCategory::Hard(total) if total >= 5 => vec![total / 2 + 1, total - total / 2 - 1],
+        HandCategory::Hard(_) => vec![2, 2],
+        HandCategory::Soft(total) => vec![1, total - 11],
+        HandCategory::Pair(card) => vec![card, card],
+    }
+}
+
+/**
+ * Returns the probability of drawing each card value (index 0 is the ace, index 9 the ten-valued cards)
+ * from the specified number of decks with the specified cards removed.
+ * @param decks the number of decks in the shoe
+ * @param removed the values of the cards already dealt (1 for an ace)
+ */
+pub fn draw_probabilities(decks: usize, removed: &[i32]) -> [f64; 10] {
+    let mut counts = [4.0 * decks as f64; 10];
+    counts[9] = 16.0 * decks as f64;
+
+    for &card in removed {
+        let index = (card.clamp(1, 10) - 1) as usize;
+        counts[index] = (counts[index] - 1.0).max(0.0);
+    }
+
+    let total: f64 = counts.iter().sum();
+    counts.map(|count| count / total)
+}
+
+/**
+ * Returns the value of a hand from its hard total (aces as 1) and whether it holds an ace
+ */
+fn hand_value(hard_sum: i32, has_ace: bool) -> i32 {
+    if has_ace && hard_sum + 10 <= 21 {
+        hard_sum + 10
+    } else {
+        hard_sum
+    }
+}
+
+/**
+ * Approximates the expected value of each action for a hand against a dealer upcard.
+ * Cards are drawn from the shoe with the player's cards and the upcard removed, without
+ * further depletion, and the dealer is known not to have blackjack (the dealer peeks).
+ * Split hands are played without resplitting, split aces receive one card each.
+ */
+pub struct EvCalculator {
+    probabilities: [f64; 10],
+    // Dealer finishing on 17, 18, 19, 20, 21 and bust
+    dealer: [f64; 6],
+    double_after_split: bool,
+    surrender: bool,
+    best_memo: HashMap<(i32, bool, bool), f64>,
+}
+impl EvCalculator {
+    /**
+     * @param rules the table rules
+     * @param hand the values of the player's cards (1 for an ace)
+     * @param dealer_card the value of the dealer's upcard (1 or 11 for an ace)
+     */
+    pub fn new(rules: &Rules, hand: &[i32], dealer_card: i32) -> Self {
+        let dealer_card = if dealer_card == 11 { 1 } else { dealer_card };
+        let mut removed = hand.to_vec();
+        removed.push(dealer_card);
+        let shoe = draw_probabilities(rules.decks, &removed);
+        let dealer = dealer_outcomes(&shoe, dealer_card, rules.dealer_hits_soft_17);
+
+        // Knowing the hole card does not make a blackjack changes what the player draws
+        let mut probabilities = [0.0; 10];
+        let hole_cards = hole_card_probabilities(&shoe, dealer_card);
+        for (index, hole_probability) in hole_cards.iter().enumerate() {
+            if *hole_probability == 0.0 {
+                continue;
+            }
+            removed.push(index as i32 + 1);
+            for (probability, after_hole) in probabilities.iter_mut().zip(draw_probabilities(rules.decks, &removed)) {
+                *probability += hole_probability * after_hole;
+            }
+            removed.pop();
+        }
+
+        EvCalculator {
+            probabilities,
+            dealer,
+            double_after_split: rules.double_after_split,
+            surrender: rules.surrender,
+            best_memo: HashMap::new(),
+        }
+    }
+
+    /**
+     * Returns the probability of the dealer finishing on 17, 18, 19, 20, 21 and busting
+     */
+    pub fn dealer_outcomes(&self) -> [f64; 6] {
+        self.dealer
+    }
+
+    /**
+     * Returns the expected value of each action for the specified hand
+     * @param hand the values of the player's cards (1 for an ace)
+     */
+    pub fn action_evs(&mut self, hand: &[i32]) -> ActionEvs {
+        let hard_sum: i32 = hand.iter().sum();
+        let has_ace = hand.contains(&1);
+        let two_cards = hand.len() == 2;
+
+        ActionEvs {
+            hit: self.hit(hard_sum, has_ace),
+            stand: self.stand(hand_value(hard_sum, has_ace)),
+            double: if two_cards { Some(self.double(hard_sum, has_ace)) } else { None },
+            split: if two_cards && hand[0] == hand[1] { Some(self.split(hand[0])) } else { None },
+            // The dealer has peeked, so surrender always loses exactly half the bet
+            surrender: if two_cards && self.surrender { Some(-0.5) } else { None },
+        }
+    }
+
+    /**
+     * Returns the expected value of standing on the specified total
+     */
+    pub fn stand(&self, total: i32) -> f64 {
+        if total > 21 {
+            return -1.0;
+        }
+
+        let mut ev = self.dealer[5];
+        for (index, probability) in self.dealer[..5].iter().enumerate() {
+            let dealer_total = 17 + index as i32;
+            if total > dealer_total {
+                ev += probability;
+            } else if total < dealer_total {
+                ev -= probability;
+            }
+        }
+        ev
+    }
+
+    fn hit(&mut self, hard_sum: i32, has_ace: bool) -> f64 {
+        let mut ev = 0.0;
+        for (index, probability) in self.probabilities.into_iter().enumerate() {
+            let card = index as i32 + 1;
+            let next = hard_sum + card;
+            ev += probability * if next > 21 { -1.0 } else { self.best(next, has_ace || card == 1, false) };
+        }
+        ev
+    }
+
+    fn double(&mut self, hard_sum: i32, has_ace: bool) -> f64 {
+        let mut ev = 0.0;
+        for (index, probability) in self.probabilities.into_iter().enumerate() {
+            let card = index as i32 + 1;
+            ev += probability * self.stand(hand_value(hard_sum + card, has_ace || card == 1));
+        }
+        2.0 * ev
+    }
+
+    /**
+     * Returns the expected value of the better of hitting and standing (and doubling if allowed)
+     */
+    fn best(&mut self, hard_sum: i32, has_ace: bool, can_double: bool) -> f64 {
+        if let Some(&ev) = self.best_memo.get(&(hard_sum, has_ace, can_double)) {
+            return ev;
+        }
+
+        let mut ev = self.stand(hand_value(hard_sum, has_ace));
+        if hand_value(hard_sum, has_ace) < 21 {
+            ev = ev.max(self.hit(hard_sum, has_ace));
+            if can_double {
+                ev = ev.max(self.double(hard_sum, has_ace));
+            }
+        }
+
+        self.best_memo.insert((hard_sum, has_ace, can_double), ev);
+        ev
+    }
+
+    fn split(&mut self, card: i32) -> f64 {
+        let mut ev = 0.0;
+        for (index, probability) in self.probabilities.into_iter().enumerate() {
+            let drawn = index as i32 + 1;
+            let has_ace = card == 1 || drawn == 1;
+            ev += probability * if card == 1 {
+                self.stand(hand_value(card + drawn, has_ace))
+            } else {
+                self.best(card + drawn, has_ace, self.double_after_split)
+            };
+        }
+        2.0 * ev
+    }
+}
+
+/**
+ * Returns the probability of the dealer finishing on 17, 18, 19, 20, 21 and busting,
+ * given the upcard and that the dealer does not have blackjack.
+ * @param probabilities the probability of drawing each card value (index 0 is the ace)
+ * @param upcard the dealer's upcard (1 for an ace)
+ * @param hits_soft_17 true if the dealer hits soft 17
+ */
+pub fn dealer_outcomes(probabilities: &[f64; 10], upcard: i32, hits_soft_17: bool) -> [f64; 6] {
+    let mut memo = HashMap::new();
+    let mut outcomes = [0.0; 6];
+    for (index, probability) in hole_card_probabilities(probabilities, upcard).iter().enumerate() {
+        let card = index as i32 + 1;
+        let next = dealer_draw(probabilities, upcard + card, upcard == 1 || card == 1, hits_soft_17, &mut memo);
+        for (outcome, next_probability) in outcomes.iter_mut().zip(next) {
+            *outcome += probability * next_probability;
+        }
+    }
+    outcomes
+}
+
+/**
+ * Returns the probability of each hole card given that it does not complete a dealer blackjack
+ */
+fn hole_card_probabilities(probabilities: &[f64; 10], upcard: i32) -> [f64; 10] {
+    let mut hole = *probabilities;
+    if upcard == 1 {
+        hole[9] = 0.0;
+    } else if upcard == 10 {
+        hole[0] = 0.0;
+    }
+    let total: f64 = hole.iter().sum();
+    hole.map(|probability| probability / total)
+}
+
+fn dealer_draw(
+    probabilities: &[f64; 10],
+    hard_sum: i32,
+    has_ace: bool,
+    hits_soft_17: bool,
+    memo: &mut HashMap<(i32, bool), [f64; 6]>,
+) -> [f64; 6] {
+    let total = hand_value(hard_sum, has_ace);
+    let soft = total != hard_sum;
+
+    if total > 21 {
+        return [0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    }
+    if total > 17 || (total == 17 && !(soft && hits_soft_17)) {
+        let mut outcomes = [0.0; 6];
+        outcomes[(total - 17) as usize] = 1.0;
+        return outcomes;
+    }
+    if let Some(&outcomes) = memo.get(&(hard_sum, has_ace)) {
+        return outcomes;
+    }
+
+    let mut outcomes = [0.0; 6];
+    for (index, probability) in probabilities.iter().enumerate() {
+        let card = index as i32 + 1;
+        let next = dealer_draw(probabilities, hard_sum + card, has_ace || card == 1, hits_soft_17, memo);
+        for (outcome, next_probability) in outcomes.iter_mut().zip(next) {
+            *outcome += probability * next_probability;
+        }
+    }
+
+    memo.insert((hard_sum, has_ace), outcomes);
+    outcomes
+}
+
+/**
+ * Returns the expected value of each action for a chart cell under the specified rules,
+ * using a typical hand for the cell's row
+ * @param rules the table rules
+ * @param category the chart row
+ * @param dealer_card the dealer's upcard (1 for an ace)
+ */
+pub fn cell_evs(rules: &Rules, category: HandCategory, dealer_card: i32) -> ActionEvs {
+    let hand = representative_hand(category);
+    let mut calculator = EvCalculator::new(rules, &hand, dealer_card);
+    let mut evs = calculator.action_evs(&hand);
+    if !matches!(category, HandCategory::Pair(_)) {
+        evs.split = None;
+    }
+    evs
+}
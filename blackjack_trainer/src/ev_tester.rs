@@ -0,0 +1,76 @@
+use crate::blackjack::Rules;
+use crate::blackjackbasicstrategy::HandCategory;
+use crate::ev::{cell_evs, dealer_outcomes, draw_probabilities, representative_hand, EvCalculator};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_probabilities() {
+        let probabilities = draw_probabilities(1, &[]);
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!((probabilities[9] - 16.0 / 52.0).abs() < 1e-12);
+
+        let probabilities = draw_probabilities(1, &[1, 1, 1, 1]);
+        assert_eq!(probabilities[0], 0.0);
+    }
+
+    #[test]
+    fn test_dealer_outcomes() {
+        let probabilities = draw_probabilities(6, &[]);
+
+        for upcard in 1..=10 {
+            let outcomes = dealer_outcomes(&probabilities, upcard, false);
+            assert!((outcomes.iter().sum::<f64>() - 1.0).abs() < 1e-9, "upcard {}", upcard);
+        }
+
+        // The dealer busts about 42% of the time with a 6 showing
+        let six = dealer_outcomes(&probabilities, 6, false);
+        assert!((six[5] - 0.42).abs() < 0.01, "{}", six[5]);
+
+        // Hitting soft 17 never leaves the dealer on 17 from a soft hand, so fewer 17s
+        let six_h17 = dealer_outcomes(&probabilities, 6, true);
+        assert!(six_h17[0] < six[0]);
+    }
+
+    #[test]
+    fn test_action_evs() {
+        let rules = Rules::default();
+
+        let evs = cell_evs(&rules, HandCategory::Hard(16), 10);
+        assert!(evs.hit > evs.stand);
+        assert!(evs.stand > -0.6 && evs.stand < -0.5);
+        assert_eq!(evs.split, None);
+
+        let evs = cell_evs(&rules, HandCategory::Hard(20), 6);
+        assert!(evs.stand > 0.6);
+        assert!(evs.hit < evs.stand);
+
+        let evs = cell_evs(&rules, HandCategory::Hard(11), 6);
+        assert!(evs.double.unwrap() > evs.hit);
+
+        let evs = cell_evs(&rules, HandCategory::Pair(8), 10);
+        assert!(evs.split.unwrap() > evs.hit.max(evs.stand));
+    }
+
+    #[test]
+    fn test_three_card_hands_cannot_double() {
+        let rules = Rules::default();
+        let mut calculator = EvCalculator::new(&rules, &[5, 3, 4], 6);
+        let evs = calculator.action_evs(&[5, 3, 4]);
+
+        assert_eq!(evs.double, None);
+        assert_eq!(evs.split, None);
+    }
+
+    #[test]
+    fn test_representative_hands() {
+        assert_eq!(representative_hand(HandCategory::Hard(4)), vec![2, 2]);
+        assert_eq!(representative_hand(HandCategory::Hard(8)), vec![5, 3]);
+        assert_eq!(representative_hand(HandCategory::Hard(11)), vec![6, 5]);
+        assert_eq!(representative_hand(HandCategory::Hard(16)), vec![10, 6]);
+        assert_eq!(representative_hand(HandCategory::Hard(21)).iter().sum::<i32>(), 21);
+        assert_eq!(representative_hand(HandCategory::Soft(18)), vec![1, 7]);
+    }
+}
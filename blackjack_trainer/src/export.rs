@@ -0,0 +1,471 @@
+use crate::blackjack::{Action, Card};
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell};
+use crate::chart::dealer_label;
+use crate::history::SessionHistory;
+use crate::simulation::{SimulationConfig, SimulationResult, MAX_BET_UNITS, TRUE_COUNT_LIMIT};
+use crate::stats::{cell_key, SessionStats};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/**
+ * The version of the export format written by this build. Columns are only added within a version;
+ * renaming, removing or changing the meaning of a column increments it.
+ */
+pub const EXPORT_FORMAT: u32 = 1;
+
+/**
+ * How an export is written, chosen by the extension of the file name
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    // One file per table, each named after the file given with the table's name added
+    Csv,
+    // A single file holding every table
+    Json,
+}
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Result<ExportFormat, String> {
+        match path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("csv") => Ok(ExportFormat::Csv),
+            Some("json") => Ok(ExportFormat::Json),
+            _ => Err(format!("{}: export files must end in .csv or .json", path.display())),
+        }
+    }
+}
+
+/**
+ * The summary of a simulation: the rules and player, and how the player did
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SummaryRow {
+    pub rules: String,
+    pub decks: usize,
+    pub dealer_hits_soft_17: bool,
+    pub double_after_split: bool,
+    pub surrender: bool,
+    pub blackjack_payout: f64,
+    pub penetration: f64,
+    pub player: String,
+    pub count_system: String,
+    pub seed: u64,
+    pub threads: usize,
+    pub rounds: u64,
+    pub hands: u64,
+    pub wagered: f64,
+    pub net: f64,
+    pub average_bet: f64,
+    pub house_edge: f64,
+    // The half width of the 95% confidence interval of the house edge
+    pub house_edge_ci: f64,
+    pub std_dev: f64,
+    // Hands finishing with each outcome
+    pub blackjacks: u64,
+    pub wins: u64,
+    pub pushes: u64,
+    pub losses: u64,
+    pub busts: u64,
+    pub surrenders: u64,
+}
+
+pub const SUMMARY_COLUMNS: [&str; 25] = [
+    "rules", "decks", "dealer_hits_soft_17", "double_after_split", "surrender", "blackjack_payout", "penetration",
+    "player", "count_system", "seed", "threads", "rounds", "hands", "wagered", "net", "average_bet", "house_edge",
+    "house_edge_ci", "std_dev", "blackjacks", "wins", "pushes", "losses", "busts", "surrenders",
+];
+
+/**
+ * The rounds bet at one true count, rounded down. The first and last rows also hold the counts beyond them.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrueCountRow {
+    pub true_count: i32,
+    pub rounds: u64,
+    pub wagered: f64,
+    pub net: f64,
+    // The average result per round, in betting units
+    pub win_rate: f64,
+    // The result as a fraction of the initial bets
+    pub edge: f64,
+}
+
+pub const TRUE_COUNT_COLUMNS: [&str; 6] = ["true_count", "rounds", "wagered", "net", "win_rate", "edge"];
+
+/**
+ * The rounds bet at one size, rounded to whole units. The last row also holds the larger bets.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BetRow {
+    pub units: usize,
+    pub rounds: u64,
+    pub fraction: f64,
+}
+
+pub const BET_COLUMNS: [&str; 3] = ["units", "rounds", "fraction"];
+
+/**
+ * A side bet of 1 unit made every round
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SideBetRow {
+    pub side_bet: &'static str,
+    pub rounds: u64,
+    pub net: f64,
+    pub house_edge: f64,
+    // The half width of the 95% confidence interval of the house edge
+    pub house_edge_ci: f64,
+}
+
+pub const SIDE_BET_COLUMNS: [&str; 5] = ["side_bet", "rounds", "net", "house_edge", "house_edge_ci"];
+
+/**
+ * A countable side bet made at one true count, rounded down
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SideBetTrueCountRow {
+    pub side_bet: &'static str,
+    pub true_count: i32,
+    pub rounds: u64,
+    pub net: f64,
+    // The result as a fraction of the side bets
+    pub edge: f64,
+}
+
+pub const SIDE_BET_TRUE_COUNT_COLUMNS: [&str; 5] = ["side_bet", "true_count", "rounds", "net", "edge"];
+
+/**
+ * A decision made at the table, read from the hand history
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DecisionRow {
+    // The time the session started, which names its history file
+    pub session: u64,
+    pub mode: String,
+    pub round: u32,
+    pub hand: usize,
+    // The hand's cards, separated by spaces, e.g. "10H 6S"
+    pub cards: String,
+    pub upcard: String,
+    // The chart cell of the hand, empty when basic strategy has no play for it
+    pub table: Option<String>,
+    pub row: Option<String>,
+    pub dealer: Option<String>,
+    pub action: Action,
+    pub recommended: Option<Action>,
+    pub matched: bool,
+}
+
+pub const DECISION_COLUMNS: [&str; 12] = [
+    "session", "mode", "round", "hand", "cards", "upcard", "table", "row", "dealer", "action", "recommended", "matched",
+];
+
+/**
+ * The graded decisions made in one chart cell
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CellRow {
+    pub table: String,
+    pub row: String,
+    pub dealer: String,
+    pub attempts: u32,
+    pub correct: u32,
+    pub accuracy: f64,
+    pub ev_lost: f64,
+}
+
+pub const CELL_COLUMNS: [&str; 7] = ["table", "row", "dealer", "attempts", "correct", "accuracy", "ev_lost"];
+
+/**
+ * A table of an export: its column names in order, and a JSON object per row
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportTable {
+    pub name: &'static str,
+    pub columns: &'static [&'static str],
+    pub rows: Vec<Value>,
+}
+impl ExportTable {
+    pub fn new<T: Serialize>(name: &'static str, columns: &'static [&'static str], rows: &[T]) -> Self {
+        let rows = rows.iter().map(|row| serde_json::to_value(row).expect("export rows serialize")).collect();
+        ExportTable { name, columns, rows }
+    }
+
+    /**
+     * Renders the table as CSV with a header line. Every row starts with the format version.
+     */
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("format_version");
+        for column in self.columns {
+            out.push(',');
+            out.push_str(column);
+        }
+        out.push('\n');
+
+        for row in &self.rows {
+            out.push_str(&EXPORT_FORMAT.to_string());
+            for column in self.columns {
+                out.push(',');
+                out.push_str(&csv_field(row.get(column).unwrap_or(&Value::Null)));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Formats a value as a CSV field, quoting it if it holds a separator, quote or line break
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/**
+ * Results ready to be written for spreadsheets and notebooks: what kind of results they are and their tables
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    // simulation, sessions or stats
+    pub kind: &'static str,
+    pub tables: Vec<ExportTable>,
+}
+impl Export {
+    /**
+     * Renders the export as a JSON object with the format version, the kind and an array of rows per table:
+     *   {"format_version":1,"kind":"simulation","summary":[{...}],"true_counts":[...],"bets":[...]}
+     */
+    pub fn to_json(&self) -> String {
+        let mut object = Map::new();
+        object.insert("format_version".to_string(), Value::from(EXPORT_FORMAT));
+        object.insert("kind".to_string(), Value::from(self.kind));
+        for table in &self.tables {
+            object.insert(table.name.to_string(), Value::Array(table.rows.clone()));
+        }
+        serde_json::to_string_pretty(&Value::Object(object)).expect("export serializes") + "\n"
+    }
+
+    /**
+     * Writes the export, returning the files written. A CSV export writes a file per table,
+     * e.g. results.csv is written as results-summary.csv, results-true_counts.csv and results-bets.csv.
+     */
+    pub fn write(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        let files = match ExportFormat::from_path(path)? {
+            ExportFormat::Json => vec![(path.to_path_buf(), self.to_json())],
+            ExportFormat::Csv => self.tables.iter().map(|table| (csv_path(path, table.name), table.to_csv())).collect(),
+        };
+        for (file, text) in &files {
+            fs::write(file, text).map_err(|err| format!("{}: {}", file.display(), err))?;
+        }
+        Ok(files.into_iter().map(|(file, _)| file).collect())
+    }
+}
+
+/**
+ * Returns the file a table of a CSV export is written to
+ * Ex: csv_path("out/results.csv", "bets") is out/results-bets.csv
+ */
+pub fn csv_path(path: &Path, table: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!("{}-{}.csv", stem, table))
+}
+
+/**
+ * Exports the results of a simulation: a summary row, the rounds at each true count, the bet distribution,
+ * and the house edge of each side bet, overall and at each true count for the countable ones.
+ * Only the true counts, bets and side bets that were played have rows.
+ */
+pub fn simulation_export(config: &SimulationConfig, result: &SimulationResult) -> Export {
+    let rules = &config.rules;
+    let summary = SummaryRow {
+        rules: rules.to_string(),
+        decks: rules.decks,
+        dealer_hits_soft_17: rules.dealer_hits_soft_17,
+        double_after_split: rules.double_after_split,
+        surrender: rules.surrender,
+        blackjack_payout: rules.blackjack_payout,
+        penetration: rules.penetration,
+        player: config.player.build(&config.strategy, config.seed).name(),
+        count_system: config.system.name().to_string(),
+        seed: config.seed,
+        threads: config.threads,
+        rounds: result.rounds,
+        hands: result.hands,
+        wagered: result.wagered,
+        net: result.total,
+        average_bet: result.average_bet(),
+        house_edge: result.house_edge(),
+        house_edge_ci: result.confidence_interval(),
+        std_dev: result.standard_deviation(),
+        blackjacks: result.outcomes[0],
+        wins: result.outcomes[1],
+        pushes: result.outcomes[2],
+        losses: result.outcomes[3],
+        busts: result.outcomes[4],
+        surrenders: result.outcomes[5],
+    };
+
+    let true_counts: Vec<TrueCountRow> = (-TRUE_COUNT_LIMIT..=TRUE_COUNT_LIMIT)
+        .map(|true_count| (true_count, result.true_count_stats(true_count)))
+        .filter(|(_, stats)| stats.rounds > 0)
+        .map(|(true_count, stats)| TrueCountRow {
+            true_count,
+            rounds: stats.rounds,
+            wagered: stats.wagered,
+            net: stats.total,
+            win_rate: stats.win_rate(),
+            edge: stats.edge(),
+        })
+        .collect();
+
+    let bets: Vec<BetRow> = (0..=MAX_BET_UNITS)
+        .filter(|&units| result.bets[units] > 0)
+        .map(|units| BetRow {
+            units,
+            rounds: result.bets[units],
+            fraction: result.bets[units] as f64 / result.rounds as f64,
+        })
+        .collect();
+
+    let mut side_bets = Vec::new();
+    let mut side_bet_true_counts = Vec::new();
+    for &side_bet in &config.side_bets {
+        let stats = &result.side_bets[side_bet.index()];
+        side_bets.push(SideBetRow {
+            side_bet: side_bet.key(),
+            rounds: stats.rounds,
+            net: stats.total,
+            house_edge: stats.house_edge(),
+            house_edge_ci: stats.confidence_interval(),
+        });
+        if !side_bet.is_countable() {
+            continue;
+        }
+        for true_count in -TRUE_COUNT_LIMIT..=TRUE_COUNT_LIMIT {
+            let count = stats.true_count_stats(true_count);
+            if count.rounds > 0 {
+                side_bet_true_counts.push(SideBetTrueCountRow {
+                    side_bet: side_bet.key(),
+                    true_count,
+                    rounds: count.rounds,
+                    net: count.total,
+                    edge: count.edge(),
+                });
+            }
+        }
+    }
+
+    Export {
+        kind: "simulation",
+        tables: vec![
+            ExportTable::new("summary", &SUMMARY_COLUMNS, &[summary]),
+            ExportTable::new("true_counts", &TRUE_COUNT_COLUMNS, &true_counts),
+            ExportTable::new("bets", &BET_COLUMNS, &bets),
+            ExportTable::new("side_bets", &SIDE_BET_COLUMNS, &side_bets),
+            ExportTable::new("side_bet_true_counts", &SIDE_BET_TRUE_COUNT_COLUMNS, &side_bet_true_counts),
+        ],
+    }
+}
+
+/**
+ * Returns the rows of statistics in chart order
+ */
+pub fn cell_rows(stats: &SessionStats) -> Vec<CellRow> {
+    stats
+        .cells()
+        .into_iter()
+        .map(|(cell, stats)| {
+            let (table, row) = cell_key(cell.category);
+            CellRow {
+                table: table.to_string(),
+                row,
+                dealer: dealer_label(cell.dealer_card),
+                attempts: stats.attempts,
+                correct: stats.correct,
+                accuracy: stats.accuracy(),
+                ev_lost: stats.ev_lost,
+            }
+        })
+        .collect()
+}
+
+// The values of cards, as the strategy chart reads them
+fn values(cards: &[Card]) -> Vec<i32> {
+    cards.iter().map(Card::get_value).collect()
+}
+
+/**
+ * Exports the decisions of sessions from the hand history, with the accuracy in each chart cell
+ * they cover. Decisions basic strategy has no play for are exported but not graded, as in training.
+ */
+pub fn sessions_export(sessions: &[SessionHistory]) -> Export {
+    let mut decisions = Vec::new();
+    let mut stats = SessionStats::new();
+
+    for history in sessions {
+        let strategy = BlackjackBasicStrategy::for_rules(&history.session.rules);
+        for round in &history.rounds {
+            for action in &round.actions {
+                let hand = values(&action.cards);
+                let can_split = hand.len() == 2 && hand[0] == hand[1];
+                let dealer_card = action.upcard.get_value();
+                let category = action.recommended.and_then(|_| strategy.classify(&hand, can_split));
+
+                if let (Some(category), Some(recommended)) = (category, action.recommended) {
+                    let ev_lost = if action.matched {
+                        0.0
+                    } else {
+                        strategy
+                            .explain(&hand, dealer_card)
+                            .and_then(|explanation| {
+                                Some(explanation.evs.action_ev(recommended)? - explanation.evs.action_ev(action.action)?)
+                            })
+                            .unwrap_or(0.0)
+                    };
+                    stats.record(ChartCell { category, dealer_card }, action.matched, ev_lost);
+                }
+
+                let key = category.map(cell_key);
+                decisions.push(DecisionRow {
+                    session: history.session.started,
+                    mode: history.session.mode.clone(),
+                    round: round.round,
+                    hand: action.hand,
+                    cards: action.cards.iter().map(ToString::to_string).collect::<Vec<_>>().join(" "),
+                    upcard: action.upcard.to_string(),
+                    table: key.as_ref().map(|(table, _)| table.to_string()),
+                    row: key.map(|(_, row)| row),
+                    dealer: category.map(|_| dealer_label(dealer_card)),
+                    action: action.action,
+                    recommended: action.recommended,
+                    matched: action.matched,
+                });
+            }
+        }
+    }
+
+    Export {
+        kind: "sessions",
+        tables: vec![
+            ExportTable::new("decisions", &DECISION_COLUMNS, &decisions),
+            ExportTable::new("cells", &CELL_COLUMNS, &cell_rows(&stats)),
+        ],
+    }
+}
+
+/**
+ * Exports the lifetime accuracy in each chart cell
+ */
+pub fn stats_export(stats: &SessionStats) -> Export {
+    Export {
+        kind: "stats",
+        tables: vec![ExportTable::new("cells", &CELL_COLUMNS, &cell_rows(stats))],
+    }
+}
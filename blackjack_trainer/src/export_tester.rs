@@ -0,0 +1,159 @@
+use crate::blackjack::{Action, Card, Rules, Suit};
+use crate::blackjackbasicstrategy::{ChartCell, HandCategory};
+use crate::export::{
+    csv_path, sessions_export, simulation_export, stats_export, ExportFormat, ExportTable, CELL_COLUMNS,
+    DECISION_COLUMNS, SUMMARY_COLUMNS,
+};
+use crate::history::{ActionRecord, RoundRecord, SessionHistory, SessionRecord, HISTORY_FORMAT};
+use crate::simulation::{simulate, SimulationConfig};
+use crate::stats::SessionStats;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    fn card(rank: i64) -> Card {
+        Card::new(Suit::Hearts, rank)
+    }
+
+    fn decision(cards: Vec<Card>, upcard: i64, action: Action, recommended: Option<Action>) -> ActionRecord {
+        ActionRecord {
+            hand: 0,
+            matched: recommended.is_none_or(|recommended| recommended == action),
+            cards,
+            upcard: card(upcard),
+            action,
+            recommended,
+        }
+    }
+
+    // A session with two correct hits on 16 against a 10, a wrong stand on 12 against a 2,
+    // and a hit on 21 that basic strategy has no play for
+    fn session() -> SessionHistory {
+        let actions = vec![
+            decision(vec![card(10), card(6)], 10, Action::Hit, Some(Action::Hit)),
+            decision(vec![card(10), card(2)], 2, Action::Stand, Some(Action::Hit)),
+        ];
+        let round = RoundRecord {
+            round: 1,
+            shoe: 1,
+            position: 0,
+            bet: 1.0,
+            dealt: Vec::new(),
+            actions,
+            hands: Vec::new(),
+            dealer: Vec::new(),
+            insurance: 0.0,
+            net: 0.0,
+        };
+        let mut second = round.clone();
+        second.round = 2;
+        second.actions = vec![
+            decision(vec![card(10), card(6)], 10, Action::Hit, Some(Action::Hit)),
+            decision(vec![card(10), card(6), card(5)], 10, Action::Hit, None),
+        ];
+
+        SessionHistory {
+            session: SessionRecord {
+                format: HISTORY_FORMAT,
+                started: 1_760_000_000,
+                seed: 7,
+                mode: "train".to_string(),
+                rules: Rules::default(),
+            },
+            rounds: vec![round, second],
+        }
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ExportFormat::from_path(Path::new("out/results.CSV")), Ok(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_path(Path::new("results.json")), Ok(ExportFormat::Json));
+        assert_eq!(
+            ExportFormat::from_path(Path::new("results.txt")).err().unwrap(),
+            "results.txt: export files must end in .csv or .json"
+        );
+        assert_eq!(csv_path(Path::new("out/results.csv"), "bets"), PathBuf::from("out/results-bets.csv"));
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        #[derive(serde::Serialize)]
+        struct Row {
+            name: &'static str,
+            value: Option<f64>,
+        }
+        const COLUMNS: [&str; 2] = ["name", "value"];
+        let table = ExportTable::new("rows", &COLUMNS, &[Row { name: "a, \"b\"", value: None }, Row { name: "c", value: Some(0.5) }]);
+
+        assert_eq!(table.to_csv(), "format_version,name,value\n1,\"a, \"\"b\"\"\",\n1,c,0.5\n");
+    }
+
+    #[test]
+    fn test_simulation_export() {
+        let config = SimulationConfig::parse(&args("hands 5000 player deviations threads 2 seed 3")).unwrap();
+        let result = simulate(&config);
+        let export = simulation_export(&config, &result);
+
+        let names: Vec<&str> = export.tables.iter().map(|table| table.name).collect();
+        assert_eq!(names, ["summary", "true_counts", "bets", "side_bets", "side_bet_true_counts"]);
+        let summary = &export.tables[0].rows[0];
+        assert_eq!(summary["rounds"], 5000);
+        assert_eq!(summary["decks"], 6);
+        assert_eq!(summary["count_system"], "Hi-Lo");
+        // Every field of the summary has a column, in a stable order
+        assert_eq!(summary.as_object().unwrap().len(), SUMMARY_COLUMNS.len());
+
+        let counted: u64 = export.tables[1].rows.iter().map(|row| row["rounds"].as_u64().unwrap()).sum();
+        let bet: u64 = export.tables[2].rows.iter().map(|row| row["rounds"].as_u64().unwrap()).sum();
+        assert_eq!((counted, bet), (5000, 5000));
+        assert!(export.tables[2].rows.len() > 1);
+
+        let csv = export.tables[0].to_csv();
+        assert!(csv.starts_with("format_version,rules,decks,dealer_hits_soft_17,"));
+        assert!(csv.lines().nth(1).unwrap().starts_with("1,\"6 decks, S17, DAS, BJ pays 3:2, 75% penetration\",6,false,true,false,1.5,0.75,"));
+
+        let json: Value = serde_json::from_str(&export.to_json()).unwrap();
+        assert_eq!((json["format_version"].as_u64(), json["kind"].as_str()), (Some(1), Some("simulation")));
+        assert_eq!(json["bets"], Value::Array(export.tables[2].rows.clone()));
+    }
+
+    #[test]
+    fn test_sessions_export() {
+        let export = sessions_export(&[session()]);
+        let decisions = &export.tables[0];
+        let cells = &export.tables[1];
+
+        assert_eq!(decisions.rows.len(), 4);
+        assert_eq!(decisions.to_csv().lines().last().unwrap(), "1,1760000000,train,2,0,10H 6H 5H,10H,,,,hit,,true");
+        assert_eq!(decisions.rows[0].as_object().unwrap().len(), DECISION_COLUMNS.len());
+        assert_eq!(
+            decisions.to_csv().lines().nth(2).unwrap(),
+            "1,1760000000,train,1,0,10H 2H,2H,hard,12,2,stand,hit,false"
+        );
+
+        assert_eq!(cells.rows.len(), 2);
+        assert_eq!(cells.rows[0].as_object().unwrap().len(), CELL_COLUMNS.len());
+        // Chart order puts hard 12 before hard 16
+        assert_eq!((cells.rows[0]["row"].as_str(), cells.rows[0]["accuracy"].as_f64()), (Some("12"), Some(0.0)));
+        assert!(cells.rows[0]["ev_lost"].as_f64().unwrap() > 0.0);
+        assert_eq!((cells.rows[1]["attempts"].as_u64(), cells.rows[1]["correct"].as_u64()), (Some(2), Some(2)));
+    }
+
+    #[test]
+    fn test_stats_export() {
+        let mut stats = SessionStats::new();
+        stats.record(ChartCell { category: HandCategory::Pair(1), dealer_card: 1 }, true, 0.0);
+        stats.record(ChartCell { category: HandCategory::Pair(1), dealer_card: 1 }, false, 0.25);
+
+        let export = stats_export(&stats);
+        assert_eq!(export.kind, "stats");
+        assert_eq!(export.tables[0].to_csv(), "format_version,table,row,dealer,attempts,correct,accuracy,ev_lost\n1,pair,A,A,2,1,0.5,0.25\n");
+    }
+}
@@ -0,0 +1,211 @@
+use crate::blackjack::Card;
+use crate::blackjackbasicstrategy::{
+    BlackjackBasicStrategy, ChartCell, HandCategory, DOUBLE_OR_HIT, DOUBLE_OR_STAND, HIT, SPLIT, STAND, SURRENDER_OR_HIT,
+    SURRENDER_OR_STAND,
+};
+use crate::chart::{dealer_label, row_name};
+use crate::drill::{drill_cells, scenario};
+use rand::prelude::*;
+use std::fmt;
+
+/**
+ * An answer to a flash card: hit, stand, double, split or surrender
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Answer {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+}
+impl Answer {
+    /**
+     * Returns the answer for a chart play. A flash card is always a two card hand, so
+     * both kinds of double are answered with double, and both kinds of surrender with surrender
+     * where the rules offer it.
+     * @param play the chart play
+     * @param surrender true if the rules offer late surrender
+     */
+    pub fn for_play(play: i32, surrender: bool) -> Option<Answer> {
+        match play {
+            HIT => Some(Answer::Hit),
+            STAND => Some(Answer::Stand),
+            DOUBLE_OR_HIT | DOUBLE_OR_STAND => Some(Answer::Double),
+            SPLIT => Some(Answer::Split),
+            SURRENDER_OR_HIT | SURRENDER_OR_STAND if surrender => Some(Answer::Surrender),
+            SURRENDER_OR_HIT => Some(Answer::Hit),
+            SURRENDER_OR_STAND => Some(Answer::Stand),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Answer::Hit => "hit",
+            Answer::Stand => "stand",
+            Answer::Double => "double",
+            Answer::Split => "split",
+            Answer::Surrender => "surrender",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ * Restricts the flash card deck to one table and/or one dealer upcard
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Filter {
+    // "hard", "soft" or "pair"
+    pub table: Option<&'static str>,
+    pub dealer_card: Option<i32>,
+}
+impl Filter {
+    /**
+     * Parses filter arguments: any of hard, soft or pair(s), and an upcard given as vs N (or vs A)
+     * Ex: ["soft", "vs", "6"]
+     */
+    pub fn parse(args: &[String]) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.to_lowercase().as_str() {
+                "hard" => filter.table = Some("hard"),
+                "soft" => filter.table = Some("soft"),
+                "pair" | "pairs" => filter.table = Some("pair"),
+                "vs" => {
+                    let card = args.next().ok_or("vs needs a dealer upcard")?;
+                    filter.dealer_card = match card.to_uppercase().as_str() {
+                        "A" | "1" | "11" => Some(1),
+                        other => match other.parse::<i32>() {
+                            Ok(value) if (2..=10).contains(&value) => Some(value),
+                            _ => return Err(format!("invalid dealer upcard {}", card)),
+                        },
+                    };
+                }
+                _ => return Err(format!("unknown filter {}", arg)),
+            }
+        }
+        Ok(filter)
+    }
+
+    /**
+     * Returns true if a cell is in the filtered deck
+     */
+    pub fn matches(&self, cell: &ChartCell) -> bool {
+        let table = match cell.category {
+            HandCategory::Hard(_) => "hard",
+            HandCategory::Soft(_) => "soft",
+            HandCategory::Pair(_) => "pair",
+        };
+        self.table.is_none_or(|wanted| wanted == table) && self.dealer_card.is_none_or(|wanted| wanted == cell.dealer_card)
+    }
+}
+
+/**
+ * A player hand and dealer upcard to answer
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlashCard {
+    pub cell: ChartCell,
+    pub player: [Card; 2],
+    pub upcard: Card,
+}
+impl fmt::Display for FlashCard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}  vs  {}", self.player[0], self.player[1], self.upcard)
+    }
+}
+
+/**
+ * The grade of an answer to a flash card
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grade {
+    pub correct: bool,
+    // The chart play for the card
+    pub play: i32,
+    pub expected: Answer,
+}
+
+/**
+ * A deck of flash cards dealt at random from the cells matching a filter
+ */
+pub struct FlashCardDeck {
+    cells: Vec<ChartCell>,
+}
+impl FlashCardDeck {
+    /**
+     * @param filter the cells to include
+     * @return the deck, or an error if no cell matches the filter
+     */
+    pub fn new(filter: &Filter) -> Result<Self, String> {
+        let cells: Vec<ChartCell> = drill_cells().into_iter().filter(|cell| filter.matches(cell)).collect();
+        if cells.is_empty() {
+            return Err("no flash cards match the filter".to_string());
+        }
+        Ok(FlashCardDeck { cells })
+    }
+
+    /**
+     * Returns the number of different cells in this deck
+     */
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /**
+     * Deals a random flash card
+     */
+    pub fn deal(&self, rng: &mut impl Rng) -> FlashCard {
+        let cell = *self.cells.choose(rng).expect("deck is not empty");
+        let mut cards = scenario(cell, rng).into_iter();
+        let mut next = || cards.next().expect("scenario has four cards");
+
+        FlashCard {
+            cell,
+            player: [next(), next()],
+            upcard: next(),
+        }
+    }
+}
+
+/**
+ * Grades an answer to a flash card against a strategy, with surrender correct only where
+ * the strategy's rules offer it
+ * @param strategy the strategy to grade against
+ * @param card the flash card
+ * @param answer the trainee's answer
+ */
+pub fn grade(strategy: &BlackjackBasicStrategy, card: &FlashCard, answer: Answer) -> Grade {
+    let play = strategy
+        .get_play(card.cell.category, card.cell.dealer_card)
+        .expect("flash cards are dealt from chart cells");
+    let expected = Answer::for_play(play, strategy.get_rules().surrender).expect("chart plays are answers");
+
+    Grade {
+        correct: answer == expected,
+        play,
+        expected,
+    }
+}
+
+/**
+ * Returns the feedback shown after an answer
+ * Ex: Wrong: hard 16 vs 10 is hit, not stand.
+ */
+pub fn feedback(card: &FlashCard, answer: Answer, grade: &Grade) -> String {
+    let cell = format!("{} vs {}", row_name(card.cell.category), dealer_label(card.cell.dealer_card));
+    if grade.correct {
+        format!("Correct: {} is {}.", cell, grade.expected)
+    } else {
+        format!("Wrong: {} is {}, not {}.", cell, grade.expected, answer)
+    }
+}
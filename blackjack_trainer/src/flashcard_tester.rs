@@ -0,0 +1,84 @@
+use crate::blackjack::Rules;
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory, SURRENDER_OR_HIT};
+use crate::flashcard::{feedback, grade, Answer, FlashCardDeck, Filter};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(Filter::parse(&[]).unwrap(), Filter::default());
+        assert_eq!(
+            Filter::parse(&args("soft vs A")).unwrap(),
+            Filter { table: Some("soft"), dealer_card: Some(1) }
+        );
+        assert_eq!(Filter::parse(&args("vs 6 pairs")).unwrap().table, Some("pair"));
+        assert_eq!(Filter::parse(&args("vs 12")).err().unwrap(), "invalid dealer upcard 12");
+        assert_eq!(Filter::parse(&args("vs")).err().unwrap(), "vs needs a dealer upcard");
+        assert_eq!(Filter::parse(&args("easy")).err().unwrap(), "unknown filter easy");
+    }
+
+    #[test]
+    fn test_filtered_deck_only_deals_matching_cards() {
+        let filter = Filter::parse(&args("pair vs 7")).unwrap();
+        let deck = FlashCardDeck::new(&filter).unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+
+        assert_eq!(deck.len(), 10);
+        for _ in 0..50 {
+            let card = deck.deal(&mut rng);
+            assert!(matches!(card.cell.category, HandCategory::Pair(_)));
+            assert_eq!(card.upcard.get_value(), 7);
+            assert_eq!(card.player[0].get_value(), card.player[1].get_value());
+        }
+    }
+
+    #[test]
+    fn test_grading() {
+        let strategy = BlackjackBasicStrategy::new();
+        let deck = FlashCardDeck::new(&Filter::parse(&args("hard vs 6")).unwrap()).unwrap();
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut card = deck.deal(&mut rng);
+
+        card.cell = ChartCell { category: HandCategory::Hard(11), dealer_card: 6 };
+        let result = grade(&strategy, &card, Answer::Double);
+        assert!(result.correct);
+        assert_eq!(feedback(&card, Answer::Double, &result), "Correct: hard 11 vs 6 is double.");
+
+        let result = grade(&strategy, &card, Answer::Hit);
+        assert!(!result.correct);
+        assert_eq!(feedback(&card, Answer::Hit, &result), "Wrong: hard 11 vs 6 is double, not hit.");
+    }
+
+    #[test]
+    fn test_grading_surrender() {
+        let strategy = BlackjackBasicStrategy::for_rules(&Rules { surrender: true, ..Rules::default() });
+        let deck = FlashCardDeck::new(&Filter::parse(&args("hard vs 10")).unwrap()).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut card = deck.deal(&mut rng);
+
+        card.cell = ChartCell { category: HandCategory::Hard(16), dealer_card: 10 };
+        let result = grade(&strategy, &card, Answer::Surrender);
+        assert!(result.correct);
+        assert_eq!(result.play, SURRENDER_OR_HIT);
+        assert_eq!(feedback(&card, Answer::Surrender, &result), "Correct: hard 16 vs 10 is surrender.");
+
+        let result = grade(&strategy, &card, Answer::Hit);
+        assert_eq!(feedback(&card, Answer::Hit, &result), "Wrong: hard 16 vs 10 is surrender, not hit.");
+
+        card.cell = ChartCell { category: HandCategory::Hard(14), dealer_card: 10 };
+        let result = grade(&strategy, &card, Answer::Surrender);
+        assert_eq!(feedback(&card, Answer::Surrender, &result), "Wrong: hard 14 vs 10 is hit, not surrender.");
+
+        // A surrender cell read under rules without surrender is played out instead
+        assert_eq!(Answer::for_play(SURRENDER_OR_HIT, false), Some(Answer::Hit));
+        assert!(!grade(&BlackjackBasicStrategy::new(), &card, Answer::Surrender).correct);
+    }
+}
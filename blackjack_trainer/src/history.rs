@@ -0,0 +1,338 @@
+use crate::blackjack::{Action, Blackjack, Card, Hand, HandOutcome, Rules};
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/**
+ * The folder of the data directory that holds a file for each session, named after the time
+ * the session started, e.g. history/1760000000.jsonl
+ */
+pub const HISTORY_DIR: &str = "history";
+
+/**
+ * The version of the hand history format written by this build
+ */
+pub const HISTORY_FORMAT: u32 = 1;
+
+/**
+ * The first line of a session's history
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub format: u32,
+    // The time the session started, in seconds since the Unix epoch
+    pub started: u64,
+    // The seed the shoe was shuffled from
+    pub seed: u64,
+    // How the table was played, e.g. play, train or drill
+    pub mode: String,
+    pub rules: Rules,
+}
+
+/**
+ * A decision the player made on a hand
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionRecord {
+    // The index of the hand, more than 0 after a split
+    pub hand: usize,
+    // The hand's cards when the decision was made
+    pub cards: Vec<Card>,
+    pub upcard: Card,
+    pub action: Action,
+    pub recommended: Option<Action>,
+    pub matched: bool,
+}
+
+/**
+ * A player hand at the end of a round
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandRecord {
+    pub cards: Vec<Card>,
+    pub doubled: bool,
+    pub outcome: HandOutcome,
+    pub net: f64,
+}
+
+/**
+ * A round played at the table
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoundRecord {
+    // The round number within the session, counting from 1
+    pub round: u32,
+    pub shoe: u64,
+    pub position: usize,
+    pub bet: f64,
+    pub dealt: Vec<Card>,
+    pub actions: Vec<ActionRecord>,
+    pub hands: Vec<HandRecord>,
+    pub dealer: Vec<Card>,
+    // The amount won or lost on insurance, 0 when it was not taken
+    #[serde(default)]
+    pub insurance: f64,
+    pub net: f64,
+}
+impl RoundRecord {
+    /**
+     * Records a finished round
+     * @param game the game, after the dealer's hand has been played
+     * @param round the round number
+     * @param position the shoe number and position before the round was dealt
+     * @param bet the initial bet
+     * @param actions the decisions made during the round
+     */
+    pub fn from_game(game: &Blackjack, round: u32, position: (u64, usize), bet: f64, actions: Vec<ActionRecord>) -> Self {
+        let hands: Vec<HandRecord> = game
+            .get_players_hands()
+            .iter()
+            .enumerate()
+            .map(|(index, hand)| HandRecord {
+                cards: hand.get_cards().to_vec(),
+                doubled: hand.is_doubled(),
+                outcome: game.outcome(index),
+                net: bet * game.net_result(index),
+            })
+            .collect();
+        let insurance = bet * game.insurance_result();
+
+        RoundRecord {
+            round,
+            shoe: position.0,
+            position: position.1,
+            bet,
+            dealt: game.get_dealt_cards().to_vec(),
+            actions,
+            net: hands.iter().map(|hand| hand.net).sum::<f64>() + insurance,
+            insurance,
+            hands,
+            dealer: game.get_dealers_hand().map(|hand| hand.get_cards().to_vec()).unwrap_or_default(),
+        }
+    }
+}
+
+/**
+ * A line of a history file. The first line of a file describes the session and every following line is a round:
+ *
+ *   {"type":"session","format":1,"started":1760000000,"seed":42,"mode":"train","rules":{"decks":6,...}}
+ *   {"type":"round","round":1,"shoe":1,"position":0,"bet":1.0,
+ *    "dealt":["10H","6S","9C","7D","5H"],
+ *    "actions":[{"hand":0,"cards":["10H","6S"],"upcard":"9C","action":"hit","recommended":"hit","matched":true}],
+ *    "hands":[{"cards":["10H","6S","5H"],"doubled":false,"outcome":"win","net":1.0}],
+ *    "dealer":["9C","7D"],"net":1.0}
+ *
+ * (each record is written on a single line). Cards are written as they are displayed: AS, 10H, QD.
+ * shoe is the shuffle the round was dealt from, counting from 1, and position the number of cards dealt
+ * from it before the round, so a seeded shoe can be dealt again from the same place. dealt lists every
+ * card in the order it left the shoe. Actions and outcomes are lowercase names, and recommended is null
+ * when basic strategy has no play for the hand. Fields are only added within a format version;
+ * a change to an existing field increments format.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HistoryRecord {
+    Session(SessionRecord),
+    Round(RoundRecord),
+}
+
+/**
+ * A session read back from its history file
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionHistory {
+    pub session: SessionRecord,
+    pub rounds: Vec<RoundRecord>,
+}
+impl SessionHistory {
+    /**
+     * Parses a history file. Blank lines are skipped; a session from a newer format is rejected.
+     */
+    pub fn parse(text: &str) -> Result<SessionHistory, String> {
+        let mut session = None;
+        let mut rounds = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: HistoryRecord =
+                serde_json::from_str(line).map_err(|err| format!("line {}: {}", number + 1, err))?;
+            match record {
+                HistoryRecord::Session(record) if session.is_none() => {
+                    if record.format > HISTORY_FORMAT {
+                        return Err(format!("history format {} is newer than this version reads", record.format));
+                    }
+                    session = Some(record);
+                }
+                HistoryRecord::Session(_) => return Err(format!("line {}: second session record", number + 1)),
+                HistoryRecord::Round(_) if session.is_none() => {
+                    return Err(format!("line {}: round before the session record", number + 1));
+                }
+                HistoryRecord::Round(round) => rounds.push(round),
+            }
+        }
+
+        let session = session.ok_or("no session record")?;
+        Ok(SessionHistory { session, rounds })
+    }
+
+    /**
+     * Reads a history file
+     */
+    pub fn load(path: &Path) -> Result<SessionHistory, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        SessionHistory::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+/**
+ * Appends a session's rounds to its history file as they are played
+ */
+pub struct HistoryWriter {
+    file: File,
+    path: PathBuf,
+}
+impl HistoryWriter {
+    /**
+     * Creates the history file of a new session and writes the session record
+     */
+    pub fn create(session: &SessionRecord) -> io::Result<HistoryWriter> {
+        let dir = storage::data_path(HISTORY_DIR);
+        fs::create_dir_all(&dir)?;
+
+        // Sessions started in the same second get a suffix
+        let mut path = dir.join(format!("{}.jsonl", session.started));
+        let mut suffix = 1;
+        while path.exists() {
+            suffix += 1;
+            path = dir.join(format!("{}-{}.jsonl", session.started, suffix));
+        }
+
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        let mut writer = HistoryWriter { file, path };
+        writer.write(&HistoryRecord::Session(session.clone()))?;
+        Ok(writer)
+    }
+
+    pub fn write_round(&mut self, round: &RoundRecord) -> io::Result<()> {
+        self.write(&HistoryRecord::Round(round.clone()))
+    }
+
+    fn write(&mut self, record: &HistoryRecord) -> io::Result<()> {
+        let line = serde_json::to_string(record).map_err(io::Error::other)?;
+        writeln!(self.file, "{}", line)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/**
+ * Returns the history files of every session, oldest first
+ */
+pub fn session_files() -> io::Result<Vec<PathBuf>> {
+    let dir = storage::data_path(HISTORY_DIR);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "jsonl") {
+            files.push(path);
+        }
+    }
+    // Names start with the session's start time, so sorting by name gives the order the sessions were played
+    files.sort_by_key(|path| {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let (started, suffix) = stem.split_once('-').unwrap_or((&stem, "1"));
+        (started.parse::<u64>().unwrap_or(0), suffix.parse::<u32>().unwrap_or(0))
+    });
+    Ok(files)
+}
+
+// Formats cards as a hand with its total, or as a list if there are too few for a hand
+fn describe(cards: &[Card]) -> String {
+    match cards {
+        [first, second, rest @ ..] => {
+            let mut hand = Hand::new(*first, *second);
+            for &card in rest {
+                hand.add_card(card);
+            }
+            hand.to_string()
+        }
+        _ => cards.iter().map(ToString::to_string).collect::<Vec<_>>().join(" "),
+    }
+}
+
+/**
+ * Renders a round for replay: the deal, each decision against basic strategy and the result of each hand
+ */
+pub fn render_round(round: &RoundRecord) -> String {
+    let mut out = format!(
+        "Round {}: shoe {}, card {}, bet {}\n",
+        round.round, round.shoe, round.position + 1, round.bet
+    );
+    out.push_str(&format!(
+        "Dealt: {}\n",
+        round.dealt.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ")
+    ));
+    if let Some(upcard) = round.dealer.first() {
+        out.push_str(&format!("Dealer shows: {}\n", upcard));
+    }
+
+    for action in &round.actions {
+        let label = if round.hands.len() > 1 { format!("Hand {}: ", action.hand + 1) } else { String::new() };
+        let verdict = match action.recommended {
+            Some(recommended) if action.matched => format!("matches basic strategy ({})", recommended),
+            Some(recommended) => format!("basic strategy says {}", recommended),
+            None => "no basic strategy play".to_string(),
+        };
+        out.push_str(&format!("  {}{}: {}, {}\n", label, describe(&action.cards), action.action, verdict));
+    }
+
+    out.push_str(&format!("Dealer has: {}\n", describe(&round.dealer)));
+    for (index, hand) in round.hands.iter().enumerate() {
+        let label = if round.hands.len() > 1 { format!("Hand {}", index + 1) } else { "You".to_string() };
+        let doubled = if hand.doubled { ", doubled" } else { "" };
+        out.push_str(&format!(
+            "{}: {}{} - {:?} {:+}\n",
+            label,
+            describe(&hand.cards),
+            doubled,
+            hand.outcome,
+            hand.net
+        ));
+    }
+    out.push_str(&format!("Net: {:+}\n", round.net));
+    out
+}
+
+/**
+ * Renders a summary of a session: when it was played, under which rules, and how it went
+ */
+pub fn render_session(history: &SessionHistory) -> String {
+    let session = &history.session;
+    let decisions: Vec<&ActionRecord> = history.rounds.iter().flat_map(|round| &round.actions).collect();
+    let matched = decisions.iter().filter(|action| action.matched).count();
+    let net: f64 = history.rounds.iter().map(|round| round.net).sum();
+
+    format!(
+        "Session started {} ({} mode, seed {})\nRules: {}\n{} rounds, {} of {} decisions matched basic strategy, net {:+}\n",
+        session.started,
+        session.mode,
+        session.seed,
+        session.rules,
+        history.rounds.len(),
+        matched,
+        decisions.len(),
+        net
+    )
+}
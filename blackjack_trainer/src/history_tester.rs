@@ -0,0 +1,98 @@
+use crate::blackjack::{Action, Blackjack, Card, HandOutcome, Rules, Suit};
+use crate::history::{
+    render_round, render_session, ActionRecord, HistoryRecord, RoundRecord, SessionHistory, SessionRecord,
+    HISTORY_FORMAT,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(rank: i64) -> Card {
+        Card::new(Suit::Clubs, rank)
+    }
+
+    fn session() -> SessionRecord {
+        SessionRecord {
+            format: HISTORY_FORMAT,
+            started: 1_760_000_000,
+            seed: 42,
+            mode: "train".to_string(),
+            rules: Rules::default(),
+        }
+    }
+
+    // Plays 10, 6 against a 9 up: the player hits to 21 and the dealer stands on 16 + 2 = 18
+    fn played_round() -> RoundRecord {
+        let mut game = Blackjack::with_seed(Rules::default(), 3);
+        game.clear_table();
+        let position = game.shoe_position();
+        game.stack_cards(vec![card(10), card(6), card(9), card(7), card(5), card(2)]);
+        game.deal_cards();
+
+        let action = ActionRecord {
+            hand: 0,
+            cards: vec![card(10), card(6)],
+            upcard: card(9),
+            action: Action::Hit,
+            recommended: Some(Action::Hit),
+            matched: true,
+        };
+        game.act(Action::Hit);
+        game.play_dealers_hand();
+        RoundRecord::from_game(&game, 1, position, 2.0, vec![action])
+    }
+
+    #[test]
+    fn test_round_from_game() {
+        let round = played_round();
+
+        assert_eq!((round.shoe, round.position), (1, 0));
+        assert_eq!(round.dealt, vec![card(10), card(6), card(9), card(7), card(5), card(2)]);
+        assert_eq!(round.dealer, vec![card(9), card(7), card(2)]);
+        assert_eq!(round.hands[0].outcome, HandOutcome::Win);
+        assert_eq!((round.hands[0].net, round.net), (2.0, 2.0));
+    }
+
+    #[test]
+    fn test_records_round_trip() {
+        let text = format!(
+            "{}\n{}\n",
+            serde_json::to_string(&HistoryRecord::Session(session())).unwrap(),
+            serde_json::to_string(&HistoryRecord::Round(played_round())).unwrap()
+        );
+
+        assert!(text.starts_with("{\"type\":\"session\",\"format\":1,"));
+        assert!(text.contains("\"dealt\":[\"10C\",\"6C\",\"9C\",\"7C\",\"5C\",\"2C\"]"));
+        assert!(text.contains("\"action\":\"hit\",\"recommended\":\"hit\",\"matched\":true"));
+        assert!(text.contains("\"outcome\":\"win\""));
+
+        let history = SessionHistory::parse(&text).unwrap();
+        assert_eq!(history.session, session());
+        assert_eq!(history.rounds, vec![played_round()]);
+    }
+
+    #[test]
+    fn test_invalid_histories() {
+        let round = serde_json::to_string(&HistoryRecord::Round(played_round())).unwrap();
+        let newer = SessionRecord { format: HISTORY_FORMAT + 1, ..session() };
+        let newer = serde_json::to_string(&HistoryRecord::Session(newer)).unwrap();
+
+        assert_eq!(SessionHistory::parse("").err().unwrap(), "no session record");
+        assert_eq!(SessionHistory::parse(&round).err().unwrap(), "line 1: round before the session record");
+        assert_eq!(SessionHistory::parse(&newer).err().unwrap(), "history format 2 is newer than this version reads");
+        assert!(SessionHistory::parse("{\"type\":\"session\"}").err().unwrap().starts_with("line 1: missing field"));
+    }
+
+    #[test]
+    fn test_render_replay() {
+        let history = SessionHistory { session: session(), rounds: vec![played_round()] };
+        let round = render_round(&history.rounds[0]);
+
+        assert!(round.starts_with("Round 1: shoe 1, card 1, bet 2\nDealt: 10C 6C 9C 7C 5C 2C\nDealer shows: 9C\n"));
+        assert!(round.contains("  10C 6C (16): hit, matches basic strategy (hit)\n"));
+        assert!(round.contains("Dealer has: 9C 7C 2C (18)"));
+        assert!(round.ends_with("You: 10C 6C 5C (21) - Win +2\nNet: +2\n"));
+        assert!(render_session(&history).contains("1 rounds, 1 of 1 decisions matched basic strategy, net +2"));
+    }
+}
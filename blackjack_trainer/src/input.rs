@@ -0,0 +1,472 @@
+use crate::blackjack::Action;
+use crate::storage;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/**
+ * The file in the data directory the keymap is read from
+ */
+pub const KEYMAP_FILE: &str = "keymap.txt";
+
+/**
+ * What the player can ask for with a key
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+    Insurance,
+    Quit,
+}
+impl Input {
+    pub const ALL: [Input; 7] = [
+        Input::Hit,
+        Input::Stand,
+        Input::Double,
+        Input::Split,
+        Input::Surrender,
+        Input::Insurance,
+        Input::Quit,
+    ];
+
+    /**
+     * Returns the name of the input, as typed at a prompt and written in the keymap file
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Input::Hit => "hit",
+            Input::Stand => "stand",
+            Input::Double => "double",
+            Input::Split => "split",
+            Input::Surrender => "surrender",
+            Input::Insurance => "insurance",
+            Input::Quit => "quit",
+        }
+    }
+
+    /**
+     * Returns the action played on a hand, or None for insurance, which is offered before the hand is played,
+     * and for quit
+     */
+    pub fn action(&self) -> Option<Action> {
+        match self {
+            Input::Hit => Some(Action::Hit),
+            Input::Stand => Some(Action::Stand),
+            Input::Double => Some(Action::Double),
+            Input::Split => Some(Action::Split),
+            Input::Surrender => Some(Action::Surrender),
+            Input::Insurance | Input::Quit => None,
+        }
+    }
+
+    /**
+     * Returns the input for an action played at the table
+     */
+    pub fn for_action(action: Action) -> Input {
+        match action {
+            Action::Hit => Input::Hit,
+            Action::Stand => Input::Stand,
+            Action::Double => Input::Double,
+            Action::Split => Input::Split,
+            Action::Surrender => Input::Surrender,
+        }
+    }
+
+    fn index(&self) -> usize {
+        Input::ALL.iter().position(|input| input == self).expect("every input is listed")
+    }
+}
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/**
+ * How answers are typed: a single key press, or a line ended with Enter
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputMode {
+    Keys,
+    Line,
+}
+
+/**
+ * The key for each input, and whether a single key press is enough to answer
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [char; 7],
+    mode: InputMode,
+}
+impl Default for Keymap {
+    /**
+     * The first letter of each action (p for split, r for surrender), i for insurance and q to quit,
+     * answered with a single key press
+     */
+    fn default() -> Self {
+        Keymap { keys: ['h', 's', 'd', 'p', 'r', 'i', 'q'], mode: InputMode::Keys }
+    }
+}
+impl Keymap {
+    /**
+     * Parses a keymap file. Each line gives an input its key, and "input" chooses how answers are typed;
+     * inputs that are not listed keep their default key. Blank lines and lines starting with # are ignored.
+     * Ex: "hit = j\nstand = k\ninput = line"
+     */
+    pub fn parse(text: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let (name, value) = line.split_once('=').ok_or_else(|| error(format!("expected name = key, not {}", line)))?;
+            let (name, value) = (name.trim().to_lowercase(), value.trim());
+
+            if name == "input" {
+                keymap.mode = match value.to_lowercase().as_str() {
+                    "keys" => InputMode::Keys,
+                    "line" => InputMode::Line,
+                    _ => return Err(error(format!("input is keys or line, not {}", value))),
+                };
+                continue;
+            }
+            let input = Input::ALL.into_iter().find(|input| input.name() == name)
+                .ok_or_else(|| error(format!("unknown input {}", name)))?;
+            let mut chars = value.chars();
+            let key = match (chars.next(), chars.next()) {
+                (Some(key), None) if key.is_alphanumeric() || key.is_ascii_punctuation() => key.to_ascii_lowercase(),
+                _ => return Err(error(format!("the key for {} must be one letter, digit or symbol, not {}", input, value))),
+            };
+            keymap.keys[input.index()] = key;
+        }
+
+        // Checked once every line is read, so keys can be swapped
+        for input in Input::ALL {
+            if let Some(other) = Input::ALL.into_iter().find(|other| other.index() < input.index() && keymap.key(*other) == keymap.key(input)) {
+                return Err(format!("{} and {} both use the key {}", other, input, keymap.key(input)));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /**
+     * Reads the keymap from the data directory, or returns the default keymap if there is no keymap file
+     */
+    pub fn load() -> Result<Keymap, String> {
+        match storage::read_data_file(KEYMAP_FILE) {
+            Ok(Some(text)) => Keymap::parse(&text).map_err(|err| format!("{}: {}", KEYMAP_FILE, err)),
+            Ok(None) => Ok(Keymap::default()),
+            Err(err) => Err(format!("Could not read {}: {}", KEYMAP_FILE, err)),
+        }
+    }
+
+    /**
+     * Returns the keymap in the keymap file format
+     */
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for input in Input::ALL {
+            text.push_str(&format!("{} = {}\n", input, self.key(input)));
+        }
+        let mode = if self.mode == InputMode::Keys { "keys" } else { "line" };
+        text.push_str(&format!("input = {}\n", mode));
+        text
+    }
+
+    pub fn key(&self, input: Input) -> char {
+        self.keys[input.index()]
+    }
+
+    pub fn get_mode(&self) -> InputMode {
+        self.mode
+    }
+
+    /**
+     * Returns the input a key press asks for, ignoring case
+     */
+    pub fn lookup_key(&self, key: char) -> Option<Input> {
+        let key = key.to_ascii_lowercase();
+        Input::ALL.into_iter().find(|&input| self.key(input) == key)
+    }
+
+    /**
+     * Parses a typed answer: an input's key, its name, or enough of its name to tell it apart, ignoring case
+     * Ex: "H", "hit", "St" and "SUR" with the default keys
+     */
+    pub fn parse_line(&self, text: &str) -> Option<Input> {
+        let text = text.trim().to_lowercase();
+        let mut chars = text.chars();
+        if let (Some(key), None) = (chars.next(), chars.next()) {
+            return self.lookup_key(key);
+        }
+        if text.is_empty() {
+            return None;
+        }
+
+        let matches: Vec<Input> = Input::ALL.into_iter().filter(|input| input.name().starts_with(&text)).collect();
+        match matches.as_slice() {
+            [input] => Some(*input),
+            _ => None,
+        }
+    }
+
+    /**
+     * Lists inputs with their keys for a prompt
+     * Ex: hit (h), stand (s) or double (d)
+     */
+    pub fn describe(&self, inputs: &[Input]) -> String {
+        let names: Vec<String> = inputs.iter().map(|&input| format!("{} ({})", input, self.key(input))).collect();
+
+        match names.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+            _ => names.join(""),
+        }
+    }
+}
+
+/**
+ * Parses the answer to a yes or no question, ignoring case. Enter on its own is yes.
+ */
+pub fn parse_yes_no(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "" | "y" | "yes" => Some(true),
+        "n" | "no" | "q" | "quit" => Some(false),
+        _ => None,
+    }
+}
+
+// A key pressed in raw mode
+enum Keystroke {
+    Char(char),
+    Enter,
+    Escape,
+}
+
+// Waits for a key press with the terminal in raw mode, so it is read without Enter
+fn read_keystroke() -> io::Result<Keystroke> {
+    terminal::enable_raw_mode()?;
+    let keystroke = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Ok(Keystroke::Escape),
+                KeyCode::Char(key) => break Ok(Keystroke::Char(key)),
+                KeyCode::Enter => break Ok(Keystroke::Enter),
+                KeyCode::Esc => break Ok(Keystroke::Escape),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    keystroke
+}
+
+/**
+ * Reads the player's answers, a single key press at a time when the terminal allows it
+ * and a line at a time otherwise
+ */
+pub struct Prompter {
+    keymap: Keymap,
+    // True while answers are read a key press at a time
+    raw: bool,
+    // Where lines are read from when they are not read from the terminal
+    reader: Option<Box<dyn BufRead>>,
+}
+impl Prompter {
+    /**
+     * Reads answers from the terminal, a key press at a time if the keymap asks for it and
+     * stdin and stdout are a terminal
+     */
+    pub fn new(keymap: Keymap) -> Self {
+        let raw = keymap.mode == InputMode::Keys && io::stdin().is_terminal() && io::stdout().is_terminal();
+        Prompter { keymap, raw, reader: None }
+    }
+
+    /**
+     * Reads answers a line at a time from a reader, e.g. a script of answers
+     */
+    pub fn with_reader(keymap: Keymap, reader: Box<dyn BufRead>) -> Self {
+        Prompter { keymap, raw: false, reader: Some(reader) }
+    }
+
+    pub fn get_keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    // Reads a line, or returns None at the end of input
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        let read = match self.reader.as_mut() {
+            Some(reader) => reader.read_line(&mut line),
+            None => io::stdin().read_line(&mut line),
+        };
+        match read {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+
+    // Reads a key press, falling back to lines for good if the terminal cannot be put in raw mode
+    fn read_keystroke(&mut self) -> Option<Keystroke> {
+        io::stdout().flush().unwrap();
+        match read_keystroke() {
+            Ok(keystroke) => Some(keystroke),
+            Err(_) => {
+                let _ = terminal::disable_raw_mode();
+                self.raw = false;
+                None
+            }
+        }
+    }
+
+    /**
+     * Asks for one of the allowed inputs until one is given. Quit is always accepted, and
+     * is returned at the end of input.
+     * @param allowed the inputs that can be answered now, in the order they are offered
+     */
+    pub fn read_input(&mut self, allowed: &[Input]) -> Input {
+        let hint = format!("Press {}.", self.keymap.describe(&[allowed, &[Input::Quit]].concat()));
+        loop {
+            io::stdout().flush().unwrap();
+            let input = if self.raw {
+                match self.read_keystroke() {
+                    Some(Keystroke::Char(key)) => self.keymap.lookup_key(key),
+                    Some(Keystroke::Escape) => Some(Input::Quit),
+                    Some(Keystroke::Enter) => None,
+                    None => continue,
+                }
+            } else {
+                match self.read_line() {
+                    Some(line) => self.keymap.parse_line(&line),
+                    None => return Input::Quit,
+                }
+            };
+
+            match input {
+                Some(input) if input == Input::Quit || allowed.contains(&input) => {
+                    if self.raw {
+                        println!("{}", input);
+                    }
+                    return input;
+                }
+                Some(Input::Insurance) => println!("Insurance is not offered now. {}", hint),
+                Some(input) => println!("You cannot {} now. {}", input, hint),
+                None if self.raw => println!("{}", hint),
+                None => println!("{}", hint.replacen("Press", "Type", 1)),
+            }
+        }
+    }
+
+    /**
+     * Asks whether to take an offer, such as insurance: its key to take it, Enter or n to decline.
+     * Returns None if the player quits or the input ends.
+     * @param input the input that takes the offer
+     */
+    pub fn read_offer(&mut self, input: Input) -> Option<bool> {
+        let hint = format!("Press {} to take it, Enter to decline or {} to quit.", self.keymap.key(input), self.keymap.key(Input::Quit));
+        loop {
+            io::stdout().flush().unwrap();
+            let answer = if self.raw {
+                match self.read_keystroke() {
+                    Some(Keystroke::Char(key)) => match self.keymap.lookup_key(key) {
+                        Some(Input::Quit) => None,
+                        Some(pressed) if pressed == input => Some(true),
+                        _ if key.eq_ignore_ascii_case(&'n') => Some(false),
+                        _ => {
+                            println!("{}", hint);
+                            continue;
+                        }
+                    },
+                    Some(Keystroke::Enter) => Some(false),
+                    Some(Keystroke::Escape) => None,
+                    None => continue,
+                }
+            } else {
+                let line = self.read_line()?;
+                match self.keymap.parse_line(&line) {
+                    Some(Input::Quit) => None,
+                    Some(typed) if typed == input => Some(true),
+                    _ if matches!(line.trim().to_lowercase().as_str(), "" | "n" | "no") => Some(false),
+                    _ => {
+                        println!("{}", hint.replacen("Press", "Type", 1));
+                        continue;
+                    }
+                }
+            };
+
+            if self.raw {
+                match answer {
+                    Some(true) => println!("{}", input),
+                    Some(false) => println!("no {}", input),
+                    None => println!("{}", Input::Quit),
+                }
+            }
+            return answer;
+        }
+    }
+
+    /**
+     * Asks for a positive amount, a line at a time, until one is given. Enter on its own answers the default,
+     * and quit or the end of input returns None.
+     * @param default the amount Enter answers
+     */
+    pub fn read_amount(&mut self, default: f64) -> Option<f64> {
+        loop {
+            io::stdout().flush().unwrap();
+            let line = self.read_line()?;
+            let text = line.trim();
+            if text.is_empty() {
+                return Some(default);
+            }
+            if self.keymap.parse_line(text) == Some(Input::Quit) {
+                return None;
+            }
+            match text.parse::<f64>() {
+                Ok(amount) if amount > 0.0 && amount.is_finite() => return Some(amount),
+                _ => println!("Please type a positive amount, or {} to quit.", Input::Quit),
+            }
+        }
+    }
+
+    /**
+     * Asks whether to carry on: Enter or y to carry on, n, q or Esc to stop, or the end of input
+     */
+    pub fn read_continue(&mut self) -> bool {
+        loop {
+            io::stdout().flush().unwrap();
+            if self.raw {
+                match self.read_keystroke() {
+                    Some(Keystroke::Enter) => return true,
+                    Some(Keystroke::Escape) => return false,
+                    // Space counts as Enter
+                    Some(Keystroke::Char(key)) => {
+                        if let Some(answer) = parse_yes_no(&key.to_string()) {
+                            return answer;
+                        }
+                    }
+                    None => continue,
+                }
+            } else {
+                match self.read_line().map(|line| parse_yes_no(&line)) {
+                    Some(Some(answer)) => return answer,
+                    Some(None) => println!("Please answer yes or no."),
+                    None => return false,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::blackjack::Action;
+use crate::input::{parse_yes_no, Input, InputMode, Keymap, Prompter};
+use std::io::Cursor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompter(keymap: Keymap, script: &str) -> Prompter {
+        Prompter::with_reader(keymap, Box::new(Cursor::new(script.to_string())))
+    }
+
+    #[test]
+    fn test_default_keys() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.get_mode(), InputMode::Keys);
+        assert_eq!(keymap.lookup_key('h'), Some(Input::Hit));
+        assert_eq!(keymap.lookup_key('P'), Some(Input::Split));
+        assert_eq!(keymap.lookup_key('r'), Some(Input::Surrender));
+        assert_eq!(keymap.lookup_key('i'), Some(Input::Insurance));
+        assert_eq!(keymap.lookup_key('q'), Some(Input::Quit));
+        assert_eq!(keymap.lookup_key('x'), None);
+        assert_eq!(Input::Double.action(), Some(Action::Double));
+        assert_eq!(Input::Surrender.action(), Some(Action::Surrender));
+        assert_eq!(Input::for_action(Action::Surrender), Input::Surrender);
+        assert_eq!(Input::Insurance.action(), None);
+        assert_eq!(Input::Quit.action(), None);
+    }
+
+    #[test]
+    fn test_abbreviations_and_case() {
+        let keymap = Keymap::default();
+        for (text, input) in [
+            ("hit", Input::Hit),
+            ("HIT", Input::Hit),
+            ("  H\n", Input::Hit),
+            ("St", Input::Stand),
+            ("dou", Input::Double),
+            ("SP", Input::Split),
+            ("sur", Input::Surrender),
+            ("ins", Input::Insurance),
+            ("Quit", Input::Quit),
+        ] {
+            assert_eq!(keymap.parse_line(text), Some(input), "{}", text);
+        }
+        // "s" is the key for stand, but "su" alone could still only be surrender
+        assert_eq!(keymap.parse_line("s"), Some(Input::Stand));
+        assert_eq!(keymap.parse_line("su"), Some(Input::Surrender));
+        assert_eq!(keymap.parse_line(""), None);
+        assert_eq!(keymap.parse_line("hits"), None);
+        assert_eq!(keymap.parse_line("x"), None);
+    }
+
+    #[test]
+    fn test_keymap_file() {
+        let keymap = Keymap::parse("# vi keys\nhit = j\nstand = K\n\ninput = line\n").unwrap();
+        assert_eq!(keymap.key(Input::Hit), 'j');
+        assert_eq!(keymap.key(Input::Stand), 'k');
+        assert_eq!(keymap.key(Input::Double), 'd');
+        assert_eq!(keymap.get_mode(), InputMode::Line);
+        assert_eq!(keymap.lookup_key('h'), None);
+        assert_eq!(keymap.parse_line("J"), Some(Input::Hit));
+        assert_eq!(keymap.parse_line("hit"), Some(Input::Hit));
+        assert_eq!(keymap.describe(&[Input::Hit, Input::Stand, Input::Double]), "hit (j), stand (k) or double (d)");
+
+        assert_eq!(Keymap::parse(&keymap.to_text()), Ok(keymap));
+        // Keys can be swapped, as duplicates are only checked at the end
+        let swapped = Keymap::parse("hit = s\nstand = h").unwrap();
+        assert_eq!(swapped.lookup_key('s'), Some(Input::Hit));
+    }
+
+    #[test]
+    fn test_keymap_errors() {
+        assert_eq!(Keymap::parse("hit = s"), Err("hit and stand both use the key s".to_string()));
+        assert_eq!(Keymap::parse("hit j"), Err("line 1: expected name = key, not hit j".to_string()));
+        assert_eq!(Keymap::parse("\nfold = f"), Err("line 2: unknown input fold".to_string()));
+        assert!(Keymap::parse("hit = jj").unwrap_err().starts_with("line 1: the key for hit"));
+        assert!(Keymap::parse("hit =").is_err());
+        assert!(Keymap::parse("input = mouse").is_err());
+    }
+
+    #[test]
+    fn test_yes_no() {
+        for text in ["", "\n", "y", "Yes", "YES"] {
+            assert_eq!(parse_yes_no(text), Some(true), "{:?}", text);
+        }
+        for text in ["n", "No", "q", "QUIT"] {
+            assert_eq!(parse_yes_no(text), Some(false), "{:?}", text);
+        }
+        assert_eq!(parse_yes_no("maybe"), None);
+    }
+
+    #[test]
+    fn test_scripted_answers() {
+        let legal = [Input::Hit, Input::Stand];
+        // Unknown answers and illegal inputs are asked again
+        let mut answers = prompter(Keymap::default(), "x\ndouble\nSTA\nh\n");
+        assert!(!answers.is_raw());
+        assert_eq!(answers.read_input(&legal), Input::Stand);
+        assert_eq!(answers.read_input(&legal), Input::Hit);
+        // The end of input quits
+        assert_eq!(answers.read_input(&legal), Input::Quit);
+
+        let mut answers = prompter(Keymap::default(), "q\n");
+        assert_eq!(answers.read_input(&legal), Input::Quit);
+
+        let mut answers = prompter(Keymap::default(), "maybe\nY\n\nno\n");
+        assert!(answers.read_continue());
+        assert!(answers.read_continue());
+        assert!(!answers.read_continue());
+        assert!(!answers.read_continue());
+    }
+
+    #[test]
+    fn test_scripted_offers() {
+        // Insurance is taken with its key or name, declined with Enter or no, and other answers are asked again
+        let mut answers = prompter(Keymap::default(), "i
+hit
+
+INS
+no
+q
+");
+        assert_eq!(answers.read_offer(Input::Insurance), Some(true));
+        assert_eq!(answers.read_offer(Input::Insurance), Some(false));
+        assert_eq!(answers.read_offer(Input::Insurance), Some(true));
+        assert_eq!(answers.read_offer(Input::Insurance), Some(false));
+        assert_eq!(answers.read_offer(Input::Insurance), None);
+        // The end of input quits
+        assert_eq!(answers.read_offer(Input::Insurance), None);
+    }
+}
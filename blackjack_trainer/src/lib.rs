@@ -1,3 +1,69 @@
+pub mod bankroll;
 pub mod blackjack;
 pub mod blackjackbasicstrategy;
-pub mod ratatui_refactor;
+pub mod cardstyle;
+pub mod chart;
+pub mod cli;
+pub mod client;
+pub mod counting;
+pub mod countingsystem;
+pub mod drill;
+pub mod ev;
+pub mod export;
+pub mod flashcard;
+pub mod history;
+pub mod input;
+pub mod playerstrategy;
+pub mod protocol;
+pub mod server;
+pub mod sidebet;
+pub mod simulation;
+pub mod stats;
+pub mod storage;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+#[cfg(test)]
+mod bankroll_tester;
+#[cfg(test)]
+mod blackjack_tester;
+#[cfg(test)]
+mod blackjackbasicstrategy_tester;
+#[cfg(test)]
+mod cardstyle_tester;
+#[cfg(test)]
+mod chart_tester;
+#[cfg(test)]
+mod cli_tester;
+#[cfg(test)]
+mod client_tester;
+#[cfg(test)]
+mod counting_tester;
+#[cfg(test)]
+mod countingsystem_tester;
+#[cfg(test)]
+mod drill_tester;
+#[cfg(test)]
+mod ev_tester;
+#[cfg(test)]
+mod export_tester;
+#[cfg(test)]
+mod flashcard_tester;
+#[cfg(test)]
+mod history_tester;
+#[cfg(test)]
+mod input_tester;
+#[cfg(test)]
+mod playerstrategy_tester;
+#[cfg(test)]
+mod protocol_tester;
+#[cfg(test)]
+mod server_tester;
+#[cfg(test)]
+mod sidebet_tester;
+#[cfg(test)]
+mod simulation_tester;
+#[cfg(test)]
+mod stats_tester;
+#[cfg(all(test, feature = "tui"))]
+mod tui_tester;
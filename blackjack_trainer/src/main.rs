@@ -1,103 +1,1065 @@
-mod blackjack;
-mod blackjackbasicstrategy;
-
-use crate::blackjack::Blackjack;
+use blackjack_trainer::bankroll::{self, RiskConfig};
+use blackjack_trainer::blackjack::{Action, Blackjack, HandOutcome, Rules};
+use blackjack_trainer::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell};
+use blackjack_trainer::cardstyle::CardRenderer;
+use blackjack_trainer::chart;
+use blackjack_trainer::cli::{self, Cli, Command, TableFlags};
+use blackjack_trainer::client::{self, JoinConfig};
+use blackjack_trainer::counting::{self, DrillConfig, TrueCountConfig, TrueCountSession, COUNTDOWN_TARGET};
+use blackjack_trainer::countingsystem::{self, CountingSystem, HiLo};
+use blackjack_trainer::drill::{self, Scheduler};
+use blackjack_trainer::export;
+use blackjack_trainer::simulation::{self, SimulationConfig};
+use blackjack_trainer::flashcard::{self, Answer, FlashCardDeck, Filter};
+use blackjack_trainer::history::{self, ActionRecord, HistoryWriter, RoundRecord, SessionHistory, SessionRecord, HISTORY_FORMAT};
+use blackjack_trainer::input::{Input, Keymap, Prompter, KEYMAP_FILE};
+use blackjack_trainer::protocol::{self, ProtocolEngine};
+use blackjack_trainer::server::{self, ServerConfig};
+use blackjack_trainer::stats::SessionStats;
+use std::env;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub struct BlackjackUI {
     bj: Blackjack,
+    strategy: BlackjackBasicStrategy,
+    // In training mode every decision is graded and the session accuracy is shown at the end
+    training: bool,
+    // Wrong decisions must be corrected before the hand continues
+    block_wrong: bool,
+    // Every graded decision this session, by chart cell
+    stats: SessionStats,
+    // In drill mode each hand is dealt from a cell chosen by the spaced-repetition schedule
+    drill: Option<Scheduler>,
+    // The cell dealt for the current drill hand, until its first decision is graded
+    drill_cell: Option<ChartCell>,
+    // The seed the shoe is shuffled from, kept in the hand history
+    seed: u64,
+    // How the table is played, kept in the hand history: play, train or drill
+    mode: &'static str,
+    // The hand history of this session, once it has been started
+    history: Option<HistoryWriter>,
+    // The number of rounds played this session
+    rounds: u32,
+    // The decisions made in the current round
+    actions: Vec<ActionRecord>,
+    // The player's bankroll in units of the 1 unit bet, shown after each round when one is given
+    bankroll: Option<f64>,
+    // Reads the player's decisions, a key press at a time when the terminal allows it
+    prompter: Prompter,
+    // Draws the cards in the style chosen with --cards or detected for the terminal
+    cards: CardRenderer,
 }
 
 impl BlackjackUI {
-    // Constructs a Blackjack game
-    pub fn new() -> Self {
-        BlackjackUI {
-            bj: Blackjack::new(),
-        }
+    // Constructs a Blackjack game with the rules, chart, seed and bankroll given as flags
+    pub fn new(flags: &TableFlags) -> Result<Self, String> {
+        let seed = flags.seed.unwrap_or_else(rand::random);
+        let rules = flags.rules();
+        let strategy = flags.strategy(&rules)?;
+        let bj = Blackjack::with_seed(rules, seed);
+        let prompter = Prompter::new(Keymap::load()?);
+        Ok(BlackjackUI {
+            bj,
+            strategy,
+            training: false,
+            block_wrong: false,
+            stats: SessionStats::new(),
+            drill: None,
+            drill_cell: None,
+            seed,
+            mode: "play",
+            history: None,
+            rounds: 0,
+            actions: Vec::new(),
+            bankroll: flags.bankroll,
+            prompter,
+            cards: CardRenderer::for_terminal(flags.cards),
+        })
+    }
+
+    // Constructs a Blackjack game that grades every decision against basic strategy
+    pub fn with_training(flags: &TableFlags, block_wrong: bool) -> Result<Self, String> {
+        Ok(BlackjackUI {
+            training: true,
+            block_wrong,
+            mode: "train",
+            ..Self::new(flags)?
+        })
     }
 
-    // Plays a single hand of blackjack
-    pub fn play_hand(&mut self) {
+    // Constructs a training game that deals the cells chosen by a spaced-repetition schedule
+    pub fn with_drill(flags: &TableFlags, scheduler: Scheduler) -> Result<Self, String> {
+        Ok(BlackjackUI {
+            drill: Some(scheduler),
+            mode: "drill",
+            ..Self::with_training(flags, false)?
+        })
+    }
+
+    // Plays a single hand of blackjack, returning false if the player quit before it was finished
+    pub fn play_hand(&mut self) -> bool {
+        self.bj.clear_table();
+        let position = self.bj.shoe_position();
+
+        if let Some(scheduler) = &self.drill {
+            let mut rng = rand::thread_rng();
+            let cell = scheduler.next_cell(&mut rng);
+            self.bj.stack_cards(drill::scenario(cell, &mut rng));
+            self.drill_cell = Some(cell);
+        }
         self.bj.deal_cards();
-        self.play_players_hand();
+        if !self.play_players_hand() {
+            return false;
+        }
         self.bj.play_dealers_hand();
+        self.display_dealers_hand();
         self.display_result();
+        self.record_round(position);
+        true
+    }
+
+    // Starts the hand history of this session, which is played without one if the file cannot be created
+    fn start_history(&mut self) {
+        let session = SessionRecord {
+            format: HISTORY_FORMAT,
+            started: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+            seed: self.seed,
+            mode: self.mode.to_string(),
+            rules: *self.bj.get_rules(),
+        };
+        match HistoryWriter::create(&session) {
+            Ok(writer) => self.history = Some(writer),
+            Err(err) => eprintln!("Could not start the hand history: {}", err),
+        }
+    }
+
+    // Writes the round just played to the hand history and settles it against the bankroll
+    fn record_round(&mut self, position: (u64, usize)) {
+        self.rounds += 1;
+        let actions = std::mem::take(&mut self.actions);
+        let round = RoundRecord::from_game(&self.bj, self.rounds, position, 1.0, actions);
+
+        if let Some(bankroll) = self.bankroll.as_mut() {
+            *bankroll += round.net;
+            println!("Bankroll: {}", bankroll);
+        }
+        if let Some(writer) = self.history.as_mut() {
+            if let Err(err) = writer.write_round(&round) {
+                eprintln!("Could not write the hand history: {}", err);
+                self.history = None;
+            }
+        }
     }
 
     // Plays blackjack hands until the user chooses to quit
     pub fn play_hands_until_quit(&mut self) {
-        let mut input = String::new();
+        self.start_history();
 
         loop {
-            self.play_hand();
-            println!("Keep playing? (yes/no): ");
-            io::stdout().flush().unwrap();
-            input.clear();
-            io::stdin().read_line(&mut input).unwrap();
-
-            if input.trim().to_lowercase() != "yes" {
+            if !self.play_hand() {
+                break;
+            }
+            if self.prompter.is_raw() {
+                println!("Press Enter to deal again, or q to quit.");
+            } else {
+                println!("Keep playing? (yes/no): ");
+            }
+            if !self.prompter.read_continue() {
                 break;
             }
         }
 
+        if self.training {
+            self.display_summary();
+        }
         println!("Thanks for playing");
     }
 
-    // Allows the player to hit until it is no longer possible or until the player chooses to stand
-    fn play_players_hand(&mut self) {
-        println!("You have: {}", self.bj.get_players_hand().expect("nothing").to_string());
-        println!("Dealer has: {}", self.bj.get_dealers_hand().expect("nothing").to_string());
+    // Lets the player act on each of their hands until every hand is finished,
+    // returning false if the player quit first
+    fn play_players_hand(&mut self) -> bool {
+        println!("You have: {}", self.cards.hand(self.bj.get_players_hand().expect("nothing")));
+        let upcard = *self.bj.get_dealers_upcard().expect("nothing");
+        if self.cards.is_multiline() {
+            println!("Dealer shows: {}\n{}", chart::dealer_label(upcard.get_value()), self.cards.cards(&[upcard], 1));
+        } else {
+            println!("Dealer shows: {}", self.cards.inline(&[upcard], 1));
+        }
 
-        let mut response = String::new();
+        if self.bj.can_insure() && !self.offer_insurance() {
+            return false;
+        }
 
-        let bj = &mut self.bj;
+        // True once the current decision has been graded, so a corrected decision is only counted once
+        let mut graded = false;
 
-        while bj.can_hit() {
-            println!("Do you want to hit or stand?");
-            io::stdout().flush().unwrap();
-            response.clear();
-            io::stdin().read_line(&mut response).unwrap();
-            
-            match response.trim().to_lowercase().as_str() {
-                "hit" => {
-                    bj.hit();
-                    if bj.get_players_hand().expect("nothing").get_value() > 21 {
-                        println!("You now have: {}", bj.get_players_hand().expect("nothing").to_string());
-                        println!("You are bust.");
-                        continue;
-                    }
+        while self.bj.is_players_turn() {
+            let legal: Vec<Input> = self.bj.legal_actions().into_iter().map(Input::for_action).collect();
+            println!("Do you want to {}?", self.prompter.get_keymap().describe(&legal));
+            let asked = Instant::now();
+
+            let action = match self.prompter.read_input(&legal).action() {
+                Some(action) => action,
+                None => return false,
+            };
+
+            let correct = self.grade_decision(action, !graded, asked.elapsed());
+            if !correct && self.block_wrong {
+                graded = true;
+                println!("Try again.");
+                continue;
+            }
+            graded = false;
+            self.record_action(action);
+
+            let hand_index = self.bj.get_current_hand_index();
+            let hands_before = self.bj.get_players_hands().len();
+            self.bj.act(action);
+
+            let hand = &self.bj.get_players_hands()[hand_index];
+            let split = self.bj.get_players_hands().len() > hands_before;
+            if split {
+                println!("You split into {} hands.", self.bj.get_players_hands().len());
+            } else if action == Action::Surrender {
+                println!("You surrender.");
+            } else if action != Action::Stand {
+                println!("You now have: {}", self.cards.hand(hand));
+                if hand.get_value() > 21 {
+                    println!("You are bust.");
                 }
-                "stand" => break,
-                _ => println!("Invalid option, please type 'hit', 'stand', or 'double'."),
             }
-    
-            println!(
-                "You have: {}",
-                bj.get_players_hand().expect("nothing").to_string()
-            );
+
+            let moved_on = self.bj.get_current_hand_index() != hand_index;
+            if self.bj.is_players_turn() && (moved_on || split) {
+                self.display_current_hand();
+            }
+        }
+        true
+    }
+
+    // Offers insurance against the dealer's ace before the dealer checks for blackjack,
+    // returning false if the player quit instead of answering
+    fn offer_insurance(&mut self) -> bool {
+        println!(
+            "Insurance? It costs half your bet and pays 2:1 if the dealer has blackjack. Press {} to take it or Enter to decline.",
+            self.prompter.get_keymap().key(Input::Insurance)
+        );
+        match self.prompter.read_offer(Input::Insurance) {
+            Some(true) => {
+                self.bj.insure();
+                // Insurance has no chart cell, so it is explained but not counted
+                println!("Wrong: basic strategy never takes insurance.");
+            }
+            Some(false) => {}
+            None => return false,
+        }
+        true
+    }
+
+    // Keeps the action about to be taken on the current hand, with basic strategy's play, for the hand history
+    fn record_action(&mut self, action: Action) {
+        let hand = self.bj.get_players_hand().expect("nothing");
+        let upcard = *self.bj.get_dealers_upcard().expect("nothing");
+        let recommended = self.strategy.recommend(
+            &hand.get_card_values(),
+            upcard.get_value(),
+            self.bj.can_double(),
+            self.bj.can_split(),
+            self.bj.can_surrender(),
+        );
+
+        self.actions.push(ActionRecord {
+            hand: self.bj.get_current_hand_index(),
+            cards: hand.get_cards().to_vec(),
+            upcard,
+            action,
+            recommended,
+            matched: recommended.is_none_or(|recommended| recommended == action),
+        });
+    }
+
+    // Shows the hand being played, numbered when the player has split
+    fn display_current_hand(&self) {
+        let hands = self.bj.get_players_hands();
+        let index = self.bj.get_current_hand_index();
+
+        if index < hands.len() {
+            println!("Hand {} of {}: {}", index + 1, hands.len(), self.cards.hand(&hands[index]));
+        }
+    }
+
+    // Compares the action with basic strategy and shows feedback, returning true if it was correct.
+    // Outside training mode only wrong decisions are explained.
+    fn grade_decision(&mut self, action: Action, count: bool, response_time: Duration) -> bool {
+        let hand = self.bj.get_players_hand().expect("nothing").get_card_values();
+        let dealer_card = self.bj.get_dealers_upcard().expect("nothing").get_value();
+        let can_split = self.bj.can_split();
+        let recommended = match self.strategy.recommend(&hand, dealer_card, self.bj.can_double(), can_split, self.bj.can_surrender()) {
+            Some(recommended) => recommended,
+            None => return true,
+        };
+        let correct = recommended == action;
+        let explanation = if correct { None } else { self.strategy.explain(&hand, dealer_card) };
+
+        if count {
+            let category = self.strategy.classify(&hand, can_split).expect("hand has a recommended play");
+            let ev_lost = explanation
+                .as_ref()
+                .and_then(|explanation| Some(explanation.evs.action_ev(recommended)? - explanation.evs.action_ev(action)?))
+                .unwrap_or(0.0);
+            self.stats.record(ChartCell { category, dealer_card }, correct, ev_lost);
+
+            // Only the decision the drill hand was dealt for is scheduled
+            if let (Some(scheduler), Some(cell)) = (self.drill.as_mut(), self.drill_cell.take()) {
+                scheduler.record(cell, correct, response_time);
+            }
+        }
+
+        if correct {
+            if self.training {
+                println!("Correct.");
+            }
+        } else {
+            println!("Wrong: basic strategy says {}, not {}.", recommended, action);
+            if let Some(explanation) = explanation {
+                println!("{}", explanation);
+            }
+        }
+        correct
+    }
+
+    // Shows the cards the dealer drew and the dealer's final hand
+    fn display_dealers_hand(&self) {
+        let dealer_hand = self.bj.get_dealers_hand().expect("dealer has a hand");
+
+        for card in &dealer_hand.get_cards()[2..] {
+            println!("Dealer gets: {}", self.cards.card(card));
         }
+        println!("Dealer has: {}", self.cards.hand(dealer_hand));
     }
 
-    // Displays the result of the hand (push, player win, player blackjack, or loss)
+    // Displays the result of each hand (push, player win, player blackjack, or loss)
     fn display_result(&mut self) {
-        if self.bj.get_dealers_hand().expect("Empty").is_blackjack() && self.bj.get_players_hand().expect("Empty").is_blackjack() {
-            println!("Y'all both got blackjack, it's a push.");
-        } else if self.bj.get_players_hand().expect("Empty").is_blackjack() {
-            println!("YOU GOT BLACKJACK!");
-        } else if self.bj.is_player_win() {
-            println!("Player win.");
-        } else if self.bj.is_push() {
-            println!("Push.");
+        let hands = self.bj.get_players_hands().len();
+
+        for index in 0..hands {
+            if hands > 1 {
+                print!("Hand {}: ", index + 1);
+            }
+            match self.bj.outcome(index) {
+                HandOutcome::Push if self.bj.get_players_hands()[index].is_blackjack() => {
+                    println!("Y'all both got blackjack, it's a push.")
+                }
+                HandOutcome::Blackjack => println!("YOU GOT BLACKJACK!"),
+                HandOutcome::Win => println!("Player win."),
+                HandOutcome::Push => println!("Push."),
+                HandOutcome::Loss | HandOutcome::Bust => println!("Player loss."),
+                HandOutcome::Surrender => println!("Surrendered, half the bet is lost."),
+            }
+        }
+        if self.bj.is_insured() {
+            if self.bj.insurance_result() > 0.0 {
+                println!("Insurance pays 2:1.");
+            } else {
+                println!("Insurance lost.");
+            }
+        }
+    }
+
+    // Displays the accuracy of the graded decisions this session, broken down by chart cell,
+    // and adds them to the statistics of previous sessions
+    fn display_summary(&self) {
+        if self.stats.is_empty() {
+            println!("No decisions graded this session.");
+            return;
+        }
+
+        let total = self.stats.total();
+        println!(
+            "Session accuracy: {} of {} decisions correct ({:.1}%)",
+            total.correct,
+            total.attempts,
+            100.0 * total.accuracy()
+        );
+        println!();
+        print!("{}", self.stats.render_table());
+        println!();
+        print!("{}", self.stats.render_chart(&self.strategy, chart::colour_enabled()));
+
+        if let Err(err) = self.stats.save_session() {
+            eprintln!("Could not save statistics: {}", err);
+        }
+        if let Some(scheduler) = &self.drill {
+            println!("{} drilled cells are due for review.", scheduler.due_count());
+            if let Err(err) = scheduler.save() {
+                eprintln!("Could not save the drill schedule: {}", err);
+            }
+        }
+    }
+}
+
+impl Default for BlackjackUI {
+    fn default() -> Self {
+        Self::new(&TableFlags::default()).expect("the chart for the default rules is built in")
+    }
+}
+
+/**
+ * Handles the chart command:
+ *   chart [variant|file]          prints a strategy chart (the default chart if none is given)
+ *   chart diff <first> <second>   prints the cells that change moving from the first chart to the second
+ *   chart export [variant|file]   prints a chart in the chart file format
+ *   chart weak [variant|file]     prints a chart with the cells of low recorded accuracy marked
+ * Variants are named by decks and soft 17 rule, e.g. 6d-h17, 2d-s17, 1d-s17-nodas.
+ * Without a chart the chart for the table flags is printed.
+ */
+fn chart_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let load = |spec: Option<&String>| match spec {
+        Some(spec) => BlackjackBasicStrategy::from_spec(spec),
+        None => flags.strategy(&flags.rules()),
+    };
+
+    match args.first().map(String::as_str) {
+        Some("diff") => {
+            if args.len() != 3 {
+                return Err("Usage: chart diff <first> <second>".to_string());
+            }
+            let first = load(args.get(1))?;
+            let second = load(args.get(2))?;
+            let differences = chart::diff_charts(&first, &second);
+            print!("{}", chart::render_diff(&first, &second, &differences, chart::colour_enabled()));
+        }
+        Some("export") => print!("{}", load(args.get(1))?.to_chart_text()),
+        Some("weak") => {
+            let accuracy = SessionStats::load()?.accuracy_map();
+            print!("{}", chart::render_chart(&load(args.get(1))?, Some(&accuracy), chart::colour_enabled()));
+        }
+        _ => print!("{}", chart::render_chart(&load(args.first())?, None, chart::colour_enabled())),
+    }
+    Ok(())
+}
+
+/**
+ * Handles the dealer command, which prints the exact probability of each dealer final total per upcard:
+ *   dealer [variant|file] [peek|no-peek]
+ * Both tables are printed unless peek or no-peek is given. The table flags override the rules.
+ */
+fn dealer_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let mut rules = Rules::default();
+    let mut tables = vec![true, false];
+
+    for arg in args {
+        match arg.as_str() {
+            "peek" => tables = vec![true],
+            "no-peek" | "nopeek" => tables = vec![false],
+            spec => rules = *BlackjackBasicStrategy::from_spec(spec)?.get_rules(),
+        }
+    }
+    flags.apply(&mut rules);
+    for (index, &peek) in tables.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        print!("{}", chart::render_dealer_table(&rules, peek));
+    }
+    Ok(())
+}
+
+/**
+ * Handles the stats command, which prints the statistics of every training session:
+ *   stats          prints the accuracy, attempts and EV lost per cell, and the accuracy chart
+ *   stats reset    deletes the saved statistics
+ * The accuracy chart is laid over the chart for the table flags, or the one given with --chart
+ */
+fn stats_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    if args.first().map(String::as_str) == Some("reset") {
+        return SessionStats::new().save().map_err(|err| err.to_string());
+    }
+
+    let stats = SessionStats::load()?;
+    if stats.is_empty() {
+        println!("No decisions graded yet. Run with \"train\" to start.");
+        return Ok(());
+    }
+
+    let strategy = flags.strategy(&flags.rules())?;
+    let total = stats.total();
+    println!(
+        "Lifetime accuracy: {} of {} decisions correct ({:.1}%)\n",
+        total.correct,
+        total.attempts,
+        100.0 * total.accuracy()
+    );
+    print!("{}", stats.render_table());
+    println!();
+    print!("{}", stats.render_chart(&strategy, chart::colour_enabled()));
+    Ok(())
+}
+
+/**
+ * Handles the drill command, which deals the situations the trainee most needs to practise.
+ * The schedule is kept between sessions, and cells missed in played hands are introduced first.
+ */
+fn drill_command(flags: &TableFlags) -> Result<(), String> {
+    let mut scheduler = Scheduler::load()?;
+    scheduler.set_history(&SessionStats::load()?);
+
+    let mut game = BlackjackUI::with_drill(flags, scheduler)?;
+    game.play_hands_until_quit();
+    Ok(())
+}
+
+/**
+ * Handles the flash command, which quizzes strategy decisions without playing hands out:
+ *   flash [hard|soft|pair] [vs <upcard>]
+ * Each card is answered with the key of a play (h, s, d or p, and r where the chart's rules offer surrender, unless
+ * the keymap changes them) and q ends the quiz. Answers are graded against the chart for the table flags, or the one given with --chart
+ */
+fn flash_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let filter = Filter::parse(args)?;
+    let deck = FlashCardDeck::new(&filter)?;
+    let strategy = flags.strategy(&flags.rules())?;
+    let mut stats = SessionStats::new();
+    let mut rng = rand::thread_rng();
+    let mut prompter = Prompter::new(Keymap::load()?);
+    let mut plays = vec![Input::Hit, Input::Stand, Input::Double, Input::Split];
+    if strategy.get_rules().surrender {
+        plays.push(Input::Surrender);
+    }
+
+    println!("{} situations. Answer {}, {} to quit.", deck.len(), prompter.get_keymap().describe(&plays), Input::Quit);
+    loop {
+        let card = deck.deal(&mut rng);
+        println!("{}", card);
+
+        let answer = match prompter.read_input(&plays) {
+            Input::Hit => Answer::Hit,
+            Input::Stand => Answer::Stand,
+            Input::Double => Answer::Double,
+            Input::Split => Answer::Split,
+            Input::Surrender => Answer::Surrender,
+            Input::Insurance | Input::Quit => break,
+        };
+
+        let grade = flashcard::grade(&strategy, &card, answer);
+        let ev_lost = if grade.correct { 0.0 } else { flash_ev_lost(&strategy, &card, answer) };
+        stats.record(card.cell, grade.correct, ev_lost);
+        println!("{}", flashcard::feedback(&card, answer, &grade));
+    }
+
+    let total = stats.total();
+    if total.attempts > 0 {
+        println!("{} of {} correct ({:.1}%)", total.correct, total.attempts, 100.0 * total.accuracy());
+        if let Err(err) = stats.save_session() {
+            eprintln!("Could not save statistics: {}", err);
+        }
+    }
+    Ok(())
+}
+
+// The expected value given up by a wrong flash card answer
+fn flash_ev_lost(strategy: &BlackjackBasicStrategy, card: &flashcard::FlashCard, answer: Answer) -> f64 {
+    let hand = [card.player[0].get_value(), card.player[1].get_value()];
+    let action = match answer {
+        Answer::Hit => Action::Hit,
+        Answer::Stand => Action::Stand,
+        Answer::Double => Action::Double,
+        Answer::Split => Action::Split,
+        Answer::Surrender => Action::Surrender,
+    };
+    strategy
+        .explain(&hand, card.cell.dealer_card)
+        .and_then(|explanation| Some(explanation.evs.best() - explanation.evs.action_ev(action)?))
+        .unwrap_or(0.0)
+}
+
+// Asks for a count until one is typed, or returns None at the end of input
+fn read_count(prompt: &str) -> Option<f64> {
+    let mut response = String::new();
+    loop {
+        println!("{}", prompt);
+        io::stdout().flush().unwrap();
+        response.clear();
+        if io::stdin().read_line(&mut response).unwrap() == 0 {
+            return None;
+        }
+        match response.trim().parse::<f64>() {
+            Ok(count) if (count * 2.0).fract() == 0.0 => return Some(count),
+            _ => println!("Please type a count, e.g. -3 or +2.5."),
+        }
+    }
+}
+
+/**
+ * Handles the count command, which practises the Hi-Lo running count:
+ *   count [singles|pairs|triples] [pace <seconds>] [cards <number>] [system <name>]
+ *       shows cards at the specified pace and asks for the count at random checkpoints and at the end
+ *   count deck [system <name>]
+ *       the deck countdown: flip through a deck minus one card as fast as possible, then give the count
+ *   count true [half|quarter] [floor|truncate|round] [decks <number>] [system <name>]
+ *       converts running counts to true counts from the depth of the discard tray
+ *   count systems
+ *       lists the counting systems and their tags
+ * Hi-Lo is used unless another system is named: ko, hi-opt-i, hi-opt-ii, omega-ii, zen or wong-halves.
+ * --decks sets the shoe of the true count drill, and --cards how the cards are drawn.
+ * Art is too tall to flash, so it is drawn with suit symbols.
+ */
+fn count_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let renderer = CardRenderer::for_terminal(flags.cards);
+    match args.first().map(String::as_str) {
+        Some("deck") if flags.decks.is_none() => return countdown_command(&args[1..], &renderer),
+        Some("true") => {
+            let mut args = args[1..].to_vec();
+            if let Some(decks) = flags.decks {
+                args.extend(["decks".to_string(), decks.to_string()]);
+            }
+            return true_count_command(&args);
+        }
+        Some("systems") => {
+            print!("{}", counting::render_systems());
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    if flags.decks.is_some() {
+        return Err("--decks only applies to count true".to_string());
+    }
+    let config = DrillConfig::parse(args)?;
+    let steps = counting::plan_drill(&config, &mut rand::thread_rng());
+    let mut asked = 0;
+    let mut correct = 0;
+    let system = config.system;
+
+    println!("Keep the {} running count. {} cards, {} at a time.", system.name(), config.cards, config.group_size);
+    if !system.is_balanced() {
+        let start = system.initial_running_count(counting::drill_decks(&config));
+        println!("The count starts at {}.", countingsystem::format_count(start));
+    }
+    for step in steps {
+        print!("\r{}", renderer.inline(&step.cards, 0));
+        io::stdout().flush().unwrap();
+        thread::sleep(config.pace);
+        print!("\r{:<16}\r", "");
+
+        if step.checkpoint {
+            let answer = match read_count("\nRunning count?") {
+                Some(answer) => answer,
+                None => break,
+            };
+            asked += 1;
+            if answer == step.running_count {
+                correct += 1;
+                println!("Correct.");
+            } else {
+                println!("Wrong: the running count is {}.", countingsystem::format_count(step.running_count));
+            }
+
+            if system.ace_side_count() {
+                let aces = match read_count("Aces seen?") {
+                    Some(aces) => aces,
+                    None => break,
+                };
+                asked += 1;
+                if aces == step.aces as f64 {
+                    correct += 1;
+                    println!("Correct.");
+                } else {
+                    println!("Wrong: {} aces have been seen.", step.aces);
+                }
+            }
+        }
+    }
+
+    println!("{} of {} counts correct.", correct, asked);
+    Ok(())
+}
+
+// Runs the deck countdown and keeps the personal best
+fn countdown_command(args: &[String], renderer: &CardRenderer) -> Result<(), String> {
+    let system = match args {
+        [] => &HiLo as &dyn CountingSystem,
+        [option, name] if option == "system" => {
+            countingsystem::system_by_name(name).ok_or_else(|| format!("unknown counting system {}", name))?
+        }
+        _ => return Err("Usage: count deck [system <name>]".to_string()),
+    };
+    let (cards, hidden) = counting::countdown_deck();
+    let best = counting::load_countdown_best()?;
+
+    println!("Press Enter to flip each card, then give the running count. One card is held back.");
+    if let Some(best) = best {
+        println!("Personal best: {:.1}s", best.as_secs_f64());
+    }
+    let mut response = String::new();
+    let start = Instant::now();
+    for card in &cards {
+        print!("{} ", renderer.card(card));
+        io::stdout().flush().unwrap();
+        response.clear();
+        if io::stdin().read_line(&mut response).unwrap() == 0 {
+            return Ok(());
+        }
+    }
+    let time = start.elapsed();
+
+    let count = countingsystem::running_count(system, 1, &cards);
+    let answer = match read_count(&format!("{} running count?", system.name())) {
+        Some(answer) => answer,
+        None => return Ok(()),
+    };
+    println!("The held back card was {}.", renderer.card(&hidden));
+    if answer != count {
+        let count = countingsystem::format_count(count);
+        println!("Wrong: the running count is {}. Time: {:.1}s", count, time.as_secs_f64());
+        return Ok(());
+    }
+
+    let target = if time <= COUNTDOWN_TARGET { "under" } else { "over" };
+    println!("Correct in {:.1}s, {} the {}s target.", time.as_secs_f64(), target, COUNTDOWN_TARGET.as_secs());
+    if counting::record_countdown(time)? {
+        println!("New personal best!");
+    }
+    Ok(())
+}
+
+// Asks for true counts until the user quits, then saves the session and shows the error trend
+fn true_count_command(args: &[String]) -> Result<(), String> {
+    let config = TrueCountConfig::parse(args)?;
+    let mut rng = rand::thread_rng();
+    let mut errors = Vec::new();
+    let mut response = String::new();
+    let part = if config.parts_per_deck == 2 { "half" } else { "quarter" };
+
+    println!(
+        "{} deck shoe, each mark in the tray is a {} deck. Give the {} true count ({}), Q to quit.",
+        config.decks,
+        part,
+        config.system.name(),
+        config.rounding
+    );
+    'questions: loop {
+        let question = counting::true_count_question(&config, &mut rng);
+        println!("\nDiscard tray {}", counting::render_tray(&config, question.decks_discarded));
+        println!("Running count: {}", countingsystem::format_count(question.running_count));
+
+        let answer: i32 = loop {
+            println!("True count?");
+            io::stdout().flush().unwrap();
+            response.clear();
+            if io::stdin().read_line(&mut response).unwrap() == 0 || response.trim().eq_ignore_ascii_case("q") {
+                break 'questions;
+            }
+            match response.trim().parse() {
+                Ok(answer) => break answer,
+                Err(_) => println!("Please type a whole number, e.g. -3, or Q to quit."),
+            }
+        };
+
+        errors.push(answer - question.true_count);
+        let detail = format!(
+            "{} / {} decks remaining = {:+}",
+            countingsystem::format_count(question.running_count),
+            question.decks_remaining,
+            question.true_count
+        );
+        if answer == question.true_count {
+            println!("Correct: {}", detail);
         } else {
-            println!("Player loss.");
+            println!("Wrong: {}", detail);
         }
     }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    let session = TrueCountSession::from_errors(now, &errors);
+    println!(
+        "\n{} of {} correct, mean error {:.2}",
+        session.correct, session.answers, session.mean_absolute_error
+    );
+
+    let mut sessions = counting::load_true_count_sessions()?;
+    counting::save_true_count_session(&session)?;
+    sessions.push(session);
+    println!("Mean error by session, most recent last:");
+    for (number, past) in sessions.iter().enumerate().skip(sessions.len().saturating_sub(10)) {
+        println!("  session {:>3}: {:.2} over {} answers", number + 1, past.mean_absolute_error, past.answers);
+    }
+    Ok(())
+}
+
+/**
+ * Handles the simulate command, which plays rounds headlessly with a computer player
+ * and reports the house edge:
+ *   simulate [hands <number>] [rules <variant|file>] [player <name>] [strategy <variant|file>]
+ *            [system <name>] [bankroll <units>] [side-bets <list|all>] [penetration <fraction>]
+ *            [payout <ratio>] [threads <number>] [seed <number>] [export <file.csv|file.json>]
+ * Players are basic, deviations (Illustrious 18 with a 1-8 spread), mimic, never-bust and random.
+ * Side bets are perfect-pairs, 21+3, lucky-ladies, match-the-dealer and buster.
+ * The export has a summary, the win rate at each true count, the distribution of bets and the side bets.
+ * The table flags override the matching options.
+ */
+fn simulate_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    // "export <file>" writes the results for spreadsheets; every other option configures the simulation
+    let mut options = Vec::new();
+    let mut export_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "export" {
+            export_path = Some(PathBuf::from(args.next().ok_or("export needs a file")?));
+        } else {
+            options.push(arg.clone());
+        }
+    }
+    if let Some(path) = &export_path {
+        export::ExportFormat::from_path(path)?;
+    }
+
+    let config = SimulationConfig::parse_with(&options, flags)?;
+    let result = simulation::simulate(&config);
+    print!("{}", simulation::render_report(&config, &result));
+    if let Some(path) = export_path {
+        report_export(&export::simulation_export(&config, &result), &path)?;
+    }
+    Ok(())
+}
+
+// Writes an export and lists the files written
+fn report_export(export: &export::Export, path: &Path) -> Result<(), String> {
+    for file in export.write(path)? {
+        println!("Exported {}", file.display());
+    }
+    Ok(())
+}
+
+/**
+ * Handles the export command, which writes training results as CSV or JSON, chosen by the file's extension:
+ *   export sessions <file> [number]   every decision in the hand history, or in one session by its number
+ *                                     in "replay list", with the accuracy in each chart cell
+ *   export stats <file>               the lifetime accuracy in each chart cell
+ * Simulation results are exported with "simulate ... export <file>"
+ */
+fn export_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: export sessions <file.csv|file.json> [number] or export stats <file.csv|file.json>";
+    let path = PathBuf::from(args.get(1).ok_or(usage)?);
+    export::ExportFormat::from_path(&path)?;
+
+    let export = match (args[0].as_str(), args.get(2)) {
+        ("sessions", number) => {
+            let files = history::session_files().map_err(|err| format!("Could not read the hand history: {}", err))?;
+            let files = match number {
+                Some(number) => {
+                    let index = number.parse::<usize>().map_err(|_| format!("invalid session number {}", number))?;
+                    let file = files
+                        .get(index.wrapping_sub(1))
+                        .ok_or_else(|| format!("no session {}, there are {}", index, files.len()))?;
+                    vec![file.clone()]
+                }
+                None => files,
+            };
+            let sessions = files.iter().map(|file| SessionHistory::load(file)).collect::<Result<Vec<_>, _>>()?;
+            export::sessions_export(&sessions)
+        }
+        ("stats", None) => export::stats_export(&SessionStats::load()?),
+        _ => return Err(usage.to_string()),
+    };
+    report_export(&export, &path)
+}
+
+/**
+ * Handles the bankroll command, which reports the risk of ruin, N0, hourly win and standard deviation,
+ * and the bankroll needed for a target risk:
+ *   bankroll [bankroll <units>] [sessions <number>] [hours <number>] [rate <rounds>] [target <percent>]
+ *            [trials <number>] [win-rate <units> sd <units>] [any simulate option]
+ * The win rate is simulated unless it is supplied with its standard deviation.
+ * The table flags override the matching options.
+ */
+fn bankroll_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let config = RiskConfig::parse_with(args, flags)?;
+    let (measured, report) = bankroll::calculate(&config);
+    print!("{}", bankroll::render_report(&config, measured.as_ref(), &report));
+    Ok(())
+}
+
+/**
+ * Handles the replay command, which steps through the hand history of a past session:
+ *   replay            replays the latest session
+ *   replay list       lists the sessions in the history
+ *   replay <number>   replays a session by its number in the list
+ *   replay <file>     replays a history file
+ */
+fn replay_command(args: &[String]) -> Result<(), String> {
+    let files = history::session_files().map_err(|err| format!("Could not read the hand history: {}", err))?;
+
+    let path = match args.first().map(String::as_str) {
+        Some("list") => {
+            if files.is_empty() {
+                println!("No sessions in the hand history.");
+            }
+            for (index, path) in files.iter().enumerate() {
+                let session = SessionHistory::load(path)?;
+                println!("{:>3}. {}", index + 1, history::render_session(&session).lines().collect::<Vec<_>>().join(" | "));
+            }
+            return Ok(());
+        }
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(number) => files
+                .get(number.wrapping_sub(1))
+                .cloned()
+                .ok_or_else(|| format!("no session {}, there are {}", number, files.len()))?,
+            Err(_) => PathBuf::from(arg),
+        },
+        None => files.last().cloned().ok_or("No sessions in the hand history.")?,
+    };
+    let session = SessionHistory::load(&path)?;
+
+    print!("{}", history::render_session(&session));
+    let mut response = String::new();
+    for round in &session.rounds {
+        println!();
+        print!("{}", history::render_round(round));
+        print!("Press Enter for the next hand, or q to quit: ");
+        io::stdout().flush().unwrap();
+        response.clear();
+        if io::stdin().read_line(&mut response).unwrap_or(0) == 0 || response.trim().eq_ignore_ascii_case("q") {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Handles the engine command, which plays the table for a bot or front-end speaking the JSON line protocol
+ * on stdin and stdout. The table flags set the rules, the chart of the recommended plays, the seed
+ * and the bankroll (default 100).
+ */
+fn engine_command(flags: &TableFlags) -> Result<(), String> {
+    let rules = flags.rules();
+    let strategy = flags.strategy(&rules)?;
+    let game = Blackjack::with_seed(rules, flags.seed.unwrap_or_else(rand::random));
+    let mut engine = ProtocolEngine::new(game, strategy, flags.bankroll.unwrap_or(100.0));
+    protocol::run(&mut engine, io::stdin().lock(), io::stdout().lock()).map_err(|err| err.to_string())
+}
+
+/**
+ * Handles the serve command, which hosts a table on a local TCP port for players to join with the join command:
+ *   serve [port <number>] [seats <number>] [bet-timeout <seconds>] [action-timeout <seconds>] [reconnect-timeout <seconds>]
+ * The table flags set the rules, the seed and the bankroll each player sits down with (default 100).
+ * The server runs until it is interrupted, or until the table fails.
+ */
+fn serve_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let config = ServerConfig::parse_with(args, flags)?;
+    let rules = flags.rules();
+    let game = Blackjack::with_seed(rules, flags.seed.unwrap_or_else(rand::random));
+    let handle = server::start(config, game).map_err(|err| format!("Could not listen on port {}: {}", config.port, err))?;
+
+    println!("Hosting a table of {} seats on {} ({})", config.seats, handle.local_addr(), rules);
+    println!("Players join with: blackjack_trainer join {}", handle.local_addr());
+    handle.wait()
+}
+
+/**
+ * Handles the join command, which plays at a table hosted by the serve command:
+ *   join [<host:port>] [name <name>] [seat <number>] [rejoin <token>] [watch]
+ * The address defaults to the local table on port 7021. Without watch the player takes the seat given,
+ * the first free seat, or with rejoin the seat they had before their connection dropped.
+ * Bets, actions and the other commands are typed a line at a time.
+ */
+fn join_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let config = JoinConfig::parse(args)?;
+    client::join(&config, CardRenderer::for_terminal(flags.cards), Keymap::load()?)
+}
+
+/**
+ * Handles the keys command, which shows the keys used to play and answer flash cards:
+ *   keys          prints the keymap in the keymap file format
+ *   keys reset    deletes the keymap file, going back to the default keys
+ * The keymap is changed by editing the keymap file in the data directory
+ */
+fn keys_command(args: &[String]) -> Result<(), String> {
+    let path = blackjack_trainer::storage::data_path(KEYMAP_FILE);
+    match args.first().map(String::as_str) {
+        Some("reset") => match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(format!("Could not delete {}: {}", path.display(), err)),
+            _ => Ok(()),
+        },
+        Some(arg) => Err(format!("unknown option {}", arg)),
+        None => {
+            print!("{}", Keymap::load()?.to_text());
+            println!("\nEdit {} to change the keys, or set input = line to answer with Enter.", path.display());
+            Ok(())
+        }
+    }
+}
+
+/**
+ * Handles the query command, which shows the basic strategy play for a hand with the expected value of each action:
+ *   query <cards> vs <upcard>   e.g. query 10 6 vs 10, query A 7 vs 9 or query 88 vs A
+ * The chart is the one for the table flags, or the one given with --chart
+ */
+fn query_command(args: &[String], flags: &TableFlags) -> Result<(), String> {
+    let (hand, upcard) = cli::parse_query(args)?;
+    let strategy = flags.strategy(&flags.rules())?;
+
+    match strategy.explain(&hand, upcard) {
+        Some(explanation) => println!("{}", explanation),
+        None => {
+            let cards: Vec<String> = hand.iter().map(|&card| chart::dealer_label(card)).collect();
+            println!("No play: {} against {}, the hand is bust.", cards.join(" "), chart::dealer_label(upcard));
+        }
+    }
+    Ok(())
 }
 
 /**
  * A text based user interface that allows the user to play a game of blackjack.
+ * Run with a command to train, drill, quiz, count, query, simulate or replay instead of playing;
+ * "help" lists the commands and the table flags they share. Every round played is kept in the hand history.
  */
 fn main() {
-    let mut game = BlackjackUI::new();
-    game.play_hands_until_quit();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let cli = match Cli::parse(&args) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let (args, flags) = (&cli.args, &cli.flags);
+
+    let result = match cli.command {
+        Command::Play => BlackjackUI::new(flags).map(|mut game| game.play_hands_until_quit()),
+        Command::Train => {
+            let block_wrong = args.first().map(String::as_str) == Some("block");
+            BlackjackUI::with_training(flags, block_wrong).map(|mut game| game.play_hands_until_quit())
+        }
+        Command::Drill => drill_command(flags),
+        Command::Flash => flash_command(args, flags),
+        Command::Count => count_command(args, flags),
+        Command::Query => query_command(args, flags),
+        Command::Chart => chart_command(args, flags),
+        Command::Dealer => dealer_command(args, flags),
+        Command::Simulate => simulate_command(args, flags),
+        Command::Bankroll => bankroll_command(args, flags),
+        Command::Replay => replay_command(args),
+        Command::Export => export_command(args),
+        Command::Engine => engine_command(flags),
+        Command::Serve => serve_command(args, flags),
+        Command::Join => join_command(args, flags),
+        Command::Stats => stats_command(args, flags),
+        Command::Keys => keys_command(args),
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,435 @@
+use crate::blackjack::{Action, Blackjack, Card, Hand, Rules};
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, HandCategory};
+use crate::countingsystem::{count_cards, CountingSystem};
+use crate::input::{Input, Prompter};
+use rand::prelude::*;
+
+const CARDS_PER_DECK: f64 = 52.0;
+
+// The true count is never divided by less than a quarter of a deck
+const MIN_DECKS_REMAINING: f64 = 0.25;
+
+/**
+ * What a player can see of the round when betting or deciding how to play a hand.
+ * While betting no cards have been dealt: the hands are empty, there is no upcard and no actions are legal.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct RoundView<'a> {
+    pub rules: &'a Rules,
+    pub hands: &'a [Hand],
+    // Index of the hand being played
+    pub current_hand: usize,
+    pub dealer_upcard: Option<&'a Card>,
+    // The cards of the earlier rounds dealt since the shoe was shuffled
+    pub seen_cards: &'a [Card],
+    // The count of every card seen, including the cards of this round
+    pub running_count: f64,
+    pub true_count: f64,
+    pub bankroll: f64,
+    pub legal_actions: &'a [Action],
+}
+impl<'a> RoundView<'a> {
+    /**
+     * Constructs the view of a game
+     * @param game the game being played
+     * @param count the count the player keeps of the shoe
+     * @param bankroll the player's bankroll in betting units
+     * @param legal_actions the actions the player can take on the current hand
+     */
+    pub fn new(game: &'a Blackjack, count: &mut ShoeCount, bankroll: f64, legal_actions: &'a [Action]) -> Self {
+        let (running_count, true_count) = count.counts(game);
+
+        RoundView {
+            rules: game.get_rules(),
+            hands: game.get_players_hands(),
+            current_hand: game.get_current_hand_index(),
+            dealer_upcard: game.get_dealers_upcard(),
+            seen_cards: game.get_discards(),
+            running_count,
+            true_count,
+            bankroll,
+            legal_actions,
+        }
+    }
+
+    /**
+     * Returns the hand being played, or None while betting
+     */
+    pub fn hand(&self) -> Option<&'a Hand> {
+        self.hands.get(self.current_hand)
+    }
+
+    /**
+     * Returns true if the action is legal on the hand being played
+     */
+    pub fn can(&self, action: Action) -> bool {
+        self.legal_actions.contains(&action)
+    }
+}
+
+/**
+ * A decision-maker that can play the engine: it sizes each bet and chooses an action for each decision
+ */
+pub trait PlayerStrategy {
+    /**
+     * Returns the name of this strategy for reports
+     */
+    fn name(&self) -> String;
+
+    /**
+     * Returns the initial bet of the next round in betting units
+     * @param view the table before the cards are dealt
+     */
+    fn bet(&mut self, view: &RoundView) -> f64 {
+        let _ = view;
+        1.0
+    }
+
+    /**
+     * Returns the action to take on the hand being played, which must be one of the view's legal actions
+     * @param view the round with the hand being played
+     */
+    fn action(&mut self, view: &RoundView) -> Action;
+}
+
+/**
+ * The running count a player keeps of a shoe. The discards are counted as they come in,
+ * so the count must be updated every round to notice when the shoe is reshuffled.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct ShoeCount {
+    pub system: &'static dyn CountingSystem,
+    // The number of discards counted so far
+    counted: usize,
+    discards_count: f64,
+}
+impl ShoeCount {
+    pub fn new(system: &'static dyn CountingSystem) -> Self {
+        ShoeCount {
+            system,
+            counted: 0,
+            discards_count: 0.0,
+        }
+    }
+
+    /**
+     * Returns the running count and the true count of every card the player has seen in the game:
+     * the discards, the player's hands, the dealer's upcard and the rest of the dealer's hand once
+     * the player's turn is over
+     */
+    pub fn counts(&mut self, game: &Blackjack) -> (f64, f64) {
+        let discards = game.get_discards();
+        if discards.len() < self.counted {
+            self.counted = 0;
+            self.discards_count = 0.0;
+        }
+        self.discards_count += count_cards(self.system, &discards[self.counted..]);
+        self.counted = discards.len();
+
+        let mut running_count = self.system.initial_running_count(game.get_rules().decks) + self.discards_count;
+        for hand in game.get_players_hands() {
+            running_count += count_cards(self.system, hand.get_cards());
+        }
+        let mut unseen = game.cards_left();
+        if let Some(dealer_hand) = game.get_dealers_hand() {
+            if game.is_players_turn() {
+                running_count += self.system.tag(&dealer_hand.get_cards()[0]);
+                unseen += 1;
+            } else {
+                running_count += count_cards(self.system, dealer_hand.get_cards());
+            }
+        }
+
+        let decks_remaining = (unseen as f64 / CARDS_PER_DECK).max(MIN_DECKS_REMAINING);
+        (running_count, running_count / decks_remaining)
+    }
+}
+
+// The values of the hand's cards and the dealer's upcard, as the strategy chart takes them
+fn chart_values(view: &RoundView) -> (Vec<i32>, i32) {
+    let hand = view.hand().expect("a hand is being played").get_card_values();
+    let dealer_card = view.dealer_upcard.expect("the dealer has an upcard").get_value();
+    (hand, dealer_card)
+}
+
+/**
+ * Flat bets and plays by a basic strategy chart
+ */
+#[derive(Clone, Debug)]
+pub struct BasicStrategyPlayer {
+    pub strategy: BlackjackBasicStrategy,
+}
+impl PlayerStrategy for BasicStrategyPlayer {
+    fn name(&self) -> String {
+        format!("basic strategy chart for {}", self.strategy.get_rules())
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        let (hand, dealer_card) = chart_values(view);
+        self.strategy
+            .recommend(&hand, dealer_card, view.can(Action::Double), view.can(Action::Split), view.can(Action::Surrender))
+            .expect("hands being played are not bust")
+    }
+}
+
+/**
+ * A play that departs from basic strategy at a true count.
+ * The action is taken at or above the index, or below it when `below` is true.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deviation {
+    pub category: HandCategory,
+    pub dealer_card: i32,
+    pub index: f64,
+    pub below: bool,
+    pub action: Action,
+}
+impl Deviation {
+    const fn at_or_above(category: HandCategory, dealer_card: i32, index: f64, action: Action) -> Self {
+        Deviation { category, dealer_card, index, below: false, action }
+    }
+
+    const fn under(category: HandCategory, dealer_card: i32, index: f64, action: Action) -> Self {
+        Deviation { category, dealer_card, index, below: true, action }
+    }
+
+    /**
+     * Returns true if the deviation applies at the true count
+     */
+    pub fn applies(&self, true_count: f64) -> bool {
+        if self.below {
+            true_count < self.index
+        } else {
+            true_count >= self.index
+        }
+    }
+}
+
+/**
+ * The Illustrious 18 Hi-Lo index plays, without insurance which the players are not offered
+ */
+pub const ILLUSTRIOUS_18: [Deviation; 17] = [
+    Deviation::at_or_above(HandCategory::Hard(16), 10, 0.0, Action::Stand),
+    Deviation::at_or_above(HandCategory::Hard(15), 10, 4.0, Action::Stand),
+    Deviation::at_or_above(HandCategory::Pair(10), 5, 5.0, Action::Split),
+    Deviation::at_or_above(HandCategory::Pair(10), 6, 4.0, Action::Split),
+    Deviation::at_or_above(HandCategory::Hard(10), 10, 4.0, Action::Double),
+    Deviation::at_or_above(HandCategory::Hard(12), 3, 2.0, Action::Stand),
+    Deviation::at_or_above(HandCategory::Hard(12), 2, 3.0, Action::Stand),
+    Deviation::at_or_above(HandCategory::Hard(11), 1, 1.0, Action::Double),
+    Deviation::at_or_above(HandCategory::Hard(9), 2, 1.0, Action::Double),
+    Deviation::at_or_above(HandCategory::Hard(10), 1, 4.0, Action::Double),
+    Deviation::at_or_above(HandCategory::Hard(9), 7, 3.0, Action::Double),
+    Deviation::at_or_above(HandCategory::Hard(16), 9, 5.0, Action::Stand),
+    Deviation::under(HandCategory::Hard(13), 2, -1.0, Action::Hit),
+    Deviation::under(HandCategory::Hard(12), 4, 0.0, Action::Hit),
+    Deviation::under(HandCategory::Hard(12), 5, -2.0, Action::Hit),
+    Deviation::under(HandCategory::Hard(12), 6, -1.0, Action::Hit),
+    Deviation::under(HandCategory::Hard(13), 3, -2.0, Action::Hit),
+];
+
+/**
+ * Plays basic strategy except where a deviation applies at the true count, and spreads its bet
+ * with the count: one unit per true count above +1, up to the spread.
+ * The indices are for Hi-Lo, so the player should keep a Hi-Lo count.
+ */
+#[derive(Clone, Debug)]
+pub struct DeviationPlayer {
+    pub strategy: BlackjackBasicStrategy,
+    pub deviations: Vec<Deviation>,
+    // The largest bet in betting units
+    pub spread: f64,
+}
+impl DeviationPlayer {
+    /**
+     * Constructs a player using the Illustrious 18 and a 1-8 spread
+     */
+    pub fn new(strategy: BlackjackBasicStrategy) -> Self {
+        DeviationPlayer {
+            strategy,
+            deviations: ILLUSTRIOUS_18.to_vec(),
+            spread: 8.0,
+        }
+    }
+
+    /**
+     * Returns the deviation taken on the view's hand, or None if the player follows basic strategy
+     */
+    pub fn deviation(&self, view: &RoundView) -> Option<Deviation> {
+        let (hand, dealer_card) = chart_values(view);
+        let category = self.strategy.classify(&hand, view.can(Action::Split))?;
+
+        self.deviations.iter().copied().find(|deviation| {
+            deviation.category == category
+                && deviation.dealer_card == dealer_card
+                && deviation.applies(view.true_count)
+                && view.can(deviation.action)
+        })
+    }
+}
+impl PlayerStrategy for DeviationPlayer {
+    fn name(&self) -> String {
+        format!("basic strategy with {} index plays and a 1-{} bet spread", self.deviations.len(), self.spread)
+    }
+
+    fn bet(&mut self, view: &RoundView) -> f64 {
+        (view.true_count.floor() - 1.0).clamp(1.0, self.spread)
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        match self.deviation(view) {
+            Some(deviation) => deviation.action,
+            None => {
+                let (hand, dealer_card) = chart_values(view);
+                self.strategy
+                    .recommend(&hand, dealer_card, view.can(Action::Double), view.can(Action::Split), view.can(Action::Surrender))
+                    .expect("hands being played are not bust")
+            }
+        }
+    }
+}
+
+/**
+ * Plays like the dealer: hits below 17, and on soft 17 when the dealer does, never doubling or splitting
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct MimicDealerPlayer;
+impl PlayerStrategy for MimicDealerPlayer {
+    fn name(&self) -> String {
+        String::from("mimic the dealer")
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        let hand = view.hand().expect("a hand is being played");
+        let total = hand.get_value();
+
+        if total < 17 || (total == 17 && hand.is_soft() && view.rules.dealer_hits_soft_17) {
+            Action::Hit
+        } else {
+            Action::Stand
+        }
+    }
+}
+
+/**
+ * Never takes a card that could bust the hand: hits hard 11 or less and soft 17 or less,
+ * never doubling or splitting
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct NeverBustPlayer;
+impl PlayerStrategy for NeverBustPlayer {
+    fn name(&self) -> String {
+        String::from("never bust")
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        let hand = view.hand().expect("a hand is being played");
+        let limit = if hand.is_soft() { 17 } else { 11 };
+
+        if hand.get_value() <= limit {
+            Action::Hit
+        } else {
+            Action::Stand
+        }
+    }
+}
+
+/**
+ * Takes a random legal action at every decision
+ */
+#[derive(Clone, Debug)]
+pub struct RandomPlayer {
+    rng: StdRng,
+}
+impl RandomPlayer {
+    /**
+     * Constructs a player whose choices are determined by the seed
+     */
+    pub fn with_seed(seed: u64) -> Self {
+        RandomPlayer { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+impl PlayerStrategy for RandomPlayer {
+    fn name(&self) -> String {
+        String::from("random play")
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        *view.legal_actions.choose(&mut self.rng).expect("a hand being played has legal actions")
+    }
+}
+
+/**
+ * Asks a person for each bet and action, with the keys of their keymap as at the table.
+ * Quitting, or the end of the input, bets one unit and stands.
+ */
+pub struct HumanPlayer {
+    prompter: Prompter,
+}
+impl HumanPlayer {
+    pub fn new(prompter: Prompter) -> Self {
+        HumanPlayer { prompter }
+    }
+}
+impl PlayerStrategy for HumanPlayer {
+    fn name(&self) -> String {
+        String::from("human")
+    }
+
+    fn bet(&mut self, view: &RoundView) -> f64 {
+        print!("Bankroll {}. Bet how many units (Enter for 1)? ", view.bankroll);
+        self.prompter.read_amount(1.0).unwrap_or(1.0)
+    }
+
+    fn action(&mut self, view: &RoundView) -> Action {
+        let hand = view.hand().expect("a hand is being played");
+        let upcard = view.dealer_upcard.expect("the dealer has an upcard");
+        let inputs: Vec<Input> = view.legal_actions.iter().map(|&action| Input::for_action(action)).collect();
+        println!("You have: {}\nDealer shows: {}", hand, upcard);
+        print!("{}? ", self.prompter.get_keymap().describe(&inputs));
+
+        self.prompter.read_input(&inputs).action().unwrap_or(Action::Stand)
+    }
+}
+
+/**
+ * The computer players a simulation can run, each built fresh for every worker
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerKind {
+    Basic,
+    Deviations,
+    MimicDealer,
+    NeverBust,
+    Random,
+}
+impl PlayerKind {
+    /**
+     * Parses a player by name: basic, deviations, mimic, never-bust or random
+     */
+    pub fn parse(name: &str) -> Option<PlayerKind> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "basic" => Some(PlayerKind::Basic),
+            "deviations" | "i18" => Some(PlayerKind::Deviations),
+            "mimic" | "mimic-dealer" => Some(PlayerKind::MimicDealer),
+            "never-bust" | "neverbust" => Some(PlayerKind::NeverBust),
+            "random" => Some(PlayerKind::Random),
+            _ => None,
+        }
+    }
+
+    /**
+     * Builds a player of this kind
+     * @param strategy the chart the basic strategy players follow
+     * @param seed the seed of a random player's choices
+     */
+    pub fn build(&self, strategy: &BlackjackBasicStrategy, seed: u64) -> Box<dyn PlayerStrategy> {
+        match self {
+            PlayerKind::Basic => Box::new(BasicStrategyPlayer { strategy: strategy.clone() }),
+            PlayerKind::Deviations => Box::new(DeviationPlayer::new(strategy.clone())),
+            PlayerKind::MimicDealer => Box::new(MimicDealerPlayer),
+            PlayerKind::NeverBust => Box::new(NeverBustPlayer),
+            PlayerKind::Random => Box::new(RandomPlayer::with_seed(seed)),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::blackjack::{Action, Blackjack, Card, Hand, Rules, Suit};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::countingsystem::{count_cards, HiLo};
+use crate::input::{Keymap, Prompter};
+use crate::playerstrategy::{
+    BasicStrategyPlayer, DeviationPlayer, HumanPlayer, MimicDealerPlayer, NeverBustPlayer, PlayerKind,
+    PlayerStrategy, RandomPlayer, RoundView, ShoeCount,
+};
+use std::io::Cursor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_ACTIONS: [Action; 4] = [Action::Hit, Action::Stand, Action::Double, Action::Split];
+
+    fn card(rank: i64) -> Card {
+        Card::new(Suit::Spades, rank)
+    }
+
+    // A view of a two card hand against an upcard at a true count
+    fn view<'a>(rules: &'a Rules, hands: &'a [Hand], upcard: &'a Card, true_count: f64) -> RoundView<'a> {
+        RoundView {
+            rules,
+            hands,
+            current_hand: 0,
+            dealer_upcard: Some(upcard),
+            seen_cards: &[],
+            running_count: true_count,
+            true_count,
+            bankroll: 100.0,
+            legal_actions: &ALL_ACTIONS,
+        }
+    }
+
+    #[test]
+    fn test_count_hides_the_hole_card() {
+        let mut game = Blackjack::with_seed(Rules::default(), 1);
+        let mut count = ShoeCount::new(&HiLo);
+        game.stack_cards(vec![card(5), card(6), card(10), card(2), card(5)]);
+        game.deal_cards();
+
+        let (running_count, true_count) = count.counts(&game);
+        assert_eq!(running_count, 1.0);
+        assert_eq!(true_count, 1.0 / ((game.cards_left() + 1) as f64 / 52.0));
+
+        game.act(Action::Stand);
+        game.play_dealers_hand();
+        // The hole card 2 and the dealer's 5 are seen once the player is done
+        assert_eq!(count.counts(&game).0, 3.0);
+
+        game.clear_table();
+        assert_eq!(game.get_discards().len(), 5);
+        assert_eq!(count.counts(&game).0, 3.0);
+    }
+
+    #[test]
+    fn test_count_restarts_after_reshuffle() {
+        let rules = Rules { penetration: 0.1, ..Rules::default() };
+        let mut game = Blackjack::with_seed(rules, 2);
+        let mut count = ShoeCount::new(&HiLo);
+
+        for _ in 0..100 {
+            game.clear_table();
+            let (running_count, _) = count.counts(&game);
+            let expected = count_cards(&HiLo, game.get_discards());
+            assert_eq!(running_count, expected);
+            game.deal_cards();
+            while game.is_players_turn() {
+                game.act(Action::Stand);
+            }
+            game.play_dealers_hand();
+        }
+    }
+
+    #[test]
+    fn test_basic_strategy_player() {
+        let rules = Rules::default();
+        let hands = [Hand::new(card(6), card(5))];
+        let upcard = card(6);
+        let mut player = BasicStrategyPlayer { strategy: BlackjackBasicStrategy::new() };
+
+        assert_eq!(player.action(&view(&rules, &hands, &upcard, 0.0)), Action::Double);
+        let no_double = RoundView { legal_actions: &[Action::Hit, Action::Stand], ..view(&rules, &hands, &upcard, 0.0) };
+        assert_eq!(player.action(&no_double), Action::Hit);
+        assert_eq!(player.bet(&no_double), 1.0);
+    }
+
+    #[test]
+    fn test_deviations_follow_the_true_count() {
+        let rules = Rules::default();
+        let sixteen = [Hand::new(card(10), card(6))];
+        let twelve = [Hand::new(card(10), card(2))];
+        let tens = [Hand::new(card(10), card(13))];
+        let mut player = DeviationPlayer::new(BlackjackBasicStrategy::new());
+
+        assert_eq!(player.action(&view(&rules, &sixteen, &card(10), -0.5)), Action::Hit);
+        assert_eq!(player.action(&view(&rules, &sixteen, &card(12), 0.0)), Action::Stand);
+        assert_eq!(player.action(&view(&rules, &twelve, &card(4), 1.0)), Action::Stand);
+        assert_eq!(player.action(&view(&rules, &twelve, &card(4), -0.5)), Action::Hit);
+        assert_eq!(player.action(&view(&rules, &tens, &card(6), 3.9)), Action::Stand);
+        assert_eq!(player.action(&view(&rules, &tens, &card(6), 4.0)), Action::Split);
+
+        assert_eq!(player.bet(&view(&rules, &[], &card(6), -3.0)), 1.0);
+        assert_eq!(player.bet(&view(&rules, &[], &card(6), 4.7)), 3.0);
+        assert_eq!(player.bet(&view(&rules, &[], &card(6), 20.0)), 8.0);
+    }
+
+    #[test]
+    fn test_mimic_dealer_and_never_bust() {
+        let h17 = Rules { dealer_hits_soft_17: true, ..Rules::default() };
+        let s17 = Rules::default();
+        let soft_17 = [Hand::new(card(1), card(6))];
+        let hard_12 = [Hand::new(card(10), card(2))];
+        let upcard = card(7);
+
+        assert_eq!(MimicDealerPlayer.action(&view(&h17, &soft_17, &upcard, 0.0)), Action::Hit);
+        assert_eq!(MimicDealerPlayer.action(&view(&s17, &soft_17, &upcard, 0.0)), Action::Stand);
+        assert_eq!(MimicDealerPlayer.action(&view(&s17, &hard_12, &upcard, 0.0)), Action::Hit);
+        assert_eq!(NeverBustPlayer.action(&view(&s17, &soft_17, &upcard, 0.0)), Action::Hit);
+        assert_eq!(NeverBustPlayer.action(&view(&s17, &hard_12, &upcard, 0.0)), Action::Stand);
+    }
+
+    #[test]
+    fn test_random_player_only_takes_legal_actions() {
+        let rules = Rules::default();
+        let hands = [Hand::new(card(9), card(3))];
+        let upcard = card(4);
+        let legal = [Action::Hit, Action::Stand];
+        let mut player = RandomPlayer::with_seed(6);
+        let mut taken = Vec::new();
+
+        for _ in 0..50 {
+            let action = player.action(&RoundView { legal_actions: &legal, ..view(&rules, &hands, &upcard, 0.0) });
+            assert!(legal.contains(&action));
+            taken.push(action);
+        }
+        assert!(taken.contains(&Action::Hit) && taken.contains(&Action::Stand));
+    }
+
+    #[test]
+    fn test_human_player() {
+        let rules = Rules::default();
+        let hands = [Hand::new(card(9), card(3))];
+        let upcard = card(4);
+        let legal = [Action::Hit, Action::Stand];
+        let round = RoundView { legal_actions: &legal, ..view(&rules, &hands, &upcard, 0.0) };
+
+        // Invalid bets and inputs that are not legal now are asked again, with the keys of the keymap
+        let answers = Cursor::new("lots\n-2\n3\nx\np\nH\n\n");
+        let mut player = HumanPlayer::new(Prompter::with_reader(Keymap::default(), Box::new(answers)));
+        assert_eq!(player.bet(&round), 3.0);
+        assert_eq!(player.action(&round), Action::Hit);
+        assert_eq!(player.bet(&round), 1.0);
+        // At the end of the input the player bets one unit and stands
+        assert_eq!(player.bet(&round), 1.0);
+        assert_eq!(player.action(&round), Action::Stand);
+
+        let mut player = HumanPlayer::new(Prompter::with_reader(Keymap::default(), Box::new(Cursor::new("q\nq\n"))));
+        assert_eq!(player.bet(&round), 1.0);
+        assert_eq!(player.action(&round), Action::Stand);
+    }
+
+    #[test]
+    fn test_player_kinds() {
+        let strategy = BlackjackBasicStrategy::new();
+
+        assert_eq!(PlayerKind::parse("Never_Bust"), Some(PlayerKind::NeverBust));
+        assert_eq!(PlayerKind::parse("mimic-dealer"), Some(PlayerKind::MimicDealer));
+        assert_eq!(PlayerKind::parse("card-counter"), None);
+        assert_eq!(PlayerKind::Random.build(&strategy, 1).name(), "random play");
+    }
+}
@@ -0,0 +1,453 @@
+use crate::blackjack::{Action, Blackjack, Card, Hand, HandOutcome, Rules};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+
+/**
+ * The version of the protocol. A client says which version it speaks in its hello, and the engine
+ * refuses any other version, so a change to the messages that breaks clients must change it.
+ */
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/**
+ * A request from the client, one JSON object per line, named by its "command" field
+ * Ex: {"command":"hello","version":1}, {"command":"bet","amount":5}, {"command":"action","action":"hit"}
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    // The handshake, which must come before any other request
+    Hello { version: u32 },
+    // Clears the table for the next round, which starts once a bet is placed
+    NewRound,
+    // Places the bet of the new round and deals it
+    Bet { amount: f64 },
+    // Plays an action on the current hand
+    Action { action: Action },
+    // Asks for a snapshot of the table without changing it
+    QueryState,
+    // Ends the session
+    Quit,
+}
+
+/**
+ * Something that happened at the table, in the order it happened
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    // The first two cards of the player's hand and the dealer's upcard
+    Dealt { player: Vec<Card>, upcard: Card },
+    // A card dealt to one of the player's hands
+    Card { hand: usize, card: Card },
+    // The player split a hand, and now has this many hands
+    Split { hands: usize },
+    // The dealer turned over the hole card
+    HoleCard { card: Card },
+    // A card the dealer drew
+    DealerCard { card: Card },
+    // The round was settled
+    RoundOver { results: Vec<HandResult>, net: f64, bankroll: f64 },
+}
+
+/**
+ * How one of the player's hands was settled
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandResult {
+    pub hand: usize,
+    pub outcome: HandOutcome,
+    // The amount won or lost on the hand, in the same units as the bet
+    pub net: f64,
+}
+
+/**
+ * What the table is waiting for
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    // No round has been started
+    Idle,
+    // A new round is waiting for its bet
+    Betting,
+    // The player is acting on their hands
+    Playing,
+    // The round has been settled and stays on the table until the next new round
+    Settled,
+}
+
+/**
+ * A player hand in a state snapshot
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandState {
+    pub cards: Vec<Card>,
+    pub value: i64,
+    pub soft: bool,
+    pub doubled: bool,
+    // How the hand was settled, once the round is over
+    pub outcome: Option<HandOutcome>,
+}
+impl HandState {
+    pub fn new(hand: &Hand, outcome: Option<HandOutcome>) -> Self {
+        HandState {
+            cards: hand.get_cards().to_vec(),
+            value: hand.get_value(),
+            soft: hand.is_soft(),
+            doubled: hand.is_doubled(),
+            outcome,
+        }
+    }
+}
+
+/**
+ * The dealer's hand in a state snapshot. While the player is acting only the upcard is shown.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DealerState {
+    pub cards: Vec<Card>,
+    // The number of cards face down
+    pub hidden: usize,
+    // The value of the cards that are face up
+    pub value: i64,
+}
+impl DealerState {
+    /**
+     * The dealer's hand as players see it: only the upcard while the hole card is face down
+     */
+    pub fn new(hand: &Hand, hole_card_hidden: bool) -> Self {
+        if hole_card_hidden {
+            let upcard = hand.get_cards()[0];
+            // An ace on its own counts 11
+            let value = if upcard.get_value() == 1 { 11 } else { upcard.get_value() as i64 };
+            DealerState { cards: vec![upcard], hidden: 1, value }
+        } else {
+            DealerState { cards: hand.get_cards().to_vec(), hidden: 0, value: hand.get_value() }
+        }
+    }
+}
+
+/**
+ * Everything a client can see at the table
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub phase: Phase,
+    // The number of rounds dealt this session
+    pub round: u32,
+    pub bankroll: f64,
+    // The bet of the round on the table
+    pub bet: Option<f64>,
+    pub hands: Vec<HandState>,
+    // The hand being played, while the player is acting
+    pub current_hand: Option<usize>,
+    pub dealer: Option<DealerState>,
+    pub legal_actions: Vec<Action>,
+    // Basic strategy's play for the current hand
+    pub recommended: Option<Action>,
+    pub cards_left: usize,
+}
+
+/**
+ * A reply from the engine, one JSON object per line, named by its "type" field. Every request is answered by
+ * the events it caused followed by a state snapshot, or by a single error that leaves the table as it was.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    // The answer to a hello in a version the engine speaks
+    Welcome { protocol_version: u32, engine: String, rules: Rules, bankroll: f64 },
+    Event(Event),
+    State(Snapshot),
+    Error { message: String },
+    // The answer to quit, after which nothing more is read
+    Goodbye,
+}
+
+/**
+ * The engine behind the protocol: a table with one player, their bankroll, and basic strategy for the
+ * recommendation in each snapshot
+ */
+pub struct ProtocolEngine {
+    pub(crate) game: Blackjack,
+    strategy: BlackjackBasicStrategy,
+    phase: Phase,
+    bankroll: f64,
+    bet: f64,
+    rounds: u32,
+    // True once the client has said hello in a version the engine speaks
+    greeted: bool,
+    finished: bool,
+}
+impl ProtocolEngine {
+    /**
+     * Constructs an engine
+     * @param game the game, under the table's rules
+     * @param strategy the chart the recommended plays are read from
+     * @param bankroll the player's starting bankroll
+     */
+    pub fn new(game: Blackjack, strategy: BlackjackBasicStrategy, bankroll: f64) -> Self {
+        ProtocolEngine {
+            game,
+            strategy,
+            phase: Phase::Idle,
+            bankroll,
+            bet: 0.0,
+            rounds: 0,
+            greeted: false,
+            finished: false,
+        }
+    }
+
+    /**
+     * Returns true once the client has quit
+     */
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /**
+     * Answers a line of the protocol with the lines of the reply
+     */
+    pub fn handle_line(&mut self, line: &str) -> Vec<String> {
+        let replies = match serde_json::from_str::<Request>(line) {
+            Ok(request) => self.handle(request),
+            Err(err) => vec![error(format!("invalid request: {}", err))],
+        };
+        replies
+            .iter()
+            .map(|reply| serde_json::to_string(reply).expect("replies are always serializable"))
+            .collect()
+    }
+
+    /**
+     * Answers a request
+     */
+    pub fn handle(&mut self, request: Request) -> Vec<Reply> {
+        let result = match (&request, self.greeted) {
+            (Request::Hello { version }, _) => return self.hello(*version),
+            (Request::Quit, _) => {
+                self.finished = true;
+                return vec![Reply::Goodbye];
+            }
+            (_, false) => return vec![error("say hello first".to_string())],
+            (Request::NewRound, true) => self.new_round(),
+            (Request::Bet { amount }, true) => self.place_bet(*amount),
+            (Request::Action { action }, true) => self.act(*action),
+            (Request::QueryState, true) => Ok(Vec::new()),
+        };
+        match result {
+            Ok(events) => {
+                let mut replies: Vec<Reply> = events.into_iter().map(Reply::Event).collect();
+                replies.push(Reply::State(self.snapshot()));
+                replies
+            }
+            Err(message) => vec![error(message)],
+        }
+    }
+
+    fn hello(&mut self, version: u32) -> Vec<Reply> {
+        if version != PROTOCOL_VERSION {
+            return vec![error(format!(
+                "unsupported protocol version {}, this engine speaks version {}",
+                version, PROTOCOL_VERSION
+            ))];
+        }
+        self.greeted = true;
+        vec![Reply::Welcome {
+            protocol_version: PROTOCOL_VERSION,
+            engine: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            rules: *self.game.get_rules(),
+            bankroll: self.bankroll,
+        }]
+    }
+
+    fn new_round(&mut self) -> Result<Vec<Event>, String> {
+        if self.phase == Phase::Playing {
+            return Err("finish the round first".to_string());
+        }
+        self.game.clear_table();
+        self.phase = Phase::Betting;
+        Ok(Vec::new())
+    }
+
+    fn place_bet(&mut self, amount: f64) -> Result<Vec<Event>, String> {
+        if self.phase != Phase::Betting {
+            return Err("start a new round before betting".to_string());
+        }
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!("invalid bet {}", amount));
+        }
+        if amount > self.bankroll {
+            return Err(format!("the bankroll of {} does not cover a bet of {}", self.bankroll, amount));
+        }
+
+        self.bet = amount;
+        self.rounds += 1;
+        self.game.deal_cards();
+        self.phase = Phase::Playing;
+        let player = self.game.get_players_hand().expect("the round was dealt").get_cards().to_vec();
+        let upcard = *self.game.get_dealers_upcard().expect("the round was dealt");
+        let mut events = vec![Event::Dealt { player, upcard }];
+
+        if !self.game.is_players_turn() {
+            events.extend(self.settle());
+        }
+        Ok(events)
+    }
+
+    fn act(&mut self, action: Action) -> Result<Vec<Event>, String> {
+        if self.phase != Phase::Playing {
+            return Err("there is no hand to play".to_string());
+        }
+        if !covered_actions(&self.game, self.bet, self.bankroll).contains(&action) {
+            return Err(format!("you cannot {} now", action));
+        }
+
+        let index = self.game.get_current_hand_index();
+        let before: Vec<usize> = self.game.get_players_hands().iter().map(|hand| hand.get_cards().len()).collect();
+        self.game.act(action);
+
+        let mut events = Vec::new();
+        let hands = self.game.get_players_hands();
+        if action == Action::Split {
+            events.push(Event::Split { hands: hands.len() });
+        }
+        let split = (action == Action::Split).then_some(index);
+        events.extend(new_cards(&before, hands, split).into_iter().map(|(hand, card)| Event::Card { hand, card }));
+
+        if !self.game.is_players_turn() {
+            events.extend(self.settle());
+        }
+        Ok(events)
+    }
+
+    // Plays the dealer's hand and settles every hand against it
+    fn settle(&mut self) -> Vec<Event> {
+        self.game.play_dealers_hand();
+        let dealer = self.game.get_dealers_hand().expect("the round was dealt").get_cards();
+        let mut events = vec![Event::HoleCard { card: dealer[1] }];
+        events.extend(dealer[2..].iter().map(|&card| Event::DealerCard { card }));
+
+        let results: Vec<HandResult> = (0..self.game.get_players_hands().len())
+            .map(|hand| HandResult { hand, outcome: self.game.outcome(hand), net: self.bet * self.game.net_result(hand) })
+            .collect();
+        let net = results.iter().map(|result| result.net).sum();
+        self.bankroll += net;
+        self.phase = Phase::Settled;
+        events.push(Event::RoundOver { results, net, bankroll: self.bankroll });
+        events
+    }
+
+    /**
+     * Returns what the client can see at the table
+     */
+    pub fn snapshot(&self) -> Snapshot {
+        let dealt = matches!(self.phase, Phase::Playing | Phase::Settled);
+        let playing = self.phase == Phase::Playing;
+
+        let hands = if dealt { self.game.get_players_hands() } else { &[] };
+        let hands = hands
+            .iter()
+            .enumerate()
+            .map(|(index, hand)| HandState::new(hand, (self.phase == Phase::Settled).then(|| self.game.outcome(index))))
+            .collect();
+        let dealer = self.game.get_dealers_hand().filter(|_| dealt).map(|hand| DealerState::new(hand, playing));
+
+        let (legal_actions, recommended) = match self.game.get_players_hand().filter(|_| playing) {
+            Some(hand) => {
+                let upcard = self.game.get_dealers_upcard().expect("the round was dealt").get_value();
+                let legal = covered_actions(&self.game, self.bet, self.bankroll);
+                let can = |action| legal.contains(&action);
+                let recommended = self.strategy.recommend(
+                    &hand.get_card_values(),
+                    upcard,
+                    can(Action::Double),
+                    can(Action::Split),
+                    can(Action::Surrender),
+                );
+                (legal, recommended)
+            }
+            None => (Vec::new(), None),
+        };
+
+        Snapshot {
+            phase: self.phase,
+            round: self.rounds,
+            bankroll: self.bankroll,
+            bet: dealt.then_some(self.bet),
+            hands,
+            current_hand: playing.then(|| self.game.get_current_hand_index()),
+            dealer,
+            legal_actions,
+            recommended,
+            cards_left: self.game.cards_left(),
+        }
+    }
+}
+
+/**
+ * Returns the cards an action dealt, as the index of the hand and the card, given the number of cards
+ * in each hand before it
+ * @param before the number of cards in each hand before the action
+ * @param hands the hands after the action
+ * @param split the index of the hand that was split, if the action was a split
+ */
+pub(crate) fn new_cards(before: &[usize], hands: &[Hand], split: Option<usize>) -> Vec<(usize, Card)> {
+    let mut cards = Vec::new();
+    for (number, hand) in hands.iter().enumerate() {
+        // Both hands of a split keep one card of the pair, and the hands after them moved up one place
+        let dealt = match split {
+            Some(index) if number == index || number == index + 1 => 1,
+            Some(index) if number > index + 1 => before[number - 1],
+            _ => before[number],
+        };
+        cards.extend(hand.get_cards()[dealt..].iter().map(|&card| (number, card)));
+    }
+    cards
+}
+
+/**
+ * Returns the legal actions on the current hand, leaving out double and split unless the bankroll covers
+ * another bet on top of the bets already on the hands of the seat whose turn it is
+ * @param game the game being played
+ * @param bet the bet on each of the seat's hands
+ * @param bankroll the seat's bankroll, which does not yet count the bets of the round
+ */
+pub(crate) fn covered_actions(game: &Blackjack, bet: f64, bankroll: f64) -> Vec<Action> {
+    let Some(seat) = game.get_current_seat() else { return Vec::new() };
+    let hands = game.get_players_hands().iter().filter(|hand| hand.get_seat() == seat);
+    let staked: f64 = hands.map(|hand| if hand.is_doubled() { 2.0 * bet } else { bet }).sum();
+    let covered = staked + bet <= bankroll;
+
+    game.legal_actions()
+        .into_iter()
+        .filter(|action| covered || !matches!(action, Action::Double | Action::Split))
+        .collect()
+}
+
+fn error(message: String) -> Reply {
+    Reply::Error { message }
+}
+
+/**
+ * Speaks the protocol until the client quits or the input ends: each line read is a request, and
+ * each reply is written as a line and flushed at once. Blank lines are ignored.
+ */
+pub fn run(engine: &mut ProtocolEngine, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        for reply in engine.handle_line(&line) {
+            writeln!(output, "{}", reply)?;
+        }
+        output.flush()?;
+        if engine.is_finished() {
+            break;
+        }
+    }
+    Ok(())
+}
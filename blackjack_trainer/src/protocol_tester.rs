@@ -0,0 +1,312 @@
+use crate::blackjack::{Blackjack, Card, Rules};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::protocol::{self, ProtocolEngine, Reply, Request, PROTOCOL_VERSION};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> ProtocolEngine {
+        ProtocolEngine::new(Blackjack::with_seed(Rules::default(), 7), BlackjackBasicStrategy::new(), 100.0)
+    }
+
+    // True if every field of the expected value is in the actual value. Arrays must have the same length.
+    fn matches(expected: &Value, actual: &Value) -> bool {
+        match (expected, actual) {
+            (Value::Object(expected), Value::Object(actual)) => expected
+                .iter()
+                .all(|(key, value)| actual.get(key).is_some_and(|actual| matches(value, actual))),
+            (Value::Array(expected), Value::Array(actual)) => {
+                expected.len() == actual.len() && expected.iter().zip(actual).all(|(expected, actual)| matches(expected, actual))
+            }
+            _ => expected == actual,
+        }
+    }
+
+    // Plays a transcript against a fresh engine. Each line is one of
+    //   > request        a line sent to the engine
+    //   < reply          the next line of the reply, which must have every field given here
+    //   = stack <cards>  the next cards dealt from the shoe
+    // and every line of a reply must be listed. A long reply can carry on over the lines that follow it.
+    // Blank lines and lines starting with # are ignored.
+    fn play(transcript: &str) {
+        let mut engine = engine();
+        let mut replies: VecDeque<String> = VecDeque::new();
+        let mut request = String::new();
+
+        // Each entry with the number of the line it starts on
+        let mut entries: Vec<(usize, String)> = Vec::new();
+        for (number, line) in transcript.lines().map(str::trim).enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match entries.last_mut() {
+                Some((_, entry)) if !line.starts_with(['>', '<', '=']) => entry.push_str(line),
+                _ => entries.push((number + 1, line.to_string())),
+            }
+        }
+
+        for (number, line) in entries {
+            let line = line.as_str();
+            if line.starts_with('>') || line.starts_with('=') {
+                assert!(replies.is_empty(), "line {}: unexpected reply to {}: {:?}", number, request, replies);
+            }
+            if let Some(line) = line.strip_prefix('>') {
+                request = line.trim().to_string();
+                replies.extend(engine.handle_line(&request));
+            } else if let Some(line) = line.strip_prefix('<') {
+                let expected: Value = serde_json::from_str(line).unwrap_or_else(|err| panic!("line {}: {}", number, err));
+                let reply = replies.pop_front().unwrap_or_else(|| panic!("line {}: no reply to {}", number, request));
+                let actual: Value = serde_json::from_str(&reply).unwrap();
+                assert!(matches(&expected, &actual), "line {}: expected {}\n got {}", number, expected, actual);
+            } else if let Some(cards) = line.strip_prefix("= stack") {
+                engine.game.stack_cards(cards.split_whitespace().map(|card| card.parse::<Card>().unwrap()).collect());
+            } else {
+                panic!("line {}: unknown line {}", number, line);
+            }
+        }
+        assert!(replies.is_empty(), "unexpected reply to {}: {:?}", request, replies);
+    }
+
+    #[test]
+    fn test_handshake() {
+        play(
+            r#"
+            # Nothing but hello and quit is answered before the handshake
+            > {"command":"query_state"}
+            < {"type":"error","message":"say hello first"}
+            > {"command":"hello","version":99}
+            < {"type":"error","message":"unsupported protocol version 99, this engine speaks version 1"}
+            > {"command":"hello","version":1}
+            < {"type":"welcome","protocol_version":1,"rules":{"decks":6,"dealer_hits_soft_17":false},"bankroll":100.0}
+            > {"command":"query_state"}
+            < {"type":"state","phase":"idle","round":0,"bankroll":100.0,"bet":null,"hands":[],"dealer":null,"legal_actions":[]}
+            > {"command":"quit"}
+            < {"type":"goodbye"}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_round_played_out() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > {"command":"new_round"}
+            < {"type":"state","phase":"betting","hands":[]}
+            = stack 10H 6S 10C 7D 5C
+            > {"command":"bet","amount":2}
+            < {"type":"event","event":"dealt","player":["10H","6S"],"upcard":"10C"}
+            < {"type":"state","phase":"playing","round":1,"bet":2.0,"current_hand":0,
+               "hands":[{"cards":["10H","6S"],"value":16,"soft":false,"outcome":null}],
+               "dealer":{"cards":["10C"],"hidden":1,"value":10},
+               "legal_actions":["hit","stand","double"],"recommended":"hit"}
+            > {"command":"action","action":"hit"}
+            < {"type":"event","event":"card","hand":0,"card":"5C"}
+            < {"type":"event","event":"hole_card","card":"7D"}
+            < {"type":"event","event":"round_over","results":[{"hand":0,"outcome":"win","net":2.0}],"net":2.0,"bankroll":102.0}
+            < {"type":"state","phase":"settled","bankroll":102.0,"current_hand":null,"legal_actions":[],
+               "hands":[{"cards":["10H","6S","5C"],"value":21,"outcome":"win"}],
+               "dealer":{"cards":["10C","7D"],"hidden":0,"value":17}}
+            # The settled round stays on the table until the next one
+            > {"command":"query_state"}
+            < {"type":"state","phase":"settled","round":1}
+            > {"command":"new_round"}
+            < {"type":"state","phase":"betting","round":1,"bet":null,"dealer":null}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_dealer_draws_and_double() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > {"command":"new_round"}
+            < {"type":"state"}
+            = stack 6H 5S 6C 10D 9C 2S
+            > {"command":"bet","amount":10}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","recommended":"double"}
+            > {"command":"action","action":"double"}
+            < {"type":"event","event":"card","hand":0,"card":"9C"}
+            < {"type":"event","event":"hole_card","card":"10D"}
+            < {"type":"event","event":"dealer_card","card":"2S"}
+            < {"type":"event","event":"round_over","results":[{"hand":0,"outcome":"win","net":20.0}],"bankroll":120.0}
+            < {"type":"state","hands":[{"cards":["6H","5S","9C"],"value":20,"doubled":true}],"dealer":{"value":18}}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_blackjack_settles_at_the_deal() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > {"command":"new_round"}
+            < {"type":"state"}
+            = stack AH KS 9C 10D
+            > {"command":"bet","amount":4}
+            < {"type":"event","event":"dealt","player":["AH","KS"],"upcard":"9C"}
+            < {"type":"event","event":"hole_card","card":"10D"}
+            < {"type":"event","event":"round_over","results":[{"hand":0,"outcome":"blackjack","net":6.0}],"net":6.0,"bankroll":106.0}
+            < {"type":"state","phase":"settled","bankroll":106.0}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > {"command":"new_round"}
+            < {"type":"state"}
+            = stack 8H 8S 10C 7D 3C 10H 10S
+            > {"command":"bet","amount":1}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","legal_actions":["hit","stand","double","split"],"recommended":"split"}
+            > {"command":"action","action":"split"}
+            < {"type":"event","event":"split","hands":2}
+            < {"type":"event","event":"card","hand":0,"card":"3C"}
+            < {"type":"state","current_hand":0,"hands":[{"cards":["8H","3C"]},{"cards":["8S"]}]}
+            > {"command":"action","action":"hit"}
+            < {"type":"event","event":"card","hand":0,"card":"10H"}
+            < {"type":"event","event":"card","hand":1,"card":"10S"}
+            < {"type":"state","current_hand":1,"hands":[{"value":21},{"cards":["8S","10S"],"value":18}]}
+            > {"command":"action","action":"stand"}
+            < {"type":"event","event":"hole_card","card":"7D"}
+            < {"type":"event","event":"round_over","results":[{"hand":0,"outcome":"win"},{"hand":1,"outcome":"win"}],"net":2.0}
+            < {"type":"state","phase":"settled"}
+            "#,
+        );
+        // Splitting the first hand again moves the second hand up one place, and only the new cards are dealt
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > {"command":"new_round"}
+            < {"type":"state"}
+            = stack 8H 8S 10C 7D 8C 3H 2C 10D
+            > {"command":"bet","amount":1}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","cards_left":308}
+            > {"command":"action","action":"split"}
+            < {"type":"event","event":"split","hands":2}
+            < {"type":"event","event":"card","hand":0,"card":"8C"}
+            < {"type":"state","cards_left":307,"hands":[{"cards":["8H","8C"]},{"cards":["8S"]}]}
+            > {"command":"action","action":"split"}
+            < {"type":"event","event":"split","hands":3}
+            < {"type":"event","event":"card","hand":0,"card":"3H"}
+            < {"type":"state","cards_left":306,"current_hand":0,"hands":[{"cards":["8H","3H"]},{"cards":["8C"]},{"cards":["8S"]}]}
+            > {"command":"action","action":"stand"}
+            < {"type":"event","event":"card","hand":1,"card":"2C"}
+            < {"type":"state","cards_left":305,"current_hand":1}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_double_and_split_need_the_bankroll_to_cover_another_bet() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome","bankroll":100.0}
+            > {"command":"new_round"}
+            < {"type":"state"}
+            # A bet of 60 leaves 40, too little to split or double
+            = stack 8H 8S 6C 10D 10H
+            > {"command":"bet","amount":60}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","legal_actions":["hit","stand"],"recommended":"stand"}
+            > {"command":"action","action":"split"}
+            < {"type":"error","message":"you cannot split now"}
+            > {"command":"action","action":"double"}
+            < {"type":"error","message":"you cannot double now"}
+            > {"command":"action","action":"stand"}
+            < {"type":"event","event":"hole_card","card":"10D"}
+            < {"type":"event","event":"dealer_card","card":"10H"}
+            < {"type":"event","event":"round_over","net":60.0,"bankroll":160.0}
+            < {"type":"state","phase":"settled"}
+            # A bet of 80 can be split, but then neither hand can be doubled
+            > {"command":"new_round"}
+            < {"type":"state"}
+            = stack 8H 8S 6C 10D 3C 3H 10S 10C
+            > {"command":"bet","amount":80}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","legal_actions":["hit","stand","double","split"]}
+            > {"command":"action","action":"split"}
+            < {"type":"event","event":"split","hands":2}
+            < {"type":"event","event":"card","hand":0,"card":"3C"}
+            < {"type":"state","legal_actions":["hit","stand"],"recommended":"hit","hands":[{"value":11},{"value":8}]}
+            > {"command":"action","action":"double"}
+            < {"type":"error","message":"you cannot double now"}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_errors_leave_the_table_unchanged() {
+        play(
+            r#"
+            > {"command":"hello","version":1}
+            < {"type":"welcome"}
+            > not json
+            < {"type":"error"}
+            > {"command":"fold"}
+            < {"type":"error"}
+            > {"command":"bet"}
+            < {"type":"error"}
+            > {"command":"bet","amount":1}
+            < {"type":"error","message":"start a new round before betting"}
+            > {"command":"action","action":"hit"}
+            < {"type":"error","message":"there is no hand to play"}
+            > {"command":"new_round"}
+            < {"type":"state","phase":"betting"}
+            > {"command":"bet","amount":101}
+            < {"type":"error","message":"the bankroll of 100 does not cover a bet of 101"}
+            > {"command":"bet","amount":-1}
+            < {"type":"error","message":"invalid bet -1"}
+            = stack 10H 6S 10C 7D
+            > {"command":"bet","amount":1}
+            < {"type":"event","event":"dealt"}
+            < {"type":"state","phase":"playing"}
+            > {"command":"action","action":"split"}
+            < {"type":"error","message":"you cannot split now"}
+            > {"command":"new_round"}
+            < {"type":"error","message":"finish the round first"}
+            > {"command":"query_state"}
+            < {"type":"state","phase":"playing","hands":[{"cards":["10H","6S"]}]}
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_run_stops_at_quit() {
+        let input = [
+            serde_json::to_string(&Request::Hello { version: PROTOCOL_VERSION }).unwrap(),
+            String::new(),
+            serde_json::to_string(&Request::Quit).unwrap(),
+            serde_json::to_string(&Request::QueryState).unwrap(),
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        let mut engine = engine();
+        protocol::run(&mut engine, Cursor::new(input), &mut output).unwrap();
+
+        // Each reply is one line, and nothing after quit is answered
+        let replies: Vec<Reply> =
+            String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(replies.len(), 2);
+        assert!(matches!(replies[0], Reply::Welcome { protocol_version: PROTOCOL_VERSION, .. }));
+        assert_eq!(replies[1], Reply::Goodbye);
+        assert!(engine.is_finished());
+    }
+}
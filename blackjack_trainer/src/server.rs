@@ -0,0 +1,856 @@
+use crate::blackjack::{Action, Blackjack, Card, HandOutcome, Rules};
+use crate::cli::TableFlags;
+use crate::protocol::{self, DealerState, HandState, Phase};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/**
+ * The version of the table protocol, which a client gives in its hello like the engine's protocol
+ */
+pub const TABLE_PROTOCOL_VERSION: u32 = 1;
+
+// A write to a client that takes longer than this drops the client, so one stalled client cannot hold up the table
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/**
+ * A request from a client of the table, one JSON object per line, named by its "command" field
+ * Ex: {"command":"hello","version":1,"name":"Ann"}, {"command":"join"}, {"command":"bet","amount":5}
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum TableRequest {
+    // The handshake, after which the client watches the table
+    Hello { version: u32, name: String },
+    // Takes a seat, the one given or the first free one
+    Join {
+        #[serde(default)]
+        seat: Option<usize>,
+    },
+    // Takes back the seat of a connection that dropped, with the token given when it was joined
+    Rejoin { token: String },
+    // Places the bet of the next round
+    Bet { amount: f64 },
+    // Plays an action on the hand whose turn it is
+    Action { action: Action },
+    // Gives up the seat and goes back to watching
+    Leave,
+    // Asks for a snapshot of the table without changing it
+    QueryState,
+    // Gives up the seat and ends the connection
+    Quit,
+}
+
+/**
+ * Something that happened at the table, sent to every client in the order it happened.
+ * Seats are numbered from 0, and a seat's hands from 0 in the order they are played.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TableEvent {
+    PlayerJoined { seat: usize, name: String },
+    // The seat is free again: the player left or did not reconnect in time
+    PlayerLeft { seat: usize, name: String },
+    // The player's connection dropped, and the seat is kept for them until the reconnect timeout
+    PlayerDisconnected { seat: usize, name: String },
+    PlayerReconnected { seat: usize, name: String },
+    // Bets for the round are taken
+    BettingOpen { round: u32 },
+    BetPlaced { seat: usize, amount: f64 },
+    // The first two cards of every seat with a bet and the dealer's upcard
+    Dealt { seats: Vec<SeatCards>, upcard: Card },
+    // A decision is awaited on the seat's hand, for at most the given number of seconds
+    Turn { seat: usize, hand: usize, seconds: f64 },
+    // The seat played an action; the table plays stand for a player who runs out of time or leaves
+    Acted { seat: usize, hand: usize, action: Action, automatic: bool },
+    // The seat split a hand, and now has this many hands
+    Split { seat: usize, hands: usize },
+    Card { seat: usize, hand: usize, card: Card },
+    HoleCard { card: Card },
+    DealerCard { card: Card },
+    RoundOver { round: u32, results: Vec<SeatResult> },
+}
+
+/**
+ * The cards dealt to a seat at the start of a round
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeatCards {
+    pub seat: usize,
+    pub cards: Vec<Card>,
+}
+
+/**
+ * How a seat's hands were settled
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeatResult {
+    pub seat: usize,
+    pub name: String,
+    pub outcomes: Vec<HandOutcome>,
+    // The amount won or lost over the seat's hands
+    pub net: f64,
+    pub bankroll: f64,
+}
+
+/**
+ * A seat in a state snapshot
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeatState {
+    pub name: String,
+    pub bankroll: f64,
+    pub bet: Option<f64>,
+    // False while the player's connection is down
+    pub connected: bool,
+    pub hands: Vec<HandState>,
+}
+
+/**
+ * The hand a decision is awaited on
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnState {
+    pub seat: usize,
+    pub hand: usize,
+}
+
+/**
+ * Everything a client can see at the table. The round stays on the table until the next one is dealt.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableSnapshot {
+    // Betting between rounds, or playing while hands are dealt
+    pub phase: Phase,
+    // The number of rounds dealt
+    pub round: u32,
+    // The seat of the client the snapshot was sent to, if it has one
+    pub you: Option<usize>,
+    pub seats: Vec<Option<SeatState>>,
+    pub dealer: Option<DealerState>,
+    pub turn: Option<TurnState>,
+    // The actions the player whose turn it is can take
+    pub legal_actions: Vec<Action>,
+    pub spectators: usize,
+    pub cards_left: usize,
+}
+
+/**
+ * A reply from the table, one JSON object per line, named by its "type" field. A request is answered by
+ * its own replies, then the events it caused and a state snapshot go to every client; a request that
+ * fails is answered by a single error.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TableReply {
+    Welcome { protocol_version: u32, rules: Rules, seats: usize },
+    // The client sits in the seat; the token takes the seat back after the connection drops
+    Seated { seat: usize, token: String, bankroll: f64 },
+    Event(TableEvent),
+    State(TableSnapshot),
+    Error { message: String },
+    Goodbye,
+}
+
+/**
+ * The settings of a table server
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerConfig {
+    // The port on 127.0.0.1 the server listens on, 0 for any free port
+    pub port: u16,
+    pub seats: usize,
+    // How long betting stays open after the first bet for the players who have not bet
+    pub bet_timeout: Duration,
+    // How long a player has for each decision before the table stands for them
+    pub action_timeout: Duration,
+    // How long a seat is kept for a player whose connection dropped
+    pub reconnect_timeout: Duration,
+    // The bankroll each player sits down with
+    pub bankroll: f64,
+}
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            port: 7021,
+            seats: 5,
+            bet_timeout: Duration::from_secs(20),
+            action_timeout: Duration::from_secs(30),
+            reconnect_timeout: Duration::from_secs(60),
+            bankroll: 100.0,
+        }
+    }
+}
+impl ServerConfig {
+    /**
+     * Parses the serve command's options, applying the table flags
+     * Ex: ["port", "7021", "seats", "3", "action-timeout", "15"]
+     */
+    pub fn parse_with(args: &[String], flags: &TableFlags) -> Result<ServerConfig, String> {
+        let mut config = ServerConfig::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let text = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let seconds = || {
+                text.parse().ok().filter(|&seconds: &f64| seconds > 0.0 && seconds.is_finite()).map(Duration::from_secs_f64)
+                    .ok_or_else(|| format!("invalid {} {}", arg, text))
+            };
+            match arg.as_str() {
+                "port" => config.port = text.parse().map_err(|_| format!("invalid port {}", text))?,
+                "seats" => {
+                    config.seats = text.parse().ok().filter(|seats| (1..=7).contains(seats))
+                        .ok_or_else(|| format!("invalid number of seats {}, a table has 1 to 7", text))?;
+                }
+                "bet-timeout" => config.bet_timeout = seconds()?,
+                "action-timeout" => config.action_timeout = seconds()?,
+                "reconnect-timeout" => config.reconnect_timeout = seconds()?,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        if let Some(bankroll) = flags.bankroll {
+            config.bankroll = bankroll;
+        }
+        Ok(config)
+    }
+}
+
+// What the connection threads tell the table thread
+enum Message {
+    Connected { client: u64, stream: TcpStream },
+    Line { client: u64, line: String },
+    Disconnected { client: u64 },
+    Shutdown,
+}
+
+/**
+ * A running table server, which stops when shut down
+ */
+pub struct ServerHandle {
+    address: SocketAddr,
+    messages: Sender<Message>,
+    stopping: Arc<AtomicBool>,
+    acceptor: JoinHandle<()>,
+    table: JoinHandle<()>,
+}
+impl ServerHandle {
+    /**
+     * Returns the address clients connect to
+     */
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /**
+     * Waits until the server stops, which it does when shut down, or returns an error if the table failed.
+     * A table that fails closes its clients' connections and stops taking new ones.
+     */
+    pub fn wait(self) -> Result<(), String> {
+        let ServerHandle { address, messages, stopping, acceptor, table } = self;
+        let table = table.join();
+        stop(address, &messages, &stopping);
+        let _ = acceptor.join();
+        table.map_err(|panic| {
+            let message = panic.downcast_ref::<&str>().map(|message| message.to_string()).or_else(|| panic.downcast_ref::<String>().cloned());
+            format!("The table stopped: {}", message.unwrap_or_else(|| "unknown error".to_string()))
+        })
+    }
+
+    /**
+     * Says goodbye to every client, closes their connections and stops the server
+     */
+    pub fn shutdown(self) {
+        stop(self.address, &self.messages, &self.stopping);
+        let _ = self.wait();
+    }
+}
+
+// Tells the table and the acceptor to stop
+fn stop(address: SocketAddr, messages: &Sender<Message>, stopping: &AtomicBool) {
+    stopping.store(true, Ordering::SeqCst);
+    let _ = messages.send(Message::Shutdown);
+    // Wakes the acceptor, which is waiting for a connection
+    let _ = TcpStream::connect(address);
+}
+
+/**
+ * Starts a server hosting a table on 127.0.0.1. Each connection is read on its own thread,
+ * and a single thread runs the table, so requests are handled one at a time in the order they arrive.
+ * @param config the table's settings
+ * @param game the game dealt at the table, under the table's rules
+ */
+pub fn start(config: ServerConfig, game: Blackjack) -> io::Result<ServerHandle> {
+    let listener = TcpListener::bind(("127.0.0.1", config.port))?;
+    let address = listener.local_addr()?;
+    let (messages, inbox) = mpsc::channel();
+    let stopping = Arc::new(AtomicBool::new(false));
+
+    let acceptor = {
+        let messages = messages.clone();
+        let stopping = Arc::clone(&stopping);
+        thread::spawn(move || accept(listener, messages, stopping))
+    };
+    let table = thread::spawn(move || Table::new(config, game).run(inbox));
+    Ok(ServerHandle { address, messages, stopping, acceptor, table })
+}
+
+// Hands each connection to the table and starts a thread reading its lines
+fn accept(listener: TcpListener, messages: Sender<Message>, stopping: Arc<AtomicBool>) {
+    for (client, stream) in (0..).zip(listener.incoming()) {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else { continue };
+        let Ok(reader) = stream.try_clone() else { continue };
+        let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        if messages.send(Message::Connected { client, stream }).is_err() {
+            break;
+        }
+
+        let messages = messages.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                if !line.trim().is_empty() && messages.send(Message::Line { client, line }).is_err() {
+                    return;
+                }
+            }
+            let _ = messages.send(Message::Disconnected { client });
+        });
+    }
+}
+
+// A connection to the table
+struct Client {
+    stream: TcpStream,
+    name: String,
+    // True once the client has said hello in a version the table speaks; only then is it sent events
+    greeted: bool,
+    seat: Option<usize>,
+}
+
+// A player sitting at the table, who keeps the seat while their connection is down
+struct Seat {
+    name: String,
+    token: String,
+    bankroll: f64,
+    bet: Option<f64>,
+    client: Option<u64>,
+    // When the seat is given up if the player has not reconnected
+    reconnect_deadline: Option<Instant>,
+    // True if the seat was dealt into the round on the table
+    dealt: bool,
+    // True once the player has gone but their hands are still being played; the seat is freed when the round is over
+    leaving: bool,
+}
+
+struct Table {
+    config: ServerConfig,
+    game: Blackjack,
+    clients: HashMap<u64, Client>,
+    seats: Vec<Option<Seat>>,
+    phase: Phase,
+    rounds: u32,
+    // The seat of each hand the game dealt, by the game's own seat number
+    dealt_in: Vec<usize>,
+    bet_deadline: Option<Instant>,
+    action_deadline: Option<Instant>,
+    // Events of the request being handled, sent to every client once it is done
+    events: Vec<TableEvent>,
+    // Clients whose connection failed while being written to
+    dropped: Vec<u64>,
+}
+impl Table {
+    fn new(config: ServerConfig, game: Blackjack) -> Self {
+        Table {
+            config,
+            game,
+            clients: HashMap::new(),
+            seats: (0..config.seats).map(|_| None).collect(),
+            phase: Phase::Betting,
+            rounds: 0,
+            dealt_in: Vec::new(),
+            bet_deadline: None,
+            action_deadline: None,
+            events: Vec::new(),
+            dropped: Vec::new(),
+        }
+    }
+
+    fn run(mut self, inbox: Receiver<Message>) {
+        loop {
+            let message = match self.next_deadline() {
+                Some(deadline) => inbox.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => inbox.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let now = Instant::now();
+            match message {
+                Ok(Message::Connected { client, stream }) => {
+                    self.clients.insert(client, Client { stream, name: String::new(), greeted: false, seat: None });
+                }
+                Ok(Message::Line { client, line }) => self.handle_line(client, &line, now),
+                Ok(Message::Disconnected { client }) => self.disconnect(client, now),
+                Ok(Message::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => self.expire(now),
+            }
+            self.flush_events();
+            while let Some(client) = self.dropped.pop() {
+                self.disconnect(client, now);
+                self.flush_events();
+            }
+        }
+
+        for client in self.clients.values_mut() {
+            let _ = write_reply(&mut client.stream, &TableReply::Goodbye);
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let reconnects = self.seats.iter().flatten().filter_map(|seat| seat.reconnect_deadline);
+        reconnects.chain(self.bet_deadline).chain(self.action_deadline).min()
+    }
+
+    fn handle_line(&mut self, client: u64, line: &str, now: Instant) {
+        let result = serde_json::from_str::<TableRequest>(line)
+            .map_err(|err| format!("invalid request: {}", err))
+            .and_then(|request| self.handle(client, request, now));
+        match result {
+            Ok(replies) => {
+                let answered_with_state = !self.events.is_empty();
+                for reply in &replies {
+                    self.send(client, reply);
+                }
+                // A request that changed nothing is answered with the state on its own
+                let quit = replies.last() == Some(&TableReply::Goodbye);
+                if !answered_with_state && !quit && self.clients.get(&client).is_some_and(|client| client.greeted) {
+                    let state = TableReply::State(self.snapshot(client));
+                    self.send(client, &state);
+                }
+                if quit {
+                    self.forget(client);
+                }
+            }
+            Err(message) => self.send(client, &TableReply::Error { message }),
+        }
+    }
+
+    fn handle(&mut self, client: u64, request: TableRequest, now: Instant) -> Result<Vec<TableReply>, String> {
+        let greeted = self.clients.get(&client).is_some_and(|client| client.greeted);
+        match request {
+            TableRequest::Hello { version, name } => self.hello(client, version, name),
+            TableRequest::Quit => {
+                self.leave(client, now);
+                Ok(vec![TableReply::Goodbye])
+            }
+            _ if !greeted => Err("say hello first".to_string()),
+            TableRequest::Join { seat } => self.join(client, seat),
+            TableRequest::Rejoin { token } => self.rejoin(client, &token),
+            TableRequest::Bet { amount } => self.place_bet(client, amount, now).map(|_| Vec::new()),
+            TableRequest::Action { action } => self.act(client, action, now).map(|_| Vec::new()),
+            TableRequest::Leave => {
+                self.seat_of(client)?;
+                self.leave(client, now);
+                Ok(Vec::new())
+            }
+            TableRequest::QueryState => Ok(Vec::new()),
+        }
+    }
+
+    fn hello(&mut self, client: u64, version: u32, name: String) -> Result<Vec<TableReply>, String> {
+        if version != TABLE_PROTOCOL_VERSION {
+            return Err(format!(
+                "unsupported protocol version {}, this table speaks version {}",
+                version, TABLE_PROTOCOL_VERSION
+            ));
+        }
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("a name is needed to sit at the table".to_string());
+        }
+        let connection = self.clients.get_mut(&client).expect("a connected client");
+        connection.name = name;
+        connection.greeted = true;
+        Ok(vec![TableReply::Welcome {
+            protocol_version: TABLE_PROTOCOL_VERSION,
+            rules: *self.game.get_rules(),
+            seats: self.config.seats,
+        }])
+    }
+
+    fn join(&mut self, client: u64, seat: Option<usize>) -> Result<Vec<TableReply>, String> {
+        if let Some(seat) = self.clients[&client].seat {
+            return Err(format!("you already sit in seat {}", seat));
+        }
+        let seat = match seat {
+            Some(seat) if seat >= self.seats.len() => return Err(format!("there is no seat {}", seat)),
+            Some(seat) if self.seats[seat].is_some() => return Err(format!("seat {} is taken", seat)),
+            Some(seat) => seat,
+            None => self.seats.iter().position(Option::is_none).ok_or("the table is full")?,
+        };
+
+        let name = self.clients[&client].name.clone();
+        let token = format!("{:016x}", rand::random::<u64>());
+        let bankroll = self.config.bankroll;
+        self.seats[seat] = Some(Seat {
+            name: name.clone(),
+            token: token.clone(),
+            bankroll,
+            bet: None,
+            client: Some(client),
+            reconnect_deadline: None,
+            dealt: false,
+            leaving: false,
+        });
+        self.clients.get_mut(&client).expect("a connected client").seat = Some(seat);
+        self.events.push(TableEvent::PlayerJoined { seat, name });
+        Ok(vec![TableReply::Seated { seat, token, bankroll }])
+    }
+
+    fn rejoin(&mut self, client: u64, token: &str) -> Result<Vec<TableReply>, String> {
+        if let Some(seat) = self.clients[&client].seat {
+            return Err(format!("you already sit in seat {}", seat));
+        }
+        let number = self
+            .seats
+            .iter()
+            .position(|seat| seat.as_ref().is_some_and(|seat| seat.token == token && !seat.leaving))
+            .ok_or("no seat is kept for that token")?;
+
+        let seat = self.seats[number].as_mut().expect("a taken seat");
+        // The token is the player's key to the seat, so a connection that has not noticed it dropped is replaced
+        if let Some(old) = seat.client.replace(client) {
+            if let Some(old) = self.clients.get_mut(&old) {
+                old.seat = None;
+                let _ = old.stream.shutdown(Shutdown::Both);
+            }
+        }
+        seat.reconnect_deadline = None;
+        let reply = TableReply::Seated { seat: number, token: seat.token.clone(), bankroll: seat.bankroll };
+        let name = seat.name.clone();
+
+        let connection = self.clients.get_mut(&client).expect("a connected client");
+        connection.seat = Some(number);
+        connection.name = name.clone();
+        self.events.push(TableEvent::PlayerReconnected { seat: number, name });
+        Ok(vec![reply])
+    }
+
+    fn seat_of(&self, client: u64) -> Result<usize, String> {
+        self.clients[&client].seat.ok_or_else(|| "you do not have a seat".to_string())
+    }
+
+    fn place_bet(&mut self, client: u64, amount: f64, now: Instant) -> Result<(), String> {
+        let number = self.seat_of(client)?;
+        if self.phase != Phase::Betting {
+            return Err("betting is closed until the round is over".to_string());
+        }
+        let seat = self.seats[number].as_mut().expect("a taken seat");
+        if seat.bet.is_some() {
+            return Err("you have already bet this round".to_string());
+        }
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!("invalid bet {}", amount));
+        }
+        if amount > seat.bankroll {
+            return Err(format!("the bankroll of {} does not cover a bet of {}", seat.bankroll, amount));
+        }
+
+        seat.bet = Some(amount);
+        self.events.push(TableEvent::BetPlaced { seat: number, amount });
+        if self.bet_deadline.is_none() {
+            self.bet_deadline = Some(now + self.config.bet_timeout);
+        }
+        self.close_betting_if_ready(now);
+        Ok(())
+    }
+
+    // Deals as soon as every player who is here and can bet has bet
+    fn close_betting_if_ready(&mut self, now: Instant) {
+        if self.phase != Phase::Betting {
+            return;
+        }
+        let mut present = self.seats.iter().flatten().filter(|seat| seat.client.is_some() && seat.bankroll > 0.0);
+        let any_bet = self.seats.iter().flatten().any(|seat| seat.bet.is_some());
+        if any_bet && present.all(|seat| seat.bet.is_some()) {
+            self.deal(now);
+        }
+    }
+
+    fn deal(&mut self, now: Instant) {
+        self.bet_deadline = None;
+        self.dealt_in = (0..self.seats.len())
+            .filter(|&number| self.seats[number].as_ref().is_some_and(|seat| seat.bet.is_some()))
+            .collect();
+        if self.dealt_in.is_empty() {
+            return;
+        }
+
+        for (number, seat) in self.seats.iter_mut().enumerate() {
+            if let Some(seat) = seat {
+                seat.dealt = self.dealt_in.contains(&number);
+            }
+        }
+        self.rounds += 1;
+        self.phase = Phase::Playing;
+        self.game.deal_seats(self.dealt_in.len());
+        let seats = self
+            .game
+            .get_players_hands()
+            .iter()
+            .map(|hand| SeatCards { seat: self.dealt_in[hand.get_seat()], cards: hand.get_cards().to_vec() })
+            .collect();
+        let upcard = *self.game.get_dealers_upcard().expect("the round was dealt");
+        self.events.push(TableEvent::Dealt { seats, upcard });
+        self.next_turn(now);
+    }
+
+    // The seat and the seat's own number of the game's hand
+    fn locate(&self, index: usize) -> TurnState {
+        let hands = self.game.get_players_hands();
+        let seat = hands[index].get_seat();
+        let hand = hands[..index].iter().filter(|hand| hand.get_seat() == seat).count();
+        TurnState { seat: self.dealt_in[seat], hand }
+    }
+
+    // The hand a decision is awaited on, while the round is being played
+    fn turn(&self) -> Option<TurnState> {
+        self.game.get_current_seat().filter(|_| self.phase == Phase::Playing).map(|_| self.locate(self.game.get_current_hand_index()))
+    }
+
+    // Announces whose turn it is, playing stand for players who have left, or settles the round once every hand is played
+    fn next_turn(&mut self, now: Instant) {
+        while let Some(turn) = self.turn() {
+            if self.seats[turn.seat].as_ref().is_some_and(|seat| seat.leaving) {
+                self.play(Action::Stand, true);
+                continue;
+            }
+            let seconds = self.config.action_timeout.as_secs_f64();
+            self.events.push(TableEvent::Turn { seat: turn.seat, hand: turn.hand, seconds });
+            self.action_deadline = Some(now + self.config.action_timeout);
+            return;
+        }
+        self.action_deadline = None;
+        self.settle();
+    }
+
+    // The actions the player whose turn it is can take, double and split only if their bankroll covers another bet
+    fn legal_actions(&self) -> Vec<Action> {
+        let Some(seat) = self.turn().and_then(|turn| self.seats[turn.seat].as_ref()) else { return Vec::new() };
+        protocol::covered_actions(&self.game, seat.bet.unwrap_or(0.0), seat.bankroll)
+    }
+
+    fn act(&mut self, client: u64, action: Action, now: Instant) -> Result<(), String> {
+        let number = self.seat_of(client)?;
+        let turn = self.turn().ok_or("there is no hand to play")?;
+        if turn.seat != number {
+            return Err("it is not your turn".to_string());
+        }
+        if !self.legal_actions().contains(&action) {
+            return Err(format!("you cannot {} now", action));
+        }
+        self.play(action, false);
+        self.next_turn(now);
+        Ok(())
+    }
+
+    // Plays an action on the current hand and reports the cards it dealt
+    fn play(&mut self, action: Action, automatic: bool) {
+        let index = self.game.get_current_hand_index();
+        let turn = self.locate(index);
+        let before: Vec<usize> = self.game.get_players_hands().iter().map(|hand| hand.get_cards().len()).collect();
+        self.game.act(action);
+        self.events.push(TableEvent::Acted { seat: turn.seat, hand: turn.hand, action, automatic });
+
+        if action == Action::Split {
+            let seat = self.game.get_players_hands()[index].get_seat();
+            let hands = self.game.get_players_hands().iter().filter(|hand| hand.get_seat() == seat).count();
+            self.events.push(TableEvent::Split { seat: turn.seat, hands });
+        }
+        let split = (action == Action::Split).then_some(index);
+        for (hand, card) in protocol::new_cards(&before, self.game.get_players_hands(), split) {
+            let TurnState { seat, hand } = self.locate(hand);
+            self.events.push(TableEvent::Card { seat, hand, card });
+        }
+    }
+
+    // Plays the dealer's hand, pays every seat, frees the seats of players who have gone and opens betting
+    fn settle(&mut self) {
+        self.game.play_dealers_hand();
+        let dealer = self.game.get_dealers_hand().expect("the round was dealt").get_cards();
+        self.events.push(TableEvent::HoleCard { card: dealer[1] });
+        self.events.extend(dealer[2..].iter().map(|&card| TableEvent::DealerCard { card }));
+
+        let mut results: Vec<SeatResult> = Vec::new();
+        for index in 0..self.game.get_players_hands().len() {
+            let number = self.dealt_in[self.game.get_players_hands()[index].get_seat()];
+            let seat = self.seats[number].as_mut().expect("a seat dealt in");
+            let net = seat.bet.unwrap_or(0.0) * self.game.net_result(index);
+            let outcome = self.game.outcome(index);
+            match results.last_mut() {
+                Some(result) if result.seat == number => {
+                    result.outcomes.push(outcome);
+                    result.net += net;
+                }
+                _ => results.push(SeatResult { seat: number, name: seat.name.clone(), outcomes: vec![outcome], net, bankroll: 0.0 }),
+            }
+        }
+        for result in &mut results {
+            let seat = self.seats[result.seat].as_mut().expect("a seat dealt in");
+            seat.bankroll += result.net;
+            result.bankroll = seat.bankroll;
+        }
+        self.events.push(TableEvent::RoundOver { round: self.rounds, results });
+
+        for number in 0..self.seats.len() {
+            if self.seats[number].as_ref().is_some_and(|seat| seat.leaving) {
+                self.free(number);
+            } else if let Some(seat) = &mut self.seats[number] {
+                seat.bet = None;
+            }
+        }
+        self.phase = Phase::Betting;
+        self.events.push(TableEvent::BettingOpen { round: self.rounds + 1 });
+    }
+
+    fn free(&mut self, number: usize) {
+        if let Some(seat) = self.seats[number].take() {
+            self.events.push(TableEvent::PlayerLeft { seat: number, name: seat.name });
+        }
+    }
+
+    // True if the seat has hands in the round being played
+    fn in_round(&self, number: usize) -> bool {
+        self.phase == Phase::Playing && self.seats[number].as_ref().is_some_and(|seat| seat.dealt)
+    }
+
+    // Gives up the client's seat: at once between rounds, or once the round is over if the seat has hands in it
+    fn leave(&mut self, client: u64, now: Instant) {
+        let Some(number) = self.clients.get_mut(&client).and_then(|client| client.seat.take()) else { return };
+        self.give_up(number, now);
+    }
+
+    fn give_up(&mut self, number: usize, now: Instant) {
+        if self.in_round(number) {
+            let seat = self.seats[number].as_mut().expect("a taken seat");
+            seat.client = None;
+            seat.reconnect_deadline = None;
+            seat.leaving = true;
+            if self.turn().is_some_and(|turn| turn.seat == number) {
+                self.next_turn(now);
+            }
+        } else {
+            self.free(number);
+            self.close_betting_if_ready(now);
+        }
+    }
+
+    fn disconnect(&mut self, client: u64, now: Instant) {
+        let Some(connection) = self.clients.remove(&client) else { return };
+        let Some(number) = connection.seat else { return };
+        let seat = self.seats[number].as_mut().expect("a taken seat");
+        seat.client = None;
+        seat.reconnect_deadline = Some(now + self.config.reconnect_timeout);
+        self.events.push(TableEvent::PlayerDisconnected { seat: number, name: seat.name.clone() });
+        // Betting does not wait for a player who is not here
+        self.close_betting_if_ready(now);
+    }
+
+    // Removes a client that quit, once it has been answered
+    fn forget(&mut self, client: u64) {
+        if let Some(connection) = self.clients.remove(&client) {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    // Acts on the deadlines that have passed
+    fn expire(&mut self, now: Instant) {
+        if self.bet_deadline.is_some_and(|deadline| deadline <= now) && self.phase == Phase::Betting {
+            self.deal(now);
+        }
+        if self.action_deadline.is_some_and(|deadline| deadline <= now) && self.turn().is_some() {
+            self.play(Action::Stand, true);
+            self.next_turn(now);
+        }
+        for number in 0..self.seats.len() {
+            let expired = self.seats[number].as_ref().is_some_and(|seat| seat.reconnect_deadline.is_some_and(|deadline| deadline <= now));
+            if expired {
+                self.give_up(number, now);
+            }
+        }
+    }
+
+    // Sends the events of the last request, each followed by the table as the client sees it
+    fn flush_events(&mut self) {
+        if self.events.is_empty() {
+            return;
+        }
+        let events: Vec<TableReply> = self.events.drain(..).map(TableReply::Event).collect();
+        let clients: Vec<u64> = self.clients.iter().filter(|(_, client)| client.greeted).map(|(&id, _)| id).collect();
+        for client in clients {
+            for event in &events {
+                self.send(client, event);
+            }
+            let state = TableReply::State(self.snapshot(client));
+            self.send(client, &state);
+        }
+    }
+
+    fn send(&mut self, client: u64, reply: &TableReply) {
+        let Some(connection) = self.clients.get_mut(&client) else { return };
+        if write_reply(&mut connection.stream, reply).is_err() && !self.dropped.contains(&client) {
+            self.dropped.push(client);
+        }
+    }
+
+    fn snapshot(&self, client: u64) -> TableSnapshot {
+        let dealt = self.game.get_dealers_hand().is_some() && !self.dealt_in.is_empty();
+        let playing = self.phase == Phase::Playing;
+        let hands = if dealt { self.game.get_players_hands() } else { &[] };
+
+        let seats = self
+            .seats
+            .iter()
+            .enumerate()
+            .map(|(number, seat)| {
+                seat.as_ref().map(|seat| SeatState {
+                    name: seat.name.clone(),
+                    bankroll: seat.bankroll,
+                    bet: seat.bet,
+                    connected: seat.client.is_some(),
+                    hands: hands
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, hand)| seat.dealt && self.dealt_in[hand.get_seat()] == number)
+                        .map(|(index, hand)| HandState::new(hand, (!playing).then(|| self.game.outcome(index))))
+                        .collect(),
+                })
+            })
+            .collect();
+        let turn = self.turn();
+
+        TableSnapshot {
+            phase: self.phase,
+            round: self.rounds,
+            you: self.clients.get(&client).and_then(|client| client.seat),
+            seats,
+            dealer: self.game.get_dealers_hand().filter(|_| dealt).map(|hand| DealerState::new(hand, playing)),
+            turn,
+            legal_actions: if turn.is_some() { self.legal_actions() } else { Vec::new() },
+            spectators: self.clients.values().filter(|client| client.greeted && client.seat.is_none()).count(),
+            cards_left: self.game.cards_left(),
+        }
+    }
+}
+
+fn write_reply(stream: &mut TcpStream, reply: &TableReply) -> io::Result<()> {
+    let line = serde_json::to_string(reply).expect("replies are always serializable");
+    writeln!(stream, "{}", line)?;
+    stream.flush()
+}
@@ -0,0 +1,421 @@
+use crate::blackjack::{Action, Blackjack, Card, HandOutcome, Rules};
+use crate::cli::TableFlags;
+use crate::protocol::Phase;
+use crate::server::{self, ServerConfig, ServerHandle, TableEvent, TableReply, TableRequest, TABLE_PROTOCOL_VERSION};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Long enough that no timeout fires in a test that does not wait for one
+    const PATIENT: Duration = Duration::from_secs(60);
+
+    fn config() -> ServerConfig {
+        ServerConfig {
+            port: 0,
+            seats: 3,
+            bet_timeout: PATIENT,
+            action_timeout: PATIENT,
+            reconnect_timeout: PATIENT,
+            bankroll: 100.0,
+        }
+    }
+
+    // Starts a table whose first round deals the given cards
+    fn start(config: ServerConfig, cards: &str) -> ServerHandle {
+        let mut game = Blackjack::with_seed(Rules::default(), 7);
+        game.stack_cards(cards.split_whitespace().map(|card| card.parse::<Card>().unwrap()).collect());
+        server::start(config, game).unwrap()
+    }
+
+    // A client of the table, reading replies with a timeout so a missing reply fails the test rather than hanging it
+    struct Client {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+    impl Client {
+        fn connect(server: &ServerHandle) -> Client {
+            let stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            Client { stream, reader }
+        }
+
+        // Connects, says hello and reads the welcome and the state that follows it
+        fn hello(server: &ServerHandle, name: &str) -> Client {
+            let mut client = Client::connect(server);
+            client.send(&TableRequest::Hello { version: TABLE_PROTOCOL_VERSION, name: name.to_string() });
+            assert!(matches!(client.next(), TableReply::Welcome { seats: 3, .. }));
+            client.state();
+            client
+        }
+
+        // Says hello and sits down, returning the client and its seat's token
+        fn seated(server: &ServerHandle, name: &str) -> (Client, String) {
+            let mut client = Client::hello(server, name);
+            client.send(&TableRequest::Join { seat: None });
+            let TableReply::Seated { token, .. } = client.next() else { panic!("not seated") };
+            client.events();
+            (client, token)
+        }
+
+        fn send(&mut self, request: &TableRequest) {
+            writeln!(self.stream, "{}", serde_json::to_string(request).unwrap()).unwrap();
+        }
+
+        fn next(&mut self) -> TableReply {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap_or_else(|err| panic!("{}: {:?}", err, line))
+        }
+
+        // Reads the events up to the next state snapshot
+        fn events(&mut self) -> Vec<TableEvent> {
+            let mut events = Vec::new();
+            loop {
+                match self.next() {
+                    TableReply::Event(event) => events.push(event),
+                    TableReply::State(_) => return events,
+                    reply => panic!("unexpected reply {:?}", reply),
+                }
+            }
+        }
+
+        fn state(&mut self) -> server::TableSnapshot {
+            match self.next() {
+                TableReply::State(state) => state,
+                reply => panic!("expected a state, got {:?}", reply),
+            }
+        }
+
+        // Reads replies until the event is seen, returning everything read on the way
+        fn until(&mut self, done: impl Fn(&TableEvent) -> bool) -> Vec<TableEvent> {
+            let mut events = Vec::new();
+            loop {
+                if let TableReply::Event(event) = self.next() {
+                    let found = done(&event);
+                    events.push(event);
+                    if found {
+                        return events;
+                    }
+                }
+            }
+        }
+
+        fn error(&mut self) -> String {
+            match self.next() {
+                TableReply::Error { message } => message,
+                reply => panic!("expected an error, got {:?}", reply),
+            }
+        }
+    }
+
+    fn bet(amount: f64) -> TableRequest {
+        TableRequest::Bet { amount }
+    }
+
+    fn act(action: Action) -> TableRequest {
+        TableRequest::Action { action }
+    }
+
+    #[test]
+    fn test_config_options() {
+        let args: Vec<String> = "seats 2 action-timeout 7.5 port 0".split_whitespace().map(String::from).collect();
+        let flags = TableFlags { bankroll: Some(40.0), ..TableFlags::default() };
+        let config = ServerConfig::parse_with(&args, &flags).unwrap();
+        assert_eq!((config.seats, config.port, config.bankroll), (2, 0, 40.0));
+        assert_eq!(config.action_timeout, Duration::from_millis(7500));
+        assert_eq!(config.bet_timeout, ServerConfig::default().bet_timeout);
+
+        let parse = |text: &str| ServerConfig::parse_with(&[text.to_string(), "0".to_string()], &flags).unwrap_err();
+        assert_eq!(parse("seats"), "invalid number of seats 0, a table has 1 to 7");
+        assert_eq!(parse("bet-timeout"), "invalid bet-timeout 0");
+        assert_eq!(parse("tables"), "unknown option tables");
+    }
+
+    #[test]
+    fn test_seats_and_spectators() {
+        let server = start(config(), "");
+        let mut ann = Client::hello(&server, "Ann");
+        let mut bob = Client::hello(&server, "Bob");
+
+        ann.send(&TableRequest::Join { seat: Some(2) });
+        assert!(matches!(ann.next(), TableReply::Seated { seat: 2, bankroll: 100.0, .. }));
+        assert_eq!(ann.events(), vec![TableEvent::PlayerJoined { seat: 2, name: "Ann".to_string() }]);
+        // The spectator sees the same event, and the state says who it was sent to
+        bob.until(|event| matches!(event, TableEvent::PlayerJoined { .. }));
+        let state = bob.state();
+        assert_eq!((state.you, state.spectators), (None, 1));
+        assert_eq!(state.seats[2].as_ref().unwrap().name, "Ann");
+
+        bob.send(&TableRequest::Join { seat: Some(2) });
+        assert_eq!(bob.error(), "seat 2 is taken");
+        bob.send(&TableRequest::Join { seat: Some(3) });
+        assert_eq!(bob.error(), "there is no seat 3");
+        bob.send(&TableRequest::Join { seat: None });
+        assert!(matches!(bob.next(), TableReply::Seated { seat: 0, .. }));
+        assert_eq!(bob.events().len(), 1);
+        bob.send(&TableRequest::Join { seat: None });
+        assert_eq!(bob.error(), "you already sit in seat 0");
+
+        let mut cat = Client::connect(&server);
+        cat.send(&TableRequest::QueryState);
+        assert_eq!(cat.error(), "say hello first");
+        cat.send(&TableRequest::Hello { version: 2, name: "Cat".to_string() });
+        assert_eq!(cat.error(), "unsupported protocol version 2, this table speaks version 1");
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_round_with_two_players() {
+        // Ann is dealt 10H 6S and Bob 9C 9D, the dealer shows 10C with 7D in the hole
+        let server = start(config(), "10H 9C 10C 6S 9D 7D 5C 2S 10S");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        let (mut bob, _) = Client::seated(&server, "Bob");
+        ann.events();
+
+        // Betting stays open until everyone at the table has bet
+        ann.send(&bet(2.0));
+        assert_eq!(ann.events(), vec![TableEvent::BetPlaced { seat: 0, amount: 2.0 }]);
+        bob.events();
+        bob.send(&bet(50.0));
+        let events = bob.events();
+        assert!(matches!(&events[1], TableEvent::Dealt { seats, .. } if seats[0].cards.len() == 2 && seats[1].seat == 1));
+        assert_eq!(events[2], TableEvent::Turn { seat: 0, hand: 0, seconds: 60.0 });
+
+        // Only the player whose turn it is can act
+        bob.send(&act(Action::Split));
+        assert_eq!(bob.error(), "it is not your turn");
+        ann.events();
+        ann.send(&act(Action::Hit));
+        let events = ann.events();
+        assert_eq!(events[1], TableEvent::Card { seat: 0, hand: 0, card: "5C".parse().unwrap() });
+        // 21 ends Ann's turn
+        assert_eq!(events[2], TableEvent::Turn { seat: 1, hand: 0, seconds: 60.0 });
+
+        bob.events();
+        bob.send(&act(Action::Split));
+        let events = bob.events();
+        assert_eq!(events[1], TableEvent::Split { seat: 1, hands: 2 });
+        assert_eq!(events[2], TableEvent::Card { seat: 1, hand: 0, card: "2S".parse().unwrap() });
+        bob.send(&act(Action::Stand));
+        let events = bob.events();
+        assert_eq!(events[1], TableEvent::Card { seat: 1, hand: 1, card: "10S".parse().unwrap() });
+        assert_eq!(events[2], TableEvent::Turn { seat: 1, hand: 1, seconds: 60.0 });
+        bob.send(&act(Action::Stand));
+        let events = bob.events();
+
+        let TableEvent::RoundOver { round: 1, results } = &events[2] else { panic!("{:?}", events) };
+        assert_eq!(results[0].outcomes, vec![HandOutcome::Win]);
+        assert_eq!(results[0].bankroll, 102.0);
+        assert_eq!(results[1].outcomes, vec![HandOutcome::Loss, HandOutcome::Win]);
+        assert_eq!((results[1].net, results[1].bankroll), (0.0, 100.0));
+        assert_eq!(events[3], TableEvent::BettingOpen { round: 2 });
+
+        // Ann sees the whole round too, ending with the settled table
+        ann.until(|event| matches!(event, TableEvent::BettingOpen { .. }));
+        let state = ann.state();
+        assert_eq!((state.phase, state.you, state.turn), (Phase::Betting, Some(0), None));
+        assert_eq!(state.seats[1].as_ref().unwrap().hands.len(), 2);
+        assert_eq!(state.dealer.unwrap().hidden, 0);
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_split_before_another_seat() {
+        // Ann is dealt 8H 8S and Bob 5C 7C, the dealer shows 10D with 7D in the hole
+        let server = start(config(), "8H 5C 10D 8S 7C 7D 3H 10S");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        let (mut bob, _) = Client::seated(&server, "Bob");
+        ann.events();
+        ann.send(&bet(1.0));
+        ann.events();
+        bob.events();
+        bob.send(&bet(1.0));
+        bob.events();
+        ann.events();
+
+        // Bob's hand moves up one place in the game, but none of his cards are dealt again
+        ann.send(&act(Action::Split));
+        let card = |text: &str| text.parse::<Card>().unwrap();
+        assert_eq!(
+            ann.events(),
+            vec![
+                TableEvent::Acted { seat: 0, hand: 0, action: Action::Split, automatic: false },
+                TableEvent::Split { seat: 0, hands: 2 },
+                TableEvent::Card { seat: 0, hand: 0, card: card("3H") },
+                TableEvent::Turn { seat: 0, hand: 0, seconds: 60.0 },
+            ]
+        );
+        ann.send(&act(Action::Stand));
+        assert_eq!(
+            ann.events(),
+            vec![
+                TableEvent::Acted { seat: 0, hand: 0, action: Action::Stand, automatic: false },
+                TableEvent::Card { seat: 0, hand: 1, card: card("10S") },
+                TableEvent::Turn { seat: 0, hand: 1, seconds: 60.0 },
+            ]
+        );
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_full_table_on_one_deck() {
+        let config = ServerConfig { seats: 7, action_timeout: Duration::from_millis(50), ..config() };
+        let rules = Rules { decks: 1, penetration: 0.99, ..Rules::default() };
+        let mut game = Blackjack::with_seed(rules, 1);
+        // Four cards are left, and the first round needs at least sixteen
+        for _ in 0..12 {
+            game.deal_cards();
+        }
+        assert_eq!(game.cards_left(), 4);
+        let server = server::start(config, game).unwrap();
+
+        let mut clients: Vec<Client> = (1..=7)
+            .map(|number| {
+                let mut client = Client::connect(&server);
+                client.send(&TableRequest::Hello { version: TABLE_PROTOCOL_VERSION, name: format!("Player {}", number) });
+                client.send(&TableRequest::Join { seat: None });
+                while !matches!(client.next(), TableReply::Seated { .. }) {}
+                client
+            })
+            .collect();
+        for client in &mut clients {
+            client.send(&bet(1.0));
+        }
+
+        // No one acts, so the table stands for everyone and the dealer plays from the reshuffled discards
+        for round in 1..=3 {
+            let events = clients[0].until(|event| matches!(event, TableEvent::RoundOver { .. }));
+            assert!(events.iter().any(|event| matches!(event, TableEvent::Dealt { seats, .. } if seats.len() == 7)));
+            let Some(TableEvent::RoundOver { round: over, results }) = events.last() else { unreachable!() };
+            assert_eq!((*over, results.len()), (round, 7));
+            for client in &mut clients {
+                client.send(&bet(1.0));
+            }
+        }
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_split_needs_the_bankroll_to_cover_another_bet() {
+        let server = start(config(), "8H 6C 8S 10D");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        ann.send(&bet(60.0));
+        let events = ann.until(|event| matches!(event, TableEvent::Turn { .. }));
+        assert!(matches!(&events[1], TableEvent::Dealt { .. }));
+
+        assert_eq!(ann.state().legal_actions, vec![Action::Hit, Action::Stand]);
+        ann.send(&act(Action::Split));
+        assert_eq!(ann.error(), "you cannot split now");
+        ann.send(&act(Action::Double));
+        assert_eq!(ann.error(), "you cannot double now");
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let config = ServerConfig { bet_timeout: Duration::from_millis(200), action_timeout: Duration::from_millis(200), ..config() };
+        let server = start(config, "10H 10C 6S 7D");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        let (_bob, _) = Client::seated(&server, "Bob");
+        ann.events();
+
+        // Bob never bets, so betting closes without him, and Ann never acts, so the table stands for her
+        ann.send(&bet(5.0));
+        let events = ann.until(|event| matches!(event, TableEvent::RoundOver { .. }));
+        assert!(matches!(&events[1], TableEvent::Dealt { seats, .. } if seats.len() == 1));
+        assert!(events.contains(&TableEvent::Acted { seat: 0, hand: 0, action: Action::Stand, automatic: true }));
+        let Some(TableEvent::RoundOver { results, .. }) = events.last() else { unreachable!() };
+        assert_eq!(results[0].outcomes, vec![HandOutcome::Loss]);
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_reconnect_with_token() {
+        let server = start(config(), "10H 10C 6S 7D 5C");
+        let (mut ann, token) = Client::seated(&server, "Ann");
+        let mut bob = Client::hello(&server, "Bob");
+
+        ann.send(&bet(5.0));
+        drop(ann);
+        bob.until(|event| matches!(event, TableEvent::PlayerDisconnected { seat: 0, .. }));
+        let state = bob.state();
+        assert!(!state.seats[0].as_ref().unwrap().connected);
+        assert_eq!(state.turn.map(|turn| turn.seat), Some(0));
+
+        // A wrong token is refused, and the right one takes back the seat in the middle of the round
+        let mut ann = Client::hello(&server, "Ann again");
+        ann.send(&TableRequest::Rejoin { token: "0".to_string() });
+        assert_eq!(ann.error(), "no seat is kept for that token");
+        ann.send(&TableRequest::Rejoin { token });
+        assert!(matches!(ann.next(), TableReply::Seated { seat: 0, bankroll: 100.0, .. }));
+        assert_eq!(ann.events(), vec![TableEvent::PlayerReconnected { seat: 0, name: "Ann".to_string() }]);
+        ann.send(&act(Action::Hit));
+        let events = ann.events();
+        assert!(matches!(events.last(), Some(TableEvent::BettingOpen { round: 2 })));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_seat_is_freed_after_the_reconnect_timeout() {
+        let config = ServerConfig { reconnect_timeout: Duration::from_millis(100), ..config() };
+        let server = start(config, "");
+        let (ann, token) = Client::seated(&server, "Ann");
+        let mut bob = Client::hello(&server, "Bob");
+
+        drop(ann);
+        bob.until(|event| matches!(event, TableEvent::PlayerLeft { seat: 0, .. }));
+        assert!(bob.state().seats[0].is_none());
+        bob.send(&TableRequest::Rejoin { token });
+        assert_eq!(bob.error(), "no seat is kept for that token");
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_leaving_mid_round() {
+        let server = start(config(), "10H 9C 10C 6S 9D 7D");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        let (mut bob, _) = Client::seated(&server, "Bob");
+        ann.events();
+        ann.send(&bet(1.0));
+        ann.events();
+        bob.events();
+        bob.send(&bet(1.0));
+        bob.events();
+        ann.events();
+
+        // Ann's hand is stood for her, and her seat is freed once the round is over
+        ann.send(&TableRequest::Leave);
+        let events = ann.events();
+        assert_eq!(events[0], TableEvent::Acted { seat: 0, hand: 0, action: Action::Stand, automatic: true });
+        assert_eq!(events[1], TableEvent::Turn { seat: 1, hand: 0, seconds: 60.0 });
+        ann.send(&TableRequest::Leave);
+        assert_eq!(ann.error(), "you do not have a seat");
+
+        bob.events();
+        bob.send(&act(Action::Stand));
+        let events = bob.events();
+        assert!(events.contains(&TableEvent::PlayerLeft { seat: 0, name: "Ann".to_string() }));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_quit_and_shutdown() {
+        let server = start(config(), "");
+        let (mut ann, _) = Client::seated(&server, "Ann");
+        let mut bob = Client::hello(&server, "Bob");
+        ann.send(&TableRequest::Quit);
+        assert_eq!(ann.next(), TableReply::Goodbye);
+        bob.until(|event| matches!(event, TableEvent::PlayerLeft { seat: 0, .. }));
+        bob.state();
+
+        server.shutdown();
+        assert_eq!(bob.next(), TableReply::Goodbye);
+        let mut line = String::new();
+        assert_eq!(bob.reader.read_line(&mut line).unwrap(), 0);
+    }
+}
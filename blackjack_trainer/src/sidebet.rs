@@ -0,0 +1,250 @@
+use crate::blackjack::{Card, Suit};
+use std::fmt;
+
+/**
+ * A side bet made before the deal alongside the main bet. Every side bet is settled from the player's
+ * first two cards and the dealer's cards, independently of how the player plays the hand.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SideBet {
+    // The player's first two cards are a pair
+    PerfectPairs,
+    // The player's first two cards and the dealer's upcard make a poker hand
+    TwentyOnePlusThree,
+    // The player's first two cards total 20
+    LuckyLadies,
+    // The player's first two cards have the rank of the dealer's upcard
+    MatchTheDealer,
+    // The dealer busts, paying more the more cards the dealer drew
+    BusterBlackjack,
+}
+impl SideBet {
+    pub const ALL: [SideBet; 5] = [
+        SideBet::PerfectPairs,
+        SideBet::TwentyOnePlusThree,
+        SideBet::LuckyLadies,
+        SideBet::MatchTheDealer,
+        SideBet::BusterBlackjack,
+    ];
+
+    /**
+     * Returns the position of this side bet in ALL
+     */
+    pub fn index(&self) -> usize {
+        SideBet::ALL.iter().position(|bet| bet == self).expect("every side bet is listed")
+    }
+
+    /**
+     * Returns the name of this side bet as it is written in options and exports, e.g. "21+3"
+     */
+    pub fn key(&self) -> &'static str {
+        match self {
+            SideBet::PerfectPairs => "perfect-pairs",
+            SideBet::TwentyOnePlusThree => "21+3",
+            SideBet::LuckyLadies => "lucky-ladies",
+            SideBet::MatchTheDealer => "match-the-dealer",
+            SideBet::BusterBlackjack => "buster",
+        }
+    }
+
+    /**
+     * Parses the name of a side bet, ignoring case
+     * Ex: "perfect-pairs", "pp", "21+3", "lucky_ladies", "buster"
+     */
+    pub fn parse(name: &str) -> Option<SideBet> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "perfect-pairs" | "pp" => Some(SideBet::PerfectPairs),
+            "21+3" | "21-3" => Some(SideBet::TwentyOnePlusThree),
+            "lucky-ladies" | "ll" => Some(SideBet::LuckyLadies),
+            "match-the-dealer" | "match" => Some(SideBet::MatchTheDealer),
+            "buster" | "buster-blackjack" => Some(SideBet::BusterBlackjack),
+            _ => None,
+        }
+    }
+
+    /**
+     * Parses a comma separated list of side bets, or "all" for every side bet
+     * Ex: "pp,21+3"
+     */
+    pub fn parse_list(list: &str) -> Result<Vec<SideBet>, String> {
+        if list.eq_ignore_ascii_case("all") {
+            return Ok(SideBet::ALL.to_vec());
+        }
+        let mut bets = Vec::new();
+        for name in list.split(',').filter(|name| !name.is_empty()) {
+            let bet = SideBet::parse(name).ok_or_else(|| format!("unknown side bet {}", name))?;
+            if !bets.contains(&bet) {
+                bets.push(bet);
+            }
+        }
+        Ok(bets)
+    }
+
+    /**
+     * Returns true if a card counter can find positive bets: these side bets depend on the proportion
+     * of tens (Lucky Ladies), of close ranks and suits (21+3) or of small cards (Buster) left in the shoe
+     */
+    pub fn is_countable(&self) -> bool {
+        matches!(self, SideBet::TwentyOnePlusThree | SideBet::LuckyLadies | SideBet::BusterBlackjack)
+    }
+
+    /**
+     * Returns the paytable of this side bet: each winning hand with what it pays to 1, best first
+     */
+    pub fn paytable(&self) -> &'static [(&'static str, f64)] {
+        match self {
+            SideBet::PerfectPairs => &[("Perfect pair", 25.0), ("Coloured pair", 12.0), ("Mixed pair", 6.0)],
+            SideBet::TwentyOnePlusThree => &[
+                ("Suited three of a kind", 100.0),
+                ("Straight flush", 40.0),
+                ("Three of a kind", 30.0),
+                ("Straight", 10.0),
+                ("Flush", 5.0),
+            ],
+            SideBet::LuckyLadies => &[
+                ("Queen of hearts pair with dealer blackjack", 1000.0),
+                ("Queen of hearts pair", 125.0),
+                ("Matched 20", 19.0),
+                ("Suited 20", 9.0),
+                ("Any 20", 4.0),
+            ],
+            // Each of the player's cards is paid separately
+            SideBet::MatchTheDealer => &[("Suited match", 11.0), ("Unsuited match", 4.0)],
+            SideBet::BusterBlackjack => &[
+                ("Dealer busts with 8 or more cards", 250.0),
+                ("Dealer busts with 7 cards", 50.0),
+                ("Dealer busts with 6 cards", 12.0),
+                ("Dealer busts with 5 cards", 4.0),
+                ("Dealer busts with 4 cards", 2.0),
+                ("Dealer busts with 3 cards", 2.0),
+            ],
+        }
+    }
+
+    /**
+     * Returns the winning hand of this side bet, as named in its paytable, or None if the bet loses
+     * @param player the player's first two cards
+     * @param dealer the dealer's cards, upcard first; Buster Blackjack needs the dealer's final hand
+     */
+    pub fn winning_hand(&self, player: [Card; 2], dealer: &[Card]) -> Option<&'static str> {
+        let [first, second] = player;
+        let paytable = self.paytable();
+        let upcard = dealer[0];
+
+        let line = match self {
+            SideBet::PerfectPairs if first.get_rank() != second.get_rank() => return None,
+            SideBet::PerfectPairs if first.get_suit() == second.get_suit() => 0,
+            SideBet::PerfectPairs if first.get_suit().is_red() == second.get_suit().is_red() => 1,
+            SideBet::PerfectPairs => 2,
+            SideBet::TwentyOnePlusThree => poker_hand_line([first, second, upcard])?,
+            SideBet::LuckyLadies => {
+                if first.get_value() + second.get_value() != 20 && !is_soft_20(first, second) {
+                    return None;
+                }
+                let queen_of_hearts = |card: Card| card.get_rank() == 12 && card.get_suit() == Suit::Hearts;
+                let dealer_blackjack = dealer.len() >= 2 && is_blackjack(dealer[0], dealer[1]);
+                if queen_of_hearts(first) && queen_of_hearts(second) {
+                    if dealer_blackjack { 0 } else { 1 }
+                } else if first == second {
+                    2
+                } else if first.get_suit() == second.get_suit() {
+                    3
+                } else {
+                    4
+                }
+            }
+            // A single match is reported here; both cards are paid by settle
+            SideBet::MatchTheDealer => {
+                let matches = [first, second].into_iter().filter(|card| card.get_rank() == upcard.get_rank());
+                let suited = matches.clone().any(|card| card.get_suit() == upcard.get_suit());
+                match (matches.count(), suited) {
+                    (0, _) => return None,
+                    (_, true) => 0,
+                    _ => 1,
+                }
+            }
+            SideBet::BusterBlackjack => {
+                if hand_total(dealer) <= 21 {
+                    return None;
+                }
+                8usize.saturating_sub(dealer.len()).min(5)
+            }
+        };
+        Some(paytable[line].0)
+    }
+
+    /**
+     * Returns the amount won or lost on a side bet of 1 unit
+     * @param player the player's first two cards
+     * @param dealer the dealer's cards, upcard first; Buster Blackjack needs the dealer's final hand
+     */
+    pub fn settle(&self, player: [Card; 2], dealer: &[Card]) -> f64 {
+        if *self == SideBet::MatchTheDealer {
+            let upcard = dealer[0];
+            let won: f64 = player
+                .iter()
+                .filter(|card| card.get_rank() == upcard.get_rank())
+                .map(|card| if card.get_suit() == upcard.get_suit() { 11.0 } else { 4.0 })
+                .sum();
+            return if won == 0.0 { -1.0 } else { won };
+        }
+
+        let paytable = self.paytable();
+        match self.winning_hand(player, dealer) {
+            Some(hand) => paytable.iter().find(|(name, _)| *name == hand).map(|&(_, pays)| pays).unwrap_or(0.0),
+            None => -1.0,
+        }
+    }
+}
+impl fmt::Display for SideBet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SideBet::PerfectPairs => "Perfect Pairs",
+            SideBet::TwentyOnePlusThree => "21+3",
+            SideBet::LuckyLadies => "Lucky Ladies",
+            SideBet::MatchTheDealer => "Match the Dealer",
+            SideBet::BusterBlackjack => "Buster Blackjack",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn is_blackjack(first: Card, second: Card) -> bool {
+    let values = [first.get_value(), second.get_value()];
+    values.contains(&1) && values.contains(&10)
+}
+
+// An ace and a 9 count as 20 for Lucky Ladies
+fn is_soft_20(first: Card, second: Card) -> bool {
+    let values = [first.get_value(), second.get_value()];
+    values.contains(&1) && values.contains(&9)
+}
+
+// The blackjack total of cards, counting an ace as 11 when that does not bust
+fn hand_total(cards: &[Card]) -> i32 {
+    let total: i32 = cards.iter().map(Card::get_value).sum();
+    if total <= 11 && cards.iter().any(|card| card.get_value() == 1) {
+        total + 10
+    } else {
+        total
+    }
+}
+
+// The line of the 21+3 paytable three cards make, or None if they make no poker hand
+fn poker_hand_line(cards: [Card; 3]) -> Option<usize> {
+    let mut ranks = cards.map(|card| card.get_rank());
+    ranks.sort_unstable();
+    let suited = cards.iter().all(|card| card.get_suit() == cards[0].get_suit());
+    let trips = ranks[0] == ranks[2];
+    // An ace plays high in Q-K-A as well as low in A-2-3
+    let straight = (ranks[1] == ranks[0] + 1 && ranks[2] == ranks[1] + 1) || ranks == [1, 12, 13];
+
+    match (trips, straight, suited) {
+        (true, _, true) => Some(0),
+        (_, true, true) => Some(1),
+        (true, _, false) => Some(2),
+        (_, true, false) => Some(3),
+        (_, _, true) => Some(4),
+        _ => None,
+    }
+}
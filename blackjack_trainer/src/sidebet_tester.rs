@@ -0,0 +1,122 @@
+use crate::blackjack::{Action, Blackjack, Card, Rules};
+use crate::sidebet::SideBet;
+use crate::simulation::{simulate, SimulationConfig};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(text: &str) -> Vec<Card> {
+        text.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    // Settles a side bet with the player's two cards and the dealer's cards written as text
+    fn settle(side_bet: SideBet, player: &str, dealer: &str) -> f64 {
+        let player = cards(player);
+        side_bet.settle([player[0], player[1]], &cards(dealer))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(SideBet::parse("PP"), Some(SideBet::PerfectPairs));
+        assert_eq!(SideBet::parse("lucky_ladies"), Some(SideBet::LuckyLadies));
+        assert_eq!(SideBet::parse("royal-match"), None);
+        assert_eq!(SideBet::parse_list("21+3,buster,21+3").unwrap(), [SideBet::TwentyOnePlusThree, SideBet::BusterBlackjack]);
+        assert_eq!(SideBet::parse_list("All").unwrap(), SideBet::ALL);
+        assert_eq!(SideBet::parse_list("pp,insurance").err().unwrap(), "unknown side bet insurance");
+        assert!(SideBet::ALL.iter().all(|bet| SideBet::parse(bet.key()) == Some(*bet)));
+    }
+
+    #[test]
+    fn test_perfect_pairs() {
+        assert_eq!(settle(SideBet::PerfectPairs, "8S 8S", "2C 5C"), 25.0);
+        assert_eq!(settle(SideBet::PerfectPairs, "8H 8D", "2C 5C"), 12.0);
+        assert_eq!(settle(SideBet::PerfectPairs, "8H 8C", "2C 5C"), 6.0);
+        // Tens of different ranks are not a pair
+        assert_eq!(settle(SideBet::PerfectPairs, "KH QH", "2C 5C"), -1.0);
+    }
+
+    #[test]
+    fn test_twenty_one_plus_three() {
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "7D 7D", "7D 5C"), 100.0);
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "QS AS", "KS 5C"), 40.0);
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "7D 7C", "7H 5C"), 30.0);
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "3D AC", "2H 5C"), 10.0);
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "3D 9D", "JD 5C"), 5.0);
+        // The ace does not wrap around from king to two
+        assert_eq!(settle(SideBet::TwentyOnePlusThree, "KD AC", "2H 5C"), -1.0);
+        assert_eq!(SideBet::TwentyOnePlusThree.winning_hand([cards("QS")[0], cards("AS")[0]], &cards("KS")), Some("Straight flush"));
+    }
+
+    #[test]
+    fn test_lucky_ladies() {
+        assert_eq!(settle(SideBet::LuckyLadies, "QH QH", "AS KD"), 1000.0);
+        assert_eq!(settle(SideBet::LuckyLadies, "QH QH", "AS 6D"), 125.0);
+        assert_eq!(settle(SideBet::LuckyLadies, "JC JC", "AS 6D"), 19.0);
+        assert_eq!(settle(SideBet::LuckyLadies, "JC 10C", "AS 6D"), 9.0);
+        assert_eq!(settle(SideBet::LuckyLadies, "AH 9C", "AS 6D"), 4.0);
+        assert_eq!(settle(SideBet::LuckyLadies, "10H 9C", "AS 6D"), -1.0);
+    }
+
+    #[test]
+    fn test_match_the_dealer() {
+        // Each matching card is paid
+        assert_eq!(settle(SideBet::MatchTheDealer, "9H 9C", "9H 5C"), 15.0);
+        assert_eq!(settle(SideBet::MatchTheDealer, "9D 2C", "9H 5C"), 4.0);
+        // Matches are by rank, so a king does not match a queen
+        assert_eq!(settle(SideBet::MatchTheDealer, "KH 2C", "QH 5C"), -1.0);
+    }
+
+    #[test]
+    fn test_buster_blackjack() {
+        assert_eq!(settle(SideBet::BusterBlackjack, "9H 9C", "10H 6C 10D"), 2.0);
+        assert_eq!(settle(SideBet::BusterBlackjack, "9H 9C", "2H 2C 2D 2S 3H 3C 3D 10S"), 250.0);
+        assert_eq!(settle(SideBet::BusterBlackjack, "9H 9C", "2H 2C 2D 2S 3H 3C 3D 3S 10S"), 250.0);
+        assert_eq!(settle(SideBet::BusterBlackjack, "9H 9C", "10H 7C"), -1.0);
+    }
+
+    #[test]
+    fn test_game_settles_side_bets() {
+        let mut game = Blackjack::with_seed(Rules::default(), 1);
+        game.set_side_bets(vec![SideBet::PerfectPairs, SideBet::BusterBlackjack]);
+        // The player hits 16 and busts, and the dealer still draws for the Buster bet
+        game.stack_cards(cards("8S 8S 10H 6C 10D 10C"));
+        game.deal_cards();
+        game.act(Action::Hit);
+        assert!(!game.is_players_turn());
+        game.play_dealers_hand();
+
+        assert_eq!(game.get_dealers_hand().unwrap().get_cards().len(), 3);
+        assert_eq!(game.side_bet_results(), vec![(SideBet::PerfectPairs, 25.0), (SideBet::BusterBlackjack, 2.0)]);
+    }
+
+    #[test]
+    fn test_side_bets_settle_on_the_first_seat() {
+        let mut game = Blackjack::with_seed(Rules::default(), 1);
+        game.set_side_bets(vec![SideBet::PerfectPairs, SideBet::BusterBlackjack]);
+        // The first seat is dealt 8S 8S around the second seat's 2C 3C, so its cards are not the first two dealt
+        game.stack_cards(cards("8S 2C 10H 8S 3C 6C 10D"));
+        game.deal_seats(2);
+        game.act(Action::Stand);
+        game.act(Action::Stand);
+        game.play_dealers_hand();
+
+        assert_eq!(game.get_players_hands()[0].get_cards()[1].to_string(), "8S");
+        assert_eq!(game.side_bet_results(), vec![(SideBet::PerfectPairs, 25.0), (SideBet::BusterBlackjack, 2.0)]);
+    }
+
+    #[test]
+    fn test_simulated_side_bets() {
+        let args: Vec<String> = "hands 60000 side-bets pp,21+3 threads 2 seed 11".split(' ').map(String::from).collect();
+        let config = SimulationConfig::parse(&args).unwrap();
+        let result = simulate(&config);
+        let pairs = result.side_bets[SideBet::PerfectPairs.index()];
+        let counted: u64 = result.side_bets[SideBet::TwentyOnePlusThree.index()].true_counts.iter().map(|count| count.rounds).sum();
+
+        // From a full six deck shoe Perfect Pairs loses 19 units in 311
+        assert!((pairs.house_edge() - 19.0 / 311.0).abs() < 2.0 * pairs.confidence_interval());
+        assert_eq!(counted, 60000);
+        assert_eq!(result.side_bets[SideBet::LuckyLadies.index()].rounds, 0);
+        assert_eq!(SimulationConfig::parse(&["side-bets".to_string(), "x".to_string()]).err().unwrap(), "unknown side bet x");
+    }
+}
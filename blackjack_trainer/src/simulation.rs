@@ -0,0 +1,610 @@
+use crate::blackjack::{Blackjack, HandOutcome, Rules};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::cli::TableFlags;
+use crate::countingsystem::{self, CountingSystem, HiLo};
+use crate::playerstrategy::{PlayerKind, PlayerStrategy, RoundView, ShoeCount};
+use crate::sidebet::SideBet;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * The outcomes in the order they are counted and reported
+ */
+pub const OUTCOMES: [HandOutcome; 6] = [
+    HandOutcome::Blackjack,
+    HandOutcome::Win,
+    HandOutcome::Push,
+    HandOutcome::Loss,
+    HandOutcome::Bust,
+    HandOutcome::Surrender,
+];
+
+fn outcome_index(outcome: HandOutcome) -> usize {
+    OUTCOMES.iter().position(|&known| known == outcome).expect("every outcome is listed")
+}
+
+/**
+ * True counts are tallied from -TRUE_COUNT_LIMIT to +TRUE_COUNT_LIMIT, with counts
+ * beyond the limit tallied at the limit
+ */
+pub const TRUE_COUNT_LIMIT: i32 = 10;
+
+const TRUE_COUNT_BUCKETS: usize = 2 * TRUE_COUNT_LIMIT as usize + 1;
+
+/**
+ * Bets are tallied by their size in whole units up to MAX_BET_UNITS, with larger bets
+ * tallied at MAX_BET_UNITS
+ */
+pub const MAX_BET_UNITS: usize = 16;
+
+/**
+ * The rounds played at one true count
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CountStats {
+    pub rounds: u64,
+    pub wagered: f64,
+    pub total: f64,
+}
+impl CountStats {
+    fn add(&mut self, bet: f64, net: f64) {
+        self.rounds += 1;
+        self.wagered += bet;
+        self.total += net;
+    }
+
+    fn merge(&mut self, other: &CountStats) {
+        self.rounds += other.rounds;
+        self.wagered += other.wagered;
+        self.total += other.total;
+    }
+
+    /**
+     * Returns the player's average result per round, in betting units
+     */
+    pub fn win_rate(&self) -> f64 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            self.total / self.rounds as f64
+        }
+    }
+
+    /**
+     * Returns the player's result as a fraction of the initial bets
+     */
+    pub fn edge(&self) -> f64 {
+        if self.wagered == 0.0 {
+            0.0
+        } else {
+            self.total / self.wagered
+        }
+    }
+}
+
+/**
+ * What to simulate: the table rules, the player and the chart and count it uses, the number of rounds,
+ * and how to split the rounds across threads
+ */
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    pub rules: Rules,
+    pub player: PlayerKind,
+    pub strategy: BlackjackBasicStrategy,
+    pub system: &'static dyn CountingSystem,
+    // The player's bankroll at the start of each worker's rounds, in betting units
+    pub bankroll: f64,
+    // The side bets made every round, 1 unit each
+    pub side_bets: Vec<SideBet>,
+    pub rounds: u64,
+    pub threads: usize,
+    // The master seed each worker's shoe is derived from
+    pub seed: u64,
+}
+impl SimulationConfig {
+    /**
+     * Parses simulation options:
+     *   hands <number>          the number of rounds to play (default 1,000,000)
+     *   rules <variant|file>    the table rules (default 6 decks S17 DAS)
+     *   player <name>           basic, deviations, mimic, never-bust or random (default basic)
+     *   strategy <variant|file> the chart the player follows (default the chart for the rules)
+     *   system <name>           the counting system the player keeps (default Hi-Lo)
+     *   bankroll <units>        the player's starting bankroll (default 1000)
+     *   side-bets <list|all>    side bets of 1 unit made every round, e.g. pp,21+3 (default none)
+     *   penetration <fraction>  how much of the shoe is dealt before reshuffling
+     *   payout <ratio>          what a blackjack pays, e.g. 1.2 for 6:5
+     *   threads <number>        the number of worker threads (default one per CPU core)
+     *   seed <number>           the master seed (default random); a seed and thread count
+     *                           always give the same results
+     * Ex: ["hands", "500000", "rules", "6d-h17", "payout", "1.2", "seed", "42"]
+     */
+    pub fn parse(args: &[String]) -> Result<SimulationConfig, String> {
+        Self::parse_with(args, &TableFlags::default())
+    }
+
+    /**
+     * Parses simulation options, then overrides them with the table flags that were given
+     * Ex: ["rules", "2d-s17"] with --h17 simulates two decks H17
+     */
+    pub fn parse_with(args: &[String], flags: &TableFlags) -> Result<SimulationConfig, String> {
+        let mut rounds = 1_000_000;
+        let mut threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        let mut seed = rand::random();
+        let mut rules = Rules::default();
+        let mut player = PlayerKind::Basic;
+        let mut strategy = None;
+        let mut system: &'static dyn CountingSystem = &HiLo;
+        let mut bankroll = 1000.0;
+        let mut side_bets = Vec::new();
+        let mut penetration = None;
+        let mut payout = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "hands" => {
+                    let text = value()?;
+                    rounds = text.replace(['_', ','], "").parse().ok().filter(|&rounds| rounds > 0)
+                        .ok_or_else(|| format!("invalid number of hands {}", text))?;
+                }
+                "rules" => rules = *BlackjackBasicStrategy::from_spec(value()?)?.get_rules(),
+                "player" => {
+                    let text = value()?;
+                    player = PlayerKind::parse(text).ok_or_else(|| format!("unknown player {}", text))?;
+                }
+                "strategy" => strategy = Some(BlackjackBasicStrategy::from_spec(value()?)?),
+                "system" => {
+                    let text = value()?;
+                    system = countingsystem::system_by_name(text)
+                        .ok_or_else(|| format!("unknown counting system {}", text))?;
+                }
+                "bankroll" => {
+                    let text = value()?;
+                    bankroll = text.replace(['_', ','], "").parse().ok().filter(|&units: &f64| units > 0.0)
+                        .ok_or_else(|| format!("invalid bankroll {}", text))?;
+                }
+                "side-bets" => side_bets = SideBet::parse_list(value()?)?,
+                "penetration" => {
+                    let text = value()?;
+                    penetration = Some(text.parse().ok().filter(|&p: &f64| p > 0.0 && p < 1.0)
+                        .ok_or_else(|| format!("invalid penetration {}", text))?);
+                }
+                "payout" => {
+                    let text = value()?;
+                    payout = Some(text.parse().ok().filter(|&payout: &f64| payout > 0.0)
+                        .ok_or_else(|| format!("invalid payout {}", text))?);
+                }
+                "threads" => {
+                    let text = value()?;
+                    threads = text.parse().ok().filter(|&threads| threads > 0 && threads <= 1024)
+                        .ok_or_else(|| format!("invalid number of threads {}", text))?;
+                }
+                "seed" => {
+                    let text = value()?;
+                    seed = text.parse().map_err(|_| format!("invalid seed {}", text))?;
+                }
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        rules.penetration = penetration.unwrap_or(rules.penetration);
+        rules.blackjack_payout = payout.unwrap_or(rules.blackjack_payout);
+        flags.apply(&mut rules);
+        let seed = flags.seed.unwrap_or(seed);
+        let bankroll = flags.bankroll.unwrap_or(bankroll);
+        let strategy = match &flags.chart {
+            Some(spec) => BlackjackBasicStrategy::from_spec(spec)?,
+            None => strategy.unwrap_or_else(|| BlackjackBasicStrategy::for_rules(&rules)),
+        };
+        Ok(SimulationConfig { rules, player, strategy, system, bankroll, side_bets, rounds, threads, seed })
+    }
+}
+
+/**
+ * The totals of a side bet of 1 unit made every round
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SideBetStats {
+    pub rounds: u64,
+    pub total: f64,
+    pub total_squares: f64,
+    // Rounds by the true count when the bet was made, from -TRUE_COUNT_LIMIT up
+    pub true_counts: [CountStats; TRUE_COUNT_BUCKETS],
+}
+impl SideBetStats {
+    /**
+     * Returns the house edge: the fraction of the side bets the player loses
+     */
+    pub fn house_edge(&self) -> f64 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            -self.total / self.rounds as f64
+        }
+    }
+
+    /**
+     * Returns the half width of the 95% confidence interval of the house edge
+     */
+    pub fn confidence_interval(&self) -> f64 {
+        if self.rounds < 2 {
+            return 0.0;
+        }
+        let n = self.rounds as f64;
+        let variance = (self.total_squares - self.total * self.total / n) / (n - 1.0);
+        1.96 * variance.max(0.0).sqrt() / n.sqrt()
+    }
+
+    /**
+     * Returns the side bets made at a true count, rounded down
+     */
+    pub fn true_count_stats(&self, true_count: i32) -> CountStats {
+        self.true_counts[true_count_index(true_count as f64)]
+    }
+}
+
+// The bucket of a true count: rounded down and limited to +/- TRUE_COUNT_LIMIT
+fn true_count_index(true_count: f64) -> usize {
+    ((true_count.floor() as i32).clamp(-TRUE_COUNT_LIMIT, TRUE_COUNT_LIMIT) + TRUE_COUNT_LIMIT) as usize
+}
+
+/**
+ * The totals of a simulation. Results are per round in betting units,
+ * so a round with a split or double can win or lose more than its initial bet.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimulationResult {
+    pub rounds: u64,
+    // Player hands played, including the extra hands from splits
+    pub hands: u64,
+    // The sum of the initial bets
+    pub wagered: f64,
+    pub total: f64,
+    pub total_squares: f64,
+    // Hands finishing with each outcome, in the order of OUTCOMES
+    pub outcomes: [u64; 6],
+    // Rounds by the true count when the bet was made, from -TRUE_COUNT_LIMIT up
+    pub true_counts: [CountStats; TRUE_COUNT_BUCKETS],
+    // Rounds by the initial bet rounded to whole units
+    pub bets: [u64; MAX_BET_UNITS + 1],
+    // Side bets, in the order of SideBet::ALL; only the side bets that were made have rounds
+    pub side_bets: [SideBetStats; SideBet::ALL.len()],
+    pub elapsed: Duration,
+}
+impl SimulationResult {
+    /**
+     * Adds a round to the totals
+     * @param bet the initial bet of the round
+     * @param net the amount won or lost in the round
+     */
+    pub fn record_round(&mut self, bet: f64, net: f64) {
+        self.rounds += 1;
+        self.wagered += bet;
+        self.total += net;
+        self.total_squares += net * net;
+        self.bets[(bet.round().max(0.0) as usize).min(MAX_BET_UNITS)] += 1;
+    }
+
+    /**
+     * Adds a round to the totals of the true count its bet was made at
+     * @param true_count the true count when the bet was made
+     * @param bet the initial bet of the round
+     * @param net the amount won or lost in the round
+     */
+    pub fn record_true_count(&mut self, true_count: f64, bet: f64, net: f64) {
+        self.true_counts[true_count_index(true_count)].add(bet, net);
+    }
+
+    /**
+     * Returns the rounds played at a true count, rounded down
+     */
+    pub fn true_count_stats(&self, true_count: i32) -> CountStats {
+        self.true_counts[true_count_index(true_count as f64)]
+    }
+
+    /**
+     * Adds a side bet of 1 unit to the totals
+     * @param side_bet the side bet
+     * @param true_count the true count when the bet was made
+     * @param net the amount won or lost on the side bet
+     */
+    pub fn record_side_bet(&mut self, side_bet: SideBet, true_count: f64, net: f64) {
+        let stats = &mut self.side_bets[side_bet.index()];
+        stats.rounds += 1;
+        stats.total += net;
+        stats.total_squares += net * net;
+        stats.true_counts[true_count_index(true_count)].add(1.0, net);
+    }
+
+    pub fn record_outcome(&mut self, outcome: HandOutcome) {
+        self.hands += 1;
+        self.outcomes[outcome_index(outcome)] += 1;
+    }
+
+    /**
+     * Adds the totals of another simulation to these totals
+     */
+    pub fn merge(&mut self, other: &SimulationResult) {
+        self.rounds += other.rounds;
+        self.hands += other.hands;
+        self.wagered += other.wagered;
+        self.total += other.total;
+        self.total_squares += other.total_squares;
+        for (count, other_count) in self.outcomes.iter_mut().zip(other.outcomes) {
+            *count += other_count;
+        }
+        for (stats, other_stats) in self.true_counts.iter_mut().zip(&other.true_counts) {
+            stats.merge(other_stats);
+        }
+        for (count, other_count) in self.bets.iter_mut().zip(other.bets) {
+            *count += other_count;
+        }
+        for (stats, other_stats) in self.side_bets.iter_mut().zip(&other.side_bets) {
+            stats.rounds += other_stats.rounds;
+            stats.total += other_stats.total;
+            stats.total_squares += other_stats.total_squares;
+            for (count, other_count) in stats.true_counts.iter_mut().zip(&other_stats.true_counts) {
+                count.merge(other_count);
+            }
+        }
+        self.elapsed = self.elapsed.max(other.elapsed);
+    }
+
+    /**
+     * Returns the player's expected result per round, in betting units
+     */
+    pub fn mean(&self) -> f64 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            self.total / self.rounds as f64
+        }
+    }
+
+    /**
+     * Returns the average initial bet
+     */
+    pub fn average_bet(&self) -> f64 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            self.wagered / self.rounds as f64
+        }
+    }
+
+    /**
+     * Returns the house edge: the fraction of the initial bets the player loses
+     */
+    pub fn house_edge(&self) -> f64 {
+        if self.wagered == 0.0 {
+            0.0
+        } else {
+            -self.total / self.wagered
+        }
+    }
+
+    /**
+     * Returns the standard deviation of the result of a round
+     */
+    pub fn standard_deviation(&self) -> f64 {
+        if self.rounds < 2 {
+            return 0.0;
+        }
+        let n = self.rounds as f64;
+        let variance = (self.total_squares - self.total * self.total / n) / (n - 1.0);
+        variance.max(0.0).sqrt()
+    }
+
+    /**
+     * Returns the half width of the 95% confidence interval of the house edge
+     */
+    pub fn confidence_interval(&self) -> f64 {
+        if self.rounds == 0 || self.wagered == 0.0 {
+            return 0.0;
+        }
+        1.96 * self.standard_deviation() / (self.rounds as f64).sqrt() / self.average_bet()
+    }
+
+    /**
+     * Returns the fraction of hands finishing with the specified outcome
+     */
+    pub fn frequency(&self, outcome: HandOutcome) -> f64 {
+        if self.hands == 0 {
+            0.0
+        } else {
+            self.outcomes[outcome_index(outcome)] as f64 / self.hands as f64
+        }
+    }
+
+    /**
+     * Returns the number of rounds simulated per second
+     */
+    pub fn rounds_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.rounds as f64 / seconds
+        }
+    }
+}
+
+/**
+ * A player at the table: who makes the decisions, the count they keep and their bankroll
+ */
+pub struct Seat {
+    pub player: Box<dyn PlayerStrategy>,
+    pub count: ShoeCount,
+    pub bankroll: f64,
+}
+impl Seat {
+    pub fn new(player: Box<dyn PlayerStrategy>, system: &'static dyn CountingSystem, bankroll: f64) -> Self {
+        Seat {
+            player,
+            count: ShoeCount::new(system),
+            bankroll,
+        }
+    }
+}
+
+/**
+ * Plays one round with the seat's player betting and deciding every action, and records it
+ * @param game the game, with the player's hands not yet dealt
+ * @param seat the player
+ * @param result the totals to record the round in
+ */
+pub fn play_round(game: &mut Blackjack, seat: &mut Seat, result: &mut SimulationResult) {
+    game.clear_table();
+    let view = RoundView::new(game, &mut seat.count, seat.bankroll, &[]);
+    let true_count = view.true_count;
+    let bet = seat.player.bet(&view);
+    game.deal_cards();
+
+    while game.is_players_turn() {
+        let legal = game.legal_actions();
+        let action = seat.player.action(&RoundView::new(game, &mut seat.count, seat.bankroll, &legal));
+        game.act(action);
+    }
+    game.play_dealers_hand();
+
+    let mut net = 0.0;
+    for index in 0..game.get_players_hands().len() {
+        net += bet * game.net_result(index);
+        result.record_outcome(game.outcome(index));
+    }
+    result.record_round(bet, net);
+    result.record_true_count(true_count, bet, net);
+    for (side_bet, side_net) in game.side_bet_results() {
+        net += side_net;
+        result.record_side_bet(side_bet, true_count, side_net);
+    }
+    seat.bankroll += net;
+}
+
+/**
+ * Returns the seed of a worker's shoe, mixing the master seed and the worker's index
+ * (SplitMix64) so neighbouring workers get unrelated shuffles
+ * @param seed the master seed
+ * @param worker the index of the worker
+ */
+pub fn worker_seed(seed: u64, worker: usize) -> u64 {
+    let mut z = seed.wrapping_add((worker as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/**
+ * Returns the number of rounds each worker plays: an equal share, with the remainder
+ * going to the first workers
+ */
+pub fn worker_rounds(rounds: u64, threads: usize) -> Vec<u64> {
+    let threads = threads as u64;
+    (0..threads).map(|worker| rounds / threads + u64::from(worker < rounds % threads)).collect()
+}
+
+/**
+ * Plays the specified number of rounds with a shoe shuffled from the seed
+ */
+pub fn simulate_worker(config: &SimulationConfig, seed: u64, rounds: u64) -> SimulationResult {
+    let mut game = Blackjack::with_seed(config.rules, seed);
+    game.set_side_bets(config.side_bets.clone());
+    // The player's own randomness is kept apart from the shoe's
+    let player = config.player.build(&config.strategy, worker_seed(seed, 0));
+    let mut seat = Seat::new(player, config.system, config.bankroll);
+    let mut result = SimulationResult::default();
+
+    for _ in 0..rounds {
+        play_round(&mut game, &mut seat, &mut result);
+    }
+    result
+}
+
+/**
+ * Plays the configured number of rounds through the engine, split across the configured number
+ * of threads. Each worker has its own shoe seeded from the master seed, and the results are merged
+ * in worker order, so a seed and thread count always give the same totals.
+ */
+pub fn simulate(config: &SimulationConfig) -> SimulationResult {
+    let start = Instant::now();
+    let shares = worker_rounds(config.rounds, config.threads);
+
+    let results: Vec<SimulationResult> = thread::scope(|scope| {
+        let workers: Vec<_> = shares
+            .iter()
+            .enumerate()
+            .map(|(worker, &rounds)| scope.spawn(move || simulate_worker(config, worker_seed(config.seed, worker), rounds)))
+            .collect();
+        workers.into_iter().map(|worker| worker.join().expect("simulation worker panicked")).collect()
+    });
+
+    let mut result = SimulationResult::default();
+    for worker_result in &results {
+        result.merge(worker_result);
+    }
+    result.elapsed = start.elapsed();
+    result
+}
+
+/**
+ * Renders the results of a simulation
+ */
+pub fn render_report(config: &SimulationConfig, result: &SimulationResult) -> String {
+    let mut out = format!(
+        "Rules:    {}\nStrategy: {}\nCount:    {}\nSeed:     {}\nThreads:  {}\n\n",
+        config.rules,
+        config.player.build(&config.strategy, config.seed).name(),
+        config.system.name(),
+        config.seed,
+        config.threads
+    );
+
+    out.push_str(&format!("Rounds played:      {}\n", result.rounds));
+    out.push_str(&format!("Hands played:       {}\n", result.hands));
+    out.push_str(&format!("Average bet:        {:.2} units\n", result.average_bet()));
+    out.push_str(&format!(
+        "House edge:         {:+.3}% +/- {:.3}% (95% confidence)\n",
+        100.0 * result.house_edge(),
+        100.0 * result.confidence_interval()
+    ));
+    out.push_str(&format!("Std dev per round:  {:.4}\n", result.standard_deviation()));
+    out.push_str(&format!("Rounds per second:  {:.0}\n\n", result.rounds_per_second()));
+
+    out.push_str("Outcome frequency per hand\n");
+    // Surrenders are only listed where the rules offer surrender
+    for outcome in OUTCOMES.into_iter().filter(|&outcome| outcome != HandOutcome::Surrender || config.rules.surrender) {
+        out.push_str(&format!(
+            "  {:<10}{:>12}  {:>6.2}%\n",
+            format!("{:?}", outcome),
+            result.outcomes[outcome_index(outcome)],
+            100.0 * result.frequency(outcome)
+        ));
+    }
+
+    for &side_bet in &config.side_bets {
+        out.push_str(&render_side_bet(side_bet, &result.side_bets[side_bet.index()]));
+    }
+    out
+}
+
+// Renders the house edge of a side bet, with the player's edge at each true count for the countable ones
+fn render_side_bet(side_bet: SideBet, stats: &SideBetStats) -> String {
+    let mut out = format!(
+        "\n{} house edge: {:+.3}% +/- {:.3}% over {} bets\n",
+        side_bet,
+        100.0 * stats.house_edge(),
+        100.0 * stats.confidence_interval(),
+        stats.rounds
+    );
+    if side_bet.is_countable() {
+        out.push_str("  True count      Bets  Player edge\n");
+        for true_count in -TRUE_COUNT_LIMIT..=TRUE_COUNT_LIMIT {
+            let count = stats.true_count_stats(true_count);
+            if count.rounds > 0 {
+                out.push_str(&format!("  {:>+10}{:>10}  {:>+10.2}%\n", true_count, count.rounds, 100.0 * count.edge()));
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,193 @@
+use crate::blackjack::{Blackjack, HandOutcome, Rules};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::countingsystem::HiLo;
+use crate::playerstrategy::{BasicStrategyPlayer, PlayerKind};
+use crate::simulation::{
+    play_round, render_report, simulate, worker_rounds, worker_seed, Seat, SimulationConfig, SimulationResult,
+    MAX_BET_UNITS, OUTCOMES, TRUE_COUNT_LIMIT,
+};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = SimulationConfig::parse(&args("hands 1_000 rules 2d-h17 penetration 0.5 payout 1.2")).unwrap();
+
+        assert_eq!(config.rounds, 1000);
+        assert_eq!(config.rules.decks, 2);
+        assert!(config.rules.dealer_hits_soft_17);
+        assert_eq!((config.rules.penetration, config.rules.blackjack_payout), (0.5, 1.2));
+        assert_eq!(config.strategy.get_rules().decks, 2);
+
+        let config = SimulationConfig::parse(&args("strategy 1d-s17")).unwrap();
+        assert_eq!(config.rules, Rules::default());
+        assert_eq!(config.strategy.get_rules().decks, 1);
+
+        assert_eq!(SimulationConfig::parse(&args("hands 0")).err().unwrap(), "invalid number of hands 0");
+        assert_eq!(SimulationConfig::parse(&args("hands")).err().unwrap(), "hands needs a value");
+        assert_eq!(SimulationConfig::parse(&args("fast")).err().unwrap(), "unknown option fast");
+        assert_eq!(SimulationConfig::parse(&args("threads 0")).err().unwrap(), "invalid number of threads 0");
+        assert_eq!(SimulationConfig::parse(&args("seed -1")).err().unwrap(), "invalid seed -1");
+
+        let config = SimulationConfig::parse(&args("threads 3 seed 42")).unwrap();
+        assert_eq!((config.threads, config.seed), (3, 42));
+        assert_eq!((config.player, config.system.name(), config.bankroll), (PlayerKind::Basic, "Hi-Lo", 1000.0));
+
+        let config = SimulationConfig::parse(&args("player never-bust system zen bankroll 5_000")).unwrap();
+        assert_eq!((config.player, config.system.name(), config.bankroll), (PlayerKind::NeverBust, "Zen", 5000.0));
+        assert_eq!(SimulationConfig::parse(&args("player martingale")).err().unwrap(), "unknown player martingale");
+        assert_eq!(SimulationConfig::parse(&args("bankroll -5")).err().unwrap(), "invalid bankroll -5");
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut result = SimulationResult::default();
+        for net in [1.0, -1.0, -2.0, 0.0] {
+            result.record_round(1.0, net);
+        }
+
+        assert_eq!(result.mean(), -0.5);
+        assert_eq!(result.house_edge(), 0.5);
+        // Sample variance of 1, -1, -2, 0 is 5/3
+        assert!((result.standard_deviation() - (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((result.confidence_interval() - 1.96 * (5.0f64 / 3.0).sqrt() / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = SimulationResult::default();
+        first.record_round(1.0, 1.0);
+        first.record_outcome(HandOutcome::Win);
+        let mut second = SimulationResult::default();
+        second.record_round(1.0, -1.0);
+        second.record_outcome(HandOutcome::Bust);
+
+        first.merge(&second);
+        assert_eq!((first.rounds, first.hands, first.total, first.total_squares), (2, 2, 0.0, 2.0));
+        assert_eq!(first.frequency(HandOutcome::Bust), 0.5);
+    }
+
+    #[test]
+    fn test_true_count_and_bet_tallies() {
+        let mut first = SimulationResult::default();
+        first.record_round(1.0, 1.0);
+        first.record_true_count(-0.5, 1.0, 1.0);
+        first.record_round(30.0, -30.0);
+        first.record_true_count(14.2, 30.0, -30.0);
+        let mut second = SimulationResult::default();
+        second.record_round(2.4, 4.8);
+        second.record_true_count(-1.0, 2.4, 4.8);
+
+        first.merge(&second);
+        let minus_one = first.true_count_stats(-1);
+        assert_eq!((minus_one.rounds, minus_one.wagered, minus_one.total), (2, 3.4, 5.8));
+        assert_eq!(minus_one.win_rate(), 2.9);
+        assert_eq!(first.true_count_stats(TRUE_COUNT_LIMIT).edge(), -1.0);
+        assert_eq!(first.true_count_stats(0).rounds, 0);
+        assert_eq!((first.bets[1], first.bets[2], first.bets[MAX_BET_UNITS]), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_played_rounds_are_consistent() {
+        // Late surrender is offered so that every outcome is played
+        let rules = Rules { surrender: true, ..Rules::default() };
+        let player = BasicStrategyPlayer { strategy: BlackjackBasicStrategy::for_rules(&rules) };
+        let mut seat = Seat::new(Box::new(player), &HiLo, 0.0);
+        let mut game = Blackjack::with_rules(rules);
+        let mut result = SimulationResult::default();
+
+        for _ in 0..2000 {
+            play_round(&mut game, &mut seat, &mut result);
+        }
+        assert_eq!(seat.bankroll, result.total);
+        assert_eq!(result.wagered, 2000.0);
+        assert_eq!(result.rounds, 2000);
+        assert!(result.hands >= result.rounds);
+        assert_eq!(result.outcomes.iter().sum::<u64>(), result.hands);
+        // Rounds win or lose at most 8 units (four doubled hands)
+        assert!(result.total.abs() <= 8.0 * 2000.0);
+        assert!(OUTCOMES.iter().all(|&outcome| result.frequency(outcome) > 0.0));
+        assert_eq!(result.true_counts.iter().map(|stats| stats.rounds).sum::<u64>(), 2000);
+        assert_eq!(result.bets[1], 2000);
+    }
+
+    #[test]
+    fn test_house_edge_is_small() {
+        let config = SimulationConfig::parse(&args("hands 200000")).unwrap();
+        let result = simulate(&config);
+
+        // Basic strategy under the default rules gives the house about 0.5%
+        assert!(result.house_edge().abs() < 0.02, "house edge {}", result.house_edge());
+        assert!(result.standard_deviation() > 1.0 && result.standard_deviation() < 1.3);
+        let report = render_report(&config, &result);
+        assert!(report.contains("House edge:"));
+        assert!(report.contains("Blackjack"));
+    }
+
+    #[test]
+    fn test_single_deck_runs_through_the_shoe() {
+        // Dealing almost every card before the reshuffle, many rounds run out of cards partway through
+        let config = SimulationConfig::parse(&args("rules 1d-s17 penetration 0.99 hands 50000 threads 2 seed 3")).unwrap();
+        let result = simulate(&config);
+
+        assert_eq!(result.rounds, 50000);
+        assert!(result.house_edge().abs() < 0.05, "house edge {}", result.house_edge());
+    }
+
+    #[test]
+    fn test_work_is_split_across_workers() {
+        assert_eq!(worker_rounds(10, 4), vec![3, 3, 2, 2]);
+        assert_eq!(worker_rounds(2, 3), vec![1, 1, 0]);
+        assert_eq!(worker_rounds(1_000_000, 7).iter().sum::<u64>(), 1_000_000);
+
+        let seeds: Vec<u64> = (0..8).map(|worker| worker_seed(42, worker)).collect();
+        for (index, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[..index].contains(seed));
+        }
+        assert_eq!(worker_seed(42, 3), seeds[3]);
+    }
+
+    #[test]
+    fn test_seed_and_threads_give_identical_results() {
+        let config = SimulationConfig::parse(&args("hands 20000 threads 4 seed 7")).unwrap();
+        let mut first = simulate(&config);
+        let mut second = simulate(&config);
+        first.elapsed = Duration::ZERO;
+        second.elapsed = Duration::ZERO;
+
+        assert_eq!(first, second);
+        assert_eq!(first.rounds, 20000);
+
+        let other_seed = SimulationConfig { seed: 8, ..config.clone() };
+        let mut third = simulate(&other_seed);
+        third.elapsed = Duration::ZERO;
+        assert_ne!(first, third);
+    }
+
+    #[test]
+    fn test_poor_strategies_give_the_house_more() {
+        let basic = simulate(&SimulationConfig::parse(&args("hands 100000 seed 3")).unwrap());
+        let mimic = simulate(&SimulationConfig::parse(&args("hands 100000 seed 3 player mimic")).unwrap());
+        let random = simulate(&SimulationConfig::parse(&args("hands 20000 seed 3 player random")).unwrap());
+
+        // Mimicking the dealer gives the house about 5.5%, random play far more
+        assert!(mimic.house_edge() > basic.house_edge() + 0.02, "mimic {}", mimic.house_edge());
+        assert!(random.house_edge() > 0.2, "random {}", random.house_edge());
+    }
+
+    #[test]
+    fn test_counter_spreads_bets() {
+        let config = SimulationConfig::parse(&args("hands 50000 seed 5 player deviations")).unwrap();
+        let result = simulate(&config);
+
+        assert!(result.average_bet() > 1.0 && result.average_bet() < 8.0);
+        assert!(render_report(&config, &result).contains("index plays"));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory, DEALER_CARDS};
+use crate::chart::{self, dealer_label, row_name};
+use crate::storage;
+use std::collections::HashMap;
+use std::io;
+
+/**
+ * The file in the data directory that accumulates the statistics of every session
+ */
+pub const STATS_FILE: &str = "stats.txt";
+
+/**
+ * The graded decisions made in one chart cell
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CellStats {
+    pub attempts: u32,
+    pub correct: u32,
+    // Expected value given up by the wrong decisions, in units of the initial bet
+    pub ev_lost: f64,
+}
+impl CellStats {
+    pub fn accuracy(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.attempts as f64
+        }
+    }
+
+    fn add(&mut self, other: &CellStats) {
+        self.attempts += other.attempts;
+        self.correct += other.correct;
+        self.ev_lost += other.ev_lost;
+    }
+}
+
+/**
+ * Graded decisions grouped by chart cell: hand category, player total and dealer upcard.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionStats {
+    cells: HashMap<ChartCell, CellStats>,
+}
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats { cells: HashMap::new() }
+    }
+
+    /**
+     * Records a graded decision
+     * @param cell the chart cell the decision was made in
+     * @param correct true if the decision matched basic strategy
+     * @param ev_lost the expected value given up by the decision (0 when correct)
+     */
+    pub fn record(&mut self, cell: ChartCell, correct: bool, ev_lost: f64) {
+        let stats = self.cells.entry(cell).or_default();
+        stats.attempts += 1;
+        if correct {
+            stats.correct += 1;
+        }
+        stats.ev_lost += ev_lost.max(0.0);
+    }
+
+    /**
+     * Adds every decision recorded in another set of statistics to this one
+     */
+    pub fn merge(&mut self, other: &SessionStats) {
+        for (cell, stats) in &other.cells {
+            self.cells.entry(*cell).or_default().add(stats);
+        }
+    }
+
+    /**
+     * Returns the statistics for a chart cell, or None if no decisions were made in it
+     */
+    pub fn get(&self, cell: &ChartCell) -> Option<&CellStats> {
+        self.cells.get(cell)
+    }
+
+    /**
+     * Returns the combined statistics of every cell
+     */
+    pub fn total(&self) -> CellStats {
+        let mut total = CellStats::default();
+        for stats in self.cells.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    /**
+     * Returns the combined statistics of the hard, soft and pair tables
+     */
+    pub fn by_table(&self) -> [(&'static str, CellStats); 3] {
+        let mut tables = [("Hard", CellStats::default()), ("Soft", CellStats::default()), ("Pairs", CellStats::default())];
+        for (cell, stats) in &self.cells {
+            let index = match cell.category {
+                HandCategory::Hard(_) => 0,
+                HandCategory::Soft(_) => 1,
+                HandCategory::Pair(_) => 2,
+            };
+            tables[index].1.add(stats);
+        }
+        tables
+    }
+
+    /**
+     * Returns the accuracy of every cell with at least one decision, as used by the chart overlay
+     */
+    pub fn accuracy_map(&self) -> HashMap<ChartCell, f64> {
+        self.cells.iter().map(|(cell, stats)| (*cell, stats.accuracy())).collect()
+    }
+
+    /**
+     * Returns the cells with at least one decision in chart order
+     */
+    pub fn cells(&self) -> Vec<(ChartCell, CellStats)> {
+        let strategy = BlackjackBasicStrategy::new();
+        let rows = strategy.hard_rows().into_iter().chain(strategy.soft_rows()).chain(strategy.pair_rows());
+        let mut cells = Vec::new();
+
+        for category in rows {
+            for dealer_card in DEALER_CARDS {
+                let cell = ChartCell { category, dealer_card };
+                if let Some(stats) = self.cells.get(&cell) {
+                    cells.push((cell, *stats));
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /**
+     * Renders the statistics as a table: a summary per table, then one line per cell
+     * with the number of attempts, the accuracy and the expected value lost.
+     */
+    pub fn render_table(&self) -> String {
+        let mut out = format!("{:<22}{:>9}{:>10}{:>10}{:>10}\n", "", "Attempts", "Correct", "Accuracy", "EV lost");
+
+        for (name, stats) in self.by_table().iter().chain([("All", self.total())].iter()) {
+            if stats.attempts > 0 {
+                out.push_str(&format_row(name, stats));
+            }
+        }
+        out.push('\n');
+
+        for (cell, stats) in self.cells() {
+            let name = format!("{} vs {}", row_name(cell.category), dealer_label(cell.dealer_card));
+            out.push_str(&format_row(&name, &stats));
+        }
+        out
+    }
+
+    /**
+     * Renders the strategy chart with the accuracy of each cell in place of the play.
+     * Cells without decisions are shown as . and cells below chart::LOW_ACCURACY are marked with *
+     */
+    pub fn render_chart(&self, strategy: &BlackjackBasicStrategy, colour: bool) -> String {
+        let title = format!("Accuracy (%) per cell: {}", strategy.get_rules());
+        let accuracy = self.accuracy_map();
+
+        chart::render_with(&title, strategy, &|cell| {
+            let play = strategy.get_play(cell.category, cell.dealer_card)?;
+            match self.cells.get(&cell) {
+                Some(stats) => Some((format!("{:.0}", stats.accuracy() * 100.0), play)),
+                None => Some((".".to_string(), 0)),
+            }
+        }, Some(&accuracy), colour)
+    }
+
+    /**
+     * Returns these statistics in the stats file format: one line per cell with the table, row,
+     * dealer upcard, attempts, correct decisions and EV lost
+     * Ex: hard 16 10 12 9 0.215
+     */
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (cell, stats) in self.cells() {
+            let (table, row) = cell_key(cell.category);
+            text.push_str(&format!(
+                "{} {} {} {} {} {}\n",
+                table,
+                row,
+                dealer_label(cell.dealer_card),
+                stats.attempts,
+                stats.correct,
+                stats.ev_lost
+            ));
+        }
+        text
+    }
+
+    /**
+     * Parses statistics written by to_text
+     */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut stats = SessionStats::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let error = || format!("line {}: invalid statistics", index + 1);
+            if words.len() != 6 {
+                return Err(error());
+            }
+
+            let cell = parse_cell(words[0], words[1], words[2]).ok_or_else(error)?;
+            let cell_stats = CellStats {
+                attempts: words[3].parse().map_err(|_| error())?,
+                correct: words[4].parse().map_err(|_| error())?,
+                ev_lost: words[5].parse().map_err(|_| error())?,
+            };
+            stats.cells.entry(cell).or_default().add(&cell_stats);
+        }
+        Ok(stats)
+    }
+
+    /**
+     * Loads the statistics of every previous session, or empty statistics if there are none
+     */
+    pub fn load() -> Result<Self, String> {
+        match storage::read_data_file(STATS_FILE) {
+            Ok(Some(text)) => Self::parse(&text),
+            Ok(None) => Ok(Self::new()),
+            Err(err) => Err(format!("Cannot read {}: {}", STATS_FILE, err)),
+        }
+    }
+
+    /**
+     * Replaces the saved statistics with these statistics
+     */
+    pub fn save(&self) -> io::Result<()> {
+        storage::write_data_file(STATS_FILE, &self.to_text())
+    }
+
+    /**
+     * Adds these statistics to the statistics of every previous session
+     */
+    pub fn save_session(&self) -> io::Result<()> {
+        let mut all = Self::load().map_err(io::Error::other)?;
+        all.merge(self);
+        all.save()
+    }
+}
+
+fn format_row(name: &str, stats: &CellStats) -> String {
+    format!(
+        "{:<22}{:>9}{:>10}{:>9.1}%{:>10.3}\n",
+        name,
+        stats.attempts,
+        stats.correct,
+        stats.accuracy() * 100.0,
+        stats.ev_lost
+    )
+}
+
+/**
+ * Returns the table and row of a chart row as written in files
+ * Ex: ("hard", "16"), ("pair", "A")
+ */
+pub fn cell_key(category: HandCategory) -> (&'static str, String) {
+    match category {
+        HandCategory::Hard(total) => ("hard", total.to_string()),
+        HandCategory::Soft(total) => ("soft", total.to_string()),
+        HandCategory::Pair(1) => ("pair", "A".to_string()),
+        HandCategory::Pair(card) => ("pair", card.to_string()),
+    }
+}
+
+/**
+ * Parses a chart cell from its table, row and dealer upcard as written in files
+ * Ex: "soft", "18", "A"
+ */
+pub fn parse_cell(table: &str, row: &str, dealer: &str) -> Option<ChartCell> {
+    let parse_card = |card: &str| if card.eq_ignore_ascii_case("a") { Some(1) } else { card.parse::<i32>().ok() };
+    let row = parse_card(row)?;
+    let category = match table {
+        "hard" => HandCategory::Hard(row),
+        "soft" => HandCategory::Soft(row),
+        "pair" => HandCategory::Pair(row),
+        _ => return None,
+    };
+    let dealer_card = parse_card(dealer).filter(|card| DEALER_CARDS.contains(card))?;
+
+    BlackjackBasicStrategy::new().get_play(category, dealer_card)?;
+    Some(ChartCell { category, dealer_card })
+}
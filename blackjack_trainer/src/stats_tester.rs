@@ -0,0 +1,113 @@
+use crate::blackjackbasicstrategy::{BlackjackBasicStrategy, ChartCell, HandCategory};
+use crate::chart::render_chart;
+use crate::stats::{parse_cell, SessionStats};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(category: HandCategory, dealer_card: i32) -> ChartCell {
+        ChartCell { category, dealer_card }
+    }
+
+    fn sample_stats() -> SessionStats {
+        let mut stats = SessionStats::new();
+        stats.record(cell(HandCategory::Hard(16), 10), false, 0.02);
+        stats.record(cell(HandCategory::Hard(16), 10), true, 0.0);
+        stats.record(cell(HandCategory::Soft(18), 1), true, 0.0);
+        stats.record(cell(HandCategory::Pair(8), 10), false, 0.3);
+        stats
+    }
+
+    #[test]
+    fn test_record_per_cell() {
+        let stats = sample_stats();
+        let hard_16 = stats.get(&cell(HandCategory::Hard(16), 10)).unwrap();
+
+        assert_eq!((hard_16.attempts, hard_16.correct), (2, 1));
+        assert_eq!(hard_16.accuracy(), 0.5);
+        assert!((hard_16.ev_lost - 0.02).abs() < 1e-12);
+        assert_eq!(stats.get(&cell(HandCategory::Hard(16), 9)), None);
+    }
+
+    #[test]
+    fn test_totals_by_table() {
+        let stats = sample_stats();
+        let tables = stats.by_table();
+
+        assert_eq!(tables[0].0, "Hard");
+        assert_eq!((tables[0].1.attempts, tables[1].1.attempts, tables[2].1.attempts), (2, 1, 1));
+        assert_eq!((stats.total().attempts, stats.total().correct), (4, 2));
+    }
+
+    #[test]
+    fn test_cells_in_chart_order() {
+        let cells: Vec<ChartCell> = sample_stats().cells().into_iter().map(|(cell, _)| cell).collect();
+
+        assert_eq!(
+            cells,
+            vec![
+                cell(HandCategory::Hard(16), 10),
+                cell(HandCategory::Soft(18), 1),
+                cell(HandCategory::Pair(8), 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_round_trip_and_merge() {
+        let stats = sample_stats();
+        let text = stats.to_text();
+
+        assert!(text.starts_with("hard 16 10 2 1 0.02\n"));
+        assert!(text.contains("pair 8 10 1 0 0.3\n"));
+        assert_eq!(SessionStats::parse(&text).unwrap(), stats);
+
+        let mut merged = SessionStats::parse(&text).unwrap();
+        merged.merge(&stats);
+        assert_eq!(merged.total().attempts, 8);
+        assert_eq!(merged.get(&cell(HandCategory::Soft(18), 1)).unwrap().correct, 2);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(SessionStats::parse("hard 16 10 2 1").err().unwrap(), "line 1: invalid statistics");
+        assert_eq!(SessionStats::parse("\nhard 3 10 2 1 0").err().unwrap(), "line 2: invalid statistics");
+        assert_eq!(parse_cell("pair", "a", "A"), Some(cell(HandCategory::Pair(1), 1)));
+        assert_eq!(parse_cell("soft", "18", "11"), None);
+        assert_eq!(parse_cell("split", "8", "10"), None);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = sample_stats().render_table();
+
+        assert!(table.contains("Attempts"));
+        assert!(table.lines().any(|line| line.starts_with("All") && line.contains("50.0%")));
+        assert!(table.lines().any(|line| line.starts_with("pair of 8s vs 10") && line.ends_with("0.300")));
+    }
+
+    #[test]
+    fn test_render_chart_overlay() {
+        let strategy = BlackjackBasicStrategy::new();
+        let chart = sample_stats().render_chart(&strategy, false);
+        let hard_16 = chart.lines().find(|line| line.starts_with("16 ")).unwrap();
+
+        // Untried cells show ., the 50% cell is below the low accuracy threshold
+        assert_eq!(hard_16.trim_end(), "16     .   .   .   .   .   .   .   .  50*  .");
+        assert!(chart.lines().any(|line| line.starts_with("A,7") && line.trim_end().ends_with("100")));
+        assert!(chart.contains("* accuracy below 80%"));
+    }
+
+    #[test]
+    fn test_weak_cells_on_the_strategy_chart() {
+        // What chart weak prints: the plays, with the cells of low recorded accuracy marked
+        let strategy = BlackjackBasicStrategy::new();
+        let chart = render_chart(&strategy, Some(&sample_stats().accuracy_map()), false);
+        let row = |label: &str| chart.lines().find(|line| line.starts_with(label)).unwrap().trim_end().to_string();
+
+        assert_eq!(row("16 "), "16     S   S   S   S   S   H   H   H   H*  H");
+        assert_eq!(row("8,8"), "8,8    P   P   P   P   P   P   P   P   P*  P");
+        assert_eq!(row("A,7"), "A,7    S   Ds  Ds  Ds  Ds  S   S   H   H   H");
+    }
+}
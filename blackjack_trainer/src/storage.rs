@@ -0,0 +1,50 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/**
+ * Returns the directory the trainer keeps its files in: $BLACKJACK_TRAINER_HOME if it is set,
+ * otherwise .blackjack_trainer in the user's home directory (or the current directory).
+ */
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("BLACKJACK_TRAINER_HOME") {
+        return PathBuf::from(dir);
+    }
+
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    match home {
+        Some(home) => PathBuf::from(home).join(".blackjack_trainer"),
+        None => PathBuf::from(".blackjack_trainer"),
+    }
+}
+
+/**
+ * Returns the path of a file in the data directory
+ * @param name the file name
+ */
+pub fn data_path(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/**
+ * Reads a file from the data directory, or returns None if it does not exist yet
+ * @param name the file name
+ */
+pub fn read_data_file(name: &str) -> io::Result<Option<String>> {
+    match fs::read_to_string(data_path(name)) {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/**
+ * Writes a file to the data directory, creating the directory if needed
+ * @param name the file name
+ * @param contents the new contents of the file
+ */
+pub fn write_data_file(name: &str, contents: &str) -> io::Result<()> {
+    fs::create_dir_all(data_dir())?;
+    fs::write(data_path(name), contents)
+}
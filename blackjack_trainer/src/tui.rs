@@ -0,0 +1,414 @@
+use crate::blackjack::{Action, Blackjack, CARDS_PER_DECK};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::cardstyle::{CardRenderer, CardStyle};
+use crate::chart::{dealer_label, row_name};
+use crate::history::{ActionRecord, HistoryWriter, RoundRecord};
+use crate::input::{Input, Keymap};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+/**
+ * The largest bet the table takes, in betting units
+ */
+pub const MAX_BET: f64 = 100.0;
+
+// The number of strategy feedback lines kept for the feedback pane
+const FEEDBACK_LINES: usize = 8;
+
+/**
+ * What the table is waiting for
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    // The player is choosing a bet; the last round stays on the table until the next deal
+    Betting,
+    // The player is acting on their hands
+    Playing,
+}
+
+/**
+ * The state of the full-screen table: the game, the player's bankroll and bet,
+ * and the strategy feedback on each decision
+ */
+pub struct TableApp {
+    pub(crate) game: Blackjack,
+    strategy: BlackjackBasicStrategy,
+    phase: Phase,
+    bankroll: f64,
+    bet: f64,
+    // The amount won or lost this session
+    session_net: f64,
+    rounds: u32,
+    // Decisions graded against basic strategy this session, and how many matched it
+    graded: u32,
+    correct: u32,
+    // Strategy feedback on the player's decisions, oldest first
+    feedback: Vec<String>,
+    // The result of the last round, or why a key could not be used
+    message: String,
+    // Where the round was dealt from the shoe, for the hand history
+    position: (u64, usize),
+    actions: Vec<ActionRecord>,
+    history: Option<HistoryWriter>,
+    // The keys that play each action and quit
+    keymap: Keymap,
+    // Draws the cards on one line, without ANSI colour as the terminal is drawn by ratatui
+    cards: CardRenderer,
+    quit: bool,
+}
+impl TableApp {
+    /**
+     * Constructs a table
+     * @param game the game, under the table's rules
+     * @param strategy the chart each decision is graded against
+     * @param bankroll the player's starting bankroll, in betting units
+     */
+    pub fn new(game: Blackjack, strategy: BlackjackBasicStrategy, bankroll: f64) -> Self {
+        TableApp {
+            game,
+            strategy,
+            phase: Phase::Betting,
+            bankroll,
+            bet: 1.0,
+            session_net: 0.0,
+            rounds: 0,
+            graded: 0,
+            correct: 0,
+            feedback: Vec::new(),
+            message: "Place your bet and press Enter to deal.".to_string(),
+            position: (0, 0),
+            actions: Vec::new(),
+            history: None,
+            keymap: Keymap::default(),
+            cards: CardRenderer::default(),
+            quit: false,
+        }
+    }
+
+    /**
+     * Writes every round played to a hand history
+     */
+    pub fn with_history(mut self, history: HistoryWriter) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /**
+     * Plays the actions with the keys of a keymap instead of the default keys
+     */
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /**
+     * Draws the cards in a style instead of as text. Art is drawn with suit symbols, as each hand has one line.
+     */
+    pub fn with_card_style(mut self, style: CardStyle) -> Self {
+        self.cards = CardRenderer::new(style, false);
+        self
+    }
+
+    pub fn get_phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn get_bankroll(&self) -> f64 {
+        self.bankroll
+    }
+
+    pub fn get_bet(&self) -> f64 {
+        self.bet
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_feedback(&self) -> &[String] {
+        &self.feedback
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /**
+     * Returns the keys that can be pressed now, with what they do
+     */
+    pub fn key_bindings(&self) -> Vec<(String, &'static str)> {
+        let mut keys = match self.phase {
+            Phase::Betting => vec![
+                ("Enter".to_string(), "deal"),
+                ("+/Up".to_string(), "raise bet"),
+                ("-/Down".to_string(), "lower bet"),
+            ],
+            Phase::Playing => self
+                .game
+                .legal_actions()
+                .into_iter()
+                .map(|action| {
+                    let input = Input::for_action(action);
+                    (self.keymap.key(input).to_string(), input.name())
+                })
+                .collect(),
+        };
+        keys.push((self.keymap.key(Input::Quit).to_string(), "quit"));
+        keys
+    }
+
+    /**
+     * Responds to a key press
+     */
+    pub fn handle_key(&mut self, key: KeyCode) {
+        let input = match key {
+            KeyCode::Char(key) => self.keymap.lookup_key(key),
+            _ => None,
+        };
+        match (self.phase, key) {
+            (_, KeyCode::Esc) => self.quit = true,
+            _ if input == Some(Input::Quit) => self.quit = true,
+            (Phase::Betting, KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('n')) => self.deal(),
+            (Phase::Betting, KeyCode::Up | KeyCode::Char('+') | KeyCode::Char('=')) => self.bet = (self.bet + 1.0).min(MAX_BET),
+            (Phase::Betting, KeyCode::Down | KeyCode::Char('-')) => self.bet = (self.bet - 1.0).max(1.0),
+            (Phase::Playing, _) => {
+                if let Some(action) = input.and_then(|input| input.action()) {
+                    self.act(action);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Deals a round for the current bet, settling it at once if either side has blackjack
+    fn deal(&mut self) {
+        if self.bankroll < self.bet {
+            self.message = format!("Your bankroll of {} does not cover a bet of {}.", self.bankroll, self.bet);
+            return;
+        }
+        self.game.clear_table();
+        self.position = self.game.shoe_position();
+        self.game.deal_cards();
+        self.message.clear();
+        self.phase = Phase::Playing;
+
+        if !self.game.is_players_turn() {
+            self.finish_round();
+        }
+    }
+
+    // Grades and takes an action on the current hand, then settles the round once every hand is finished
+    fn act(&mut self, action: Action) {
+        if !self.game.legal_actions().contains(&action) {
+            self.message = format!("You cannot {} now.", action);
+            return;
+        }
+        let hand = self.game.get_players_hand().expect("a round is being played");
+        let upcard = *self.game.get_dealers_upcard().expect("a round is being played");
+        let values = hand.get_card_values();
+        let recommended = self.strategy.recommend(
+            &values,
+            upcard.get_value(),
+            self.game.can_double(),
+            self.game.can_split(),
+            self.game.can_surrender(),
+        );
+
+        let situation = match self.strategy.classify(&values, self.game.can_split()) {
+            Some(category) => format!("{} against {}", row_name(category), dealer_label(upcard.get_value())),
+            None => format!("{} against {}", hand, upcard),
+        };
+        let line = match recommended {
+            Some(recommended) if recommended == action => format!("Correct: {} on {}", action, situation),
+            Some(recommended) => format!("Basic strategy says {} on {}, not {}", recommended, situation, action),
+            None => format!("{} on {}: basic strategy has no play", action, situation),
+        };
+        if let Some(recommended) = recommended {
+            self.graded += 1;
+            self.correct += u32::from(recommended == action);
+        }
+        self.feedback.push(line);
+        if self.feedback.len() > FEEDBACK_LINES {
+            self.feedback.remove(0);
+        }
+
+        self.actions.push(ActionRecord {
+            hand: self.game.get_current_hand_index(),
+            cards: hand.get_cards().to_vec(),
+            upcard,
+            action,
+            recommended,
+            matched: recommended.is_none_or(|recommended| recommended == action),
+        });
+        self.game.act(action);
+
+        if !self.game.is_players_turn() {
+            self.finish_round();
+        }
+    }
+
+    // Plays the dealer's hand, pays the player's hands and records the round
+    fn finish_round(&mut self) {
+        self.game.play_dealers_hand();
+        let hands = self.game.get_players_hands().len();
+        let mut net = 0.0;
+        let mut results = Vec::new();
+        for index in 0..hands {
+            let hand_net = self.bet * self.game.net_result(index);
+            net += hand_net;
+            results.push(format!("{:?} {:+}", self.game.outcome(index), hand_net));
+        }
+
+        self.bankroll += net;
+        self.session_net += net;
+        self.rounds += 1;
+        self.phase = Phase::Betting;
+        self.message = format!("{}. Press Enter to deal again.", results.join(", "));
+
+        let actions = std::mem::take(&mut self.actions);
+        if let Some(writer) = self.history.as_mut() {
+            let round = RoundRecord::from_game(&self.game, self.rounds, self.position, self.bet, actions);
+            if writer.write_round(&round).is_err() {
+                self.message.push_str(" The hand history could not be written.");
+                self.history = None;
+            }
+        }
+    }
+}
+
+/**
+ * Draws the table: the dealer's and player's areas and the result on the left, the bankroll, shoe and
+ * strategy feedback panels on the right, and the keys that can be pressed along the bottom
+ */
+pub fn draw(frame: &mut Frame, app: &TableApp) {
+    let [body, keys] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [table, panels] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(body);
+    let [dealer, player, message] =
+        Layout::vertical([Constraint::Length(4), Constraint::Min(4), Constraint::Length(3)]).areas(table);
+    let [bankroll, shoe, feedback] =
+        Layout::vertical([Constraint::Length(7), Constraint::Length(4), Constraint::Min(3)]).areas(panels);
+
+    draw_dealer(frame, app, dealer);
+    draw_player(frame, app, player);
+    frame.render_widget(
+        Paragraph::new(app.message.as_str()).wrap(Wrap { trim: true }).block(Block::default().borders(Borders::ALL)),
+        message,
+    );
+    draw_bankroll(frame, app, bankroll);
+    draw_shoe(frame, app, shoe);
+
+    let lines: Vec<Line> = app
+        .feedback
+        .iter()
+        .map(|line| {
+            let colour = if line.starts_with("Correct") { Color::Green } else { Color::Red };
+            Line::styled(line.as_str(), Style::default().fg(colour))
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: true }).block(Block::default().title("Strategy").borders(Borders::ALL)),
+        feedback,
+    );
+
+    let mut spans = Vec::new();
+    for (key, description) in app.key_bindings() {
+        spans.push(Span::styled(format!(" {} ", key), Style::default().add_modifier(Modifier::REVERSED)));
+        spans.push(Span::raw(format!(" {}  ", description)));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), keys);
+}
+
+// The dealer's hand, with the hole card face down while the player is acting
+fn draw_dealer(frame: &mut Frame, app: &TableApp, area: Rect) {
+    let text = match app.game.get_dealers_hand() {
+        None => String::new(),
+        Some(hand) if app.phase == Phase::Playing => {
+            let upcard = hand.get_cards()[0];
+            format!("{}  (showing {})", app.cards.inline(&[upcard], 1), dealer_label(upcard.get_value()))
+        }
+        Some(hand) => app.cards.hand_inline(hand),
+    };
+    frame.render_widget(Paragraph::new(text).block(Block::default().title("Dealer").borders(Borders::ALL)), area);
+}
+
+// The player's hands, marking the hand being played
+fn draw_player(frame: &mut Frame, app: &TableApp, area: Rect) {
+    let hands = app.game.get_players_hands();
+    let lines: Vec<Line> = hands
+        .iter()
+        .enumerate()
+        .map(|(index, hand)| {
+            let current = app.phase == Phase::Playing && index == app.game.get_current_hand_index();
+            let marker = if current { "> " } else { "  " };
+            let doubled = if hand.is_doubled() { ", doubled" } else { "" };
+            let line = format!("{}{}{}", marker, app.cards.hand_inline(hand), doubled);
+            if current {
+                Line::styled(line, Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Line::raw(line)
+            }
+        })
+        .collect();
+    let title = if hands.len() > 1 { format!("You ({} hands)", hands.len()) } else { "You".to_string() };
+    frame.render_widget(Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL)), area);
+}
+
+fn draw_bankroll(frame: &mut Frame, app: &TableApp, area: Rect) {
+    let accuracy = if app.graded == 0 {
+        "-".to_string()
+    } else {
+        format!("{} of {} ({:.0}%)", app.correct, app.graded, 100.0 * app.correct as f64 / app.graded as f64)
+    };
+    let lines = vec![
+        Line::raw(format!("Bankroll:  {}", app.bankroll)),
+        Line::raw(format!("Bet:       {}", app.bet)),
+        Line::raw(format!("Session:   {:+}", app.session_net)),
+        Line::raw(format!("Rounds:    {}", app.rounds)),
+        Line::raw(format!("Correct:   {}", accuracy)),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(Block::default().title("Bankroll").borders(Borders::ALL)), area);
+}
+
+// How much of the shoe has been dealt, against where the cut card is
+fn draw_shoe(frame: &mut Frame, app: &TableApp, area: Rect) {
+    let rules = app.game.get_rules();
+    let total = rules.decks * CARDS_PER_DECK;
+    let dealt = total - app.game.cards_left();
+    let ratio = dealt as f64 / total as f64;
+    let label = format!("{:.0}% dealt, cut card at {:.0}%", 100.0 * ratio, 100.0 * rules.penetration);
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::default().title(format!("Shoe {}", app.game.shoe_position().0)).borders(Borders::ALL))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label),
+        area,
+    );
+}
+
+/**
+ * Runs the table full screen until the player quits, restoring the terminal afterwards
+ */
+pub fn run(app: &mut TableApp) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run_loop(&mut terminal, app);
+    ratatui::restore();
+    result
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, app: &mut TableApp) -> io::Result<()> {
+    while !app.should_quit() {
+        terminal.draw(|frame| draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key.code);
+            }
+        }
+    }
+    Ok(())
+}
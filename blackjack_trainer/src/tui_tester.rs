@@ -0,0 +1,97 @@
+use crate::blackjack::{Blackjack, Card, Rules};
+use crate::blackjackbasicstrategy::BlackjackBasicStrategy;
+use crate::tui::{draw, Phase, TableApp, MAX_BET};
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::Terminal;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(text: &str) -> Vec<Card> {
+        text.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn table(bankroll: f64) -> TableApp {
+        TableApp::new(Blackjack::with_seed(Rules::default(), 4), BlackjackBasicStrategy::new(), bankroll)
+    }
+
+    // Renders the table on a test terminal and returns the screen as text
+    fn screen(app: &TableApp) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let mut text = String::new();
+        for y in 0..buffer.area.height {
+            for x in 0..buffer.area.width {
+                text.push_str(buffer[(x, y)].symbol());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn test_betting_keys() {
+        let mut app = table(3.0);
+        for _ in 0..3 {
+            app.handle_key(KeyCode::Up);
+        }
+        app.handle_key(KeyCode::Char('-'));
+        assert_eq!(app.get_bet(), 3.0);
+
+        app.handle_key(KeyCode::Up);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.get_phase(), Phase::Betting);
+        assert_eq!(app.get_message(), "Your bankroll of 3 does not cover a bet of 4.");
+
+        for _ in 0..200 {
+            app.handle_key(KeyCode::Char('+'));
+        }
+        assert_eq!(app.get_bet(), MAX_BET);
+        app.handle_key(KeyCode::Char('q'));
+        assert!(app.should_quit());
+    }
+
+    #[test]
+    fn test_round_with_feedback() {
+        let mut app = table(100.0);
+        app.game.stack_cards(cards("10H 6S 10C 7D 5C"));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.get_phase(), Phase::Playing);
+
+        let playing = screen(&app);
+        // The hole card is face down and only the legal actions are offered
+        assert!(playing.contains("10C ??"));
+        assert!(!playing.contains("7D"));
+        assert!(playing.contains(" h  hit"));
+        assert!(!playing.contains("split"));
+
+        // 10 6 cannot be split, so p is refused
+        app.handle_key(KeyCode::Char('p'));
+        assert_eq!(app.get_message(), "You cannot split now.");
+        app.handle_key(KeyCode::Char('s'));
+        assert_eq!(app.get_feedback(), ["Basic strategy says hit on hard 16 against 10, not stand"]);
+        assert_eq!(app.get_phase(), Phase::Betting);
+        assert_eq!(app.get_bankroll(), 99.0);
+        assert!(app.get_message().starts_with("Loss -1."));
+
+        let settled = screen(&app);
+        assert!(settled.contains("10C 7D (17)"));
+        assert!(settled.contains("Bankroll:  99"));
+        assert!(settled.contains("Enter  deal"));
+    }
+
+    #[test]
+    fn test_blackjack_settles_at_the_deal() {
+        let mut app = table(100.0);
+        app.handle_key(KeyCode::Up);
+        app.game.stack_cards(cards("AH KS 9C 7D"));
+        app.handle_key(KeyCode::Enter);
+
+        assert_eq!(app.get_phase(), Phase::Betting);
+        assert_eq!(app.get_bankroll(), 103.0);
+        assert!(screen(&app).contains("Correct:   -"));
+    }
+}